@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::process::Command;
 
 use anyhow::{anyhow, Context, Result};
@@ -128,3 +129,100 @@ pub fn ensure_llvm_toolchain_from_env() -> Result<()> {
     let cfg = LlvmToolchainConfig::current_default();
     ensure_llvm_toolchain(&cfg)
 }
+
+/// clang で作る native 成果物の種類。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeOutput {
+    Object,
+    Executable,
+}
+
+fn clang_native_args(
+    ir_path: &Path,
+    out_path: &Path,
+    kind: NativeOutput,
+    triple: &str,
+    optimize: bool,
+) -> Vec<String> {
+    let mut args = vec![
+        "-x".to_string(),
+        "ir".to_string(),
+        format!("--target={}", triple),
+        if optimize { "-O2" } else { "-O0" }.to_string(),
+    ];
+    if kind == NativeOutput::Object {
+        args.push("-c".to_string());
+    }
+    args.push(ir_path.display().to_string());
+    args.push("-o".to_string());
+    args.push(out_path.display().to_string());
+    args
+}
+
+/// LLVM IR を clang でオブジェクトファイルまたは実行ファイルに変換する。
+///
+/// stdio / fs 用のランタイム shim は IR 側に含まれているため、実行ファイルは libc とだけリンクする。
+/// clang は `NEPL_LLVM_CLANG_BIN` で差し替えられる。
+pub fn build_native_from_ll(
+    ir_path: &Path,
+    out_path: &Path,
+    kind: NativeOutput,
+    triple: &str,
+    optimize: bool,
+) -> Result<()> {
+    let cfg = LlvmToolchainConfig::current_default();
+    let args = clang_native_args(ir_path, out_path, kind, triple, optimize);
+    let out = Command::new(&cfg.clang_bin)
+        .args(&args)
+        .output()
+        .with_context(|| format!("failed to execute {}", cfg.clang_bin))?;
+    if !out.status.success() {
+        return Err(anyhow!(
+            "{} failed with status {} while building {}:\n{}",
+            cfg.clang_bin,
+            out.status,
+            out_path.display(),
+            String::from_utf8_lossy(&out.stderr)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clang_args_for_object_and_executable() {
+        let obj = clang_native_args(
+            Path::new("out/a.native.ll"),
+            Path::new("out/a.o"),
+            NativeOutput::Object,
+            "aarch64-unknown-linux-gnu",
+            false,
+        );
+        assert_eq!(
+            obj,
+            vec![
+                "-x",
+                "ir",
+                "--target=aarch64-unknown-linux-gnu",
+                "-O0",
+                "-c",
+                "out/a.native.ll",
+                "-o",
+                "out/a.o"
+            ]
+        );
+        let exe = clang_native_args(
+            Path::new("out/a.native.ll"),
+            Path::new("out/a"),
+            NativeOutput::Executable,
+            "x86_64-pc-linux-gnu",
+            true,
+        );
+        assert!(exe.contains(&"-O2".to_string()));
+        assert!(!exe.contains(&"-c".to_string()));
+        assert_eq!(exe.last().map(String::as_str), Some("out/a"));
+    }
+}
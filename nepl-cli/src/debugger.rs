@@ -0,0 +1,493 @@
+//! `nepl-cli debug`: NEPLg2 ソース行単位の対話的デバッガ。
+//!
+//! `compile_module_for_debugging` でフックを埋め込んだ wasm を wasmi の再開可能呼び出しで実行する。
+//! `nepl_debug::pause` がホストトラップを返すと実行が中断され、ここでプロンプトを出す。
+//! 実行は燃料で区切られており、区切りごとに Ctrl-C による中断要求を確認する。
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use nepl_core::debugger::{DebugInfo, DebugSession, DebugValue, StepCommand, DEBUG_IMPORT_MODULE};
+use nepl_core::loader::SourceMap;
+use nepl_core::span::{FileId, Span};
+use nepl_core::{CompilationArtifact, CompileTarget};
+use wasmi::core::{Trap, F32, F64};
+use wasmi::{Caller, Config, Engine, Linker, Module, ResumableCall, Store, Value};
+
+use crate::{
+    check_run_imports, flush_stdout_buffer, link_wasi_host, new_host_state, restore_host_tty,
+    AllocState,
+};
+
+/// 1 スライスで消費する燃料の目安。スライスの境目で中断要求を確認する。
+const FUEL_SLICE: u64 = 1_000_000;
+
+/// `nepl_debug::pause` が返すホストトラップのメッセージ。
+const PAUSE_MESSAGE: &str = "nepl_debug pause";
+
+/// SIGINT ハンドラから立てられる中断要求。
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+const HELP: &str = "\
+commands:
+  break <line> | break <file>:<line>   set a breakpoint (b)
+  delete <line> | delete <file>:<line> remove a breakpoint (d)
+  run | continue                       run until the next breakpoint (r, c)
+  start                                stop before the first statement
+  step                                 next statement, entering calls (s)
+  next                                 next statement in this function (n)
+  finish                               run until the function returns (f)
+  bt                                   show the call stack
+  locals                               show locals of the current statement
+  print <name>                         show one local (p)
+  quit                                 stop debugging (q)";
+
+/// デバッグ対象のプログラム。
+pub(crate) struct DebugTarget<'a> {
+    pub artifact: &'a CompilationArtifact,
+    pub info: DebugInfo,
+    pub source_map: &'a SourceMap,
+    /// `break <line>` の対象ファイル。
+    pub entry: FileId,
+    /// ステップ実行で止まらないファイル（stdlib）。
+    pub library_files: Vec<FileId>,
+    pub target: CompileTarget,
+    pub args: Vec<String>,
+}
+
+struct HostState {
+    session: DebugSession,
+    /// 現在のスライス開始時点の消費燃料。
+    slice_start: u64,
+}
+
+enum Action {
+    Resume(StepCommand),
+    Quit,
+}
+
+/// デバッガを起動し、`input` のコマンドで操作する。戻り値は `main` の戻り値。
+pub(crate) fn run_debugger(
+    target: DebugTarget<'_>,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<i32> {
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, target.artifact.wasm.as_slice())
+        .context("failed to compile wasm artifact")?;
+    check_run_imports(&module, target.target, &[DEBUG_IMPORT_MODULE])?;
+
+    let mut session = DebugSession::new(target.info);
+    for file in &target.library_files {
+        session.skip_file(*file);
+    }
+    let host = Arc::new(Mutex::new(HostState {
+        session,
+        slice_start: 0,
+    }));
+    let mut linker: Linker<AllocState> = Linker::new(&engine);
+    if matches!(target.target, CompileTarget::Wasi | CompileTarget::Wasix) {
+        link_wasi_host(&mut linker)?;
+    }
+    link_debug_hooks(&mut linker, &host)?;
+
+    let mut store = Store::new(&engine, new_host_state(target.args));
+    store
+        .add_fuel(u64::MAX / 2)
+        .map_err(|e| anyhow::anyhow!("failed to enable fuel metering: {e}"))?;
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .context("failed to instantiate module")?
+        .start(&mut store)
+        .context("failed to start module")?;
+    let main = instance
+        .get_func(&store, "main")
+        .ok_or_else(|| anyhow::anyhow!("exported main function missing"))?;
+    let memory = instance.get_memory(&store, "memory");
+    let mut outputs = vec![Value::I32(0); main.ty(&store).results().len()];
+
+    let prompt = Prompt {
+        source_map: target.source_map,
+        entry: target.entry,
+        host: &host,
+    };
+    let command = match prompt.read_action(input, out, &[])? {
+        Action::Resume(command) => command,
+        Action::Quit => return Ok(0),
+    };
+    host.lock().unwrap().session.resume(command);
+    install_interrupt_handler();
+
+    let mut call = main
+        .call_resumable(&mut store, &[], &mut outputs)
+        .context("failed to execute main")?;
+    // true: main が最後まで実行された。false: 途中で quit した。
+    let result = loop {
+        let invocation = match call {
+            ResumableCall::Finished => break Ok(true),
+            ResumableCall::Resumable(invocation) => invocation,
+        };
+        if invocation.host_error().to_string() != PAUSE_MESSAGE {
+            break Err(anyhow::anyhow!("{}", invocation.host_error()));
+        }
+        let _ = flush_stdout_buffer(store.data_mut());
+        let data = memory.map(|m| m.data(&store)).unwrap_or(&[]);
+        prompt.show_location(out)?;
+        match prompt.read_action(input, out, data)? {
+            Action::Resume(command) => host.lock().unwrap().session.resume(command),
+            Action::Quit => break Ok(false),
+        }
+        call = invocation
+            .resume(&mut store, &[], &mut outputs)
+            .context("failed to execute main")?;
+    };
+    let _ = flush_stdout_buffer(store.data_mut());
+    restore_host_tty(store.data());
+    if !result? {
+        return Ok(0);
+    }
+    let code = match outputs.first() {
+        Some(Value::I32(v)) => *v,
+        _ => 0,
+    };
+    writeln!(out, "program exited with {code}")?;
+    Ok(code)
+}
+
+fn link_debug_hooks(linker: &mut Linker<AllocState>, host: &Arc<Mutex<HostState>>) -> Result<()> {
+    let h = host.clone();
+    linker.func_wrap(DEBUG_IMPORT_MODULE, "enter", move |function: i32| {
+        h.lock().unwrap().session.on_enter(function as usize)
+    })?;
+    let h = host.clone();
+    linker.func_wrap(DEBUG_IMPORT_MODULE, "leave", move || {
+        h.lock().unwrap().session.on_leave()
+    })?;
+    let h = host.clone();
+    linker.func_wrap(
+        DEBUG_IMPORT_MODULE,
+        "step",
+        move |caller: Caller<'_, AllocState>, site: i32| -> i32 {
+            let mut host = h.lock().unwrap();
+            let consumed = caller.fuel_consumed().unwrap_or(0);
+            if consumed.saturating_sub(host.slice_start) >= FUEL_SLICE {
+                host.slice_start = consumed;
+                if INTERRUPTED.swap(false, Ordering::SeqCst) {
+                    host.session.interrupt();
+                }
+            }
+            host.session.on_step(site as usize) as i32
+        },
+    )?;
+    let h = host.clone();
+    linker.func_wrap(DEBUG_IMPORT_MODULE, "local_i32", move |slot: i32, v: i32| {
+        h.lock().unwrap().session.on_local(slot as usize, DebugValue::I32(v))
+    })?;
+    let h = host.clone();
+    linker.func_wrap(DEBUG_IMPORT_MODULE, "local_i64", move |slot: i32, v: i64| {
+        h.lock().unwrap().session.on_local(slot as usize, DebugValue::I64(v))
+    })?;
+    let h = host.clone();
+    linker.func_wrap(DEBUG_IMPORT_MODULE, "local_f32", move |slot: i32, v: F32| {
+        h.lock().unwrap().session.on_local(slot as usize, DebugValue::F32(v.into()))
+    })?;
+    let h = host.clone();
+    linker.func_wrap(DEBUG_IMPORT_MODULE, "local_f64", move |slot: i32, v: F64| {
+        h.lock().unwrap().session.on_local(slot as usize, DebugValue::F64(v.into()))
+    })?;
+    linker.func_wrap(DEBUG_IMPORT_MODULE, "pause", || -> Result<(), Trap> {
+        Err(Trap::new(PAUSE_MESSAGE))
+    })?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn on_sigint(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    unsafe {
+        libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_interrupt_handler() {}
+
+struct Prompt<'a> {
+    source_map: &'a SourceMap,
+    entry: FileId,
+    host: &'a Arc<Mutex<HostState>>,
+}
+
+impl Prompt<'_> {
+    /// 実行再開か終了のコマンドが来るまで読み続ける。入力の終端は終了扱い。
+    fn read_action(
+        &self,
+        input: &mut dyn BufRead,
+        out: &mut dyn Write,
+        memory: &[u8],
+    ) -> Result<Action> {
+        loop {
+            write!(out, "(nepl-debug) ")?;
+            out.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                return Ok(Action::Quit);
+            }
+            let mut words = line.split_whitespace();
+            let Some(cmd) = words.next() else {
+                continue;
+            };
+            let arg = words.next();
+            let command = match cmd {
+                "r" | "run" | "c" | "continue" => StepCommand::Continue,
+                "start" | "s" | "step" => StepCommand::StepInto,
+                "n" | "next" => StepCommand::StepOver,
+                "f" | "finish" => StepCommand::StepOut,
+                "q" | "quit" => return Ok(Action::Quit),
+                "b" | "break" => {
+                    self.toggle_breakpoint(arg, true, out)?;
+                    continue;
+                }
+                "d" | "delete" => {
+                    self.toggle_breakpoint(arg, false, out)?;
+                    continue;
+                }
+                "bt" | "backtrace" => {
+                    self.show_backtrace(out)?;
+                    continue;
+                }
+                "locals" => {
+                    let host = self.host.lock().unwrap();
+                    let locals = host.session.locals(memory);
+                    if locals.is_empty() {
+                        writeln!(out, "no locals")?;
+                    }
+                    for (name, value) in locals {
+                        writeln!(out, "{name} = {value}")?;
+                    }
+                    continue;
+                }
+                "p" | "print" => {
+                    let Some(name) = arg else {
+                        writeln!(out, "usage: print <name>")?;
+                        continue;
+                    };
+                    let host = self.host.lock().unwrap();
+                    match host.session.locals(memory).into_iter().find(|(n, _)| n == name) {
+                        Some((_, value)) => writeln!(out, "{name} = {value}")?,
+                        None => writeln!(out, "no local named `{name}` here")?,
+                    }
+                    continue;
+                }
+                "h" | "help" => {
+                    writeln!(out, "{HELP}")?;
+                    continue;
+                }
+                other => {
+                    writeln!(out, "unknown command `{other}` (try `help`)")?;
+                    continue;
+                }
+            };
+            return Ok(Action::Resume(command));
+        }
+    }
+
+    /// `<line>` または `<file>:<line>`（1 始まり）を解決する。
+    fn resolve_location(&self, arg: &str) -> Option<(FileId, usize)> {
+        let (file, line) = match arg.rsplit_once(':') {
+            Some((name, line)) => {
+                let file = self
+                    .source_map
+                    .iter_paths()
+                    .find(|(_, path)| path.ends_with(Path::new(name)))?
+                    .0;
+                (file, line)
+            }
+            None => (self.entry, arg),
+        };
+        let line = line.parse::<usize>().ok()?.checked_sub(1)?;
+        Some((file, line))
+    }
+
+    fn toggle_breakpoint(&self, arg: Option<&str>, set: bool, out: &mut dyn Write) -> Result<()> {
+        let Some((file, line)) = arg.and_then(|a| self.resolve_location(a)) else {
+            writeln!(out, "usage: break <line> | break <file>:<line>")?;
+            return Ok(());
+        };
+        let mut host = self.host.lock().unwrap();
+        let sites = host.session.sites_on_line(self.source_map, file, line);
+        if sites.is_empty() {
+            writeln!(out, "no statement on line {}", line + 1)?;
+            return Ok(());
+        }
+        for site in sites {
+            if set {
+                host.session.set_breakpoint(site);
+            } else {
+                host.session.clear_breakpoint(site);
+            }
+        }
+        let verb = if set { "breakpoint set at" } else { "breakpoint removed at" };
+        writeln!(out, "{verb} {}:{}", self.path(file), line + 1)?;
+        Ok(())
+    }
+
+    fn show_location(&self, out: &mut dyn Write) -> Result<()> {
+        let host = self.host.lock().unwrap();
+        let Some(site) = host.session.paused_at() else {
+            return Ok(());
+        };
+        let function = host
+            .session
+            .function(site.function)
+            .map(|f| f.name.as_str())
+            .unwrap_or("?");
+        writeln!(out, "stopped in {function} at {}", self.describe(site.span))?;
+        let (line, _) = self
+            .source_map
+            .line_col(site.span.file_id, site.span.start)
+            .unwrap_or((0, 0));
+        if let Some(text) = self.source_map.line_str(site.span.file_id, line) {
+            writeln!(out, "  {:>4} | {}", line + 1, text)?;
+        }
+        Ok(())
+    }
+
+    fn show_backtrace(&self, out: &mut dyn Write) -> Result<()> {
+        let host = self.host.lock().unwrap();
+        for (depth, frame) in host.session.call_stack().enumerate() {
+            let Some(function) = host.session.function(frame.function) else {
+                continue;
+            };
+            let span = frame
+                .site
+                .and_then(|site| host.session.info().sites.get(site))
+                .map(|site| site.span)
+                .unwrap_or(function.span);
+            writeln!(out, "#{depth} {} at {}", function.name, self.describe(span))?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self, span: Span) -> String {
+        let (line, _) = self
+            .source_map
+            .line_col(span.file_id, span.start)
+            .unwrap_or((0, 0));
+        format!("{}:{}", self.path(span.file_id), line + 1)
+    }
+
+    fn path(&self, file: FileId) -> String {
+        self.source_map
+            .path(file)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "<unknown>".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nepl_core::loader::Loader;
+    use nepl_core::{compile_module_for_debugging, CompileOptions};
+    use std::path::PathBuf;
+
+    const SRC: &str = "#entry main
+#indent 4
+#target core
+#import \"core/math\" as *
+
+fn twice <(i32)->i32> (n):
+    let doubled mul n 2
+    doubled
+
+fn main <()->i32> ():
+    let a 20
+    let b twice a
+    add b 2
+";
+
+    fn debug_script(script: &str) -> (String, i32) {
+        let mut loader = Loader::new(crate::stdlib_root().unwrap());
+        let loaded = loader
+            .load_inline(PathBuf::from("dbg.nepl"), SRC.to_string())
+            .unwrap();
+        let source_map = loaded.source_map;
+        let (artifact, info) = compile_module_for_debugging(
+            loaded.module,
+            Some(&source_map),
+            CompileOptions {
+                target: Some(CompileTarget::Wasm),
+                verbose: false,
+                profile: None,
+            },
+        )
+        .unwrap();
+        let entry = source_map
+            .iter_paths()
+            .find(|(_, path)| path.ends_with("dbg.nepl"))
+            .unwrap()
+            .0;
+        let library_files = source_map
+            .iter_paths()
+            .map(|(id, _)| id)
+            .filter(|id| *id != entry)
+            .collect();
+        let mut out = Vec::new();
+        let code = run_debugger(
+            DebugTarget {
+                artifact: &artifact,
+                info,
+                source_map: &source_map,
+                entry,
+                library_files,
+                target: CompileTarget::Wasm,
+                args: Vec::new(),
+            },
+            &mut script.as_bytes(),
+            &mut out,
+        )
+        .unwrap();
+        (String::from_utf8(out).unwrap(), code)
+    }
+
+    #[test]
+    fn breakpoint_then_step_into_shows_stack_and_locals() {
+        let (out, code) = debug_script("break 12\nrun\nprint a\nstep\nbt\nlocals\ncontinue\n");
+        assert_eq!(code, 42);
+        assert!(out.contains("breakpoint set at dbg.nepl:12"), "{out}");
+        assert!(out.contains("stopped in main at dbg.nepl:12"), "{out}");
+        assert!(out.contains("a = 20"), "{out}");
+        assert!(out.contains("stopped in twice at dbg.nepl:7"), "{out}");
+        assert!(
+            out.contains("#0 twice at dbg.nepl:7\n#1 main at dbg.nepl:12"),
+            "{out}"
+        );
+        assert!(out.contains("n = 20"), "{out}");
+        assert!(out.contains("program exited with 42"), "{out}");
+    }
+
+    #[test]
+    fn next_steps_over_calls_and_quit_stops_the_program() {
+        let (out, _) = debug_script("start\nnext\nnext\nprint b\nquit\n");
+        assert!(out.contains("stopped in main at dbg.nepl:13"), "{out}");
+        assert!(!out.contains("stopped in twice"), "{out}");
+        assert!(out.contains("b = 40"), "{out}");
+        assert!(!out.contains("program exited"), "{out}");
+    }
+
+    #[test]
+    fn break_on_a_line_without_statements_is_reported() {
+        let (out, code) = debug_script("break 5\nrun\n");
+        assert!(out.contains("no statement on line 5"), "{out}");
+        assert_eq!(code, 42);
+    }
+}
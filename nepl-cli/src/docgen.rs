@@ -0,0 +1,984 @@
+//! `nepl-cli doc`: `//:` ドキュメントコメントから API ドキュメントの静的 HTML を生成する。
+//!
+//! `.nepl` を 1 ファイルずつ読み込んで型検査し、そのファイルで定義された項目を 1 ページにまとめる。
+//! シグネチャは型検査後の `TypeCtx` から組み立て、文書化した型には定義ページへのリンクを張る。
+//! 本文は `nm::render_document_html` で描く。`neplg2:test` ブロックは `nodesrc/html_gen.js` と同じ
+//! マークアップで出すので、`--playground` を指定すると playground runtime が実行ボタンを付ける。
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use nepl_core::ast::{Effect, GlobalKind, Module, Stmt, TypeDefKind};
+use nepl_core::diagnostic::Severity;
+use nepl_core::hir::{HirFunction, HirImpl, HirModule};
+use nepl_core::loader::{LoadResult, Loader};
+use nepl_core::nm::{self, BlockNode, InlineNode};
+use nepl_core::span::FileId;
+use nepl_core::typecheck::typecheck;
+use nepl_core::types::{TypeCtx, TypeId, TypeKind, TypeVar};
+use nepl_core::{BuildProfile, CompileTarget};
+
+use crate::detect_module_target;
+
+/// `nepl-cli doc` の出力設定。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DocOptions {
+    /// playground の資産（`playground_runtime.js` / `playground.css` / `search.js` / `nepl-web.js`）の置き場所。
+    /// 相対パスなら出力ディレクトリから見た位置として扱う。
+    pub playground: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ItemKind {
+    Type,
+    Trait,
+    Function,
+    Global,
+}
+
+impl ItemKind {
+    fn anchor_prefix(self) -> &'static str {
+        match self {
+            ItemKind::Type => "type",
+            ItemKind::Trait => "trait",
+            ItemKind::Function => "fn",
+            ItemKind::Global => "global",
+        }
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            ItemKind::Type => "Types",
+            ItemKind::Trait => "Traits",
+            ItemKind::Function => "Functions",
+            ItemKind::Global => "Constants and statics",
+        }
+    }
+}
+
+/// ページに載せる 1 項目。
+struct Item {
+    kind: ItemKind,
+    name: String,
+    anchor: String,
+    /// 型から組み立てたシグネチャ（HTML）。
+    signature: String,
+    doc: Option<String>,
+    /// 型に対する impl（HTML）。どのファイルで見つかったものもここに集める。
+    impls: BTreeSet<String>,
+}
+
+/// 1 ファイル分のページ。
+struct Page {
+    /// 出力ディレクトリからの相対パス（拡張子なし、`/` 区切り）。
+    rel: String,
+    doc: Option<String>,
+    items: Vec<Item>,
+}
+
+/// 読み込みと型検査を済ませた 1 ファイル。
+struct CheckedFile {
+    rel: String,
+    module: Module,
+    file_id: FileId,
+    hir: Option<HirModule>,
+    types: TypeCtx,
+}
+
+/// 型名から定義ページのアンカーへのリンク表。ページからの相対 URL を作るのに使う。
+struct Links {
+    types: BTreeMap<String, String>,
+    /// 現在のページから出力ディレクトリの根への接頭辞（`../` の繰り返し）。
+    root: String,
+}
+
+impl Links {
+    fn type_html(&self, name: &str) -> String {
+        match self.types.get(name) {
+            Some(url) => format!(
+                "<a class=\"doc-type\" href=\"{}{}\">{}</a>",
+                self.root,
+                escape(url),
+                escape(name)
+            ),
+            None => escape(name),
+        }
+    }
+}
+
+/// `input`（ファイルまたはディレクトリ）の `.nepl` を文書化して `out_dir` に書き出す。
+/// 書き出したページ数を返す。
+pub(crate) fn generate_docs(
+    input: &Path,
+    std_root: &Path,
+    out_dir: &Path,
+    options: &DocOptions,
+) -> Result<usize> {
+    let (base, mut files) = if input.is_dir() {
+        let mut files = Vec::new();
+        collect_sources(input, &mut files)
+            .with_context(|| format!("failed to scan {}", input.display()))?;
+        (input.to_path_buf(), files)
+    } else {
+        let base = input.parent().unwrap_or(Path::new("")).to_path_buf();
+        (base, vec![input.to_path_buf()])
+    };
+    files.sort();
+
+    let mut checked = Vec::new();
+    for file in &files {
+        let rel = file
+            .strip_prefix(&base)
+            .unwrap_or(file)
+            .with_extension("")
+            .to_string_lossy()
+            .replace('\\', "/");
+        match check_file(file, std_root, rel) {
+            Ok(file) => checked.push(file),
+            Err(e) => eprintln!("warning: skipping {}: {e:#}", file.display()),
+        }
+    }
+    if checked.is_empty() {
+        return Err(anyhow::anyhow!(
+            "no documentable sources in {}",
+            input.display()
+        ));
+    }
+
+    let mut type_urls = BTreeMap::new();
+    for file in &checked {
+        for (name, _) in type_defs(file) {
+            type_urls
+                .entry(name.clone())
+                .or_insert_with(|| format!("{}.html#type.{}", file.rel, name));
+        }
+    }
+
+    // impl は読み込んだ各ファイルの HIR に散らばるので、対象の型ごとに (ファイル, impl) を集める。
+    let mut impls: BTreeMap<String, Vec<(&CheckedFile, &HirImpl)>> = BTreeMap::new();
+    for file in &checked {
+        for imp in file.hir.iter().flat_map(|hir| &hir.impls) {
+            if let Some(head) = type_head(&file.types, imp.target_ty) {
+                impls.entry(head).or_default().push((file, imp));
+            }
+        }
+    }
+
+    let mut pages = Vec::new();
+    for file in &checked {
+        let links = Links {
+            types: type_urls.clone(),
+            root: root_prefix(&file.rel),
+        };
+        let mut page = build_page(file, &links);
+        let own = format!("{}.html#", file.rel);
+        for item in &mut page.items {
+            let defined_here = type_urls
+                .get(&item.name)
+                .is_some_and(|url| url.starts_with(&own));
+            if item.kind != ItemKind::Type || !defined_here {
+                continue;
+            }
+            // 同じ impl が複数のファイルから読み込まれるので、描いた文字列で重複を除く。
+            for (found, imp) in impls.get(&item.name).into_iter().flatten() {
+                item.impls.insert(impl_html(&found.types, imp, &links));
+            }
+        }
+        pages.push(page);
+    }
+
+    fs::create_dir_all(out_dir)
+        .with_context(|| format!("failed to create {}", out_dir.display()))?;
+    let search = search_index(&pages);
+    let search_json = search_index_json(&search);
+    write_file(&out_dir.join("search-index.json"), &search_json)?;
+    write_file(
+        &out_dir.join("search-index.js"),
+        &format!("window.NEPL_DOC_SEARCH_INDEX = {search_json};\n"),
+    )?;
+    let page_list = pages.iter().map(|p| p.rel.clone()).collect::<Vec<_>>();
+    for page in &pages {
+        let root = root_prefix(&page.rel);
+        let body = render_page(page);
+        let html = wrap_page(&page.rel, &body, &root, &page_list, &search_json, options);
+        write_file(&out_dir.join(format!("{}.html", page.rel)), &html)?;
+    }
+    let index = render_index(&pages);
+    let html = wrap_page("API", &index, "", &page_list, &search_json, options);
+    write_file(&out_dir.join("index.html"), &html)?;
+    Ok(pages.len())
+}
+
+fn collect_sources(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_sources(&path, out)?;
+        } else if path.extension().and_then(|s| s.to_str()) == Some("nepl") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn check_file(path: &Path, std_root: &Path, rel: String) -> Result<CheckedFile> {
+    let loaded = load_file(path, std_root)?;
+    let canonical = path.canonicalize().ok();
+    let file_id = loaded
+        .source_map
+        .iter_paths()
+        .find(|(_, p)| p.canonicalize().ok() == canonical)
+        .map(|(id, _)| id)
+        .unwrap_or(loaded.module.root.span.file_id);
+    let target = detect_module_target(&loaded.module).unwrap_or(CompileTarget::Wasm);
+    let tc = typecheck(
+        &loaded.module,
+        target,
+        BuildProfile::Debug,
+        Some(&loaded.source_map),
+    );
+    if tc.module.is_none() {
+        let first = tc
+            .diagnostics
+            .iter()
+            .find(|d| d.severity == Severity::Error)
+            .map(|d| d.message.as_str())
+            .unwrap_or("unknown error");
+        eprintln!(
+            "warning: {} does not type-check ({first}); signatures are omitted",
+            path.display()
+        );
+    }
+    Ok(CheckedFile {
+        rel,
+        file_id,
+        module: loaded.module,
+        hir: tc.module,
+        types: tc.types,
+    })
+}
+
+/// ファイルを入口として読み込む。prelude から import されるファイルは入口にすると
+/// 循環 import になるため、そのファイルを import するだけの入口を介して読み込み直す。
+fn load_file(path: &Path, std_root: &Path) -> Result<LoadResult> {
+    let mut loader = Loader::new(std_root.to_path_buf());
+    let err = match loader.load(&path.to_path_buf()) {
+        Ok(loaded) => return Ok(loaded),
+        Err(err) => err,
+    };
+    let (Ok(abs), Some(dir)) = (path.canonicalize(), path.parent()) else {
+        return Err(anyhow::anyhow!(err.to_string()));
+    };
+    // import 済みかどうかは解決後のパスで判定されるため、標準ライブラリのファイルは
+    // prelude と同じ `core/option` の形で指す。
+    let spec = match std_root
+        .canonicalize()
+        .ok()
+        .and_then(|root| abs.strip_prefix(root).ok().map(Path::to_path_buf))
+    {
+        Some(rel) => rel.with_extension("").to_string_lossy().replace('\\', "/"),
+        None => abs.to_string_lossy().into_owned(),
+    };
+    let src = format!("#import \"{spec}\" as *\n");
+    loader
+        .load_inline(dir.join("__nepl_doc__.nepl"), src)
+        .map_err(|_| anyhow::anyhow!(err.to_string()))
+}
+
+/// このファイルで定義された型（struct / enum / type / newtype）の名前と文書。
+fn type_defs(file: &CheckedFile) -> Vec<(&String, &Option<String>)> {
+    own_items(file)
+        .filter_map(|stmt| match stmt {
+            Stmt::StructDef(s) => Some((&s.name.name, &s.doc)),
+            Stmt::EnumDef(e) => Some((&e.name.name, &e.doc)),
+            Stmt::TypeDef(t) => Some((&t.name.name, &t.doc)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 読み込み時に合流した import 先を除いた、このファイル自身の項目。
+fn own_items(file: &CheckedFile) -> impl Iterator<Item = &Stmt> {
+    file.module.root.items.iter().filter(move |stmt| {
+        let span = match stmt {
+            Stmt::FnDef(f) => f.name.span,
+            Stmt::FnAlias(a) => a.name.span,
+            Stmt::StructDef(s) => s.name.span,
+            Stmt::EnumDef(e) => e.name.span,
+            Stmt::TypeDef(t) => t.span,
+            Stmt::Global(g) => g.span,
+            Stmt::Trait(t) => t.span,
+            _ => return false,
+        };
+        span.file_id == file.file_id
+    })
+}
+
+fn build_page(file: &CheckedFile, links: &Links) -> Page {
+    let types = &file.types;
+    let mut items = Vec::new();
+    let mut used = BTreeSet::new();
+    let mut push = |kind: ItemKind, name: &str, signature: String, doc: &Option<String>| {
+        // 多重定義の関数は同じ名前が並ぶので、2 つ目以降のアンカーに番号を付ける。
+        let base = format!("{}.{}", kind.anchor_prefix(), name);
+        let mut anchor = base.clone();
+        let mut n = 1;
+        while !used.insert(anchor.clone()) {
+            n += 1;
+            anchor = format!("{base}-{n}");
+        }
+        items.push(Item {
+            kind,
+            name: name.to_string(),
+            anchor,
+            signature,
+            doc: doc.clone(),
+            impls: BTreeSet::new(),
+        });
+    };
+    for stmt in own_items(file) {
+        match stmt {
+            Stmt::FnDef(f) => {
+                let hir = file
+                    .hir
+                    .as_ref()
+                    .and_then(|hir| hir.functions.iter().find(|hf| hf.span == f.name.span));
+                let signature = match hir {
+                    Some(hf) => fn_signature_html(types, &f.name.name, hf, links),
+                    None => format!("fn {}", escape(&f.name.name)),
+                };
+                push(ItemKind::Function, &f.name.name, signature, &f.doc);
+            }
+            Stmt::FnAlias(a) => {
+                let signature = format!("fn {} = {}", escape(&a.name.name), escape(&a.target.name));
+                push(ItemKind::Function, &a.name.name, signature, &a.doc);
+            }
+            Stmt::StructDef(s) => {
+                let mut signature = format!("struct {}", escape(&s.name.name));
+                if let Some(TypeKind::Struct {
+                    type_params,
+                    fields,
+                    field_names,
+                    ..
+                }) = types.lookup_named(&s.name.name).map(|ty| types.get(ty))
+                {
+                    signature.push_str(&type_args_html(types, &type_params, links));
+                    signature.push(':');
+                    for (name, ty) in field_names.iter().zip(fields) {
+                        signature.push_str(&format!(
+                            "\n    {} {}",
+                            escape(name),
+                            angle(&type_html(types, ty, links))
+                        ));
+                    }
+                }
+                push(ItemKind::Type, &s.name.name, signature, &s.doc);
+            }
+            Stmt::EnumDef(e) => {
+                let mut signature = format!("enum {}", escape(&e.name.name));
+                if let Some(TypeKind::Enum {
+                    type_params,
+                    variants,
+                    ..
+                }) = types.lookup_named(&e.name.name).map(|ty| types.get(ty))
+                {
+                    signature.push_str(&type_args_html(types, &type_params, links));
+                    signature.push(':');
+                    for variant in variants {
+                        signature.push_str(&format!("\n    {}", escape(&variant.name)));
+                        if let Some(payload) = variant.payload {
+                            signature.push(' ');
+                            signature.push_str(&angle(&type_html(types, payload, links)));
+                        }
+                    }
+                }
+                push(ItemKind::Type, &e.name.name, signature, &e.doc);
+            }
+            Stmt::TypeDef(t) => {
+                let keyword = match t.kind {
+                    TypeDefKind::Alias => "type",
+                    TypeDefKind::Newtype => "newtype",
+                };
+                let target = match t.kind {
+                    TypeDefKind::Alias => types.lookup_alias(&t.name.name).map(|a| a.target),
+                    TypeDefKind::Newtype => {
+                        types
+                            .lookup_named(&t.name.name)
+                            .and_then(|ty| match types.get(ty) {
+                                TypeKind::Newtype { inner, .. } => Some(inner),
+                                _ => None,
+                            })
+                    }
+                };
+                let mut signature = format!("{keyword} {}", escape(&t.name.name));
+                if let Some(target) = target {
+                    signature.push_str(" = ");
+                    signature.push_str(&type_html(types, target, links));
+                }
+                push(ItemKind::Type, &t.name.name, signature, &t.doc);
+            }
+            Stmt::Trait(t) => {
+                let mut signature = format!("trait {}", escape(&t.name.name));
+                let info = file
+                    .hir
+                    .as_ref()
+                    .and_then(|hir| hir.traits.iter().find(|ht| ht.span == t.name.span));
+                if let Some(info) = info {
+                    signature.push_str(&type_args_html(types, &info.type_params, links));
+                    signature.push(':');
+                    for (name, ty) in &info.methods {
+                        signature.push_str(&format!(
+                            "\n    fn {} {}",
+                            escape(name),
+                            angle(&type_html(types, *ty, links))
+                        ));
+                    }
+                }
+                push(ItemKind::Trait, &t.name.name, signature, &t.doc);
+            }
+            Stmt::Global(g) => {
+                let keyword = match g.kind {
+                    GlobalKind::Const => "const",
+                    GlobalKind::Static { mutable: false } => "static",
+                    GlobalKind::Static { mutable: true } => "static mut",
+                };
+                let mut signature = format!("{keyword} {}", escape(&g.name.name));
+                let global = file
+                    .hir
+                    .as_ref()
+                    .and_then(|hir| hir.globals.iter().find(|hg| hg.span == g.span));
+                if let Some(global) = global {
+                    signature.push(' ');
+                    signature.push_str(&angle(&type_html(types, global.ty, links)));
+                }
+                push(ItemKind::Global, &g.name.name, signature, &g.doc);
+            }
+            _ => {}
+        }
+    }
+    items.sort_by_key(|item| item.kind);
+    Page {
+        rel: file.rel.clone(),
+        doc: file.module.doc.clone(),
+        items,
+    }
+}
+
+/// `fn name <.T> <(A,B)->R>` の形のシグネチャ。
+fn fn_signature_html(types: &TypeCtx, name: &str, function: &HirFunction, links: &Links) -> String {
+    let mut out = format!("fn {}", escape(name));
+    if let TypeKind::Function { type_params, .. } = types.get(function.func_ty) {
+        if !type_params.is_empty() {
+            out.push(' ');
+            out.push_str(&type_args_html(types, &type_params, links));
+        }
+    }
+    out.push(' ');
+    out.push_str(&angle(&type_html(types, function.func_ty, links)));
+    if !function.params.is_empty() {
+        let params = function
+            .params
+            .iter()
+            .map(|p| escape(&p.name))
+            .collect::<Vec<_>>();
+        out.push_str(&format!(" ({})", params.join(", ")));
+    }
+    out
+}
+
+fn impl_html(types: &TypeCtx, imp: &HirImpl, links: &Links) -> String {
+    let trait_name = imp.trait_base_name.as_deref().unwrap_or(&imp.trait_name);
+    let mut out = format!("impl {}", escape(trait_name));
+    out.push_str(&type_args_html(types, &imp.trait_args, links));
+    out.push_str(" for ");
+    out.push_str(&type_html(types, imp.target_ty, links));
+    for method in &imp.methods {
+        out.push_str(&format!(
+            "\n    fn {} {}",
+            escape(&method.name),
+            angle(&type_html(types, method.func.func_ty, links))
+        ));
+    }
+    out
+}
+
+/// 宣言側の型引数（`<.T,.U>`）。
+fn type_args_html(types: &TypeCtx, args: &[TypeId], links: &Links) -> String {
+    if args.is_empty() {
+        return String::new();
+    }
+    let args = args
+        .iter()
+        .map(|a| type_html(types, *a, links))
+        .collect::<Vec<_>>();
+    angle(&args.join(","))
+}
+
+fn angle(inner: &str) -> String {
+    format!("&lt;{inner}&gt;")
+}
+
+/// 型をソースの記法で書き、名前付き型は定義へのリンクにする。
+/// 記法の無いもの（タプルなど）は `TypeCtx::type_to_string` の表記を使う。
+fn type_html(types: &TypeCtx, ty: TypeId, links: &Links) -> String {
+    let ty = types.resolve_id(ty);
+    match types.get(ty) {
+        TypeKind::Unit => "()".to_string(),
+        TypeKind::Struct {
+            name, type_params, ..
+        }
+        | TypeKind::Enum {
+            name, type_params, ..
+        }
+        | TypeKind::Newtype {
+            name, type_params, ..
+        } => links.type_html(&name) + &type_args_html(types, &type_params, links),
+        TypeKind::Named(name) => links.type_html(&name),
+        TypeKind::Apply { base, args } => {
+            let base = match type_head(types, base) {
+                Some(name) => links.type_html(&name),
+                None => type_html(types, base, links),
+            };
+            base + &type_args_html(types, &args, links)
+        }
+        TypeKind::Function {
+            params,
+            result,
+            effect,
+            ..
+        } => {
+            let params = params
+                .iter()
+                .map(|p| type_html(types, *p, links))
+                .collect::<Vec<_>>();
+            let arrow = match effect {
+                Effect::Pure => "-&gt;",
+                Effect::Impure => "*&gt;",
+            };
+            format!(
+                "({}){arrow}{}",
+                params.join(","),
+                type_html(types, result, links)
+            )
+        }
+        TypeKind::Reference(inner, is_mut) => {
+            let prefix = if is_mut { "&amp;mut " } else { "&amp;" };
+            format!("{prefix}{}", type_html(types, inner, links))
+        }
+        TypeKind::Box(inner) => format!("Box{}", angle(&type_html(types, inner, links))),
+        TypeKind::Var(TypeVar {
+            label: Some(label), ..
+        }) if label != "Self" => format!(".{}", escape(label.trim_start_matches('.'))),
+        _ => escape(&types.type_to_string(ty)),
+    }
+}
+
+/// 型の先頭にある名前付き型（`Vec<.T>` なら `Vec`）。
+fn type_head(types: &TypeCtx, ty: TypeId) -> Option<String> {
+    match types.get(types.resolve_id(ty)) {
+        TypeKind::Struct { name, .. }
+        | TypeKind::Enum { name, .. }
+        | TypeKind::Newtype { name, .. }
+        | TypeKind::Named(name) => Some(name),
+        TypeKind::Apply { base, .. } => type_head(types, base),
+        _ => None,
+    }
+}
+
+fn root_prefix(rel: &str) -> String {
+    "../".repeat(rel.matches('/').count())
+}
+
+fn render_page(page: &Page) -> String {
+    let mut out = format!("<h1 class=\"doc-module\">{}</h1>\n", escape(&page.rel));
+    if let Some(doc) = &page.doc {
+        out.push_str(&render_doc_html(doc, 1, ""));
+    }
+    let mut current = None;
+    for item in &page.items {
+        if current != Some(item.kind) {
+            current = Some(item.kind);
+            out.push_str(&format!(
+                "<h2 class=\"doc-group\">{}</h2>\n",
+                item.kind.heading()
+            ));
+        }
+        out.push_str(&format!(
+            "<section class=\"doc-item\" id=\"{}\">\n<pre class=\"doc-sig\"><code>{}</code></pre>\n",
+            escape(&item.anchor),
+            item.signature
+        ));
+        if let Some(doc) = &item.doc {
+            out.push_str(&render_doc_html(doc, 2, &item.anchor));
+        }
+        if !item.impls.is_empty() {
+            out.push_str(
+                "<h4 class=\"doc-impls\">Implementations</h4>\n<ul class=\"doc-impls\">\n",
+            );
+            for imp in &item.impls {
+                out.push_str(&format!(
+                    "<li><pre class=\"doc-sig\"><code>{imp}</code></pre></li>\n"
+                ));
+            }
+            out.push_str("</ul>\n");
+        }
+        out.push_str("</section>\n");
+    }
+    out
+}
+
+fn render_index(pages: &[Page]) -> String {
+    let mut out = String::from("<h1 class=\"doc-module\">API</h1>\n<ul class=\"doc-index\">\n");
+    for page in pages {
+        out.push_str(&format!(
+            "<li><a href=\"{}.html\">{}</a>",
+            escape(&page.rel),
+            escape(&page.rel)
+        ));
+        if let Some(summary) = page.doc.as_deref().and_then(doc_summary) {
+            out.push_str(&format!(
+                " — {}",
+                nm::render_inlines_html(&nm::parse_inlines(&summary))
+            ));
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ul>\n");
+    out
+}
+
+/// 文書の最初の段落（見出しの直後にあるものも含む）。
+fn doc_summary(doc: &str) -> Option<String> {
+    fn first(blocks: &[BlockNode]) -> Option<String> {
+        blocks.iter().find_map(|block| match block {
+            BlockNode::Paragraph(p) => Some(inlines_source(&p.inlines)),
+            BlockNode::Section(s) => first(&s.children),
+            _ => None,
+        })
+    }
+    first(&nm::parse_document(doc).children).map(|s| s.lines().next().unwrap_or("").to_string())
+}
+
+/// 段落を元の nm 記法に戻す（要約を `parse_inlines` で描き直すため）。
+fn inlines_source(inlines: &[InlineNode]) -> String {
+    let doc = nm::Document {
+        children: vec![BlockNode::Paragraph(nm::ParagraphNode {
+            inlines: inlines.to_vec(),
+        })],
+    };
+    nm::render_document_markdown(&doc)
+}
+
+/// `//:` の本文を HTML にする。`shift` は見出しの段を下げる数、`id_prefix` は節の id の接頭辞。
+fn render_doc_html(doc: &str, shift: usize, id_prefix: &str) -> String {
+    let options = nm::HtmlOptions {
+        playground: true,
+        heading_offset: shift,
+        id_prefix: id_prefix.to_string(),
+        ..nm::HtmlOptions::default()
+    };
+    nm::render_document_html(&nm::parse_document(doc), &options)
+}
+
+/// 検索用の本文。ルビは親字と読みの両方を残して、どちらでも引けるようにする。
+fn search_text(inlines: &[InlineNode], out: &mut String) {
+    for inline in inlines {
+        match inline {
+            InlineNode::Text(text) | InlineNode::CodeInline(text) => out.push_str(text),
+            InlineNode::Math { text, .. } => out.push_str(text),
+            InlineNode::Ruby { base, ruby } => {
+                search_text(base, out);
+                out.push(' ');
+                search_text(ruby, out);
+                out.push(' ');
+            }
+            InlineNode::Gloss { base, notes } => {
+                search_text(base, out);
+                for note in notes {
+                    out.push(' ');
+                    search_text(note, out);
+                }
+                out.push(' ');
+            }
+            InlineNode::Link { text, .. } => search_text(text, out),
+        }
+    }
+}
+
+fn search_blocks(blocks: &[BlockNode], out: &mut String) {
+    for block in blocks {
+        match block {
+            BlockNode::Section(s) => {
+                search_text(&s.heading, out);
+                out.push(' ');
+                search_blocks(&s.children, out);
+            }
+            BlockNode::Paragraph(p) => {
+                let doctest = matches!(
+                    p.inlines.as_slice(),
+                    [InlineNode::Text(text)] if text.trim_start().starts_with("neplg2:test")
+                );
+                if !doctest {
+                    search_text(&p.inlines, out);
+                    out.push(' ');
+                }
+            }
+            BlockNode::List(list) => {
+                for item in &list.items {
+                    search_text(item, out);
+                    out.push(' ');
+                }
+            }
+            BlockNode::Code(_) | BlockNode::Hr => {}
+        }
+    }
+}
+
+/// `nodesrc/search.js` の SearchEntry（`{ id, title, url, body }`）。
+struct SearchEntry {
+    title: String,
+    url: String,
+    body: String,
+}
+
+fn search_index(pages: &[Page]) -> Vec<SearchEntry> {
+    let body_of = |doc: &Option<String>| {
+        let mut body = String::new();
+        if let Some(doc) = doc {
+            search_blocks(&nm::parse_document(doc).children, &mut body);
+        }
+        body.split_whitespace().collect::<Vec<_>>().join(" ")
+    };
+    let mut entries = Vec::new();
+    for page in pages {
+        entries.push(SearchEntry {
+            title: page.rel.clone(),
+            url: format!("{}.html", page.rel),
+            body: body_of(&page.doc),
+        });
+        for item in &page.items {
+            entries.push(SearchEntry {
+                title: format!("{} ({})", item.name, page.rel),
+                url: format!("{}.html#{}", page.rel, item.anchor),
+                body: body_of(&item.doc),
+            });
+        }
+    }
+    entries
+}
+
+fn search_index_json(entries: &[SearchEntry]) -> String {
+    let rows = entries
+        .iter()
+        .enumerate()
+        .map(|(id, e)| {
+            format!(
+                "{{\"id\":{id},\"title\":{},\"url\":{},\"body\":{}}}",
+                json_string(&e.title),
+                json_string(&e.url),
+                json_string(&e.body)
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", rows.join(",\n"))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            // `</script>` で埋め込み先の script 要素が閉じないようにする。
+            '<' => out.push_str("\\u003c"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+const STYLE: &str = r#":root{--bg:#0b0f19;--fg:#e6edf3;--muted:#aab6c3;--card:#121a2a;--border:#23304a;--code:#0f1626;--accent:#7aa2f7;}
+html,body{background:var(--bg);color:var(--fg);font-family:system-ui,-apple-system,Segoe UI,Roboto,Helvetica,Arial;line-height:1.65;}
+main{max-width:980px;margin:24px auto;padding:0 16px;}
+a{color:var(--accent);}
+nav.doc-nav{display:flex;gap:12px;align-items:center;max-width:980px;margin:12px auto;padding:0 16px;}
+#doc-search{flex:1;padding:6px 10px;border-radius:8px;border:1px solid var(--border);background:var(--card);color:var(--fg);}
+#doc-search-results{max-width:980px;margin:0 auto;padding:0 16px 0 40px;}
+.doc-item{border-left:3px solid var(--border);padding:0.25em 1em;margin:1.5em 0;}
+.doc-sig{background:var(--code);padding:10px 12px;border-radius:8px;overflow:auto;margin:0.5em 0;}
+.doc-sig code{font-family:ui-monospace,SFMono-Regular,Menlo,Monaco,Consolas,monospace;font-size:13px;}
+.doc-impls{list-style:none;padding-left:0;}
+.nm-code-container{border:1px solid var(--border);border-radius:12px;background:var(--card);margin:16px 0;overflow:hidden;}
+.nm-code-header{display:flex;gap:8px;padding:6px 12px;border-bottom:1px solid var(--border);}
+.nm-badge-main{padding:2px 8px;border-radius:6px;background:#7aa2f7;color:#1a202e;font-size:11px;font-weight:bold;}
+.nm-badge-flag{padding:2px 8px;border-radius:6px;border:1px solid var(--border);color:var(--muted);font-size:11px;}
+.nm-code{background:var(--code);padding:12px;overflow:auto;margin:0;}
+.nm-code code{font-family:ui-monospace,SFMono-Regular,Menlo,Monaco,Consolas,monospace;font-size:13px;white-space:pre;}
+.nm-code-inline{background:rgba(255,255,255,0.06);border-radius:6px;padding:1px 6px;}
+.nm-code-footer{padding:8px 12px;border-top:1px solid var(--border);}
+.nm-doctest-row{display:flex;gap:8px;margin:4px 0;}
+.nm-doctest-badge{min-width:56px;text-align:center;padding:2px 8px;border-radius:999px;border:1px solid var(--border);color:var(--muted);font-size:11px;}
+.nm-doctest-pre{margin:0;white-space:pre-wrap;flex:1;}
+.nm-hidden{display:none;}
+ruby rt{font-size:0.6em;opacity:0.95;}
+"#;
+
+/// 単体で開けるページ。`--playground` があれば tutorial と同じ runtime を読み込む。
+fn wrap_page(
+    title: &str,
+    body: &str,
+    root: &str,
+    pages: &[String],
+    search_json: &str,
+    options: &DocOptions,
+) -> String {
+    let title = escape(title);
+    let Some(assets) = &options.playground else {
+        return format!(
+            r#"<!doctype html>
+<html lang="ja">
+<head>
+<meta charset="utf-8"/>
+<meta name="viewport" content="width=device-width,initial-scale=1"/>
+<title>{title}</title>
+<style>
+{STYLE}</style>
+<script src="{root}search-index.js"></script>
+</head>
+<body>
+<nav class="doc-nav"><a href="{root}index.html">API</a><input id="doc-search" type="search" placeholder="Search"/></nav>
+<ul id="doc-search-results"></ul>
+<main>
+{body}</main>
+<script>
+(function(){{
+  const input = document.getElementById('doc-search');
+  const list = document.getElementById('doc-search-results');
+  input.addEventListener('input', () => {{
+    const q = input.value.trim().toLowerCase();
+    list.innerHTML = '';
+    if (!q) return;
+    for (const e of window.NEPL_DOC_SEARCH_INDEX || []) {{
+      if (!(e.title + ' ' + e.body).toLowerCase().includes(q)) continue;
+      const li = document.createElement('li');
+      const a = document.createElement('a');
+      a.href = '{root}' + e.url;
+      a.textContent = e.title;
+      li.appendChild(a);
+      list.appendChild(li);
+      if (list.children.length >= 30) break;
+    }}
+  }});
+}})();
+</script>
+</body>
+</html>
+"#
+        );
+    };
+    let assets = if assets.starts_with('/') || assets.contains("://") {
+        assets.clone()
+    } else {
+        format!("{root}{assets}")
+    };
+    let assets = match assets.ends_with('/') {
+        true => assets,
+        false => format!("{assets}/"),
+    };
+    let toc = pages
+        .iter()
+        .map(|rel| {
+            format!(
+                "<li><a class=\"toc-link depth-0\" href=\"{root}{}.html\">{}</a></li>",
+                escape(rel),
+                escape(rel)
+            )
+        })
+        .collect::<String>();
+    format!(
+        r#"<!doctype html>
+<html lang="ja">
+<head>
+<meta charset="utf-8"/>
+<meta name="viewport" content="width=device-width,initial-scale=1"/>
+<title>{title}</title>
+<link rel="stylesheet" href="{assets}playground.css">
+<script src="{assets}search.js"></script>
+<script type="module">
+  import {{ initPlayground }} from '{assets}playground_runtime.js';
+  initPlayground({{
+    searchIndex: {search_json},
+    rootPrefix: '{root}',
+    vfsOverrides: {{}},
+    moduleJsPath: '{assets}nepl-web.js',
+    tocHtml: {toc},
+    tocTitle: 'API',
+    title: '{title}'
+  }});
+</script>
+</head>
+<body>
+<div class="doc-layout">
+<main>
+{body}</main>
+</div>
+</body>
+</html>
+"#,
+        toc = json_string(&toc)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doctest_blocks_keep_metadata_and_hidden_lines() {
+        let html = render_doc_html(
+            "neplg2:test[normalize_newlines]\nret: 3\n```neplg2\n| #entry main\nfn main <()->i32> ():\n    3\n```\n",
+            1,
+            "fn.main",
+        );
+        assert!(html.contains("<span class=\"nm-badge-flag\">normalize_newlines</span>"));
+        assert!(html.contains("<span class=\"nm-hidden\">"));
+        assert!(html.contains("<code class=\"language-neplg2\">"));
+        assert!(html.contains("<code class=\"nm-doctest-inline\">3</code>"));
+    }
+
+    #[test]
+    fn item_doc_sections_are_prefixed_with_the_anchor() {
+        let html = render_doc_html("# Examples\n\ntext\n", 2, "fn.map");
+        assert!(html.contains("<section class=\"nm-sec level-3\" id=\"fn.map-examples\"><h3>"));
+    }
+
+    #[test]
+    fn ruby_is_searchable_by_base_and_reading() {
+        let mut body = String::new();
+        search_text(&nm::parse_inlines("[漢字/かんじ]を[読/よ]む"), &mut body);
+        assert!(body.contains("漢字"));
+        assert!(body.contains("かんじ"));
+    }
+}
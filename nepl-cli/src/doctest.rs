@@ -0,0 +1,428 @@
+//! `nepl-cli test` で `.n.md` の `neplg2:test` ケースを実行する。
+//!
+//! ケースの切り出しは `nodesrc/parser.js` と同じ規則に従う。マーカー行から次の ```` ```neplg2 ````
+//! までをメタデータとして読み、コード中の `|`（非表示行）の接頭辞を取り除く。
+//! 判定は `nodesrc/tests.js` に合わせ、`ret:` / `stdout:` / `diag_id:` を確認する。
+//! `fuel:` / `max_memory_pages:` / `timeout:` はそのケースだけ資源制限を上書きする。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use nepl_core::diagnostic::Severity;
+use nepl_core::error::CoreError;
+use nepl_core::loader::{Loader, LoaderError};
+use nepl_core::{compile_module_with_source_map, CompileOptions, CompileTarget};
+use nepl_wasi::SharedBuffer;
+
+use crate::limits::RunLimits;
+use crate::{host_state, host_wasi, run_wasm_with_state};
+
+/// wasmi 上では実行しないケースのタグ。
+const IGNORED_TAGS: &[&str] = &["skip", "skip_wasm", "llvm_cli", "llvm_only"];
+
+/// `.n.md` から切り出した 1 ケース。
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DocCase {
+    /// 直前の見出し（無ければ空）。
+    pub heading: String,
+    pub tags: Vec<String>,
+    /// マーカーとコードの間のメタデータ（`key: value`）。
+    pub meta: Vec<(String, String)>,
+    pub source: String,
+}
+
+/// 1 ケースの判定結果。
+pub(crate) enum CaseOutcome {
+    Passed,
+    Ignored,
+    Failed(anyhow::Error),
+}
+
+impl DocCase {
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    fn meta(&self, key: &str) -> Option<&str> {
+        self.meta
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn ignored(&self) -> bool {
+        IGNORED_TAGS.iter().any(|t| self.has_tag(t))
+            || self
+                .source
+                .lines()
+                .any(|l| l.trim() == "#target llvm")
+    }
+}
+
+/// `.n.md` 1 ファイル分のケースを読む。
+pub(crate) fn collect_doc_cases(path: &Path) -> Result<Vec<DocCase>> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(parse_doc_cases(&text))
+}
+
+fn parse_doc_cases(text: &str) -> Vec<DocCase> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut cases = Vec::new();
+    let mut heading = String::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        i += 1;
+        if line.starts_with('#') && !line.starts_with("#!") {
+            let title = line.trim_start_matches('#');
+            if title.starts_with(' ') {
+                heading = title.trim().to_string();
+            }
+            continue;
+        }
+        let Some(rest) = line.strip_prefix("neplg2:test") else {
+            continue;
+        };
+        let tags: Vec<String> = match rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            Some(list) => list
+                .split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            None if rest.is_empty() => Vec::new(),
+            None => continue,
+        };
+        let mut meta = Vec::new();
+        while i < lines.len() && lines[i].trim() != "```neplg2" {
+            if let Some((key, value)) = lines[i].trim().split_once(':') {
+                meta.push((key.trim().to_string(), value.trim().to_string()));
+            }
+            i += 1;
+        }
+        i += 1;
+        let mut source = String::new();
+        while i < lines.len() && lines[i].trim() != "```" {
+            let line = lines[i];
+            let line = match line.strip_prefix('|') {
+                Some(hidden) => hidden.strip_prefix(' ').unwrap_or(hidden),
+                None => line,
+            };
+            source.push_str(line);
+            source.push('\n');
+            i += 1;
+        }
+        i += 1;
+        cases.push(DocCase {
+            heading: heading.clone(),
+            tags,
+            meta,
+            source,
+        });
+    }
+    cases
+}
+
+/// JSON 文字列として書かれた値（`"a\nb"`）を戻す。引用符の無い値はそのまま返す。
+fn parse_meta_string(raw: &str) -> String {
+    let Some(body) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) else {
+        return raw.to_string();
+    };
+    let mut out = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    out.push(c);
+                }
+            }
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// `["--flag", "value"]` 形式の文字列配列を読む。
+fn parse_meta_list(raw: &str) -> Result<Vec<String>> {
+    let body = raw
+        .trim()
+        .strip_prefix('[')
+        .and_then(|r| r.strip_suffix(']'))
+        .ok_or_else(|| anyhow::anyhow!("expected a list like [\"a\", \"b\"]: {raw}"))?;
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in body.chars() {
+        if in_string {
+            current.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == ',' {
+            items.push(parse_meta_string(current.trim()));
+            current.clear();
+        } else {
+            in_string = c == '"';
+            current.push(c);
+        }
+    }
+    if !current.trim().is_empty() {
+        items.push(parse_meta_string(current.trim()));
+    }
+    Ok(items)
+}
+
+fn normalize_output(text: &str, case: &DocCase) -> String {
+    let mut out = text.to_string();
+    if case.has_tag("normalize_newlines") {
+        out = out.replace("\r\n", "\n").replace('\r', "\n");
+    }
+    if case.has_tag("strip_ansi") {
+        out = strip_ansi(&out);
+    }
+    out
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            chars.next();
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// ケースを `path` の位置にあるものとしてコンパイル・実行し、メタデータと照合する。
+/// 制限に掛かった場合は `limits::LimitExceeded` を含む `Failed` になる。
+pub(crate) fn run_doc_case(
+    case: &DocCase,
+    path: &Path,
+    std_root: &Path,
+    limits: RunLimits,
+) -> CaseOutcome {
+    if case.ignored() {
+        return CaseOutcome::Ignored;
+    }
+    match check_doc_case(case, path, std_root, limits) {
+        Ok(()) => CaseOutcome::Passed,
+        Err(err) => CaseOutcome::Failed(err),
+    }
+}
+
+fn check_doc_case(case: &DocCase, path: &Path, std_root: &Path, mut limits: RunLimits) -> Result<()> {
+    let mut expected_ids = Vec::new();
+    for (key, value) in &case.meta {
+        if limits.apply_meta(key, value)? {
+            continue;
+        }
+        if key == "diag_id" || key == "diag_ids" {
+            for id in value.trim_matches(['[', ']']).split(',') {
+                let id = id.trim();
+                if !id.is_empty() {
+                    expected_ids.push(
+                        id.parse::<u32>()
+                            .with_context(|| format!("invalid diag_id: {id}"))?,
+                    );
+                }
+            }
+        }
+    }
+
+    let mut loader = Loader::new(std_root.to_path_buf());
+    let compiled = loader
+        .load_inline(PathBuf::from(path), case.source.clone())
+        .map_err(|e| match e {
+            LoaderError::Core(core) => core,
+            LoaderError::Io(io) => CoreError::Io(io),
+        })
+        .and_then(|loaded| {
+            compile_module_with_source_map(
+                loaded.module,
+                Some(&loaded.source_map),
+                CompileOptions {
+                    target: Some(CompileTarget::Wasi),
+                    verbose: false,
+                    profile: None,
+                },
+            )
+        });
+
+    if case.has_tag("compile_fail") {
+        return match compiled {
+            Ok(_) => Err(anyhow::anyhow!(
+                "expected compile_fail, but compiled successfully"
+            )),
+            Err(CoreError::Diagnostics(diags)) => {
+                let ids: Vec<u32> = diags
+                    .iter()
+                    .filter(|d| matches!(d.severity, Severity::Error))
+                    .filter_map(|d| d.id.map(|id| id.as_u32()))
+                    .collect();
+                let missing: Vec<u32> = expected_ids
+                    .iter()
+                    .copied()
+                    .filter(|id| !ids.contains(id))
+                    .collect();
+                if missing.is_empty() {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!(
+                        "compile_fail diagnostic id mismatch\nexpected ids: {expected_ids:?}\nactual ids:   {ids:?}"
+                    ))
+                }
+            }
+            Err(e) => Err(anyhow::anyhow!(e.to_string())),
+        };
+    }
+    let artifact = match compiled {
+        Ok(artifact) => artifact,
+        Err(CoreError::Diagnostics(diags)) => {
+            let messages: Vec<String> = diags
+                .iter()
+                .filter(|d| matches!(d.severity, Severity::Error))
+                .map(|d| d.message.clone())
+                .collect();
+            return Err(anyhow::anyhow!(
+                "compilation failed\n{}",
+                messages.join("\n")
+            ));
+        }
+        Err(e) => return Err(anyhow::anyhow!(e.to_string())),
+    };
+
+    let mut args = vec![path.display().to_string()];
+    if let Some(argv) = case.meta("argv") {
+        args.extend(parse_meta_list(argv)?);
+    }
+    // stdout はホストへ書かずに取り込み、期待値と比較する。
+    let stdout = SharedBuffer::new();
+    let mut wasi = host_wasi(args).stdout(stdout.clone());
+    if let Some(stdin) = case.meta("stdin") {
+        wasi = wasi.stdin_bytes(parse_meta_string(stdin));
+    }
+    let result = run_wasm_with_state(
+        artifact.wasm,
+        CompileTarget::Wasi,
+        host_state(wasi.build()),
+        limits,
+    );
+
+    if case.has_tag("should_panic") {
+        return match result {
+            Err(err) if err.downcast_ref::<crate::limits::LimitExceeded>().is_some() => Err(err),
+            Err(_) => Ok(()),
+            Ok(_) => Err(anyhow::anyhow!(
+                "expected should_panic, but program finished without trap"
+            )),
+        };
+    }
+    let (code, _) = result?;
+    let stdout = stdout.to_string_lossy();
+    if let Some(ret) = case.meta("ret") {
+        let expected: i32 = ret
+            .parse()
+            .with_context(|| format!("invalid ret: {ret}"))?;
+        if code != expected {
+            return Err(anyhow::anyhow!(
+                "return value mismatch\nexpected: {expected}\nactual:   {code}"
+            ));
+        }
+    }
+    if let Some(expected) = case.meta("stdout") {
+        let expected = normalize_output(&parse_meta_string(expected), case);
+        let actual = normalize_output(&stdout, case);
+        if expected != actual {
+            return Err(anyhow::anyhow!(
+                "stdout mismatch\nexpected: {expected:?}\nactual:   {actual:?}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cases_carry_heading_tags_and_metadata() {
+        let text = "# lib\n\n## greet\n\nneplg2:test[assert_io, normalize_newlines]\nargv: [\"--flag\", \"a,b\"]\nstdout: \"hi\\n\"\n```neplg2\n| #entry main\nfn main <()->i32> ():\n    0\n```\n\nneplg2:test[compile_fail]\ndiag_id: 1001\n```neplg2\nbad\n```\n";
+        let cases = parse_doc_cases(text);
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].heading, "greet");
+        assert_eq!(cases[0].tags, vec!["assert_io", "normalize_newlines"]);
+        assert_eq!(cases[0].source, "#entry main\nfn main <()->i32> ():\n    0\n");
+        assert_eq!(
+            parse_meta_list(cases[0].meta("argv").unwrap()).unwrap(),
+            vec!["--flag", "a,b"]
+        );
+        assert_eq!(parse_meta_string(cases[0].meta("stdout").unwrap()), "hi\n");
+        assert_eq!(cases[1].meta("diag_id"), Some("1001"));
+        assert!(cases[1].has_tag("compile_fail"));
+    }
+
+    #[test]
+    fn llvm_cases_are_ignored_on_wasmi() {
+        let cases = parse_doc_cases(
+            "neplg2:test[llvm_cli]\n```neplg2\n#target std\n```\nneplg2:test\n```neplg2\n#target llvm\n```\nneplg2:test\n```neplg2\n#target std\n```\n",
+        );
+        let ignored: Vec<bool> = cases.iter().map(DocCase::ignored).collect();
+        assert_eq!(ignored, vec![true, true, false]);
+    }
+
+    #[test]
+    fn case_metadata_overrides_the_run_limits() {
+        let cases = parse_doc_cases(
+            "neplg2:test\nfuel: 20000\n```neplg2\n#entry main\n#indent 4\n#target std\n#import \"core/math\" as *\n\nfn main <()*>i32> ():\n    let mut i 0\n    while true:\n        set i add i 1\n    i\n```\n",
+        );
+        let root = crate::stdlib_root().unwrap();
+        let path = root.join("tests").join("limits.n.md");
+        let limits = RunLimits {
+            fuel: Some(u64::MAX / 2),
+            ..RunLimits::default()
+        };
+        let CaseOutcome::Failed(err) = run_doc_case(&cases[0], &path, &root, limits) else {
+            panic!("the loop should hit the fuel limit");
+        };
+        assert_eq!(
+            err.downcast_ref::<crate::limits::LimitExceeded>(),
+            Some(&crate::limits::LimitExceeded::Fuel(20000))
+        );
+    }
+
+    #[test]
+    fn ansi_sequences_are_stripped_on_request() {
+        let case = DocCase {
+            heading: String::new(),
+            tags: vec!["strip_ansi".to_string()],
+            meta: Vec::new(),
+            source: String::new(),
+        };
+        assert_eq!(normalize_output("\x1b[31mred\x1b[0m\r\n", &case), "red\r\n");
+    }
+}
@@ -0,0 +1,215 @@
+//! `--fuel` / `--max-memory-pages` / `--timeout` による実行時の資源制限。
+//!
+//! 燃料は wasmi の燃料計測、メモリは `ResourceLimiter`、時間は実行スレッドの待ち合わせで制限する。
+//! 時間切れになった実行はスレッドごと置き去りにし、呼び出し側は結果を待たずに先へ進む。
+
+use std::fmt;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use wasmi::core::TrapCode;
+use wasmi::errors::{MemoryError, TableError};
+use wasmi::ResourceLimiter;
+
+/// 資源制限に掛かったときの終了コード（GNU `timeout` に合わせる）。
+pub(crate) const LIMIT_EXIT_CODE: i32 = 124;
+
+const WASM_PAGE_BYTES: usize = 64 * 1024;
+
+/// 1 回の実行に課す制限。`None` の項目は無制限。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct RunLimits {
+    pub fuel: Option<u64>,
+    pub max_memory_pages: Option<u32>,
+    pub timeout: Option<Duration>,
+}
+
+impl RunLimits {
+    /// `neplg2:test` のメタデータ 1 行を取り込む。制限のキーでなければ `false` を返す。
+    pub fn apply_meta(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "fuel" => {
+                self.fuel = Some(value.parse().with_context(|| format!("invalid fuel: {value}"))?)
+            }
+            "max_memory_pages" => {
+                self.max_memory_pages = Some(
+                    value
+                        .parse()
+                        .with_context(|| format!("invalid max_memory_pages: {value}"))?,
+                )
+            }
+            "timeout" => {
+                self.timeout = Some(parse_duration(value).map_err(|e| anyhow::anyhow!(e))?)
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// 実行を打ち切った制限。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LimitExceeded {
+    Fuel(u64),
+    Memory(u32),
+    Timeout(Duration),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::Fuel(fuel) => write!(f, "fuel limit exhausted ({fuel} units)"),
+            LimitExceeded::Memory(pages) => write!(
+                f,
+                "memory limit exceeded ({pages} pages, {} KiB)",
+                *pages as usize * WASM_PAGE_BYTES / 1024
+            ),
+            LimitExceeded::Timeout(timeout) => write!(f, "timed out after {timeout:?}"),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// 線形メモリの上限。上限を超える `memory.grow` はトラップにする。
+#[derive(Debug, Default)]
+pub(crate) struct MemoryCap {
+    max_pages: Option<u32>,
+    /// 上限に掛かって成長を拒否したか。
+    pub exceeded: bool,
+}
+
+impl MemoryCap {
+    pub fn new(max_pages: Option<u32>) -> Self {
+        Self {
+            max_pages,
+            exceeded: false,
+        }
+    }
+}
+
+impl ResourceLimiter for MemoryCap {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> Result<bool, MemoryError> {
+        match self.max_pages {
+            Some(max) if desired > max as usize * WASM_PAGE_BYTES => {
+                self.exceeded = true;
+                Err(MemoryError::OutOfBoundsGrowth)
+            }
+            _ => Ok(true),
+        }
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> Result<bool, TableError> {
+        Ok(true)
+    }
+}
+
+/// 実行エラーが燃料切れによるものか。
+pub(crate) fn is_out_of_fuel(err: &wasmi::Error) -> bool {
+    matches!(err, wasmi::Error::Trap(trap) if matches!(trap.trap_code(), Some(TrapCode::OutOfFuel)))
+}
+
+/// `timeout` があれば `run` を別スレッドで実行し、時間内に終わらなければ `LimitExceeded::Timeout` を返す。
+pub(crate) fn with_timeout<T: Send + 'static>(
+    timeout: Option<Duration>,
+    run: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return run();
+    };
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("nepl-run".to_string())
+        .spawn(move || {
+            let _ = tx.send(run());
+        })
+        .context("failed to spawn the runner thread")?;
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(LimitExceeded::Timeout(timeout).into()),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            Err(anyhow::anyhow!("the runner thread panicked"))
+        }
+    }
+}
+
+/// `10` / `1.5s` / `500ms` / `2m` を読む。単位を省略すると秒。
+pub(crate) fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let (number, scale) = if let Some(n) = text.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = text.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = text.strip_suffix('m') {
+        (n, 60.0)
+    } else {
+        (text, 1.0)
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n > 0.0)
+        .map(|n| Duration::from_secs_f64(n * scale))
+        .ok_or_else(|| format!("invalid duration `{text}` (expected e.g. 10, 1.5s or 500ms)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_accept_seconds_and_milliseconds() {
+        assert_eq!(parse_duration("10"), Ok(Duration::from_secs(10)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("soon").is_err());
+    }
+
+    #[test]
+    fn metadata_overrides_only_limit_keys() {
+        let mut limits = RunLimits {
+            fuel: Some(10),
+            ..RunLimits::default()
+        };
+        assert!(limits.apply_meta("timeout", "2s").unwrap());
+        assert!(limits.apply_meta("max_memory_pages", "4").unwrap());
+        assert!(!limits.apply_meta("ret", "0").unwrap());
+        assert!(limits.apply_meta("fuel", "lots").is_err());
+        assert_eq!(
+            limits,
+            RunLimits {
+                fuel: Some(10),
+                max_memory_pages: Some(4),
+                timeout: Some(Duration::from_secs(2)),
+            }
+        );
+    }
+
+    #[test]
+    fn slow_runs_are_abandoned_at_the_timeout() {
+        let err = with_timeout(Some(Duration::from_millis(50)), || {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(())
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LimitExceeded>(),
+            Some(&LimitExceeded::Timeout(Duration::from_millis(50)))
+        );
+        assert_eq!(with_timeout(Some(Duration::from_secs(5)), || Ok(7)).unwrap(), 7);
+    }
+}
@@ -1,35 +1,41 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeSet;
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 #[cfg(unix)]
 use std::os::fd::AsRawFd;
 
 use anyhow::{Context, Result};
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use nepl_core::codegen_llvm::LlvmTargetSpec;
 use nepl_core::{
     compile_module,
+    compile_module_for_debugging,
+    compile_module_for_profiling,
+    compile_module_with_bindings,
     compile_module_with_source_map,
     diagnostic::{Diagnostic, Severity},
     error::CoreError,
     loader::{Loader, SourceMap},
     BuildProfile, CompilationArtifact, CompileOptions, CompileTarget,
 };
-use wasmi::{Caller, Engine, Linker, Module, Store};
+use nepl_wasi::{Vfs, WasiCtx, WasiCtxBuilder, WasiView};
+use wasmi::{Caller, Config, Engine, Linker, Module, Store};
 use wasmprinter::print_bytes;
 
 mod codegen_llvm;
+mod debugger;
+mod docgen;
+mod doctest;
+mod limits;
+mod profiler;
+mod repl;
 
 struct AllocState {
     // head of free list (address in linear memory), 0 == null
     free_head: u32,
-    stdin: Vec<u8>,
-    stdin_pos: usize,
-    stdin_eof: bool,
-    args: Vec<Vec<u8>>,
-    files: BTreeMap<i32, FileState>,
-    next_fd: i32,
+    wasi: WasiCtx,
     tty_cols: u32,
     tty_rows: u32,
     tty_width: u32,
@@ -39,17 +45,17 @@ struct AllocState {
     tty_stderr_tty: bool,
     tty_echo: bool,
     tty_line_buffered: bool,
-    stdout_buf: Vec<u8>,
-    stdout_last_flush: Instant,
+    memory_cap: limits::MemoryCap,
     #[cfg(unix)]
     tty_saved: bool,
     #[cfg(unix)]
     tty_original: libc::termios,
 }
 
-struct FileState {
-    data: Vec<u8>,
-    pos: usize,
+impl WasiView for AllocState {
+    fn wasi(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
 }
 
 #[cfg(unix)]
@@ -131,15 +137,7 @@ fn restore_host_tty(state: &AllocState) {
 fn restore_host_tty(_state: &AllocState) {}
 
 fn flush_stdout_buffer(state: &mut AllocState) -> io::Result<()> {
-    if state.stdout_buf.is_empty() {
-        return Ok(());
-    }
-    let mut out = io::stdout().lock();
-    out.write_all(&state.stdout_buf)?;
-    out.flush()?;
-    state.stdout_buf.clear();
-    state.stdout_last_flush = Instant::now();
-    Ok(())
+    state.wasi.flush()
 }
 
 /// コマンドライン引数を定義するための構造体
@@ -160,7 +158,7 @@ struct Cli {
         value_enum,
         value_delimiter = ',',
         default_value = "wasm",
-        help = "Output formats: wasm, wat, wat-min, all"
+        help = "Output formats: wasm, wat, wat-min, llvm, llvm-min, obj, exe, bindings, all"
     )]
     emit: Vec<Emit>,
 
@@ -171,6 +169,14 @@ struct Cli {
     #[arg(long, help = "Run the code if the output format is wasm")]
     run: bool,
 
+    // --run の実行を関数単位で計測し、folded stack 形式で書き出す（表は stderr）
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "With --run, profile instructions and allocations per function and write folded stacks to PATH"
+    )]
+    profile_out: Option<PathBuf>,
+
     #[arg(long, help = "Only check the code for errors without generating output")]
     check: bool,
     #[arg(
@@ -189,11 +195,38 @@ struct Cli {
     #[arg(long, value_name = "TARGET", value_parser = ["wasm", "wasi", "wasix", "llvm", "core", "std"], help = "Compilation target: wasm, wasi, wasix, llvm, core(alias wasm), std(alias wasi)")]
     target: Option<String>,
 
+    #[arg(long, value_name = "TRIPLE", help = "LLVM target triple; also selects the datalayout (default: x86_64-pc-linux-gnu)")]
+    triple: Option<String>,
+
     #[arg(short, long, global = true, help = "Enable verbose compiler logging")]
     verbose: bool,
 
     #[arg(long, value_enum, value_name = "PROFILE", help = "Compile profile: debug or release")]
     profile: Option<ProfileArg>,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+}
+
+/// `--run` と `nepl-cli test` の資源制限
+#[derive(Args, Debug, Clone, Default)]
+struct LimitArgs {
+    #[arg(long, value_name = "UNITS", help = "Stop the program after this much wasmi fuel (about one unit per instruction)")]
+    fuel: Option<u64>,
+    #[arg(long, value_name = "PAGES", help = "Trap when linear memory grows beyond this many 64KiB pages")]
+    max_memory_pages: Option<u32>,
+    #[arg(long, value_name = "DURATION", value_parser = limits::parse_duration, help = "Stop the program after this wall-clock time (e.g. 10, 1.5s, 500ms)")]
+    timeout: Option<Duration>,
+}
+
+impl LimitArgs {
+    fn to_limits(&self) -> limits::RunLimits {
+        limits::RunLimits {
+            fuel: self.fuel,
+            max_memory_pages: self.max_memory_pages,
+            timeout: self.timeout,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
@@ -205,6 +238,9 @@ enum Emit {
     Llvm,
     #[value(name = "llvm-min")]
     LlvmMin,
+    Obj,
+    Exe,
+    Bindings,
     All,
 }
 
@@ -217,28 +253,81 @@ enum ProfileArg {
 #[derive(Subcommand, Debug)]
 enum Command {
     Test(TestArgs),
+    /// ソース行単位のブレークポイントとステップ実行で wasm を実行する
+    Debug(DebugArgs),
+    /// 項目と式を 1 つずつ評価する対話環境を起動する
+    Repl,
+    /// `//:` ドキュメントコメントから API ドキュメントの HTML を生成する
+    Doc(DocArgs),
 }
 
 #[derive(Args, Debug)]
 struct TestArgs {
     #[arg(value_name = "FILTER")]
     filter: Option<String>,
-    #[arg(long, default_value = "tests", help = "Relative path inside stdlib to scan for .nepl tests")]
+    #[arg(long, default_value = "tests", help = "Relative path inside stdlib to scan for .nepl and .n.md tests")]
     dir: String,
+    #[command(flatten)]
+    limits: LimitArgs,
+}
+
+#[derive(Args, Debug)]
+struct DocArgs {
+    #[arg(value_name = "PATH", help = "Source file or directory to document (default: the stdlib)")]
+    input: Option<PathBuf>,
+    #[arg(long, value_name = "DIR", default_value = "target/nepl-doc", help = "Directory to write the HTML pages into")]
+    out_dir: PathBuf,
+    #[arg(long, value_name = "PREFIX", help = "Where playground_runtime.js, playground.css, search.js and nepl-web.js are served; makes examples runnable")]
+    playground: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct DebugArgs {
+    #[arg(value_name = "FILE")]
+    input: String,
+    #[arg(long, value_name = "TARGET", value_parser = ["wasm", "wasi", "wasix", "core", "std"], help = "Compilation target: wasm, wasi, wasix, core(alias wasm), std(alias wasi)")]
+    target: Option<String>,
+    #[arg(long, value_enum, value_name = "PROFILE", help = "Compile profile: debug or release")]
+    profile: Option<ProfileArg>,
+    #[arg(
+        value_name = "ARGS",
+        num_args = 0..,
+        trailing_var_arg = true,
+        help = "Arguments passed to the WASI program after --"
+    )]
+    run_args: Vec<String>,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    execute(cli)
+    match execute(cli) {
+        // 資源制限による停止は CI で区別できるよう専用の終了コードにする
+        Err(err) if err.downcast_ref::<limits::LimitExceeded>().is_some() => {
+            eprintln!("error: {err:#}");
+            std::process::exit(limits::LIMIT_EXIT_CODE);
+        }
+        other => other,
+    }
 }
 
 fn execute(cli: Cli) -> Result<()> {
-    if let Some(Command::Test(args)) = cli.command {
-        return run_tests(args, cli.verbose);
+    match cli.command {
+        Some(Command::Test(args)) => return run_tests(args, cli.verbose),
+        Some(Command::Debug(args)) => return run_debug(args, cli.verbose),
+        Some(Command::Doc(args)) => return run_doc(args),
+        Some(Command::Repl) => {
+            let mut session = repl::Repl::new(stdlib_root()?);
+            let stdin = io::stdin();
+            return repl::run_repl(&mut session, &mut stdin.lock(), &mut io::stdout());
+        }
+        None => {}
     }
     if !cli.run && !cli.check && cli.output.is_none() {
         return Err(anyhow::anyhow!("Either --run, --check or --output is required"));
     }
+    if cli.profile_out.is_some() && !cli.run {
+        return Err(anyhow::anyhow!("--profile-out requires --run"));
+    }
     let program_name = cli
         .input
         .clone()
@@ -290,16 +379,24 @@ fn execute(cli: Cli) -> Result<()> {
 
     // If target is llvm, and no specific llvm-ish emits are requested,
     // add Llvm to emits. This handles the case where --emit defaults to wasm.
-    if matches!(target_override, Some(CompileTarget::Llvm)) {
-        if !emits.contains(&Emit::Llvm) && !emits.contains(&Emit::LlvmMin) {
-            emits.insert(Emit::Llvm);
-        }
+    if matches!(target_override, Some(CompileTarget::Llvm))
+        && !emits
+            .iter()
+            .any(|e| matches!(e, Emit::Llvm | Emit::LlvmMin | Emit::Obj | Emit::Exe))
+    {
+        emits.insert(Emit::Llvm);
     }
 
     let module_decl_target = detect_module_target(&module);
     let run_target = target_override
         .or(module_decl_target)
         .unwrap_or(CompileTarget::Wasm);
+    let wants_native = emits.contains(&Emit::Obj) || emits.contains(&Emit::Exe);
+    if !matches!(run_target, CompileTarget::Llvm) && (wants_native || cli.triple.is_some()) {
+        return Err(anyhow::anyhow!(
+            "--emit obj/exe and --triple require --target llvm"
+        ));
+    }
 
     if cli.check {
         eprintln!("Check successful");
@@ -314,7 +411,7 @@ fn execute(cli: Cli) -> Result<()> {
             if matches!(run_target, CompileTarget::Llvm) {
         if cli.run {
             return Err(anyhow::anyhow!(
-                "--run is not supported for --target llvm (emit .ll and execute with clang/lli)"
+                "--run is not supported for --target llvm (use --emit exe and run the binary)"
             ));
         }
         codegen_llvm::ensure_llvm_toolchain_from_env()?;
@@ -323,17 +420,55 @@ fn execute(cli: Cli) -> Result<()> {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("--output is required for --target llvm"))?;
         let base = output_base_from_arg(output);
+        let spec = match &cli.triple {
+            Some(triple) => LlvmTargetSpec::from_triple(triple)
+                .map_err(|e| anyhow::anyhow!(e.to_string()))?,
+            None => LlvmTargetSpec::default(),
+        };
+        let emit_ll = |minify: bool| {
+            nepl_core::codegen_llvm::emit_ll_from_module_for_triple(
+                &module,
+                run_target,
+                active_profile,
+                minify,
+                &spec,
+            )
+            .map_err(|e| anyhow::anyhow!(e.to_string()))
+        };
 
         if emits.contains(&Emit::Llvm) {
-            let ir = nepl_core::codegen_llvm::emit_ll_from_module_for_target(&module, run_target, active_profile, false)
-                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            let ir = emit_ll(false)?;
             write_bytes(&base.with_extension("ll"), ir.as_bytes())?;
         }
         if emits.contains(&Emit::LlvmMin) {
-            let ir = nepl_core::codegen_llvm::emit_ll_from_module_for_target(&module, run_target, active_profile, true)
-                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            let ir = emit_ll(true)?;
             write_bytes(&output_path(&base, Emit::LlvmMin), ir.as_bytes())?;
         }
+        if wants_native {
+            // clang reads the IR from a scratch file next to the outputs.
+            let ir = emit_ll(false)?;
+            let ll_path = PathBuf::from(format!("{}.native.ll", base.display()));
+            write_bytes(&ll_path, ir.as_bytes())?;
+            let optimize = matches!(active_profile, BuildProfile::Release);
+            let mut result = Ok(());
+            for (emit, kind) in [
+                (Emit::Obj, codegen_llvm::NativeOutput::Object),
+                (Emit::Exe, codegen_llvm::NativeOutput::Executable),
+            ] {
+                if emits.contains(&emit) && result.is_ok() {
+                    let out_path = native_output_path(&base, emit, &spec.triple);
+                    result = codegen_llvm::build_native_from_ll(
+                        &ll_path,
+                        &out_path,
+                        kind,
+                        &spec.triple,
+                        optimize,
+                    );
+                }
+            }
+            let _ = fs::remove_file(&ll_path);
+            result?;
+        }
         return Ok(());
     }
     let options = CompileOptions {
@@ -342,8 +477,28 @@ fn execute(cli: Cli) -> Result<()> {
         profile,
     };
 
+    // 計測用のフックは出力ファイルに含めないため、実行用に別途コンパイルする。
+    let profiled = match &cli.profile_out {
+        Some(_) => match compile_module_for_profiling(module.clone(), Some(&source_map), options) {
+            Ok(compiled) => Some(compiled),
+            Err(CoreError::Diagnostics(diags)) => {
+                render_diagnostics(&diags, &source_map);
+                return Err(anyhow::anyhow!("compilation failed"));
+            }
+            Err(e) => return Err(anyhow::anyhow!(e.to_string())),
+        },
+        None => None,
+    };
+
     eprintln!("DEBUG: Calling compile_module");
-    let artifact = match compile_module_with_source_map(module, Some(&source_map), options) {
+    let compiled = if emits.contains(&Emit::Bindings) {
+        compile_module_with_bindings(module, Some(&source_map), options)
+            .map(|(artifact, bindings)| (artifact, Some(bindings)))
+    } else {
+        compile_module_with_source_map(module, Some(&source_map), options)
+            .map(|artifact| (artifact, None))
+    };
+    let (artifact, bindings) = match compiled {
         Ok(a) => {
             eprintln!("DEBUG: compile_module returned Ok");
             a
@@ -383,12 +538,28 @@ fn execute(cli: Cli) -> Result<()> {
              None,
              None,
          )?;
+        if let Some(bindings) = &bindings {
+            write_bindings(&base, bindings)?;
+        }
     }
     if cli.run {
         let mut wasm_args = Vec::new();
         wasm_args.push(program_name);
         wasm_args.extend(cli.run_args.clone());
-        let result = run_wasm(&artifact, run_target, wasm_args)?;
+        let result = match (profiled, &cli.profile_out) {
+            (Some((artifact, info)), Some(path)) => {
+                let (result, session) = profiler::run_profiled(profiler::ProfileTarget {
+                    wasm: artifact.wasm,
+                    info,
+                    target: run_target,
+                    args: wasm_args,
+                    limits: cli.limits.to_limits(),
+                });
+                profiler::write_report(&session, path)?;
+                result?
+            }
+            _ => run_wasm(&artifact, run_target, wasm_args, cli.limits.to_limits())?,
+        };
         if result != 0 {
             println!("Program exited with {result}");
         }
@@ -401,6 +572,93 @@ fn execute(cli: Cli) -> Result<()> {
     Ok(())
 }
 
+fn run_debug(args: DebugArgs, verbose: bool) -> Result<()> {
+    let std_root = stdlib_root()?;
+    let entry_path = PathBuf::from(&args.input);
+    let mut loader = Loader::new(std_root.clone());
+    let module = match loader.load(&entry_path) {
+        Ok(res) => res.module,
+        Err(e) => {
+            if let nepl_core::loader::LoaderError::Core(CoreError::Diagnostics(diags)) = &e {
+                render_diagnostics(diags, loader.source_map());
+                std::process::exit(1);
+            }
+            return Err(anyhow::anyhow!(e.to_string()));
+        }
+    };
+    let source_map = loader.source_map().clone();
+    let target_override = args.target.as_deref().map(|t| match t {
+        "wasm" | "core" => CompileTarget::Wasm,
+        "wasi" | "std" => CompileTarget::Wasi,
+        "wasix" => CompileTarget::Wasix,
+        _ => unreachable!(),
+    });
+    let run_target = target_override
+        .or(detect_module_target(&module))
+        .unwrap_or(CompileTarget::Wasm);
+    if matches!(run_target, CompileTarget::Llvm) {
+        return Err(anyhow::anyhow!("debug only supports wasm targets"));
+    }
+    let options = CompileOptions {
+        target: target_override,
+        verbose,
+        profile: args.profile.map(|p| match p {
+            ProfileArg::Debug => BuildProfile::Debug,
+            ProfileArg::Release => BuildProfile::Release,
+        }),
+    };
+    let (artifact, info) = match compile_module_for_debugging(module, Some(&source_map), options) {
+        Ok(compiled) => compiled,
+        Err(CoreError::Diagnostics(diags)) => {
+            render_diagnostics(&diags, &source_map);
+            return Err(anyhow::anyhow!("compilation failed"));
+        }
+        Err(e) => return Err(anyhow::anyhow!(e.to_string())),
+    };
+
+    let canonical_entry = entry_path.canonicalize().unwrap_or(entry_path.clone());
+    let mut entry = None;
+    let mut library_files = Vec::new();
+    for (id, path) in source_map.iter_paths() {
+        let canonical = path.canonicalize().unwrap_or(path.clone());
+        if canonical == canonical_entry {
+            entry = Some(id);
+        } else if canonical.starts_with(&std_root) {
+            library_files.push(id);
+        }
+    }
+    let entry = entry.ok_or_else(|| anyhow::anyhow!("entry file missing from the source map"))?;
+
+    let mut wasm_args = vec![args.input.clone()];
+    wasm_args.extend(args.run_args);
+    let stdin = io::stdin();
+    debugger::run_debugger(
+        debugger::DebugTarget {
+            artifact: &artifact,
+            info,
+            source_map: &source_map,
+            entry,
+            library_files,
+            target: run_target,
+            args: wasm_args,
+        },
+        &mut stdin.lock(),
+        &mut io::stdout(),
+    )?;
+    Ok(())
+}
+
+fn run_doc(args: DocArgs) -> Result<()> {
+    let std_root = stdlib_root()?;
+    let input = args.input.unwrap_or_else(|| std_root.clone());
+    let options = docgen::DocOptions {
+        playground: args.playground,
+    };
+    let pages = docgen::generate_docs(&input, &std_root, &args.out_dir, &options)?;
+    println!("generated {pages} page(s) into {}", args.out_dir.display());
+    Ok(())
+}
+
 fn run_tests(args: TestArgs, verbose: bool) -> Result<()> {
     const ANSI_RESET: &str = "\x1b[0m";
     const ANSI_GREEN: &str = "\x1b[32m";
@@ -420,6 +678,7 @@ fn run_tests(args: TestArgs, verbose: bool) -> Result<()> {
             base.display()
         ));
     }
+    let limits = args.limits.to_limits();
     let mut files = Vec::new();
     collect_nepl_files(&base, &mut files)?;
     files.sort();
@@ -431,33 +690,65 @@ fn run_tests(args: TestArgs, verbose: bool) -> Result<()> {
     }
 
     let mut failed = 0usize;
+    // 最初に掛かった資源制限。1 つでもあれば終了コードを分ける
+    let mut first_limit = None;
+    let mut report = |name: &str, outcome: doctest::CaseOutcome| match outcome {
+        doctest::CaseOutcome::Passed => println!("{ANSI_GREEN}ok{ANSI_RESET}"),
+        doctest::CaseOutcome::Ignored => println!("ignored"),
+        doctest::CaseOutcome::Failed(e) => {
+            match e.downcast_ref::<limits::LimitExceeded>() {
+                Some(limit) => {
+                    println!("{ANSI_RED}FAILED ({limit}){ANSI_RESET}");
+                    first_limit.get_or_insert(*limit);
+                }
+                None => {
+                    println!("{ANSI_RED}FAILED{ANSI_RESET}");
+                    eprintln!("{name}: {e:#}");
+                }
+            }
+            failed += 1;
+        }
+    };
     for file in files {
         let name = file
             .strip_prefix(&base)
             .unwrap_or(&file)
             .display()
             .to_string();
-        print!("{ANSI_CYAN}test{ANSI_RESET} {name} ... ");
-        match run_test_file(&file, &std_root, verbose) {
-            Ok(()) => {
-                println!("{ANSI_GREEN}ok{ANSI_RESET}");
-            }
-            Err(e) => {
-                println!("{ANSI_RED}FAILED{ANSI_RESET}");
-                eprintln!("{e}");
-                failed += 1;
-            }
+        if !name.ends_with(".n.md") {
+            print!("{ANSI_CYAN}test{ANSI_RESET} {name} ... ");
+            let outcome = match run_test_file(&file, &std_root, verbose, limits) {
+                Ok(()) => doctest::CaseOutcome::Passed,
+                Err(e) => doctest::CaseOutcome::Failed(e),
+            };
+            report(&name, outcome);
+            continue;
+        }
+        for (index, case) in doctest::collect_doc_cases(&file)?.iter().enumerate() {
+            let name = match case.heading.as_str() {
+                "" => format!("{name}#{index}"),
+                heading => format!("{name}#{index} ({heading})"),
+            };
+            print!("{ANSI_CYAN}test{ANSI_RESET} {name} ... ");
+            let _ = io::stdout().flush();
+            report(&name, doctest::run_doc_case(case, &file, &std_root, limits));
         }
     }
 
-    if failed > 0 {
-        Err(anyhow::anyhow!("{failed} tests failed"))
-    } else {
-        Ok(())
+    let summary = format!("{failed} tests failed");
+    match first_limit {
+        Some(limit) => Err(anyhow::Error::new(limit).context(summary)),
+        None if failed > 0 => Err(anyhow::anyhow!(summary)),
+        None => Ok(()),
     }
 }
 
-fn run_test_file(path: &Path, std_root: &Path, verbose: bool) -> Result<()> {
+fn run_test_file(
+    path: &Path,
+    std_root: &Path,
+    verbose: bool,
+    limits: limits::RunLimits,
+) -> Result<()> {
     let mut loader = Loader::new(std_root.to_path_buf());
     println!("[nepl-cli] run_test_file: loading {}", path.display());
     let res = match loader.load(&path.to_path_buf()) {
@@ -489,7 +780,7 @@ fn run_test_file(path: &Path, std_root: &Path, verbose: bool) -> Result<()> {
     wasm_args.push(path.display().to_string());
     wasm_args.push("--flag".to_string());
     wasm_args.push("value".to_string());
-    let result = run_wasm(&artifact, CompileTarget::Wasi, wasm_args)?;
+    let result = run_wasm(&artifact, CompileTarget::Wasi, wasm_args, limits)?;
     if result != 0 {
         return Err(anyhow::anyhow!("non-zero exit code: {result}"));
     }
@@ -502,7 +793,9 @@ fn collect_nepl_files(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
         let path = entry.path();
         if path.is_dir() {
             collect_nepl_files(&path, out)?;
-        } else if path.extension().and_then(|s| s.to_str()) == Some("nepl") {
+        } else if path.extension().and_then(|s| s.to_str()) == Some("nepl")
+            || path.to_string_lossy().ends_with(".n.md")
+        {
             out.push(path);
         }
     }
@@ -627,10 +920,24 @@ fn output_path(base: &Path, emit: Emit) -> PathBuf {
         Emit::WatMin => PathBuf::from(format!("{}.min.wat", base.display())),
         Emit::Llvm => base.with_extension("ll"),
         Emit::LlvmMin => PathBuf::from(format!("{}.min.ll", base.display())),
+        Emit::Obj => base.with_extension("o"),
+        Emit::Exe => base.to_path_buf(),
+        Emit::Bindings => base.with_extension("js"),
         Emit::All => base.to_path_buf(),
     }
 }
 
+fn native_output_path(base: &Path, emit: Emit, triple: &str) -> PathBuf {
+    if triple.contains("-windows") {
+        match emit {
+            Emit::Obj => return base.with_extension("obj"),
+            Emit::Exe => return base.with_extension("exe"),
+            _ => {}
+        }
+    }
+    output_path(base, emit)
+}
+
 fn write_outputs(
     base: &Path,
     wasm: &[u8],
@@ -677,6 +984,12 @@ fn write_outputs(
     }
     Ok(())
 }
+fn write_bindings(base: &Path, bindings: &nepl_core::bindings::Bindings) -> Result<()> {
+    write_bytes(&output_path(base, Emit::Bindings), bindings.js.as_bytes())?;
+    let dts = PathBuf::from(format!("{}.d.ts", base.display()));
+    write_bytes(&dts, bindings.dts.as_bytes())
+}
+
 fn write_bytes(path: &Path, bytes: &[u8]) -> Result<()> {
     if let Some(parent) = path.parent() {
         if !parent.as_os_str().is_empty() {
@@ -795,23 +1108,97 @@ fn run_wasm(
     artifact: &CompilationArtifact,
     target: CompileTarget,
     args: Vec<String>,
+    limits: limits::RunLimits,
 ) -> Result<i32> {
-    let engine = Engine::default();
-    let module = Module::new(&engine, artifact.wasm.as_slice())
-        .context("failed to compile wasm artifact")?;
-    let args_bytes: Vec<Vec<u8>> = args
-        .into_iter()
-        .map(|s| {
-            let mut b = s.into_bytes();
-            b.push(0);
-            b
-        })
-        .collect();
+    let (code, _) =
+        run_wasm_with_state(artifact.wasm.clone(), target, new_host_state(args), limits)?;
+    Ok(code)
+}
 
+/// `limits` の範囲で `main` を実行し、終了コードと実行後のホスト状態を返す。
+/// 制限に掛かった場合は `limits::LimitExceeded` のエラーになる。
+fn run_wasm_with_state(
+    wasm: Vec<u8>,
+    target: CompileTarget,
+    state: AllocState,
+    limits: limits::RunLimits,
+) -> Result<(i32, AllocState)> {
+    limits::with_timeout(limits.timeout, move || {
+        execute_wasm(&wasm, target, state, limits)
+    })
+}
+
+fn execute_wasm(
+    wasm: &[u8],
+    target: CompileTarget,
+    mut state: AllocState,
+    limits: limits::RunLimits,
+) -> Result<(i32, AllocState)> {
+    let mut config = Config::default();
+    config.consume_fuel(limits.fuel.is_some());
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, wasm).context("failed to compile wasm artifact")?;
+    check_run_imports(&module, target, &[])?;
     let mut linker: Linker<AllocState> = Linker::new(&engine);
+    if matches!(target, CompileTarget::Wasi | CompileTarget::Wasix) {
+        link_wasi_host(&mut linker)?;
+    }
+    state.memory_cap = limits::MemoryCap::new(limits.max_memory_pages);
+    let mut store = Store::new(&engine, state);
+    store.limiter(|state| &mut state.memory_cap);
+    if let Some(fuel) = limits.fuel {
+        store
+            .add_fuel(fuel)
+            .map_err(|e| anyhow::anyhow!("failed to enable fuel metering: {e}"))?;
+    }
+    // 制限による停止は通常のトラップと区別して返す。
+    let classify = |store: &Store<AllocState>, err: wasmi::Error, context: &'static str| {
+        match (limits.max_memory_pages, limits.fuel) {
+            (Some(pages), _) if store.data().memory_cap.exceeded => {
+                anyhow::Error::new(limits::LimitExceeded::Memory(pages))
+            }
+            (_, Some(fuel)) if limits::is_out_of_fuel(&err) => {
+                anyhow::Error::new(limits::LimitExceeded::Fuel(fuel))
+            }
+            _ => anyhow::Error::new(err).context(context),
+        }
+    };
+    let instance = match linker.instantiate(&mut store, &module) {
+        Ok(pre) => pre.start(&mut store),
+        Err(err) => Err(err),
+    }
+    .map_err(|err| classify(&store, err, "failed to instantiate module"))?;
+    // `proc_exit` はトラップで巻き戻るので、その終了コードを main の戻り値として扱う。
+    let exit_status = |trap: wasmi::core::Trap| trap.i32_exit_status().ok_or(trap);
+    let result = if let Ok(main) = instance.get_typed_func::<(), i32>(&store, "main") {
+        main.call(&mut store, ())
+            .or_else(exit_status)
+            .map_err(|err| classify(&store, err.into(), "failed to execute main"))
+    } else if let Ok(main_unit) = instance.get_typed_func::<(), ()>(&store, "main") {
+        main_unit
+            .call(&mut store, ())
+            .map(|()| 0)
+            .or_else(exit_status)
+            .map_err(|err| classify(&store, err.into(), "failed to execute main"))
+    } else {
+        Err(anyhow::anyhow!(
+            "exported main function missing or has wrong type"
+        ))
+    };
+    let _ = flush_stdout_buffer(store.data_mut());
+    restore_host_tty(store.data());
+    result.map(|code| (code, store.into_data()))
+}
+
+/// 実行時に許可される import だけが使われているか確認する。
+/// `extra_modules` はデバッガなど呼び出し側がリンクする追加の import モジュール。
+fn check_run_imports(module: &Module, target: CompileTarget, extra_modules: &[&str]) -> Result<()> {
+    let mut imports = module
+        .imports()
+        .filter(|import| !extra_modules.contains(&import.module()));
     match target {
         CompileTarget::Wasi | CompileTarget::Wasix => {
-            for import in module.imports() {
+            for import in imports {
                 if import.module() != "wasi_snapshot_preview1" && import.module() != "wasix_32v1" {
                     return Err(anyhow::anyhow!(
                         "unsupported import {}::{} (only wasi_snapshot_preview1 or wasix_32v1 are allowed for wasi/wasix targets)",
@@ -822,7 +1209,7 @@ fn run_wasm(
             }
         }
         CompileTarget::Wasm => {
-            if let Some(import) = module.imports().next() {
+            if let Some(import) = imports.next() {
                 return Err(anyhow::anyhow!(
                     "wasm target does not allow host imports during run: {}::{} (use #target wasi or --target wasi)",
                     import.module(),
@@ -836,418 +1223,130 @@ fn run_wasm(
             ));
         }
     }
-    if matches!(target, CompileTarget::Wasi | CompileTarget::Wasix) {
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "args_sizes_get",
-            |mut caller: Caller<'_, AllocState>, argc_ptr: i32, argv_buf_size_ptr: i32| -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if argc_ptr < 0 || argv_buf_size_ptr < 0 {
-                    return 21;
-                }
-                let argc = caller.data().args.len() as u32;
-                let buf_size: u32 = caller
-                    .data()
-                    .args
-                    .iter()
-                    .map(|a| a.len() as u32)
-                    .sum();
-                let mem_len = memory.data(&caller).len();
-                let argc_offset = argc_ptr as usize;
-                let buf_offset = argv_buf_size_ptr as usize;
-                if argc_offset + 4 > mem_len || buf_offset + 4 > mem_len {
-                    return 21;
-                }
-                if memory
-                    .write(&mut caller, argc_offset, &argc.to_le_bytes())
-                    .is_err()
-                {
-                    return 21;
-                }
-                if memory
-                    .write(&mut caller, buf_offset, &buf_size.to_le_bytes())
-                    .is_err()
-                {
-                    return 21;
-                }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "args_get",
-            |mut caller: Caller<'_, AllocState>, argv: i32, argv_buf: i32| -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if argv < 0 || argv_buf < 0 {
-                    return 21;
-                }
-                let mem_len = memory.data(&caller).len();
-                let args = caller.data().args.clone();
-                let mut argv_offset = argv as usize;
-                let mut buf_offset = argv_buf as usize;
-                for arg in args.iter() {
-                    if argv_offset + 4 > mem_len {
-                        return 21;
-                    }
-                    let ptr_bytes = (buf_offset as u32).to_le_bytes();
-                    if memory
-                        .write(&mut caller, argv_offset, &ptr_bytes)
-                        .is_err()
-                    {
-                        return 21;
-                    }
-                    if buf_offset + arg.len() > mem_len {
-                        return 21;
-                    }
-                    if memory.write(&mut caller, buf_offset, arg).is_err() {
-                        return 21;
-                    }
-                    argv_offset += 4;
-                    buf_offset += arg.len();
-                }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "path_open",
-            |mut caller: Caller<'_, AllocState>,
-             _dirfd: i32,
-             _dirflags: i32,
-             path_ptr: i32,
-             path_len: i32,
-             _oflags: i32,
-             _rights_base: i64,
-             _rights_inherit: i64,
-             _fdflags: i32,
-             fd_out: i32|
-             -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if path_ptr < 0 || path_len < 0 || fd_out < 0 {
-                    return 21;
-                }
-                let mem = memory.data(&caller);
-                let start = path_ptr as usize;
-                let end = start.saturating_add(path_len as usize);
-                if end > mem.len() || (fd_out as usize) + 4 > mem.len() {
-                    return 21;
-                }
-                let path = std::str::from_utf8(&mem[start..end]).unwrap_or("");
-                let data = match fs::read(path) {
-                    Ok(d) => d,
-                    Err(_) => return 44,
-                };
-                let fd = caller.data().next_fd;
-                caller.data_mut().next_fd += 1;
-                caller
-                    .data_mut()
-                    .files
-                    .insert(fd, FileState { data, pos: 0 });
-                let fd_bytes = (fd as u32).to_le_bytes();
-                if memory
-                    .write(&mut caller, fd_out as usize, &fd_bytes)
-                    .is_err()
-                {
-                    return 21;
-                }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasix_32v1",
-            "tty_get",
-            |mut caller: Caller<'_, AllocState>, tty_ptr: i32| -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if tty_ptr < 0 {
-                    return 21;
-                }
-                let base = tty_ptr as usize;
-                if base + 21 > memory.data(&caller).len() {
-                    return 21;
-                }
-                if let Some((cols, rows)) = current_terminal_size() {
-                    let state = caller.data_mut();
-                    state.tty_cols = cols;
-                    state.tty_rows = rows;
-                    state.tty_width = cols;
-                    state.tty_height = rows;
-                }
-                let cols = caller.data().tty_cols.to_le_bytes();
-                let rows = caller.data().tty_rows.to_le_bytes();
-                let width = caller.data().tty_width.to_le_bytes();
-                let height = caller.data().tty_height.to_le_bytes();
-                if memory.write(&mut caller, base, &cols).is_err() {
-                    return 21;
-                }
-                if memory.write(&mut caller, base + 4, &rows).is_err() {
-                    return 21;
-                }
-                if memory.write(&mut caller, base + 8, &width).is_err() {
-                    return 21;
-                }
-                if memory.write(&mut caller, base + 12, &height).is_err() {
-                    return 21;
-                }
-                let stdin_tty = [if caller.data().tty_stdin_tty { 1 } else { 0 }];
-                let stdout_tty = [if caller.data().tty_stdout_tty { 1 } else { 0 }];
-                let stderr_tty = [if caller.data().tty_stderr_tty { 1 } else { 0 }];
-                let echo = [if caller.data().tty_echo { 1 } else { 0 }];
-                let line_buffered = [if caller.data().tty_line_buffered { 1 } else { 0 }];
-                if memory.write(&mut caller, base + 16, &stdin_tty).is_err() {
-                    return 21;
-                }
-                if memory.write(&mut caller, base + 17, &stdout_tty).is_err() {
-                    return 21;
-                }
-                if memory.write(&mut caller, base + 18, &stderr_tty).is_err() {
-                    return 21;
-                }
-                if memory.write(&mut caller, base + 19, &echo).is_err() {
-                    return 21;
-                }
-                if memory.write(&mut caller, base + 20, &line_buffered).is_err() {
-                    return 21;
-                }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasix_32v1",
-            "tty_set",
-            |mut caller: Caller<'_, AllocState>, tty_ptr: i32| -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if tty_ptr < 0 {
-                    return 21;
-                }
-                let base = tty_ptr as usize;
-                let data = memory.data(&caller);
-                if base + 21 > data.len() {
-                    return 21;
-                }
-                let cols = u32::from_le_bytes(data[base..base + 4].try_into().unwrap());
-                let rows = u32::from_le_bytes(data[base + 4..base + 8].try_into().unwrap());
-                let width = u32::from_le_bytes(data[base + 8..base + 12].try_into().unwrap());
-                let height = u32::from_le_bytes(data[base + 12..base + 16].try_into().unwrap());
-                let stdin_tty = data[base + 16] != 0;
-                let stdout_tty = data[base + 17] != 0;
-                let stderr_tty = data[base + 18] != 0;
-                let echo = data[base + 19] != 0;
-                let line_buffered = data[base + 20] != 0;
+    Ok(())
+}
+
+/// WASI preview1 は `nepl-wasi` に任せ、端末制御の `wasix_32v1` だけここで実装する。
+fn link_wasi_host(linker: &mut Linker<AllocState>) -> Result<()> {
+    nepl_wasi::add_to_linker(linker)?;
+    linker.func_wrap(
+        "wasix_32v1",
+        "tty_get",
+        |mut caller: Caller<'_, AllocState>, tty_ptr: i32| -> i32 {
+            let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+                Some(m) => m,
+                None => return 21,
+            };
+            if tty_ptr < 0 {
+                return 21;
+            }
+            let base = tty_ptr as usize;
+            if base + 21 > memory.data(&caller).len() {
+                return 21;
+            }
+            if let Some((cols, rows)) = current_terminal_size() {
                 let state = caller.data_mut();
                 state.tty_cols = cols;
                 state.tty_rows = rows;
-                state.tty_width = width;
-                state.tty_height = height;
-                state.tty_stdin_tty = stdin_tty;
-                state.tty_stdout_tty = stdout_tty;
-                state.tty_stderr_tty = stderr_tty;
-                state.tty_echo = echo;
-                state.tty_line_buffered = line_buffered;
-                apply_host_tty_mode(state)
-            },
-        )?;
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_read",
-            |mut caller: Caller<'_, AllocState>,
-             fd: i32,
-             iovs: i32,
-             iovs_len: i32,
-             nread: i32|
-             -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                let data_snapshot = memory.data(&caller).to_vec();
-                let mut total = 0usize;
-                let mut offset = iovs as usize;
-                let count = if iovs_len > 0 { iovs_len as usize } else { 0 };
-                if fd == 0 {
-                    if caller.data().stdin_pos >= caller.data().stdin.len()
-                        && !caller.data().stdin_eof
-                    {
-                        let mut buf = vec![0u8; 4096];
-                        let read = match io::stdin().read(&mut buf) {
-                            Ok(n) => n,
-                            Err(_) => 0,
-                        };
-                        if read == 0 {
-                            caller.data_mut().stdin_eof = true;
-                            caller.data_mut().stdin.clear();
-                            caller.data_mut().stdin_pos = 0;
-                        } else {
-                            caller.data_mut().stdin = buf[..read].to_vec();
-                            caller.data_mut().stdin_pos = 0;
-                        }
-                    }
-                    let stdin_snapshot = caller.data().stdin.clone();
-                    let mut pos = caller.data().stdin_pos;
-                    for _ in 0..count {
-                        if offset + 8 > data_snapshot.len() {
-                            return 21;
-                        }
-                        let base = u32::from_le_bytes(
-                            data_snapshot[offset..offset + 4].try_into().unwrap(),
-                        ) as usize;
-                        let len = u32::from_le_bytes(
-                            data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
-                        ) as usize;
-                        offset += 8;
-                        if base + len > data_snapshot.len() {
-                            return 21;
-                        }
-                        if pos >= stdin_snapshot.len() {
-                            break;
-                        }
-                        let avail = stdin_snapshot.len() - pos;
-                        let take = if len < avail { len } else { avail };
-                        if take == 0 {
-                            break;
-                        }
-                        memory
-                            .write(&mut caller, base, &stdin_snapshot[pos..pos + take])
-                            .ok();
-                        pos += take;
-                        total += take;
-                    }
-                    caller.data_mut().stdin_pos = pos;
-                } else {
-                    for _ in 0..count {
-                        if offset + 8 > data_snapshot.len() {
-                            return 21;
-                        }
-                        let base = u32::from_le_bytes(
-                            data_snapshot[offset..offset + 4].try_into().unwrap(),
-                        ) as usize;
-                        let len = u32::from_le_bytes(
-                            data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
-                        ) as usize;
-                        offset += 8;
-                        if base + len > data_snapshot.len() {
-                            return 21;
-                        }
-                        let (take, chunk) = {
-                            let file = match caller.data_mut().files.get_mut(&fd) {
-                                Some(f) => f,
-                                None => return 8,
-                            };
-                            if file.pos >= file.data.len() {
-                                (0, Vec::new())
-                            } else {
-                                let avail = file.data.len() - file.pos;
-                                let take = if len < avail { len } else { avail };
-                                let chunk = file.data[file.pos..file.pos + take].to_vec();
-                                file.pos += take;
-                                (take, chunk)
-                            }
-                        };
-                        if take == 0 {
-                            break;
-                        }
-                        memory.write(&mut caller, base, &chunk).ok();
-                        total += take;
-                    }
-                }
-                if let Some(mem) = caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    let bytes = (total as u32).to_le_bytes();
-                    if (nread as usize) + 4 <= mem.data(&caller).len() {
-                        mem.write(&mut caller, nread as usize, &bytes).ok();
-                    }
-                }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_close",
-            |mut caller: Caller<'_, AllocState>, fd: i32| -> i32 {
-                if fd <= 2 {
-                    return 0;
-                }
-                if caller.data_mut().files.remove(&fd).is_none() {
-                    return 8;
-                }
-                0
-            },
-        )?;
-        // Minimal wasi fd_write implementation for stdout (fd 1)
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_write",
-            |mut caller: Caller<'_, AllocState>,
-             fd: i32,
-             iovs: i32,
-             iovs_len: i32,
-             nwritten: i32|
-             -> i32 {
-                if fd != 1 {
-                    return 8; // badf
-                }
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21, // enomem-ish
-                };
-                let data_snapshot = memory.data(&caller).to_vec(); // snapshot to avoid alias issues
-                let mut total = 0usize;
-                let mut offset = iovs as usize;
-                let mut saw_newline = false;
-                for _ in 0..iovs_len {
-                    if offset + 8 > data_snapshot.len() {
-                        return 21;
-                    }
-                    let base =
-                        u32::from_le_bytes(data_snapshot[offset..offset + 4].try_into().unwrap())
-                            as usize;
-                    let len = u32::from_le_bytes(
-                        data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
-                    ) as usize;
-                    offset += 8;
-                    if base + len > data_snapshot.len() {
-                        return 21;
-                    }
-                    let slice = &data_snapshot[base..base + len];
-                    if slice.contains(&b'\n') {
-                        saw_newline = true;
-                    }
-                    caller.data_mut().stdout_buf.extend_from_slice(slice);
-                    total += len;
-                }
-                let should_flush = {
-                    let state = caller.data();
-                    saw_newline
-                        || state.stdout_buf.len() >= 8192
-                        || state.stdout_last_flush.elapsed() >= Duration::from_millis(16)
-                };
-                if should_flush && flush_stdout_buffer(caller.data_mut()).is_err() {
-                    return 21;
-                }
-                // write nwritten
-                if let Some(mem) = caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    let bytes = (total as u32).to_le_bytes();
-                    if (nwritten as usize) + 4 <= mem.data(&caller).len() {
-                        mem.write(&mut caller, nwritten as usize, &bytes).ok();
-                    }
-                }
-                0
-            },
-        )?;
-    }
+                state.tty_width = cols;
+                state.tty_height = rows;
+            }
+            let cols = caller.data().tty_cols.to_le_bytes();
+            let rows = caller.data().tty_rows.to_le_bytes();
+            let width = caller.data().tty_width.to_le_bytes();
+            let height = caller.data().tty_height.to_le_bytes();
+            if memory.write(&mut caller, base, &cols).is_err() {
+                return 21;
+            }
+            if memory.write(&mut caller, base + 4, &rows).is_err() {
+                return 21;
+            }
+            if memory.write(&mut caller, base + 8, &width).is_err() {
+                return 21;
+            }
+            if memory.write(&mut caller, base + 12, &height).is_err() {
+                return 21;
+            }
+            let stdin_tty = [if caller.data().tty_stdin_tty { 1 } else { 0 }];
+            let stdout_tty = [if caller.data().tty_stdout_tty { 1 } else { 0 }];
+            let stderr_tty = [if caller.data().tty_stderr_tty { 1 } else { 0 }];
+            let echo = [if caller.data().tty_echo { 1 } else { 0 }];
+            let line_buffered = [if caller.data().tty_line_buffered { 1 } else { 0 }];
+            if memory.write(&mut caller, base + 16, &stdin_tty).is_err() {
+                return 21;
+            }
+            if memory.write(&mut caller, base + 17, &stdout_tty).is_err() {
+                return 21;
+            }
+            if memory.write(&mut caller, base + 18, &stderr_tty).is_err() {
+                return 21;
+            }
+            if memory.write(&mut caller, base + 19, &echo).is_err() {
+                return 21;
+            }
+            if memory.write(&mut caller, base + 20, &line_buffered).is_err() {
+                return 21;
+            }
+            0
+        },
+    )?;
+    linker.func_wrap(
+        "wasix_32v1",
+        "tty_set",
+        |mut caller: Caller<'_, AllocState>, tty_ptr: i32| -> i32 {
+            let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+                Some(m) => m,
+                None => return 21,
+            };
+            if tty_ptr < 0 {
+                return 21;
+            }
+            let base = tty_ptr as usize;
+            let data = memory.data(&caller);
+            if base + 21 > data.len() {
+                return 21;
+            }
+            let cols = u32::from_le_bytes(data[base..base + 4].try_into().unwrap());
+            let rows = u32::from_le_bytes(data[base + 4..base + 8].try_into().unwrap());
+            let width = u32::from_le_bytes(data[base + 8..base + 12].try_into().unwrap());
+            let height = u32::from_le_bytes(data[base + 12..base + 16].try_into().unwrap());
+            let stdin_tty = data[base + 16] != 0;
+            let stdout_tty = data[base + 17] != 0;
+            let stderr_tty = data[base + 18] != 0;
+            let echo = data[base + 19] != 0;
+            let line_buffered = data[base + 20] != 0;
+            let state = caller.data_mut();
+            state.tty_cols = cols;
+            state.tty_rows = rows;
+            state.tty_width = width;
+            state.tty_height = height;
+            state.tty_stdin_tty = stdin_tty;
+            state.tty_stdout_tty = stdout_tty;
+            state.tty_stderr_tty = stderr_tty;
+            state.tty_echo = echo;
+            state.tty_line_buffered = line_buffered;
+            apply_host_tty_mode(state)
+        },
+    )?;
+    Ok(())
+}
+
+/// `--run` 用の WASI 設定。標準入出力はホストのものを使い、
+/// VFS に無いファイルはカレントディレクトリから読み込む。
+fn host_wasi(args: Vec<String>) -> WasiCtxBuilder {
+    WasiCtx::builder()
+        .args(args)
+        .vfs(Vfs::with_host_root("."))
+        .stdin(io::stdin())
+        .stdout(io::stdout())
+        .stderr(io::stderr())
+}
+
+fn new_host_state(args: Vec<String>) -> AllocState {
+    host_state(host_wasi(args).build())
+}
+
+fn host_state(wasi: WasiCtx) -> AllocState {
     let (tty_cols, tty_rows) = current_terminal_size().unwrap_or_else(|| {
         let cols = std::env::var("COLUMNS")
             .ok()
@@ -1261,54 +1360,24 @@ fn run_wasm(
             .unwrap_or(25);
         (cols, rows)
     });
-    let mut store = Store::new(
-        &engine,
-        AllocState {
-            free_head: 0,
-            stdin: Vec::new(),
-            stdin_pos: 0,
-            stdin_eof: false,
-            args: args_bytes,
-            files: BTreeMap::new(),
-            next_fd: 4,
-            tty_cols,
-            tty_rows,
-            tty_width: tty_cols,
-            tty_height: tty_rows,
-            tty_stdin_tty: true,
-            tty_stdout_tty: true,
-            tty_stderr_tty: true,
-            tty_echo: true,
-            tty_line_buffered: true,
-            stdout_buf: Vec::new(),
-            stdout_last_flush: Instant::now(),
-            #[cfg(unix)]
-            tty_saved: false,
-            #[cfg(unix)]
-            tty_original: unsafe { std::mem::zeroed() },
-        },
-    );
-    let instance_pre = linker
-        .instantiate(&mut store, &module)
-        .context("failed to instantiate module")?;
-    let instance = instance_pre
-        .start(&mut store)
-        .context("failed to start module")?;
-    let result = if let Ok(main) = instance.get_typed_func::<(), i32>(&store, "main") {
-        main.call(&mut store, ()).context("failed to execute main")
-    } else if let Ok(main_unit) = instance.get_typed_func::<(), ()>(&store, "main") {
-        main_unit
-            .call(&mut store, ())
-            .context("failed to execute main")?;
-        Ok(0)
-    } else {
-        Err(anyhow::anyhow!(
-            "exported main function missing or has wrong type"
-        ))
-    };
-    let _ = flush_stdout_buffer(store.data_mut());
-    restore_host_tty(store.data());
-    result
+    AllocState {
+        free_head: 0,
+        wasi,
+        tty_cols,
+        tty_rows,
+        tty_width: tty_cols,
+        tty_height: tty_rows,
+        tty_stdin_tty: true,
+        tty_stdout_tty: true,
+        tty_stderr_tty: true,
+        tty_echo: true,
+        tty_line_buffered: true,
+        memory_cap: limits::MemoryCap::default(),
+        #[cfg(unix)]
+        tty_saved: false,
+        #[cfg(unix)]
+        tty_original: unsafe { std::mem::zeroed() },
+    }
 }
 
 fn detect_module_target(module: &nepl_core::ast::Module) -> Option<CompileTarget> {
@@ -1435,6 +1504,48 @@ mod tests {
         assert_eq!(cli.emit, vec![Emit::Wasm, Emit::WatMin]);
     }
 
+    #[test]
+    fn cli_parses_bindings_emit() {
+        let cli = Cli::parse_from(["nepl-cli", "--emit", "wasm,bindings"]);
+        assert_eq!(cli.emit, vec![Emit::Wasm, Emit::Bindings]);
+        assert_eq!(
+            output_path(Path::new("out/lib"), Emit::Bindings),
+            PathBuf::from("out/lib.js")
+        );
+    }
+
+    #[test]
+    fn cli_parses_native_emits_and_triple() {
+        let cli = Cli::parse_from([
+            "nepl-cli",
+            "--target",
+            "llvm",
+            "--emit",
+            "obj,exe",
+            "--triple",
+            "aarch64-apple-darwin",
+        ]);
+        assert_eq!(cli.emit, vec![Emit::Obj, Emit::Exe]);
+        assert_eq!(cli.triple.as_deref(), Some("aarch64-apple-darwin"));
+    }
+
+    #[test]
+    fn native_outputs_follow_the_triple() {
+        let base = Path::new("out/a");
+        assert_eq!(
+            native_output_path(base, Emit::Exe, "x86_64-pc-linux-gnu"),
+            PathBuf::from("out/a")
+        );
+        assert_eq!(
+            native_output_path(base, Emit::Obj, "x86_64-pc-linux-gnu"),
+            PathBuf::from("out/a.o")
+        );
+        assert_eq!(
+            native_output_path(base, Emit::Exe, "x86_64-pc-windows-msvc"),
+            PathBuf::from("out/a.exe")
+        );
+    }
+
     #[test]
     fn cli_parses_profile() {
         let cli = Cli::parse_from(["nepl-cli", "--run", "--profile", "debug"]);
@@ -1479,6 +1590,125 @@ mod tests {
         assert!(!out.contains("\n"));
     }
 
+    fn compile_core(src: &str) -> Vec<u8> {
+        let mut loader = Loader::new(stdlib_root().unwrap());
+        let loaded = loader
+            .load_inline(PathBuf::from("limits.nepl"), src.to_string())
+            .unwrap();
+        compile_module_with_source_map(
+            loaded.module,
+            Some(&loaded.source_map),
+            CompileOptions {
+                target: Some(CompileTarget::Wasm),
+                verbose: false,
+                profile: None,
+            },
+        )
+        .unwrap()
+        .wasm
+    }
+
+    fn run_limited(src: &str, limits: limits::RunLimits) -> Result<i32> {
+        run_wasm_with_state(compile_core(src), CompileTarget::Wasm, new_host_state(Vec::new()), limits)
+            .map(|(code, _)| code)
+    }
+
+    const SPIN: &str = "#entry main
+#indent 4
+#target core
+#import \"core/math\" as *
+
+fn main <()*>i32> ():
+    let mut i 0
+    while true:
+        set i add i 1
+    i
+";
+
+    #[test]
+    fn fuel_limit_stops_a_runaway_loop() {
+        let err = run_limited(
+            SPIN,
+            limits::RunLimits {
+                fuel: Some(50_000),
+                ..limits::RunLimits::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<limits::LimitExceeded>(),
+            Some(&limits::LimitExceeded::Fuel(50_000))
+        );
+    }
+
+    #[test]
+    fn timeout_stops_a_runaway_loop() {
+        let timeout = Duration::from_millis(200);
+        let err = run_limited(
+            SPIN,
+            limits::RunLimits {
+                timeout: Some(timeout),
+                ..limits::RunLimits::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<limits::LimitExceeded>(),
+            Some(&limits::LimitExceeded::Timeout(timeout))
+        );
+    }
+
+    #[test]
+    fn memory_cap_stops_unbounded_allocation() {
+        let src = "#entry main
+#indent 4
+#target core
+#import \"core/mem\" as *
+
+fn main <()*>i32> ():
+    while true:
+        alloc_raw 65536;
+    0
+";
+        let err = run_limited(
+            src,
+            limits::RunLimits {
+                max_memory_pages: Some(16),
+                ..limits::RunLimits::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<limits::LimitExceeded>(),
+            Some(&limits::LimitExceeded::Memory(16))
+        );
+    }
+
+    #[test]
+    fn limits_leave_finishing_programs_alone() {
+        let src = "#entry main
+#indent 4
+#target core
+
+fn main <()*>i32> ():
+    3
+";
+        let limits = Cli::parse_from([
+            "nepl-cli",
+            "--run",
+            "--fuel",
+            "100000",
+            "--max-memory-pages",
+            "64",
+            "--timeout",
+            "5s",
+        ])
+        .limits
+        .to_limits();
+        assert_eq!(limits.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(run_limited(src, limits).unwrap(), 3);
+    }
+
     #[test]
     fn write_outputs_creates_files() {
         let tmp = tempfile::tempdir().expect("tempdir");
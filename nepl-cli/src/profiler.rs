@@ -0,0 +1,257 @@
+//! `--run --profile-out`: 関数単位の実行プロファイル。
+//!
+//! `compile_module_for_profiling` でフックを埋め込んだ wasm を燃料計測付きの wasmi で実行する。
+//! `nepl_profile::enter/leave/alloc` が呼ばれるたびに消費燃料（実行命令数の近似）を読み、
+//! `ProfileSession` が呼び出しスタックに振り分ける。
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use nepl_core::profiler::{ProfileInfo, ProfileSession, PROFILE_IMPORT_MODULE};
+use nepl_core::CompileTarget;
+use wasmi::{Caller, Config, Engine, Linker, Module, Store, Value};
+
+use crate::limits::{self, LimitExceeded, MemoryCap, RunLimits};
+use crate::{
+    check_run_imports, flush_stdout_buffer, link_wasi_host, new_host_state, restore_host_tty,
+    AllocState,
+};
+
+pub(crate) struct ProfileTarget {
+    pub wasm: Vec<u8>,
+    pub info: ProfileInfo,
+    pub target: CompileTarget,
+    pub args: Vec<String>,
+    pub limits: RunLimits,
+}
+
+/// 計測しながら `main` を実行し、終了コード（失敗時はエラー）と集計結果を返す。
+/// トラップや制限で止まった場合も、それまでの計測結果を返す。
+pub(crate) fn run_profiled(target: ProfileTarget) -> (Result<i32>, ProfileSession) {
+    let session = Arc::new(Mutex::new(ProfileSession::new(target.info.clone())));
+    let shared = session.clone();
+    let result = limits::with_timeout(target.limits.timeout, move || profile_main(target, shared));
+    // 時間切れの場合も実行スレッドは動き続けるので、その時点の写しを閉じて使う。
+    let mut snapshot = session.lock().unwrap().clone();
+    snapshot.finish(snapshot.counter());
+    (result, snapshot)
+}
+
+fn profile_main(target: ProfileTarget, session: Arc<Mutex<ProfileSession>>) -> Result<i32> {
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module =
+        Module::new(&engine, target.wasm.as_slice()).context("failed to compile wasm artifact")?;
+    check_run_imports(&module, target.target, &[PROFILE_IMPORT_MODULE])?;
+
+    let mut linker: Linker<AllocState> = Linker::new(&engine);
+    if matches!(target.target, CompileTarget::Wasi | CompileTarget::Wasix) {
+        link_wasi_host(&mut linker)?;
+    }
+    link_profile_hooks(&mut linker, &session)?;
+
+    let mut state = new_host_state(target.args);
+    state.memory_cap = MemoryCap::new(target.limits.max_memory_pages);
+    let mut store = Store::new(&engine, state);
+    store.limiter(|state| &mut state.memory_cap);
+    store
+        .add_fuel(target.limits.fuel.unwrap_or(u64::MAX / 2))
+        .map_err(|e| anyhow::anyhow!("failed to enable fuel metering: {e}"))?;
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .context("failed to instantiate module")?
+        .start(&mut store)
+        .context("failed to start module")?;
+    let main = instance
+        .get_func(&store, "main")
+        .ok_or_else(|| anyhow::anyhow!("exported main function missing"))?;
+    let mut outputs = vec![Value::I32(0); main.ty(&store).results().len()];
+    let result = main.call(&mut store, &[], &mut outputs);
+    let _ = flush_stdout_buffer(store.data_mut());
+    restore_host_tty(store.data());
+    session
+        .lock()
+        .unwrap()
+        .finish(store.fuel_consumed().unwrap_or(0));
+    match (result, target.limits) {
+        (Ok(()), _) => Ok(match outputs.first() {
+            Some(Value::I32(v)) => *v,
+            _ => 0,
+        }),
+        (Err(_), RunLimits {
+            max_memory_pages: Some(pages),
+            ..
+        }) if store.data().memory_cap.exceeded => Err(LimitExceeded::Memory(pages).into()),
+        (Err(err), RunLimits {
+            fuel: Some(fuel), ..
+        }) if limits::is_out_of_fuel(&err) => Err(LimitExceeded::Fuel(fuel).into()),
+        (Err(err), _) => Err(anyhow::Error::new(err).context("failed to execute main")),
+    }
+}
+
+fn link_profile_hooks(
+    linker: &mut Linker<AllocState>,
+    session: &Arc<Mutex<ProfileSession>>,
+) -> Result<()> {
+    let s = session.clone();
+    linker.func_wrap(
+        PROFILE_IMPORT_MODULE,
+        "enter",
+        move |caller: Caller<'_, AllocState>, function: i32| {
+            let counter = caller.fuel_consumed().unwrap_or(0);
+            s.lock().unwrap().on_enter(function as usize, counter)
+        },
+    )?;
+    let s = session.clone();
+    linker.func_wrap(
+        PROFILE_IMPORT_MODULE,
+        "leave",
+        move |caller: Caller<'_, AllocState>| {
+            let counter = caller.fuel_consumed().unwrap_or(0);
+            s.lock().unwrap().on_leave(counter)
+        },
+    )?;
+    let s = session.clone();
+    linker.func_wrap(PROFILE_IMPORT_MODULE, "alloc", move |bytes: i32| {
+        s.lock().unwrap().on_alloc(bytes)
+    })?;
+    Ok(())
+}
+
+/// `out.folded` に対する `out.alloc.folded`。
+fn alloc_output_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.alloc.{}", ext.to_string_lossy()),
+        None => format!("{stem}.alloc"),
+    };
+    path.with_file_name(name)
+}
+
+/// 命令数の folded stack を `out`、確保バイト数のものを `alloc_output_path(out)` に書き、
+/// 表を stderr に出す。
+pub(crate) fn write_report(session: &ProfileSession, path: &Path) -> Result<()> {
+    std::fs::write(path, session.folded_instructions())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    let alloc_path = alloc_output_path(path);
+    std::fs::write(&alloc_path, session.folded_alloc_bytes())
+        .with_context(|| format!("failed to write {}", alloc_path.display()))?;
+    eprint!("{}", session.table());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nepl_core::loader::Loader;
+    use nepl_core::{compile_module_for_profiling, CompileOptions};
+
+    const SRC: &str = "#entry main
+#indent 4
+#target core
+#import \"core/math\" as *
+#import \"core/mem\" as *
+
+fn leaf <(i32)->i32> (n):
+    mul n 3
+
+fn busy <(i32)->i32> (n):
+    let mut i 0
+    let mut acc 0
+    while lt i n:
+        do:
+            set acc add acc leaf i
+            set i add i 1
+    acc
+
+fn grab <()*>i32> ():
+    let p alloc_raw 24
+    dealloc_raw p 24
+    p
+
+fn main <()*>i32> ():
+    grab;
+    grab;
+    busy 100
+";
+
+    fn profile() -> (i32, ProfileSession) {
+        let mut loader = Loader::new(crate::stdlib_root().unwrap());
+        let loaded = loader
+            .load_inline(PathBuf::from("prof.nepl"), SRC.to_string())
+            .unwrap();
+        let (artifact, info) = compile_module_for_profiling(
+            loaded.module,
+            Some(&loaded.source_map),
+            CompileOptions {
+                target: Some(CompileTarget::Wasm),
+                verbose: false,
+                profile: None,
+            },
+        )
+        .unwrap();
+        let (code, session) = run_profiled(ProfileTarget {
+            wasm: artifact.wasm,
+            info,
+            target: CompileTarget::Wasm,
+            args: Vec::new(),
+            limits: RunLimits::default(),
+        });
+        (code.unwrap(), session)
+    }
+
+    #[test]
+    fn instructions_and_allocations_are_attributed_by_source_name() {
+        let (code, session) = profile();
+        assert_eq!(code, 14850);
+        let rows = session.rows();
+        let row = |name: &str| {
+            rows.iter()
+                .find(|r| r.name == name)
+                .unwrap_or_else(|| panic!("no row for {name}: {rows:?}"))
+                .clone()
+        };
+        assert_eq!(row("main").calls, 1);
+        assert_eq!(row("busy").calls, 1);
+        assert_eq!(row("leaf").calls, 100);
+        assert_eq!(row("grab").calls, 2);
+        assert!(row("busy").self_instructions > row("main").self_instructions);
+        assert!(row("busy").total_instructions > row("busy").self_instructions);
+        assert_eq!(
+            row("main").total_instructions,
+            rows.iter().map(|r| r.self_instructions).sum::<u64>()
+        );
+        assert_eq!(row("grab").alloc_bytes, 48);
+        assert_eq!(row("grab").allocs, 2);
+        assert_eq!(row("busy").alloc_bytes, 0);
+    }
+
+    #[test]
+    fn folded_stacks_use_unmangled_names() {
+        let (_, session) = profile();
+        let folded = session.folded_instructions();
+        assert!(folded.lines().any(|l| l.starts_with("main;busy;leaf ")), "{folded}");
+        assert!(!folded.contains("__"), "{folded}");
+        assert_eq!(
+            session.folded_alloc_bytes(),
+            "main;grab;alloc_raw 48\n"
+        );
+        let table = session.table();
+        assert!(table.starts_with("function"), "{table}");
+        assert!(table.lines().nth(1).unwrap().starts_with("busy"), "{table}");
+    }
+
+    #[test]
+    fn alloc_output_sits_next_to_the_folded_file() {
+        assert_eq!(
+            alloc_output_path(Path::new("out/prof.folded")),
+            PathBuf::from("out/prof.alloc.folded")
+        );
+        assert_eq!(alloc_output_path(Path::new("prof")), PathBuf::from("prof.alloc"));
+    }
+}
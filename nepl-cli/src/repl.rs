@@ -0,0 +1,475 @@
+//! `nepl-cli repl`: 対話的な read-eval-print loop。
+//!
+//! 入力された `fn` / `struct` などの項目と `#import` はセッションモジュールに蓄積される。
+//! 式は毎回セッションモジュールの末尾に `__repl_main` として埋め込み、型検査・コンパイルした上で
+//! wasmi で実行する。数値と bool はホスト側で、それ以外は `Debug`、なければ `Stringify` の impl で
+//! 文字列化して表示する。
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use nepl_core::diagnostic::{Diagnostic, Severity};
+use nepl_core::error::CoreError;
+use nepl_core::hir::{HirBody, HirExprKind};
+use nepl_core::loader::{Loader, LoaderError, SourceMap};
+use nepl_core::typecheck::typecheck;
+use nepl_core::{compile_module_with_source_map, BuildProfile, CompileOptions, CompileTarget};
+use wasmi::{Engine, Linker, Module, Store, Value};
+
+use crate::{
+    check_run_imports, flush_stdout_buffer, link_wasi_host, new_host_state, render_diagnostics,
+    restore_host_tty, AllocState,
+};
+
+const REPL_PATH: &str = "<repl>";
+const ENTRY: &str = "__repl_main";
+const VALUE: &str = "__repl_value";
+
+/// 全エントリで共通のヘッダ。結果表示に使う trait は常に読み込む。
+const HEADER: &str = "#entry __repl_main
+#indent 4
+#target std
+#import \"core/traits/debug\" as *
+#import \"core/traits/stringify\" as *
+";
+
+const HELP: &str = "\
+enter an expression to evaluate it, or an item (fn, struct, enum, trait, impl, type,
+newtype, #import) to add it to the session. Lines ending with `:` start a block that
+continues until an empty line.
+  :type <expr>   show the type of an expression
+  :load <file>   add the items and imports of a file
+  :reset         forget all items and imports
+  :quit          leave the REPL";
+
+/// 値をそのまま `main` から返し、ホスト側で表示する型。
+const SCALAR_TYPES: &[&str] = &["i32", "u8", "bool", "f32", "i64", "f64"];
+
+/// `__repl_main` の戻り値の扱い。
+enum Output {
+    Unit,
+    /// `str` のポインタを返す。
+    Str,
+    /// `SCALAR_TYPES` のいずれかをそのまま返す。
+    Scalar(String),
+}
+
+/// 1 エントリの評価結果。
+#[derive(Debug, PartialEq)]
+pub(crate) enum Reply {
+    /// 項目の追加や unit の式など、表示するものがない。
+    Nothing,
+    /// 式の値の文字列表現。
+    Value(String),
+    /// `:type` の結果。
+    Type(String),
+    Message(String),
+    Quit,
+}
+
+#[derive(Debug)]
+pub(crate) enum ReplError {
+    Diagnostics(Vec<Diagnostic>, SourceMap),
+    Message(String),
+}
+
+impl From<anyhow::Error> for ReplError {
+    fn from(err: anyhow::Error) -> Self {
+        ReplError::Message(format!("{err:#}"))
+    }
+}
+
+/// セッションモジュールの状態。
+pub(crate) struct Repl {
+    std_root: PathBuf,
+    imports: Vec<String>,
+    /// 受理済みの項目のソース。各要素は改行で終わる。
+    items: Vec<String>,
+}
+
+impl Repl {
+    pub(crate) fn new(std_root: PathBuf) -> Self {
+        Self {
+            std_root,
+            imports: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+
+    /// 1 エントリ（複数行可）を評価する。失敗したエントリはセッションに残らない。
+    pub(crate) fn eval(&mut self, entry: &str) -> Result<Reply, ReplError> {
+        let trimmed = entry.trim();
+        if trimmed.is_empty() {
+            return Ok(Reply::Nothing);
+        }
+        if let Some(command) = trimmed.strip_prefix(':') {
+            let (name, arg) = command
+                .split_once(char::is_whitespace)
+                .map(|(n, a)| (n, a.trim()))
+                .unwrap_or((command, ""));
+            return match name {
+                "t" | "type" => self.type_of(arg).map(Reply::Type),
+                "l" | "load" => self.load(Path::new(arg)),
+                "r" | "reset" => {
+                    self.imports.clear();
+                    self.items.clear();
+                    Ok(Reply::Message("session cleared".to_string()))
+                }
+                "q" | "quit" => Ok(Reply::Quit),
+                "h" | "help" => Ok(Reply::Message(HELP.to_string())),
+                other => Err(ReplError::Message(format!(
+                    "unknown command `:{other}` (try `:help`)"
+                ))),
+            };
+        }
+        if trimmed.starts_with("#import") {
+            return self.add(vec![trimmed.to_string()], Vec::new());
+        }
+        if trimmed.starts_with('#') {
+            return Err(ReplError::Message(
+                "only #import directives are accepted in the REPL".to_string(),
+            ));
+        }
+        if is_item(trimmed) {
+            return self.add(Vec::new(), vec![format!("{}\n", entry.trim_end())]);
+        }
+        self.evaluate(entry)
+    }
+
+    /// 項目と import を仮に加えて型検査し、通れば確定する。
+    fn add(&mut self, imports: Vec<String>, items: Vec<String>) -> Result<Reply, ReplError> {
+        let imports_len = self.imports.len();
+        let items_len = self.items.len();
+        self.imports.extend(imports);
+        self.items.extend(items);
+        let main = format!("fn {ENTRY} <()*>()> ():\n    ()\n");
+        if let Err(err) = self.check(&main) {
+            self.imports.truncate(imports_len);
+            self.items.truncate(items_len);
+            return Err(err);
+        }
+        Ok(Reply::Nothing)
+    }
+
+    fn load(&mut self, path: &Path) -> Result<Reply, ReplError> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut imports = Vec::new();
+        let mut body = String::new();
+        for line in text.lines() {
+            let directive = line.trim_start();
+            if directive.starts_with("#import") {
+                imports.push(directive.to_string());
+            } else if let Some(width) = directive.strip_prefix("#indent") {
+                if width.trim() != "4" {
+                    return Err(ReplError::Message(format!(
+                        "{} uses `#indent{width}`; only `#indent 4` files can be loaded",
+                        path.display()
+                    )));
+                }
+            } else if !directive.starts_with("#entry") && !directive.starts_with("#target") {
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+        let items = if body.trim().is_empty() { Vec::new() } else { vec![body] };
+        self.add(imports, items)?;
+        Ok(Reply::Message(format!("loaded {}", path.display())))
+    }
+
+    /// `expr` を `let __repl_value` に束縛した `__repl_main` を作る。
+    fn entry_with(&self, expr: &str, result: &str, tail: &str) -> String {
+        let expr = expr.trim_end();
+        let binding = if expr.contains('\n') {
+            let body: String = expr
+                .lines()
+                .map(|line| format!("        {line}\n"))
+                .collect();
+            format!("    let {VALUE} block:\n{body}")
+        } else {
+            format!("    let {VALUE} {}\n", expr.trim())
+        };
+        format!("fn {ENTRY} <()*>{result}> ():\n{binding}    {tail}\n")
+    }
+
+    fn type_of(&self, expr: &str) -> Result<String, ReplError> {
+        if expr.is_empty() {
+            return Err(ReplError::Message("usage: :type <expr>".to_string()));
+        }
+        let (types, hir) = self.check(&self.entry_with(expr, "()", "()"))?;
+        let ty = hir
+            .functions
+            .iter()
+            .find(|f| f.name == ENTRY || f.name.starts_with(&format!("{ENTRY}__")))
+            .and_then(|f| match &f.body {
+                HirBody::Block(block) => block.lines.iter().find_map(|line| match &line.expr.kind {
+                    HirExprKind::Let { name, value, .. } if name == VALUE => Some(value.ty),
+                    _ => None,
+                }),
+                _ => None,
+            })
+            .ok_or_else(|| ReplError::Message("could not infer the type".to_string()))?;
+        Ok(types.type_to_string(ty))
+    }
+
+    fn evaluate(&self, expr: &str) -> Result<Reply, ReplError> {
+        // 式そのものの誤りはここで報告し、以降の失敗は表示用 impl の有無として扱う。
+        let ty = self.type_of(expr)?;
+        if ty == "unit" {
+            self.run(&self.entry_with(expr, "()", "()"), Output::Unit)?;
+            return Ok(Reply::Nothing);
+        }
+        if SCALAR_TYPES.contains(&ty.as_str()) {
+            let text = self.run(&self.entry_with(expr, &ty, VALUE), Output::Scalar(ty))?;
+            return Ok(Reply::Value(text.unwrap_or_default()));
+        }
+        for show in ["debug_string", "stringify"] {
+            let source = self.entry_with(expr, "str", &format!("{show} {VALUE}"));
+            if self.compile(&source).is_ok() {
+                let text = self.run(&source, Output::Str)?;
+                return Ok(Reply::Value(text.unwrap_or_default()));
+            }
+        }
+        self.run(&self.entry_with(expr, "()", "()"), Output::Unit)?;
+        Ok(Reply::Value(format!("<{ty}>")))
+    }
+
+    fn source(&self, entry: &str) -> String {
+        let mut src = String::from(HEADER);
+        for import in &self.imports {
+            src.push_str(import);
+            src.push('\n');
+        }
+        src.push('\n');
+        for item in &self.items {
+            src.push_str(item);
+            src.push('\n');
+        }
+        src.push_str(entry);
+        src
+    }
+
+    fn load_source(&self, entry: &str) -> Result<(nepl_core::ast::Module, SourceMap), ReplError> {
+        let mut loader = Loader::new(self.std_root.clone());
+        match loader.load_inline(PathBuf::from(REPL_PATH), self.source(entry)) {
+            Ok(res) => Ok((res.module, res.source_map)),
+            Err(LoaderError::Core(CoreError::Diagnostics(diags))) => {
+                Err(ReplError::Diagnostics(diags, loader.source_map().clone()))
+            }
+            Err(e) => Err(ReplError::Message(e.to_string())),
+        }
+    }
+
+    fn check(
+        &self,
+        entry: &str,
+    ) -> Result<(nepl_core::types::TypeCtx, nepl_core::hir::HirModule), ReplError> {
+        let (module, source_map) = self.load_source(entry)?;
+        let tc = typecheck(&module, CompileTarget::Wasi, BuildProfile::Debug, Some(&source_map));
+        let errors: Vec<Diagnostic> = tc
+            .diagnostics
+            .into_iter()
+            .filter(|d| matches!(d.severity, Severity::Error))
+            .collect();
+        match tc.module {
+            Some(hir) if errors.is_empty() => Ok((tc.types, hir)),
+            _ => Err(ReplError::Diagnostics(errors, source_map)),
+        }
+    }
+
+    fn compile(&self, entry: &str) -> Result<Vec<u8>, ReplError> {
+        let (module, source_map) = self.load_source(entry)?;
+        let options = CompileOptions {
+            target: Some(CompileTarget::Wasi),
+            verbose: false,
+            profile: Some(BuildProfile::Debug),
+        };
+        match compile_module_with_source_map(module, Some(&source_map), options) {
+            Ok(artifact) => Ok(artifact.wasm),
+            Err(CoreError::Diagnostics(diags)) => Err(ReplError::Diagnostics(diags, source_map)),
+            Err(e) => Err(ReplError::Message(e.to_string())),
+        }
+    }
+
+    /// `__repl_main` を実行し、`output` に従って戻り値を文字列にする。
+    fn run(&self, entry: &str, output: Output) -> Result<Option<String>, ReplError> {
+        let wasm = self.compile(entry)?;
+        let engine = Engine::default();
+        let module = Module::new(&engine, wasm.as_slice()).context("failed to compile wasm")?;
+        check_run_imports(&module, CompileTarget::Wasi, &[])?;
+        let mut linker: Linker<AllocState> = Linker::new(&engine);
+        link_wasi_host(&mut linker)?;
+        let mut store = Store::new(&engine, new_host_state(vec![REPL_PATH.to_string()]));
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .context("failed to instantiate module")?
+            .start(&mut store)
+            .context("failed to start module")?;
+        let main = instance
+            .get_func(&store, "main")
+            .context("exported main function missing")?;
+        let mut results = vec![Value::I32(0); main.ty(&store).results().len()];
+        let result = main
+            .call(&mut store, &[], &mut results)
+            .context("evaluation trapped")
+            .map(|()| match (output, results.first()) {
+                (Output::Str, Some(Value::I32(ptr))) => instance
+                    .get_memory(&store, "memory")
+                    .and_then(|mem| read_str(mem.data(&store), *ptr as u32)),
+                (Output::Scalar(ty), Some(value)) => Some(format_scalar(&ty, value)),
+                _ => None,
+            });
+        let _ = flush_stdout_buffer(store.data_mut());
+        restore_host_tty(store.data());
+        Ok(result?)
+    }
+}
+
+fn format_scalar(ty: &str, value: &Value) -> String {
+    match (ty, value) {
+        ("bool", Value::I32(v)) => (*v != 0).to_string(),
+        ("u8", Value::I32(v)) => (*v as u8).to_string(),
+        (_, Value::I32(v)) => v.to_string(),
+        (_, Value::I64(v)) => v.to_string(),
+        (_, Value::F32(v)) => v.to_float().to_string(),
+        (_, Value::F64(v)) => v.to_float().to_string(),
+        (_, other) => format!("{other:?}"),
+    }
+}
+
+/// 線形メモリ上の `str`（`[len: u32][bytes]`）を読む。
+fn read_str(memory: &[u8], ptr: u32) -> Option<String> {
+    let start = ptr as usize;
+    let len = u32::from_le_bytes(memory.get(start..start + 4)?.try_into().ok()?) as usize;
+    let bytes = memory.get(start + 4..start + 4 + len)?;
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn is_item(entry: &str) -> bool {
+    let first = entry.split_whitespace().next().unwrap_or("");
+    let first = if first == "pub" {
+        entry.split_whitespace().nth(1).unwrap_or("")
+    } else {
+        first
+    };
+    matches!(
+        first,
+        "fn" | "struct" | "enum" | "trait" | "impl" | "type" | "newtype"
+    )
+}
+
+/// off-side 規則による継続判定。`:` で終わる行はブロックを開き、空行で閉じる。
+fn entry_is_complete(buffer: &str) -> bool {
+    let mut lines = buffer.lines();
+    let first = lines.next().unwrap_or("");
+    if lines.next().is_none() {
+        return !first.trim_end().ends_with(':');
+    }
+    buffer.ends_with("\n\n") || buffer.ends_with("\r\n\r\n")
+}
+
+/// プロンプトを出しながら `input` を読み、入力の終端か `:quit` で終わる。
+pub(crate) fn run_repl(repl: &mut Repl, input: &mut dyn BufRead, out: &mut dyn Write) -> Result<()> {
+    writeln!(out, "NEPLg2 REPL. Type :help for commands.")?;
+    let mut buffer = String::new();
+    loop {
+        write!(out, "{}", if buffer.is_empty() { ">>> " } else { "... " })?;
+        out.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(out)?;
+            return Ok(());
+        }
+        if buffer.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        buffer.push_str(&line);
+        if !entry_is_complete(&buffer) {
+            continue;
+        }
+        let entry = std::mem::take(&mut buffer);
+        match repl.eval(&entry) {
+            Ok(Reply::Nothing) => {}
+            Ok(Reply::Value(text)) | Ok(Reply::Type(text)) | Ok(Reply::Message(text)) => {
+                writeln!(out, "{text}")?
+            }
+            Ok(Reply::Quit) => return Ok(()),
+            Err(ReplError::Diagnostics(diags, source_map)) => {
+                out.flush()?;
+                render_diagnostics(&diags, &source_map);
+            }
+            Err(ReplError::Message(msg)) => writeln!(out, "error: {msg}")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repl() -> Repl {
+        Repl::new(crate::stdlib_root().unwrap())
+    }
+
+    fn value(repl: &mut Repl, entry: &str) -> String {
+        match repl.eval(entry) {
+            Ok(Reply::Value(text)) => text,
+            other => panic!("expected a value for `{entry}`, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn expressions_print_their_value() {
+        let mut repl = repl();
+        assert_eq!(repl.eval("#import \"core/math\" as *").unwrap(), Reply::Nothing);
+        assert_eq!(value(&mut repl, "add 1 2"), "3");
+        assert_eq!(value(&mut repl, "lt 2 1"), "false");
+        assert_eq!(value(&mut repl, "\"hi\""), "\"hi\"");
+        assert_eq!(value(&mut repl, "if true:\n    then 1\n    else 2\n"), "1");
+    }
+
+    #[test]
+    fn items_stay_in_the_session_until_reset() {
+        let mut repl = repl();
+        repl.eval("#import \"core/math\" as *").unwrap();
+        let item = "fn sq <(i32)->i32> (x):\n    mul x x\n";
+        assert_eq!(repl.eval(item).unwrap(), Reply::Nothing);
+        assert_eq!(value(&mut repl, "sq 7"), "49");
+        assert_eq!(repl.eval(":type sq 7").unwrap(), Reply::Type("i32".to_string()));
+        assert_eq!(
+            repl.eval(":reset").unwrap(),
+            Reply::Message("session cleared".to_string())
+        );
+        assert!(matches!(repl.eval("sq 7"), Err(ReplError::Diagnostics(..))));
+    }
+
+    #[test]
+    fn rejected_items_are_not_kept() {
+        let mut repl = repl();
+        let bad = "fn bad <()->i32> ():\n    nope\n";
+        assert!(matches!(repl.eval(bad), Err(ReplError::Diagnostics(..))));
+        assert!(repl.items.is_empty());
+        assert_eq!(repl.eval("struct P:\n    x <i32>\n").unwrap(), Reply::Nothing);
+        assert_eq!(value(&mut repl, "P 1"), "<P>");
+    }
+
+    #[test]
+    fn blocks_continue_until_an_empty_line() {
+        assert!(entry_is_complete("add 1 2\n"));
+        assert!(!entry_is_complete("fn f <()->i32> ():\n"));
+        assert!(!entry_is_complete("fn f <()->i32> ():\n    1\n"));
+        assert!(entry_is_complete("fn f <()->i32> ():\n    1\n\n"));
+    }
+
+    #[test]
+    fn run_repl_reads_entries_and_stops_at_quit() {
+        let mut repl = repl();
+        let script = "#import \"core/math\" as *\nfn inc <(i32)->i32> (x):\n    add x 1\n\ninc 41\n:quit\ninc 1\n";
+        let mut out = Vec::new();
+        run_repl(&mut repl, &mut script.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(">>> ... ... >>> 42\n>>> "), "{out}");
+        assert!(!out.contains("\n2\n"), "{out}");
+    }
+}
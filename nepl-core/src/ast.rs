@@ -28,7 +28,7 @@ pub enum TypeExpr {
     Named(String),
     Apply(Box<TypeExpr>, Vec<TypeExpr>),
     Boxed(Box<TypeExpr>),
-    Reference(Box<TypeExpr>, bool), // (inner, is_mut)
+    Reference(Box<TypeExpr>, bool, Option<String>), // (inner, is_mut, region)
     Tuple(Vec<TypeExpr>),
     Function {
         params: Vec<TypeExpr>,
@@ -77,6 +77,7 @@ pub enum PrefixItem {
     TypeAnnotation(TypeExpr, Span),
     Block(Block, Span),
     Match(MatchExpr, Span),
+    For(ForExpr, Span),
     Pipe(Span),
     Tuple(Vec<PrefixExpr>, Span),
     Group(PrefixExpr, Span),
@@ -95,9 +96,27 @@ pub enum Symbol {
     },
     Set { name: Ident },
     If(Span),
-    While(Span),
+    /// `while` with an optional loop label given as `while <.label> ...`.
+    While(Span, Option<String>),
+    /// `break` / `break <.label>`: leaves the innermost (or labelled) loop.
+    Break {
+        label: Option<String>,
+        span: Span,
+    },
+    /// `continue` / `continue <.label>`: jumps to the next iteration.
+    Continue {
+        label: Option<String>,
+        span: Span,
+    },
+    /// `return <expr>`: leaves the enclosing function with a value.
+    Return(Span),
+    /// `try <expr>`: unwraps an `Ok`/`Some`, or returns the `Err`/`None` from
+    /// the enclosing function.
+    Try(Span),
     AddrOf(Span),
     Deref(Span),
+    /// `#comptime <expr>`: the argument is evaluated during compilation.
+    Comptime(Span),
 }
 
 /// A block of statements (introduced by `:` or the file root).
@@ -114,6 +133,8 @@ pub struct FnDef {
     pub vis: Visibility,
     pub name: Ident,
     pub no_shadow: bool,
+    /// Region parameters (`'a`) declared before the type parameters.
+    pub regions: Vec<Ident>,
     pub type_params: Vec<TypeParam>,
     pub signature: TypeExpr,
     pub params: Vec<Ident>,
@@ -254,6 +275,8 @@ pub enum Stmt {
     FnAlias(FnAlias),
     StructDef(StructDef),
     EnumDef(EnumDef),
+    TypeDef(TypeDef),
+    Global(GlobalDef),
     Wasm(WasmBlock),
     LlvmIr(LlvmIrBlock),
     Trait(TraitDef),
@@ -326,6 +349,46 @@ pub struct EnumDef {
     pub variants: Vec<EnumVariant>,
 }
 
+/// Type declaration: `type Name<.T> = TypeExpr` or `newtype Name<.T> = TypeExpr`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDef {
+    pub doc: Option<String>,
+    pub vis: Visibility,
+    pub kind: TypeDefKind,
+    pub name: Ident,
+    pub type_params: Vec<TypeParam>,
+    pub target: TypeExpr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeDefKind {
+    /// Another name for `target`; interchangeable with it everywhere.
+    Alias,
+    /// A distinct type with the same runtime representation as `target`.
+    Newtype,
+}
+
+/// Module-level value item: `const NAME <T> expr` or `static [mut] NAME <T> expr`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalDef {
+    pub doc: Option<String>,
+    pub vis: Visibility,
+    pub kind: GlobalKind,
+    pub name: Ident,
+    pub ty: TypeExpr,
+    pub value: PrefixExpr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalKind {
+    /// Folded at compile time and inlined at every use.
+    Const,
+    /// Stored once in the module; `mutable` allows `set` from impure code.
+    Static { mutable: bool },
+}
+
 /// Match expression arms.
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchArm {
@@ -343,6 +406,16 @@ pub struct MatchExpr {
     pub span: Span,
 }
 
+/// `for [<.label>] x in <iterable>:` loop over an `Iterator`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForExpr {
+    pub label: Option<String>,
+    pub binding: Ident,
+    pub iterable: PrefixExpr,
+    pub body: Block,
+    pub span: Span,
+}
+
 /// Intrinsic expression: `#intrinsic "name" <Args...> (Exprs...)`
 #[derive(Debug, Clone, PartialEq)]
 pub struct IntrinsicExpr {
@@ -0,0 +1,653 @@
+//! JavaScript / TypeScript bindings for the `pub` functions of a wasm module.
+//!
+//! `generate_bindings` reads the typed signatures of `HirModule::exports` and
+//! describes every argument and result type with the layouts the wasm backend
+//! uses (`type_storage_size_bytes`, `struct_field_layout_by_name`,
+//! `tuple_field_layout`, `enum_payload_layout`). The generated JS embeds those
+//! descriptions as a table and marshals values through linear memory with a
+//! small runtime; strings and aggregates are allocated through the module's
+//! allocator, exported as `ALLOC_EXPORT`.
+//!
+//! Structs map to objects keyed by field name, tuples to arrays and enums
+//! (including `Option` / `Result`) to `{ tag, value }` objects. Memory handed
+//! to or returned from the module is not freed by the bindings.
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::codegen_wasm::{
+    enum_payload_layout, enum_variant_payload, is_aggregate_storage_type,
+    struct_field_layout_by_name, tuple_field_layout, type_storage_size_bytes,
+};
+use crate::diagnostic::Diagnostic;
+use crate::hir::*;
+use crate::types::{TypeCtx, TypeId, TypeKind};
+
+/// Export name of the allocator used to pass strings and aggregates in.
+pub const ALLOC_EXPORT: &str = "__nepl_alloc";
+
+/// Export names the module already uses for itself.
+const RESERVED_EXPORTS: &[&str] = &["memory", "main", "_start", ALLOC_EXPORT];
+
+/// Generated glue: an ES module and its type declarations.
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    pub js: String,
+    pub dts: String,
+}
+
+/// How a value of some type is represented in JS and in linear memory.
+#[derive(Debug, Clone)]
+enum Repr {
+    /// `i32`, `u8`, `bool`, `f32`, `i64`, `u64`, `f64`, `str` or `unit`.
+    Scalar(&'static str),
+    /// A struct or enum registered in the type table under this name.
+    Named(String),
+    /// Items and storage size of a tuple.
+    Tuple(Vec<Slot>, u32),
+}
+
+/// A value stored at `offset` inside an aggregate.
+#[derive(Debug, Clone)]
+struct Slot {
+    offset: u32,
+    /// Size of an aggregate stored inline, or 0 when the slot holds a wasm value.
+    inline_size: u32,
+    repr: Repr,
+}
+
+#[derive(Debug, Clone)]
+enum Decl {
+    Struct {
+        fields: Vec<(String, Slot)>,
+        size: u32,
+    },
+    Enum {
+        /// Variant name, payload offset, allocation size and payload.
+        variants: Vec<(String, u32, u32, Option<Repr>)>,
+    },
+}
+
+struct Describer<'a> {
+    types: &'a TypeCtx,
+    decls: BTreeMap<String, Option<Decl>>,
+}
+
+/// Generates bindings for the exported functions of `module`.
+///
+/// `module` must be the monomorphized module that is passed to the wasm backend.
+pub fn generate_bindings(types: &TypeCtx, module: &HirModule) -> Result<Bindings, Vec<Diagnostic>> {
+    let mut describer = Describer {
+        types,
+        decls: BTreeMap::new(),
+    };
+    let mut diagnostics = Vec::new();
+    let mut functions = Vec::new();
+    for export in &module.exports {
+        let Some(func) = module.functions.iter().find(|f| f.name == export.symbol) else {
+            continue;
+        };
+        if RESERVED_EXPORTS.contains(&export.name.as_str()) {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "cannot export '{}': the name is reserved by the module",
+                    export.name
+                ),
+                func.span,
+            ));
+            continue;
+        }
+        if module
+            .exports
+            .iter()
+            .filter(|other| other.name == export.name)
+            .count()
+            > 1
+        {
+            diagnostics.push(Diagnostic::error(
+                format!("cannot export overloaded function '{}'", export.name),
+                func.span,
+            ));
+            continue;
+        }
+        let params = func
+            .params
+            .iter()
+            .map(|p| describer.describe(p.ty).map(|repr| (p.name.clone(), repr)))
+            .collect::<Result<Vec<_>, _>>();
+        let result = describer.describe(func.result);
+        match (params, result) {
+            (Ok(params), Ok(result)) => functions.push((export.name.clone(), params, result)),
+            (Err(msg), _) | (_, Err(msg)) => diagnostics.push(Diagnostic::error(
+                format!("cannot generate bindings for '{}': {}", export.name, msg),
+                func.span,
+            )),
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    let decls = describer
+        .decls
+        .into_iter()
+        .filter_map(|(name, decl)| decl.map(|decl| (name, decl)))
+        .collect::<Vec<_>>();
+    Ok(Bindings {
+        js: render_js(&decls, &functions),
+        dts: render_dts(&decls, &functions),
+    })
+}
+
+impl Describer<'_> {
+    fn describe(&mut self, ty: TypeId) -> Result<Repr, String> {
+        let ctx = self.types;
+        let ty = ctx.resolve_id(ty);
+        let kind = ctx.get(ty);
+        match &kind {
+            TypeKind::Unit => Ok(Repr::Scalar("unit")),
+            TypeKind::I32 => Ok(Repr::Scalar("i32")),
+            TypeKind::U8 => Ok(Repr::Scalar("u8")),
+            TypeKind::Bool => Ok(Repr::Scalar("bool")),
+            TypeKind::F32 => Ok(Repr::Scalar("f32")),
+            TypeKind::Str => Ok(Repr::Scalar("str")),
+            TypeKind::Named(name) => match name.as_str() {
+                "i64" => Ok(Repr::Scalar("i64")),
+                "u64" => Ok(Repr::Scalar("u64")),
+                "f64" => Ok(Repr::Scalar("f64")),
+                _ => Err(format!("type '{name}' has no JS representation")),
+            },
+            TypeKind::Tuple { items } => (0..items.len())
+                .map(|index| {
+                    let (item_ty, offset) = tuple_field_layout(ctx, ty, index)
+                        .ok_or_else(|| format!("no layout for '{}'", ctx.type_to_string(ty)))?;
+                    self.slot(item_ty, offset)
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(|items| Repr::Tuple(items, type_storage_size_bytes(ctx, ty))),
+            TypeKind::Struct { .. } | TypeKind::Enum { .. } => self.describe_named(ty, &kind),
+            TypeKind::Apply { base, .. } => {
+                let base_kind = ctx.get(ctx.resolve_id(*base));
+                match base_kind {
+                    TypeKind::Struct { .. } | TypeKind::Enum { .. } => {
+                        self.describe_named(ty, &base_kind)
+                    }
+                    _ => Err(format!(
+                        "type '{}' has no JS representation",
+                        ctx.type_to_string(ty)
+                    )),
+                }
+            }
+            _ => Err(format!(
+                "type '{}' has no JS representation",
+                ctx.type_to_string(ty)
+            )),
+        }
+    }
+
+    /// Registers a struct or enum in the type table. `kind` is the type itself,
+    /// or the generic base for an applied type.
+    fn describe_named(&mut self, ty: TypeId, kind: &TypeKind) -> Result<Repr, String> {
+        let ctx = self.types;
+        let name = type_name(ctx, ty);
+        if self.decls.contains_key(&name) {
+            return Ok(Repr::Named(name));
+        }
+        // Recursive types refer back to the entry while it is being described.
+        self.decls.insert(name.clone(), None);
+        let decl = match kind {
+            TypeKind::Struct { field_names, .. } => {
+                let mut fields = Vec::new();
+                for field_name in field_names {
+                    let (field_ty, offset) = struct_field_layout_by_name(ctx, ty, field_name)
+                        .ok_or_else(|| format!("no layout for field '{field_name}'"))?;
+                    fields.push((field_name.clone(), self.slot(field_ty, offset)?));
+                }
+                Decl::Struct {
+                    fields,
+                    size: type_storage_size_bytes(ctx, ty),
+                }
+            }
+            TypeKind::Enum { variants, .. } => {
+                let mut out = Vec::new();
+                for variant in variants {
+                    let payload = enum_variant_payload(ctx, ty, &variant.name).filter(|payload| {
+                        !matches!(ctx.get(ctx.resolve_id(*payload)), TypeKind::Unit)
+                    });
+                    let (offset, size) = enum_payload_layout(ctx, payload);
+                    let repr = payload.map(|payload| self.describe(payload)).transpose()?;
+                    out.push((variant.name.clone(), offset, size, repr));
+                }
+                Decl::Enum { variants: out }
+            }
+            _ => unreachable!("describe_named is only called for structs and enums"),
+        };
+        self.decls.insert(name.clone(), Some(decl));
+        Ok(Repr::Named(name))
+    }
+
+    fn slot(&mut self, ty: TypeId, offset: u32) -> Result<Slot, String> {
+        let inline_size = if is_aggregate_storage_type(self.types, ty) {
+            type_storage_size_bytes(self.types, ty)
+        } else {
+            0
+        };
+        Ok(Slot {
+            offset,
+            inline_size,
+            repr: self.describe(ty)?,
+        })
+    }
+}
+
+/// Names a type such as `Result<i32,str>` with an identifier like `Result_i32_str`.
+fn type_name(ctx: &TypeCtx, ty: TypeId) -> String {
+    let ty = ctx.resolve_id(ty);
+    match ctx.get(ty) {
+        TypeKind::Struct { name, .. } | TypeKind::Enum { name, .. } => identifier(&name),
+        TypeKind::Apply { base, args } => {
+            let mut out = type_name(ctx, base);
+            for arg in args {
+                out.push('_');
+                out.push_str(&type_name(ctx, arg));
+            }
+            out
+        }
+        _ => identifier(&ctx.type_to_string(ty)),
+    }
+}
+
+fn identifier(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c);
+        } else if !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    while out.ends_with('_') {
+        out.pop();
+    }
+    out
+}
+
+fn js_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn js_repr(repr: &Repr) -> String {
+    match repr {
+        Repr::Scalar(name) => js_string(name),
+        Repr::Named(name) => js_string(name),
+        Repr::Tuple(items, size) => {
+            let items = items.iter().map(js_slot).collect::<Vec<_>>();
+            format!(
+                "{{ kind: \"tuple\", size: {size}, items: [{}] }}",
+                items.join(", ")
+            )
+        }
+    }
+}
+
+fn js_slot(slot: &Slot) -> String {
+    format!(
+        "[{}, {}, {}]",
+        slot.offset,
+        slot.inline_size,
+        js_repr(&slot.repr)
+    )
+}
+
+fn ts_type(repr: &Repr) -> String {
+    match repr {
+        Repr::Scalar("i64" | "u64") => "bigint".to_string(),
+        Repr::Scalar("bool") => "boolean".to_string(),
+        Repr::Scalar("str") => "string".to_string(),
+        Repr::Scalar("unit") => "void".to_string(),
+        Repr::Scalar(_) => "number".to_string(),
+        Repr::Named(name) => name.clone(),
+        Repr::Tuple(items, _) => {
+            let items = items
+                .iter()
+                .map(|slot| ts_type(&slot.repr))
+                .collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Property key, quoted only when it is not an identifier.
+fn js_key(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        js_string(name)
+    }
+}
+
+/// Parameter name usable in a declaration; falls back to `argN`.
+fn ts_param(name: &str, index: usize) -> String {
+    const RESERVED: &[&str] = &[
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "debugger",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "new",
+        "null",
+        "return",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "with",
+        "yield",
+        "let",
+        "static",
+        "await",
+    ];
+    if is_identifier(name) && !RESERVED.contains(&name) {
+        name.to_string()
+    } else {
+        format!("arg{index}")
+    }
+}
+
+type Function = (String, Vec<(String, Repr)>, Repr);
+
+fn render_js(decls: &[(String, Decl)], functions: &[Function]) -> String {
+    let mut out = String::from("// Generated by nepl-cli --emit bindings. Do not edit.\n\n");
+    out.push_str("const TYPES = {\n");
+    for (name, decl) in decls {
+        let body = match decl {
+            Decl::Struct { fields, size } => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, slot)| {
+                        format!(
+                            "[{}, {}, {}, {}]",
+                            js_string(field),
+                            slot.offset,
+                            slot.inline_size,
+                            js_repr(&slot.repr)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    "{{ kind: \"struct\", size: {size}, fields: [{}] }}",
+                    fields.join(", ")
+                )
+            }
+            Decl::Enum { variants } => {
+                let variants = variants
+                    .iter()
+                    .map(|(variant, offset, size, payload)| {
+                        format!(
+                            "[{}, {offset}, {size}, {}]",
+                            js_string(variant),
+                            payload
+                                .as_ref()
+                                .map(js_repr)
+                                .unwrap_or_else(|| "null".to_string())
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                format!("{{ kind: \"enum\", variants: [{}] }}", variants.join(", "))
+            }
+        };
+        out.push_str(&format!("  {}: {body},\n", js_key(name)));
+    }
+    out.push_str("};\n\n");
+    out.push_str(JS_RUNTIME);
+    out.push_str("\nexport function bind(instance) {\n");
+    out.push_str("  const exports = instance.exports;\n");
+    out.push_str("  const rt = runtime(exports);\n");
+    out.push_str("  return {\n");
+    for (name, params, result) in functions {
+        let args = (0..params.len())
+            .map(|i| format!("a{i}"))
+            .collect::<Vec<_>>();
+        let lowered = params
+            .iter()
+            .enumerate()
+            .map(|(i, (_, repr))| format!("rt.lower({}, a{i})", js_repr(repr)))
+            .collect::<Vec<_>>();
+        out.push_str(&format!(
+            "    {}: ({}) => rt.lift({}, exports[{}]({})),\n",
+            js_key(name),
+            args.join(", "),
+            js_repr(result),
+            js_string(name),
+            lowered.join(", ")
+        ));
+    }
+    out.push_str("  };\n}\n\n");
+    out.push_str(
+        "export async function instantiate(source, imports = {}) {\n  \
+         const result = await WebAssembly.instantiate(source, imports);\n  \
+         return bind(result instanceof WebAssembly.Instance ? result : result.instance);\n}\n",
+    );
+    out
+}
+
+fn render_dts(decls: &[(String, Decl)], functions: &[Function]) -> String {
+    let mut out = String::from("// Generated by nepl-cli --emit bindings. Do not edit.\n\n");
+    for (name, decl) in decls {
+        match decl {
+            Decl::Struct { fields, .. } => {
+                out.push_str(&format!("export interface {name} {{\n"));
+                for (field, slot) in fields {
+                    out.push_str(&format!("  {}: {};\n", js_key(field), ts_type(&slot.repr)));
+                }
+                out.push_str("}\n\n");
+            }
+            Decl::Enum { variants } => {
+                out.push_str(&format!("export type {name} =\n"));
+                for (variant, _, _, payload) in variants {
+                    match payload {
+                        Some(repr) => out.push_str(&format!(
+                            "  | {{ tag: {}; value: {} }}\n",
+                            js_string(variant),
+                            ts_type(repr)
+                        )),
+                        None => out.push_str(&format!("  | {{ tag: {} }}\n", js_string(variant))),
+                    }
+                }
+                if variants.is_empty() {
+                    out.push_str("  never\n");
+                }
+                out.push_str(";\n\n");
+            }
+        }
+    }
+    out.push_str("export interface Exports {\n");
+    for (name, params, result) in functions {
+        let params = params
+            .iter()
+            .enumerate()
+            .map(|(i, (param, repr))| format!("{}: {}", ts_param(param, i), ts_type(repr)))
+            .collect::<Vec<_>>();
+        out.push_str(&format!(
+            "  {}({}): {};\n",
+            js_key(name),
+            params.join(", "),
+            ts_type(result)
+        ));
+    }
+    out.push_str("}\n\n");
+    out.push_str("export function bind(instance: WebAssembly.Instance): Exports;\n");
+    out.push_str(
+        "export function instantiate(\n  \
+         source: BufferSource | WebAssembly.Module,\n  \
+         imports?: WebAssembly.Imports,\n): Promise<Exports>;\n",
+    );
+    out
+}
+
+/// Marshalling between JS values and the wasm representation described by `TYPES`.
+///
+/// `lift` / `lower` convert wasm values (scalars or pointers); `read` / `write`
+/// access a value stored at an address. Aggregate fields stored inline are
+/// copied byte-wise, as the wasm backend does.
+const JS_RUNTIME: &str = r#"function runtime(exports) {
+  const memory = exports.memory;
+  const encoder = new TextEncoder();
+  const decoder = new TextDecoder();
+  const view = () => new DataView(memory.buffer);
+  const resolve = (t) => (typeof t === "string" && t in TYPES ? TYPES[t] : t);
+
+  function alloc(size) {
+    const ptr = exports.__nepl_alloc(size);
+    if (size > 0 && ptr === 0) throw new Error("nepl: allocation failed");
+    return ptr;
+  }
+
+  function lift(t, v) {
+    const d = resolve(t);
+    switch (d) {
+      case "i32": case "f32": case "f64": case "i64": return v;
+      case "u64": return BigInt.asUintN(64, v);
+      case "u8": return v & 0xff;
+      case "bool": return v !== 0;
+      case "unit": return undefined;
+      case "str": {
+        const len = view().getUint32(v, true);
+        return decoder.decode(new Uint8Array(memory.buffer, v + 4, len));
+      }
+    }
+    const field = ([offset, inline, ty]) => (inline ? lift(ty, v + offset) : read(ty, v + offset));
+    if (d.kind === "struct") {
+      const out = {};
+      for (const [name, ...slot] of d.fields) out[name] = field(slot);
+      return out;
+    }
+    if (d.kind === "tuple") return d.items.map(field);
+    const tag = view().getUint32(v, true);
+    const variant = d.variants[tag];
+    if (!variant) throw new Error(`nepl: invalid enum tag ${tag}`);
+    const [name, offset, , payload] = variant;
+    return payload === null ? { tag: name } : { tag: name, value: read(payload, v + offset) };
+  }
+
+  function read(t, addr) {
+    const dv = view();
+    switch (resolve(t)) {
+      case "i32": return dv.getInt32(addr, true);
+      case "u8": return dv.getUint8(addr);
+      case "bool": return dv.getInt32(addr, true) !== 0;
+      case "f32": return dv.getFloat32(addr, true);
+      case "f64": return dv.getFloat64(addr, true);
+      case "i64": return dv.getBigInt64(addr, true);
+      case "u64": return dv.getBigUint64(addr, true);
+      case "unit": return undefined;
+      default: return lift(t, dv.getInt32(addr, true));
+    }
+  }
+
+  function lower(t, x) {
+    const d = resolve(t);
+    switch (d) {
+      case "i32": return x | 0;
+      case "u8": return x & 0xff;
+      case "bool": return x ? 1 : 0;
+      case "f32": case "f64": return Number(x);
+      case "i64": case "u64": return BigInt.asIntN(64, BigInt(x));
+      case "unit": return undefined;
+      case "str": {
+        const bytes = encoder.encode(x);
+        const ptr = alloc(4 + bytes.length);
+        view().setUint32(ptr, bytes.length, true);
+        new Uint8Array(memory.buffer, ptr + 4, bytes.length).set(bytes);
+        return ptr;
+      }
+    }
+    if (d.kind === "struct" || d.kind === "tuple") {
+      const slots = d.kind === "struct"
+        ? d.fields.map(([name, ...slot]) => [slot, x[name]])
+        : d.items.map((slot, i) => [slot, x[i]]);
+      const ptr = alloc(d.size);
+      for (const [[offset, inline, ty], value] of slots) {
+        if (inline) {
+          const src = lower(ty, value);
+          new Uint8Array(memory.buffer).copyWithin(ptr + offset, src, src + inline);
+        } else {
+          write(ty, ptr + offset, value);
+        }
+      }
+      return ptr;
+    }
+    const tag = d.variants.findIndex(([name]) => name === x.tag);
+    if (tag < 0) throw new TypeError(`nepl: unknown variant ${x.tag}`);
+    const [, offset, size, payload] = d.variants[tag];
+    const ptr = alloc(size);
+    view().setUint32(ptr, tag, true);
+    if (payload !== null) write(payload, ptr + offset, x.value);
+    return ptr;
+  }
+
+  function write(t, addr, x) {
+    switch (resolve(t)) {
+      case "i32": return view().setInt32(addr, x | 0, true);
+      case "u8": return view().setUint8(addr, x & 0xff);
+      case "bool": return view().setInt32(addr, x ? 1 : 0, true);
+      case "f32": return view().setFloat32(addr, Number(x), true);
+      case "f64": return view().setFloat64(addr, Number(x), true);
+      case "i64": return view().setBigInt64(addr, BigInt(x), true);
+      case "u64": return view().setBigUint64(addr, BigInt.asUintN(64, BigInt(x)), true);
+      case "unit": return;
+      default: {
+        const ptr = lower(t, x);
+        return view().setInt32(addr, ptr, true);
+      }
+    }
+  }
+
+  return { lift, lower };
+}
+"#;
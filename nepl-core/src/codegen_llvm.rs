@@ -13,6 +13,7 @@ use alloc::vec::Vec;
 use crate::ast::{Block, FnBody, Ident, Literal, Module, PrefixExpr, PrefixItem, Stmt, TypeExpr};
 use crate::ast::Directive;
 use crate::compiler::{self, BuildProfile, CompileTarget, PreparedLlvmProgram};
+use crate::diagnostic::Diagnostic;
 use crate::hir::{FuncRef, HirBlock, HirBody, HirExpr, HirExprKind, HirFunction, HirModule};
 use crate::runtime_helpers::{
     helper_base_name, helper_candidates, RuntimeHelperKind,
@@ -24,8 +25,13 @@ use crate::types::{TypeCtx, TypeId, TypeKind};
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LlvmCodegenError {
     MissingLlvmIrBlock,
-    TypecheckFailed { reason: String },
+    /// `diagnostics` は前段が返した診断そのもの（wasm 経路と ID を突き合わせるために保持する）。
+    TypecheckFailed {
+        reason: String,
+        diagnostics: Vec<Diagnostic>,
+    },
     MissingEntryFunction { function: String },
+    UnsupportedTriple { triple: String },
 }
 
 impl core::fmt::Display for LlvmCodegenError {
@@ -37,7 +43,7 @@ impl core::fmt::Display for LlvmCodegenError {
                     "llvm target requires at least one #llvmir block in module/function body"
                 )
             }
-            LlvmCodegenError::TypecheckFailed { reason } => {
+            LlvmCodegenError::TypecheckFailed { reason, .. } => {
                 write!(f, "failed to typecheck module for llvm lowering: {}", reason)
             }
             LlvmCodegenError::MissingEntryFunction { function } => write!(
@@ -45,10 +51,74 @@ impl core::fmt::Display for LlvmCodegenError {
                 "entry function '{}' was not found in lowered module",
                 function
             ),
+            LlvmCodegenError::UnsupportedTriple { triple } => write!(
+                f,
+                "unsupported llvm target triple '{}' (supported: x86_64, aarch64 and riscv64 on linux, x86_64 and aarch64 on darwin, x86_64 on windows-msvc)",
+                triple
+            ),
         }
     }
 }
 
+/// LLVM IR の先頭に書く `target triple` と `target datalayout`。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlvmTargetSpec {
+    pub triple: String,
+    pub datalayout: String,
+}
+
+impl LlvmTargetSpec {
+    /// triple から datalayout を決める。
+    ///
+    /// ランタイム shim と `core/mem` は 64bit ポインタを前提にしているため、64bit target のみ受け付ける。
+    pub fn from_triple(triple: &str) -> Result<Self, LlvmCodegenError> {
+        let arch = triple.split('-').next().unwrap_or_default();
+        let is_darwin = triple.contains("-apple-");
+        let is_windows = triple.contains("-windows");
+        // clang 21 の TargetInfo と同じ文字列（不一致だと clang が IR 入力を拒否する）。
+        let datalayout = match arch {
+            "x86_64" if is_darwin => {
+                "e-m:o-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128"
+            }
+            "x86_64" if is_windows => {
+                "e-m:w-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128"
+            }
+            "x86_64" => {
+                "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128"
+            }
+            "aarch64" | "arm64" if is_darwin => {
+                "e-m:o-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-n32:64-S128-Fn32"
+            }
+            "aarch64" if !is_windows => {
+                "e-m:e-p270:32:32-p271:32:32-p272:64:64-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128-Fn32"
+            }
+            "riscv64" | "riscv64gc" if !is_darwin && !is_windows => "e-m:e-p:64:64-i64:64-i128:128-n32:64-S128",
+            _ => {
+                return Err(LlvmCodegenError::UnsupportedTriple {
+                    triple: String::from(triple),
+                })
+            }
+        };
+        Ok(Self {
+            triple: String::from(triple),
+            datalayout: String::from(datalayout),
+        })
+    }
+
+    fn header(&self) -> String {
+        format!(
+            "target datalayout = \"{}\"\ntarget triple = \"{}\"\n\n",
+            self.datalayout, self.triple
+        )
+    }
+}
+
+impl Default for LlvmTargetSpec {
+    fn default() -> Self {
+        Self::from_triple("x86_64-pc-linux-gnu").expect("default triple is supported")
+    }
+}
+
 /// `#llvmir` ブロックを連結して LLVM IR テキストを生成する。
 ///
 /// 現段階では手書き `#llvmir` を主経路とし、Parsed 関数は最小 subset のみ lower する。
@@ -56,6 +126,25 @@ pub fn emit_ll_from_module(module: &Module) -> Result<String, LlvmCodegenError>
     emit_ll_from_module_for_target(module, CompileTarget::Llvm, BuildProfile::Debug, false)
 }
 
+/// `emit_ll_from_module_for_target` の出力に `target triple` / `target datalayout` を付ける。
+///
+/// `#llvmir` ブロックが自分で triple を書いている場合はそちらを優先する。
+pub fn emit_ll_from_module_for_triple(
+    module: &Module,
+    target: CompileTarget,
+    profile: BuildProfile,
+    minify: bool,
+    spec: &LlvmTargetSpec,
+) -> Result<String, LlvmCodegenError> {
+    let body = emit_ll_from_module_for_target(module, target, profile, minify)?;
+    if body.lines().any(|l| l.trim_start().starts_with("target triple")) {
+        return Ok(body);
+    }
+    let mut out = spec.header();
+    out.push_str(&body);
+    Ok(out)
+}
+
 /// `target/profile` 条件を評価しながら LLVM IR を生成する。
 pub fn emit_ll_from_module_for_target(
     module: &Module,
@@ -204,9 +293,11 @@ fn map_core_error_to_llvm_codegen_error(err: crate::error::CoreError) -> LlvmCod
     match err {
         crate::error::CoreError::Diagnostics(diags) => LlvmCodegenError::TypecheckFailed {
             reason: summarize_diagnostics_for_message(diags.as_slice()),
+            diagnostics: diags,
         },
         other => LlvmCodegenError::TypecheckFailed {
             reason: other.to_string(),
+            diagnostics: Vec::new(),
         },
     }
 }
@@ -270,7 +361,7 @@ fn llty_for_type_expr(ty: &TypeExpr) -> Option<LlTy> {
         TypeExpr::F32 => Some(LlTy::F32),
         TypeExpr::Named(name) if name == "i64" || name == "u64" => Some(LlTy::I64),
         TypeExpr::Named(name) if name == "f64" => Some(LlTy::F64),
-        TypeExpr::Reference(_, _)
+        TypeExpr::Reference(_, _, _)
         | TypeExpr::Boxed(_)
         | TypeExpr::Tuple(_)
         | TypeExpr::Apply(_, _)
@@ -687,6 +778,14 @@ struct LowerCtx<'a> {
     tmp_seq: usize,
     label_seq: usize,
     scopes: Vec<BTreeMap<String, LocalBinding>>,
+    /// LLVM return type of the function being lowered (used by `return`).
+    ret_ty: LlTy,
+    /// Enclosing `while` loops as (label, continue target, break target).
+    loops: Vec<(Option<String>, String, String)>,
+    /// LLVM types of `static` items, keyed by item name.
+    globals: BTreeMap<String, LlTy>,
+    /// Blobs of compile-time evaluated constants (`HirModule::const_data`).
+    const_data: &'a [Vec<u8>],
 }
 
 impl<'a> LowerCtx<'a> {
@@ -716,6 +815,10 @@ impl<'a> LowerCtx<'a> {
             tmp_seq: 0,
             label_seq: 0,
             scopes: Vec::new(),
+            ret_ty: LlTy::Void,
+            loops: Vec::new(),
+            globals: BTreeMap::new(),
+            const_data: &[],
         }
     }
 
@@ -838,7 +941,27 @@ fn try_lower_entry_from_hir(
         "@__nepl_mem"
     };
 
+    for g in &hir.globals {
+        let ty = llty_for_type(types, g.ty);
+        let init = match g.init.kind {
+            HirExprKind::LiteralI32(v) => format!("{}", v),
+            HirExprKind::LiteralBool(b) => format!("{}", b as i32),
+            HirExprKind::LiteralF32(v) => llvm_f32_literal(v),
+            _ => panic!(
+                "internal compiler error: static '{}' reached llvm codegen without a folded initializer",
+                g.name
+            ),
+        };
+        out.push_str(&format!(
+            "{} = internal global {} {}\n",
+            static_symbol(g.name.as_str()),
+            ty.ir(),
+            init
+        ));
+    }
+
     let mut declared_extern_symbols: BTreeSet<String> = BTreeSet::new();
+    let mut needs_libc_shim = false;
     for ex in &hir.externs {
         let local_name_raw = ex.local_name.as_str();
         let base_alias = find_mangled_signature_separator(local_name_raw)
@@ -861,7 +984,10 @@ fn try_lower_entry_from_hir(
             continue;
         }
 
-        if declared_extern_symbols.insert(ex.name.clone()) {
+        if ex.module == LIBC_SHIM_MODULE {
+            // Defined by the shim appended below instead of being imported.
+            needs_libc_shim = true;
+        } else if declared_extern_symbols.insert(ex.name.clone()) {
             out.push_str(&format!("declare {} {}({})\n", ret, external_name, params_ll));
         }
 
@@ -935,6 +1061,9 @@ fn try_lower_entry_from_hir(
             }
         }
     }
+    if needs_libc_shim {
+        emit_libc_runtime_shim(out, memory_global);
+    }
     if !prepared.reachable_set.is_empty() {
         out.push('\n');
     }
@@ -1198,6 +1327,149 @@ fn emit_fallback_linear_memory_runtime(out: &mut String) {
     out.push_str("}\n\n");
 }
 
+/// `#extern "nepl_rt" ...` で参照される、libc 上に実装した WASI 互換関数のモジュール名。
+pub const LIBC_SHIM_MODULE: &str = "nepl_rt";
+
+/// WASI の `fd_read` / `fd_write` / `path_open` / `fd_close` を libc の
+/// `read` / `write` / `open` / `close` へ対応付けるランタイム shim。
+///
+/// ポインタ引数はすべて線形メモリ上のオフセットで、`@__NEPL_MEMORY__` を基点に解決する。
+/// 戻り値は WASI と同じく 0 が成功、0 以外が失敗。
+const LIBC_RUNTIME_SHIM: &str = r#"; nepl: libc runtime shim
+define internal ptr @__nepl_rt_mem(i32 %off) {
+entry:
+  %idx = zext i32 %off to i64
+  %p = getelementptr [67108864 x i8], ptr @__NEPL_MEMORY__, i64 0, i64 %idx
+  ret ptr %p
+}
+
+define internal i32 @__nepl_rt_load_i32(i32 %off) {
+entry:
+  %p = call ptr @__nepl_rt_mem(i32 %off)
+  %v = load i32, ptr %p, align 1
+  ret i32 %v
+}
+
+define internal void @__nepl_rt_store_i32(i32 %off, i32 %v) {
+entry:
+  %p = call ptr @__nepl_rt_mem(i32 %off)
+  store i32 %v, ptr %p, align 1
+  ret void
+}
+
+define internal i32 @__nepl_rt_fd_rw(i1 %is_write, i32 %fd, i32 %iovs, i32 %iovs_len, i32 %nout) {
+entry:
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i.next, %ok ]
+  %total = phi i32 [ 0, %entry ], [ %total.next, %ok ]
+  %more = icmp slt i32 %i, %iovs_len
+  br i1 %more, label %body, label %done
+body:
+  %iov.off = mul i32 %i, 8
+  %iov = add i32 %iovs, %iov.off
+  %base = call i32 @__nepl_rt_load_i32(i32 %iov)
+  %len.at = add i32 %iov, 4
+  %len = call i32 @__nepl_rt_load_i32(i32 %len.at)
+  %buf = call ptr @__nepl_rt_mem(i32 %base)
+  %len64 = zext i32 %len to i64
+  br i1 %is_write, label %do.write, label %do.read
+do.write:
+  %w = call i64 @write(i32 %fd, ptr %buf, i64 %len64)
+  br label %after
+do.read:
+  %r = call i64 @read(i32 %fd, ptr %buf, i64 %len64)
+  br label %after
+after:
+  %n64 = phi i64 [ %w, %do.write ], [ %r, %do.read ]
+  %failed = icmp slt i64 %n64, 0
+  br i1 %failed, label %fail, label %ok
+ok:
+  %n = trunc i64 %n64 to i32
+  %total.next = add i32 %total, %n
+  %i.next = add i32 %i, 1
+  %short = icmp slt i32 %n, %len
+  br i1 %short, label %short.done, label %loop
+short.done:
+  call void @__nepl_rt_store_i32(i32 %nout, i32 %total.next)
+  ret i32 0
+done:
+  call void @__nepl_rt_store_i32(i32 %nout, i32 %total)
+  ret i32 0
+fail:
+  call void @__nepl_rt_store_i32(i32 %nout, i32 %total)
+  ret i32 1
+}
+
+define i32 @__nepl_rt_fd_read(i32 %fd, i32 %iovs, i32 %iovs_len, i32 %nread) {
+entry:
+  %rc = call i32 @__nepl_rt_fd_rw(i1 false, i32 %fd, i32 %iovs, i32 %iovs_len, i32 %nread)
+  ret i32 %rc
+}
+
+define i32 @__nepl_rt_fd_write(i32 %fd, i32 %iovs, i32 %iovs_len, i32 %nwritten) {
+entry:
+  %rc = call i32 @__nepl_rt_fd_rw(i1 true, i32 %fd, i32 %iovs, i32 %iovs_len, i32 %nwritten)
+  ret i32 %rc
+}
+
+define i32 @__nepl_rt_path_open(i32 %dirfd, i32 %dirflags, i32 %path, i32 %path_len, i32 %oflags, i64 %rights_base, i64 %rights_inheriting, i32 %fdflags, i32 %fd_out) {
+entry:
+  %len64 = zext i32 %path_len to i64
+  %size = add i64 %len64, 1
+  %cpath = call ptr @malloc(i64 %size)
+  %nomem = icmp eq ptr %cpath, null
+  br i1 %nomem, label %fail, label %copy
+copy:
+  %src = call ptr @__nepl_rt_mem(i32 %path)
+  call ptr @memcpy(ptr %cpath, ptr %src, i64 %len64)
+  %end = getelementptr i8, ptr %cpath, i64 %len64
+  store i8 0, ptr %end
+  %fd = call i32 (ptr, i32, ...) @open(ptr %cpath, i32 0)
+  call void @free(ptr %cpath)
+  %bad = icmp slt i32 %fd, 0
+  br i1 %bad, label %fail, label %ok
+ok:
+  call void @__nepl_rt_store_i32(i32 %fd_out, i32 %fd)
+  ret i32 0
+fail:
+  ret i32 1
+}
+
+define i32 @__nepl_rt_fd_close(i32 %fd) {
+entry:
+  %rc = call i32 @close(i32 %fd)
+  %bad = icmp slt i32 %rc, 0
+  %r = zext i1 %bad to i32
+  ret i32 %r
+}
+
+"#;
+
+const LIBC_SHIM_IMPORTS: &[(&str, &str)] = &[
+    ("read", "declare i64 @read(i32, ptr, i64)"),
+    ("write", "declare i64 @write(i32, ptr, i64)"),
+    ("open", "declare i32 @open(ptr, i32, ...)"),
+    ("close", "declare i32 @close(i32)"),
+    ("malloc", "declare ptr @malloc(i64)"),
+    ("free", "declare void @free(ptr)"),
+    ("memcpy", "declare ptr @memcpy(ptr, ptr, i64)"),
+];
+
+fn emit_libc_runtime_shim(out: &mut String, memory_global: &str) {
+    for (name, decl) in LIBC_SHIM_IMPORTS {
+        let already = out
+            .lines()
+            .any(|l| l.starts_with("declare ") && llvm_output_mentions_symbol(l, name));
+        if !already {
+            out.push_str(decl);
+            out.push('\n');
+        }
+    }
+    let memory = memory_global.trim_start_matches('@');
+    out.push_str(&LIBC_RUNTIME_SHIM.replace("__NEPL_MEMORY__", memory));
+}
+
 fn find_mangled_signature_separator(name: &str) -> Option<usize> {
     let bytes = name.as_bytes();
     if bytes.len() < 3 {
@@ -1264,6 +1536,11 @@ fn lower_hir_function(
         memory_global,
         fallback_alloc_symbol,
     );
+    ctx.ret_ty = ret_ty;
+    for g in &module.globals {
+        ctx.globals.insert(g.name.clone(), llty_for_type(types, g.ty));
+    }
+    ctx.const_data = &module.const_data;
     let mut params = Vec::new();
     for (idx, p) in func.params.iter().enumerate() {
         let pty = llty_for_type(types, p.ty);
@@ -1378,6 +1655,7 @@ fn lower_hir_expr(
             repr: if *v { String::from("1") } else { String::from("0") },
         })),
         HirExprKind::LiteralStr(id) => lower_hir_string_literal(types, ctx, *id as usize),
+        HirExprKind::ConstData(id) => lower_hir_const_data(ctx, *id as usize),
         HirExprKind::Unit => Ok(None),
         HirExprKind::Var(name) => {
             let Some(binding) = ctx.lookup_local_fuzzy(name.as_str()) else {
@@ -1459,6 +1737,42 @@ fn lower_hir_expr(
             ));
             Ok(None)
         }
+        HirExprKind::GlobalGet(name) => {
+            let Some(ty) = ctx.globals.get(name).copied() else {
+                panic!(
+                    "internal compiler error: unknown static '{}' reached llvm codegen",
+                    name
+                );
+            };
+            let tmp = ctx.next_tmp();
+            ctx.push_line(&format!(
+                "  {} = load {}, {}* {}",
+                tmp,
+                ty.ir(),
+                ty.ir(),
+                static_symbol(name.as_str())
+            ));
+            Ok(Some(LlValue { ty, repr: tmp }))
+        }
+        HirExprKind::GlobalSet { name, value } => {
+            let Some(ty) = ctx.globals.get(name).copied() else {
+                panic!(
+                    "internal compiler error: set on unknown static '{}' reached llvm codegen",
+                    name
+                );
+            };
+            let Some(v) = lower_hir_expr(types, ctx, value)? else {
+                return Ok(None);
+            };
+            ctx.push_line(&format!(
+                "  store {} {}, {}* {}",
+                v.ty.ir(),
+                v.repr,
+                ty.ir(),
+                static_symbol(name.as_str())
+            ));
+            Ok(None)
+        }
         HirExprKind::FnValue(name) => {
             if let Some(fid) = ctx.function_id_of(name.as_str()) {
                 Ok(Some(LlValue {
@@ -1767,7 +2081,7 @@ fn lower_hir_expr(
                 Ok(None)
             }
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, label } => {
             let cond_label = ctx.next_label("while_cond");
             let body_label = ctx.next_label("while_body");
             let end_label = ctx.next_label("while_end");
@@ -1792,11 +2106,61 @@ fn lower_hir_expr(
                 cmp, body_label, end_label
             ));
             ctx.push_line(&format!("{}:", body_label));
-            let _ = lower_hir_expr(types, ctx, body)?;
+            ctx.loops
+                .push((label.clone(), cond_label.clone(), end_label.clone()));
+            let body_res = lower_hir_expr(types, ctx, body);
+            ctx.loops.pop();
+            let _ = body_res?;
             ctx.push_line(&format!("  br label %{}", cond_label));
             ctx.push_line(&format!("{}:", end_label));
             Ok(None)
         }
+        HirExprKind::Break { label } | HirExprKind::Continue { label } => {
+            let target = match label {
+                None => ctx.loops.last(),
+                Some(label) => ctx
+                    .loops
+                    .iter()
+                    .rev()
+                    .find(|(l, _, _)| l.as_ref() == Some(label)),
+            };
+            let Some((_, cond_label, end_label)) = target else {
+                panic!(
+                    "internal compiler error: jump outside of loop reached llvm lowering in '{}'",
+                    ctx.function_name
+                );
+            };
+            let dest = if matches!(expr.kind, HirExprKind::Break { .. }) {
+                end_label.clone()
+            } else {
+                cond_label.clone()
+            };
+            ctx.push_line(&format!("  br label %{}", dest));
+            // Anything emitted after the jump lands in an unreachable block.
+            let dead = ctx.next_label("after_jump");
+            ctx.push_line(&format!("{}:", dead));
+            Ok(None)
+        }
+        HirExprKind::Return(value) => {
+            let v = lower_hir_expr(types, ctx, value)?;
+            match (ctx.ret_ty, v) {
+                (LlTy::Void, _) => ctx.push_line("  ret void"),
+                (ret_ty, Some(v)) if v.ty == ret_ty => {
+                    ctx.push_line(&format!("  ret {} {}", ret_ty.ir(), v.repr));
+                }
+                (ret_ty, v) => {
+                    panic!(
+                        "internal compiler error: return type mismatch in '{}' ({:?} -> {:?})",
+                        ctx.function_name,
+                        v.map(|v| v.ty),
+                        ret_ty
+                    );
+                }
+            }
+            let dead = ctx.next_label("after_return");
+            ctx.push_line(&format!("{}:", dead));
+            Ok(None)
+        }
         HirExprKind::EnumConstruct {
             name: _,
             variant,
@@ -2917,6 +3281,42 @@ fn lower_hir_string_literal(
     }))
 }
 
+/// Copies a compile-time evaluated blob into freshly allocated linear memory.
+fn lower_hir_const_data(
+    ctx: &mut LowerCtx<'_>,
+    id: usize,
+) -> Result<Option<LlValue>, LlvmCodegenError> {
+    let Some(bytes) = ctx.const_data.get(id) else {
+        panic!(
+            "internal compiler error: constant data id {} was out of bounds in '{}'",
+            id, ctx.function_name
+        );
+    };
+    let Some(alloc_name) = resolve_alloc_symbol(ctx) else {
+        panic!(
+            "internal compiler error: alloc function is required to materialize constant data in '{}'",
+            ctx.function_name
+        );
+    };
+    let ptr_tmp = ctx.next_tmp();
+    ctx.push_line(&format!(
+        "  {} = call i32 {}(i32 {})",
+        ptr_tmp,
+        ll_symbol(alloc_name.as_str()),
+        bytes.len()
+    ));
+    for (idx, b) in bytes.iter().enumerate() {
+        let off = ctx.next_tmp();
+        ctx.push_line(&format!("  {} = add i32 {}, {}", off, ptr_tmp, idx));
+        let ptr8 = ctx.linear_i8_ptr_from_i32(off.as_str());
+        ctx.push_line(&format!("  store i8 {}, i8* {}, align 1", *b as i8, ptr8));
+    }
+    Ok(Some(LlValue {
+        ty: LlTy::I32,
+        repr: ptr_tmp,
+    }))
+}
+
 fn llty_for_type(types: &TypeCtx, ty: TypeId) -> LlTy {
     match types.get(types.resolve_id(ty)) {
         TypeKind::Unit | TypeKind::Never => LlTy::Void,
@@ -2928,6 +3328,7 @@ fn llty_for_type(types: &TypeCtx, ty: TypeId) -> LlTy {
         TypeKind::Box(_) => LlTy::I32,
         TypeKind::Tuple { .. } => LlTy::I32,
         TypeKind::Struct { .. } => LlTy::I32,
+        TypeKind::Newtype { inner, .. } => llty_for_type(types, inner),
         TypeKind::Enum { .. } => LlTy::I32,
         TypeKind::Apply { .. } => LlTy::I32,
         TypeKind::Function { .. } => LlTy::I32,
@@ -3200,6 +3601,11 @@ fn ll_symbol(name: &str) -> String {
     format!("@\"{}\"", escaped)
 }
 
+/// Symbol of the LLVM global backing a `static` item.
+fn static_symbol(name: &str) -> String {
+    ll_symbol(format!("__nepl_static_{}", name).as_str())
+}
+
 fn llvm_f32_literal(v: f32) -> String {
     if v.is_nan() {
         return String::from("0x7FC00000");
@@ -13,7 +13,7 @@ use alloc::borrow::Cow;
 
 use wasm_encoder::{
     CodeSection, ConstExpr, DataSection, ElementMode, ElementSection, ElementSegment, Elements,
-    EntityType, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction,
+    EntityType, ExportKind, ExportSection, Function, FunctionSection, GlobalSection, GlobalType, ImportSection, Instruction,
     MemArg, MemorySection, MemoryType, Module, RefType, TableSection, TableType, TypeSection,
     ValType,
 };
@@ -33,6 +33,8 @@ pub struct CodegenResult {
 struct StringLower {
     values: Vec<String>,
     offsets: Vec<u32>,
+    /// Addresses of `HirModule::const_data` blobs, placed after the strings.
+    data_offsets: Vec<u32>,
     segments: Vec<(u32, Vec<u8>)>,
     min_pages: u32,
     heap_base: u32,
@@ -43,12 +45,16 @@ impl StringLower {
         self.offsets.get(idx as usize).copied()
     }
 
+    fn data_offset(&self, idx: u32) -> Option<u32> {
+        self.data_offsets.get(idx as usize).copied()
+    }
+
     fn is_empty(&self) -> bool {
         self.offsets.is_empty()
     }
 }
 
-fn lower_strings(strings: &[String]) -> StringLower {
+fn lower_strings(strings: &[String], const_data: &[Vec<u8>]) -> StringLower {
     let values = strings.to_vec();
     let mut offsets = Vec::new();
     let mut segments = Vec::new();
@@ -65,11 +71,19 @@ fn lower_strings(strings: &[String]) -> StringLower {
         segments.push((cursor, data));
         cursor = cursor.saturating_add(4 + len);
     }
+    let mut data_offsets = Vec::new();
+    for data in const_data {
+        cursor = align_to(cursor, 4);
+        data_offsets.push(cursor);
+        segments.push((cursor, data.clone()));
+        cursor = cursor.saturating_add(data.len() as u32);
+    }
     let heap_base = align_to(cursor, 4);
     let min_pages = ((heap_base + 0xFFFF) / 0x10000).max(1);
     StringLower {
         values,
         offsets,
+        data_offsets,
         segments,
         min_pages,
         heap_base,
@@ -155,7 +169,7 @@ fn type_storage_align_bytes(ctx: &TypeCtx, ty: TypeId) -> u32 {
     }
 }
 
-fn type_storage_size_bytes(ctx: &TypeCtx, ty: TypeId) -> u32 {
+pub(crate) fn type_storage_size_bytes(ctx: &TypeCtx, ty: TypeId) -> u32 {
     let ty = ctx.resolve_id(ty);
     match ctx.get(ty) {
         TypeKind::Unit | TypeKind::Never => 0,
@@ -229,7 +243,7 @@ fn type_storage_size_bytes(ctx: &TypeCtx, ty: TypeId) -> u32 {
     }
 }
 
-fn is_aggregate_storage_type(ctx: &TypeCtx, ty: TypeId) -> bool {
+pub(crate) fn is_aggregate_storage_type(ctx: &TypeCtx, ty: TypeId) -> bool {
     let ty = ctx.resolve_id(ty);
     match ctx.get(ty) {
         TypeKind::Struct { .. } | TypeKind::Tuple { .. } | TypeKind::Enum { .. } => true,
@@ -241,7 +255,7 @@ fn is_aggregate_storage_type(ctx: &TypeCtx, ty: TypeId) -> bool {
     }
 }
 
-fn tuple_field_layout(ctx: &TypeCtx, ty: TypeId, index: usize) -> Option<(TypeId, u32)> {
+pub(crate) fn tuple_field_layout(ctx: &TypeCtx, ty: TypeId, index: usize) -> Option<(TypeId, u32)> {
     let ty = ctx.resolve_id(ty);
     match ctx.get(ty) {
         TypeKind::Tuple { items } => {
@@ -277,7 +291,7 @@ fn tuple_field_layouts_by_result(ctx: &TypeCtx, ty: TypeId, result_ty: TypeId) -
     }
 }
 
-fn struct_field_layout_by_name(
+pub(crate) fn struct_field_layout_by_name(
     ctx: &TypeCtx,
     ty: TypeId,
     field_name: &str,
@@ -402,7 +416,7 @@ fn collect_indirect_sigs(
             collect_indirect_sigs(then_branch, out, ctx);
             collect_indirect_sigs(else_branch, out, ctx);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             collect_indirect_sigs(cond, out, ctx);
             collect_indirect_sigs(body, out, ctx);
         }
@@ -432,7 +446,9 @@ fn collect_indirect_sigs(
                 collect_indirect_sigs(&line.expr, out, ctx);
             }
         }
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             collect_indirect_sigs(value, out, ctx);
         }
         HirExprKind::Intrinsic { args, .. } => {
@@ -440,7 +456,9 @@ fn collect_indirect_sigs(
                 collect_indirect_sigs(a, out, ctx);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+        HirExprKind::AddrOf(inner)
+        | HirExprKind::Deref(inner)
+        | HirExprKind::Return(inner) => {
             collect_indirect_sigs(inner, out, ctx);
         }
         HirExprKind::Unit
@@ -448,9 +466,13 @@ fn collect_indirect_sigs(
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
         | HirExprKind::Var(_)
         | HirExprKind::FnValue(_)
-        | HirExprKind::Drop { .. } => {}
+        | HirExprKind::Drop { .. }
+        | HirExprKind::Break { .. }
+        | HirExprKind::Continue { .. }
+        | HirExprKind::GlobalGet(_) => {}
     }
 }
 
@@ -464,7 +486,7 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
             .collect::<Vec<_>>();
         std::eprintln!("wasm codegen functions(new*): {:?}", names);
     }
-    let strings = lower_strings(&module.string_literals);
+    let strings = lower_strings(&module.string_literals, &module.const_data);
 
     // Build imports / function list (builtins first)
     let mut imports: Vec<ImportLower> = Vec::new();
@@ -574,9 +596,35 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
         func_section.function(type_idx);
     }
 
+    let mut global_section = GlobalSection::new();
+    let mut global_index = BTreeMap::new();
+    for g in &module.globals {
+        let Some(val_type) = valtype(&ctx.get(g.ty)) else {
+            continue;
+        };
+        let init = match g.init.kind {
+            HirExprKind::LiteralI32(v) => ConstExpr::i32_const(v),
+            HirExprKind::LiteralBool(b) => ConstExpr::i32_const(b as i32),
+            HirExprKind::LiteralF32(v) => ConstExpr::f32_const(v.into()),
+            _ => panic!(
+                "internal compiler error: static '{}' reached wasm codegen without a folded initializer",
+                g.name
+            ),
+        };
+        global_index.insert(g.name.clone(), global_section.len());
+        global_section.global(
+            GlobalType {
+                val_type,
+                mutable: g.mutable,
+                shared: false,
+            },
+            &init,
+        );
+    }
+
     let mut code_section = CodeSection::new();
     for f in &functions {
-        let body = lower_body(ctx, f, &name_to_index, &sig_map, &strings);
+        let body = lower_body(ctx, f, &name_to_index, &sig_map, &strings, &global_index);
         code_section.function(&body);
     }
 
@@ -600,6 +648,20 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
             export_section.export("_start", ExportKind::Func, *idx);
         }
     }
+    for export in &module.exports {
+        if let Some(idx) = name_to_index.get(&export.symbol) {
+            export_section.export(&export.name, ExportKind::Func, *idx);
+        }
+    }
+    if !module.exports.is_empty() {
+        if let Some(idx) = runtime_helpers::find_runtime_helper_index(
+            &name_to_index,
+            RuntimeHelperKind::Alloc,
+            None,
+        ) {
+            export_section.export(crate::bindings::ALLOC_EXPORT, ExportKind::Func, idx);
+        }
+    }
 
     let mut data_section = DataSection::new();
     // Store initial heap pointer (aligned end of static data) at address 0.
@@ -644,6 +706,9 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
         module_bytes.section(&table_section);
     }
     module_bytes.section(&memory_section);
+    if !global_section.is_empty() {
+        module_bytes.section(&global_section);
+    }
     module_bytes.section(&export_section);
     if need_table {
         module_bytes.section(&element_section);
@@ -724,7 +789,7 @@ fn collect_called_functions_from_expr(
             collect_called_functions_from_expr(then_branch, out, has_indirect);
             collect_called_functions_from_expr(else_branch, out, has_indirect);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             collect_called_functions_from_expr(cond, out, has_indirect);
             collect_called_functions_from_expr(body, out, has_indirect);
         }
@@ -754,7 +819,9 @@ fn collect_called_functions_from_expr(
                 collect_called_functions_from_expr(&line.expr, out, has_indirect);
             }
         }
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             collect_called_functions_from_expr(value, out, has_indirect);
         }
         HirExprKind::Intrinsic { args, .. } => {
@@ -762,7 +829,9 @@ fn collect_called_functions_from_expr(
                 collect_called_functions_from_expr(a, out, has_indirect);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+        HirExprKind::AddrOf(inner)
+        | HirExprKind::Deref(inner)
+        | HirExprKind::Return(inner) => {
             collect_called_functions_from_expr(inner, out, has_indirect);
         }
         HirExprKind::Var(name) | HirExprKind::FnValue(name) => {
@@ -773,7 +842,11 @@ fn collect_called_functions_from_expr(
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
-        | HirExprKind::Drop { .. } => {}
+        | HirExprKind::ConstData(_)
+        | HirExprKind::Drop { .. }
+        | HirExprKind::Break { .. }
+        | HirExprKind::Continue { .. }
+        | HirExprKind::GlobalGet(_) => {}
     }
 }
 
@@ -852,7 +925,7 @@ pub(crate) fn wasm_sig_ids(
     crate::wasm_shared::wasm_sig_ids(ctx, result, params)
 }
 
-fn valtype(kind: &TypeKind) -> Option<ValType> {
+pub(crate) fn valtype(kind: &TypeKind) -> Option<ValType> {
     match kind {
         TypeKind::Unit => None,
         TypeKind::I32 | TypeKind::U8 | TypeKind::Bool | TypeKind::Str => Some(ValType::I32),
@@ -970,9 +1043,10 @@ fn lower_body<'a>(
     name_map: &BTreeMap<String, u32>,
     sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
     strings: &StringLower,
+    globals: &BTreeMap<String, u32>,
 ) -> Function {
     match func.body {
-        FuncBodyLower::User(f) => lower_user(ctx, f, name_map, sig_map, strings),
+        FuncBodyLower::User(f) => lower_user(ctx, f, name_map, sig_map, strings, globals),
     }
 }
 
@@ -986,12 +1060,14 @@ fn lower_user(
     name_map: &BTreeMap<String, u32>,
     sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
     strings: &StringLower,
+    globals: &BTreeMap<String, u32>,
 ) -> Function {
     let mut locals = LocalMap::new(func.params.len());
     for p in &func.params {
         locals.register_param(p.name.clone(), p.ty);
     }
     locals.alloc_helper_idx = find_alloc_index(name_map, &func.name);
+    locals.globals = globals.clone();
 
     let mut insts: Vec<Instruction<'static>> = Vec::new();
 
@@ -1007,7 +1083,8 @@ fn lower_user(
                 &mut insts,
             );
             let expected = valtype(&ctx.get(func.result));
-            if expected.is_some() && produced.flatten().is_none() {
+            let diverges = block.lines.iter().any(|line| line.expr.diverges());
+            if expected.is_some() && produced.flatten().is_none() && !diverges {
                 panic!(
                     "internal compiler error: wasm codegen reached function '{}' without return value after precheck",
                     func.name
@@ -1085,6 +1162,28 @@ fn gen_block(
     Some(last_val)
 }
 
+/// Relative branch depth from the current position to the `loop` of the
+/// targeted `while` (innermost when `label` is `None`).
+fn loop_branch_depth(
+    loops: &[(Option<String>, usize)],
+    label: &Option<String>,
+    insts: &[Instruction<'static>],
+) -> Option<u32> {
+    let (_, start) = match label {
+        None => loops.last()?,
+        Some(label) => loops.iter().rev().find(|(l, _)| l.as_ref() == Some(label))?,
+    };
+    let mut depth: u32 = 0;
+    for inst in &insts[*start..] {
+        match inst {
+            Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => depth += 1,
+            Instruction::End => depth -= 1,
+            _ => {}
+        }
+    }
+    Some(depth)
+}
+
 fn predeclare_block_locals(ctx: &TypeCtx, block: &HirBlock, locals: &mut LocalMap) {
     for line in &block.lines {
         if let HirExprKind::Let { name, value, .. } = &line.expr.kind {
@@ -1123,6 +1222,13 @@ fn gen_expr(
                 panic!("internal compiler error: string literal not found during codegen")
             }
         }
+        HirExprKind::ConstData(id) => {
+            let Some(off) = strings.data_offset(*id) else {
+                panic!("internal compiler error: constant data not found during codegen")
+            };
+            insts.push(Instruction::I32Const(off as i32));
+            Some(ValType::I32)
+        }
         HirExprKind::Unit => None,
         HirExprKind::Var(name) => {
             if let Some(idx) = locals.lookup(name) {
@@ -1232,7 +1338,7 @@ fn gen_expr(
             insts.push(Instruction::End);
             result_ty
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, label } => {
             // while cond body:
             // block  ;; break target depth=1
             //   loop ;; continue target depth=0
@@ -1248,12 +1354,29 @@ fn gen_expr(
             gen_expr(ctx, cond, name_map, sig_map, strings, locals, insts);
             insts.push(Instruction::I32Eqz);
             insts.push(Instruction::BrIf(1));
+            locals.loops.push((label.clone(), insts.len()));
             gen_expr(ctx, body, name_map, sig_map, strings, locals, insts);
+            locals.loops.pop();
             insts.push(Instruction::Br(0));
             insts.push(Instruction::End);
             insts.push(Instruction::End);
             None
         }
+        HirExprKind::Break { label } | HirExprKind::Continue { label } => {
+            // `continue` targets the `loop` itself, `break` the enclosing `block`.
+            let depth = loop_branch_depth(&locals.loops, label, insts)
+                .unwrap_or_else(|| {
+                    panic!("internal compiler error: wasm codegen reached jump outside of loop")
+                });
+            let is_break = matches!(expr.kind, HirExprKind::Break { .. });
+            insts.push(Instruction::Br(if is_break { depth + 1 } else { depth }));
+            None
+        }
+        HirExprKind::Return(value) => {
+            gen_expr(ctx, value, name_map, sig_map, strings, locals, insts);
+            insts.push(Instruction::Return);
+            None
+        }
         HirExprKind::Block(b) => {
             gen_block(ctx, b, name_map, sig_map, strings, locals, insts).flatten()
         }
@@ -1828,11 +1951,8 @@ fn gen_expr(
             type_args: _,
         } => {
             let payload_vt = payload.as_ref().and_then(|p| valtype(&ctx.get(p.ty)));
-            let (payload_offset, size) = match payload_vt {
-                Some(ValType::I64) | Some(ValType::F64) => (8i32, 16i32),
-                Some(_) => (4i32, 8i32),
-                None => (0i32, 4i32),
-            };
+            let (payload_offset, size) = enum_payload_layout(ctx, payload.as_ref().map(|p| p.ty));
+            let payload_offset = payload_offset as i32;
             insts.push(Instruction::I32Const(size as i32));
             emit_alloc_call(locals, insts);
             let ptr_local = locals.alloc_temp(ValType::I32);
@@ -2222,6 +2342,27 @@ fn gen_expr(
             }
             None
         }
+        HirExprKind::GlobalGet(name) => {
+            let Some(idx) = locals.globals.get(name).copied() else {
+                panic!(
+                    "internal compiler error: unknown static '{}' reached wasm codegen",
+                    name
+                );
+            };
+            insts.push(Instruction::GlobalGet(idx));
+            valtype(&ctx.get(expr.ty))
+        }
+        HirExprKind::GlobalSet { name, value } => {
+            let Some(idx) = locals.globals.get(name).copied() else {
+                panic!(
+                    "internal compiler error: unknown static '{}' in set reached wasm codegen",
+                    name
+                );
+            };
+            gen_expr(ctx, value, name_map, sig_map, strings, locals, insts);
+            insts.push(Instruction::GlobalSet(idx));
+            None
+        }
         HirExprKind::Drop { .. } => {
             // For now, Drop is a no-op at the wasm level.
             None
@@ -2257,6 +2398,10 @@ struct LocalMap {
     next_idx: u32,
     decls: Vec<ValType>,
     alloc_helper_idx: Option<u32>,
+    /// Enclosing `while` loops as (label, index of the first instruction inside `loop`).
+    loops: Vec<(Option<String>, usize)>,
+    /// Wasm global indices of `static` items.
+    globals: BTreeMap<String, u32>,
 }
 
 impl LocalMap {
@@ -2268,6 +2413,8 @@ impl LocalMap {
             next_idx: param_count as u32,
             decls: Vec::new(),
             alloc_helper_idx: None,
+            loops: Vec::new(),
+            globals: BTreeMap::new(),
         }
     }
 
@@ -2402,7 +2549,17 @@ fn enum_variant_tag(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> u32 {
     }
 }
 
-fn enum_variant_payload(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> Option<TypeId> {
+/// Payload offset and allocation size of an enum value carrying `payload`.
+/// 8-byte payloads are aligned after the 4-byte tag.
+pub(crate) fn enum_payload_layout(ctx: &TypeCtx, payload: Option<TypeId>) -> (u32, u32) {
+    match payload.and_then(|ty| valtype(&ctx.get(ty))) {
+        Some(ValType::I64) | Some(ValType::F64) => (8, 16),
+        Some(_) => (4, 8),
+        None => (0, 4),
+    }
+}
+
+pub(crate) fn enum_variant_payload(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> Option<TypeId> {
     let name = if let Some(pos) = variant.rfind("::") {
         &variant[pos + 2..]
     } else {
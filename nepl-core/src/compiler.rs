@@ -230,8 +230,9 @@ pub struct CompilationArtifact {
 /// 2. typecheck
 /// 3. monomorphize
 /// 4. move check
-/// 5. drop 挿入
-/// 6. wasm 生成と妥当性検証
+/// 5. newtype を内側の型へ消去
+/// 6. drop 挿入
+/// 7. wasm 生成と妥当性検証
 pub fn compile_module(
     module: ast::Module,
     options: CompileOptions,
@@ -244,8 +245,76 @@ pub fn compile_module_with_source_map(
     source_map: Option<&SourceMap>,
     options: CompileOptions,
 ) -> Result<CompilationArtifact, CoreError> {
+    let prepared = prepare_wasm_module(&module, source_map, options)?;
+    precheck_and_emit_wasm(prepared)
+}
+
+/// デバッガ用のフックを埋め込んだ wasm を生成する。
+///
+/// 返される `DebugInfo` は `nepl_debug` import に渡される関数番号・文番号の対応表で、
+/// `debugger::DebugSession` に渡して使う。
+pub fn compile_module_for_debugging(
+    module: ast::Module,
+    source_map: Option<&SourceMap>,
+    options: CompileOptions,
+) -> Result<(CompilationArtifact, crate::debugger::DebugInfo), CoreError> {
+    let mut prepared = prepare_wasm_module(&module, source_map, options)?;
+    let info = crate::debugger::instrument_module(&prepared.types, &mut prepared.hir_module);
+    let artifact = precheck_and_emit_wasm(prepared)?;
+    Ok((artifact, info))
+}
+
+/// プロファイラ用のフックを埋め込んだ wasm を生成する。
+///
+/// 返される `ProfileInfo` は `nepl_profile::enter` に渡される関数番号とソース上の関数名の対応表で、
+/// `profiler::ProfileSession` に渡して使う。
+pub fn compile_module_for_profiling(
+    module: ast::Module,
+    source_map: Option<&SourceMap>,
+    options: CompileOptions,
+) -> Result<(CompilationArtifact, crate::profiler::ProfileInfo), CoreError> {
+    let mut prepared = prepare_wasm_module(&module, source_map, options)?;
+    let info = crate::profiler::instrument_module(&prepared.types, &mut prepared.hir_module);
+    let artifact = precheck_and_emit_wasm(prepared)?;
+    Ok((artifact, info))
+}
+
+/// エントリファイルの非ジェネリックな `pub` 関数を公開した wasm と、
+/// それを呼び出す JS / TypeScript バインディングを生成する。
+pub fn compile_module_with_bindings(
+    module: ast::Module,
+    source_map: Option<&SourceMap>,
+    options: CompileOptions,
+) -> Result<(CompilationArtifact, crate::bindings::Bindings), CoreError> {
+    let prepared = prepare_wasm_module_with(&module, source_map, options, true)?;
+    let bindings = match crate::bindings::generate_bindings(&prepared.types, &prepared.hir_module) {
+        Ok(bindings) => bindings,
+        Err(diags) => {
+            let mut diagnostics = prepared.diagnostics;
+            diagnostics.extend(diags);
+            return Err(CoreError::from_diagnostics(diagnostics));
+        }
+    };
+    let artifact = precheck_and_emit_wasm(prepared)?;
+    Ok((artifact, bindings))
+}
+
+fn prepare_wasm_module(
+    module: &ast::Module,
+    source_map: Option<&SourceMap>,
+    options: CompileOptions,
+) -> Result<PreparedProgram, CoreError> {
+    prepare_wasm_module_with(module, source_map, options, false)
+}
+
+fn prepare_wasm_module_with(
+    module: &ast::Module,
+    source_map: Option<&SourceMap>,
+    options: CompileOptions,
+    keep_exports: bool,
+) -> Result<PreparedProgram, CoreError> {
     crate::log::set_verbose(options.verbose);
-    let target = resolve_target(&module, options)?;
+    let target = resolve_target(module, options)?;
     if matches!(target, CompileTarget::Llvm) {
         let mut diags = Vec::new();
         diags.push(Diagnostic::error(
@@ -255,8 +324,10 @@ pub fn compile_module_with_source_map(
         return Err(CoreError::from_diagnostics(diags));
     }
     let profile = options.profile.unwrap_or(BuildProfile::detect());
-    let prepared =
-        prepare_module_for_codegen_with_source_map(&module, target, profile, source_map)?;
+    prepare_program(module, target, profile, source_map, keep_exports)
+}
+
+fn precheck_and_emit_wasm(prepared: PreparedProgram) -> Result<CompilationArtifact, CoreError> {
     let pre_codegen_diags =
         passes::codegen_precheck::precheck_wasm_codegen(&prepared.types, &prepared.hir_module);
     if pre_codegen_diags
@@ -345,7 +416,8 @@ fn run_move_check(
     types: &crate::types::TypeCtx,
     diagnostics: &mut Vec<Diagnostic>,
 ) -> Result<(), CoreError> {
-    let move_errors = passes::move_check::run(hir_module, types);
+    let mut move_errors = passes::move_check::run(hir_module, types);
+    move_errors.extend(passes::lifetime_check::run(hir_module, types));
     if move_errors.is_empty() {
         return Ok(());
     }
@@ -366,6 +438,16 @@ pub fn prepare_module_for_codegen_with_source_map(
     target: CompileTarget,
     profile: BuildProfile,
     source_map: Option<&SourceMap>,
+) -> Result<PreparedProgram, CoreError> {
+    prepare_program(module, target, profile, source_map, false)
+}
+
+fn prepare_program(
+    module: &ast::Module,
+    target: CompileTarget,
+    profile: BuildProfile,
+    source_map: Option<&SourceMap>,
+    keep_exports: bool,
 ) -> Result<PreparedProgram, CoreError> {
     let precheck_diags = crate::target_precheck::precheck_module_before_codegen(module, target, profile);
     if precheck_diags
@@ -375,11 +457,16 @@ pub fn prepare_module_for_codegen_with_source_map(
         return Err(CoreError::from_diagnostics(precheck_diags));
     }
     let mut tc = run_typecheck(module, target, profile, source_map)?;
+    if !keep_exports {
+        // 公開しない関数まで残すと到達不能なコードが生成されるため、通常は捨てる。
+        tc.module.exports.clear();
+    }
     passes::insert_drops(&mut tc.module, &mut tc.types);
     let mut types = tc.types;
     let mut hir_module = monomorphize::monomorphize(&mut types, tc.module);
     let mut diagnostics = tc.diagnostics;
     run_move_check(&hir_module, &types, &mut diagnostics)?;
+    types.erase_newtypes();
     Ok(PreparedProgram {
         types,
         hir_module,
@@ -493,6 +580,15 @@ fn collect_reachable_functions(
     let mut visited: BTreeSet<String> = BTreeSet::new();
     let mut stack = Vec::new();
     stack.push(String::from(entry));
+    // Materialized constants are copied into memory obtained from the allocator.
+    if !module.const_data.is_empty() {
+        if let Some(alloc) = crate::runtime_helpers::find_runtime_helper_key(
+            &function_map,
+            crate::runtime_helpers::RuntimeHelperKind::Alloc,
+        ) {
+            stack.push(String::from(alloc));
+        }
+    }
     while let Some(name) = stack.pop() {
         if !visited.insert(name.clone()) {
             continue;
@@ -576,7 +672,7 @@ fn collect_called_functions_from_expr(
             collect_called_functions_from_expr(then_branch, stack);
             collect_called_functions_from_expr(else_branch, stack);
         }
-        crate::hir::HirExprKind::While { cond, body } => {
+        crate::hir::HirExprKind::While { cond, body, .. } => {
             collect_called_functions_from_expr(cond, stack);
             collect_called_functions_from_expr(body, stack);
         }
@@ -603,18 +699,24 @@ fn collect_called_functions_from_expr(
         }
         crate::hir::HirExprKind::Let { value, .. }
         | crate::hir::HirExprKind::Set { value, .. }
+        | crate::hir::HirExprKind::GlobalSet { value, .. }
         | crate::hir::HirExprKind::AddrOf(value)
-        | crate::hir::HirExprKind::Deref(value) => {
+        | crate::hir::HirExprKind::Deref(value)
+        | crate::hir::HirExprKind::Return(value) => {
             collect_called_functions_from_expr(value, stack);
         }
         crate::hir::HirExprKind::LiteralI32(_)
         | crate::hir::HirExprKind::LiteralF32(_)
         | crate::hir::HirExprKind::LiteralBool(_)
         | crate::hir::HirExprKind::LiteralStr(_)
+        | crate::hir::HirExprKind::ConstData(_)
         | crate::hir::HirExprKind::Unit
         | crate::hir::HirExprKind::Var(_)
         | crate::hir::HirExprKind::FnValue(_)
-        | crate::hir::HirExprKind::Drop { .. } => {}
+        | crate::hir::HirExprKind::Drop { .. }
+        | crate::hir::HirExprKind::Break { .. }
+        | crate::hir::HirExprKind::Continue { .. }
+        | crate::hir::HirExprKind::GlobalGet(_) => {}
     }
 }
 
@@ -787,7 +889,8 @@ fn collect_expr_locals(
             locals.entry(name.clone()).or_insert(value.ty);
             collect_expr_locals(value, locals);
         }
-        crate::hir::HirExprKind::Set { value, .. } => {
+        crate::hir::HirExprKind::Set { value, .. }
+        | crate::hir::HirExprKind::GlobalSet { value, .. } => {
             collect_expr_locals(value, locals);
         }
         crate::hir::HirExprKind::Call { args, .. } => {
@@ -810,7 +913,7 @@ fn collect_expr_locals(
             collect_expr_locals(then_branch, locals);
             collect_expr_locals(else_branch, locals);
         }
-        crate::hir::HirExprKind::While { cond, body } => {
+        crate::hir::HirExprKind::While { cond, body, .. } => {
             collect_expr_locals(cond, locals);
             collect_expr_locals(body, locals);
         }
@@ -841,7 +944,8 @@ fn collect_expr_locals(
             }
         }
         crate::hir::HirExprKind::AddrOf(inner)
-        | crate::hir::HirExprKind::Deref(inner) => {
+        | crate::hir::HirExprKind::Deref(inner)
+        | crate::hir::HirExprKind::Return(inner) => {
             collect_expr_locals(inner, locals);
         }
         crate::hir::HirExprKind::Block(block) => {
@@ -853,8 +957,12 @@ fn collect_expr_locals(
         | crate::hir::HirExprKind::LiteralF32(_)
         | crate::hir::HirExprKind::LiteralBool(_)
         | crate::hir::HirExprKind::LiteralStr(_)
+        | crate::hir::HirExprKind::ConstData(_)
         | crate::hir::HirExprKind::Unit
-        | crate::hir::HirExprKind::Drop { .. } => {}
+        | crate::hir::HirExprKind::Drop { .. }
+        | crate::hir::HirExprKind::Break { .. }
+        | crate::hir::HirExprKind::Continue { .. }
+        | crate::hir::HirExprKind::GlobalGet(_) => {}
     }
 }
 
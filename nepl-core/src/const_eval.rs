@@ -0,0 +1,772 @@
+//! Compile-time evaluation of `const`/`static` initializers and `#comptime`
+//! expressions.
+//!
+//! Initializers are type-checked like the body of a pure zero-argument
+//! function and then interpreted here over HIR. The interpreter runs pure
+//! user functions (including loops, `match` and early `return`), reads of
+//! other constants, struct/tuple/enum construction, field access and
+//! functions whose body is a plain `#wasm` arithmetic sequence. Every step
+//! consumes fuel so that runaway evaluation is reported instead of hanging
+//! the compiler. Errors carry the compile-time call stack that led to them.
+//!
+//! Scalar results are inlined as literals. Struct and tuple results made of
+//! 4-byte scalars are serialized into `HirModule::const_data` and each use
+//! loads a fresh copy from that data segment.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use wasm_encoder::Instruction;
+
+use crate::ast::{Effect, WasmBlock};
+use crate::hir::*;
+use crate::span::Span;
+use crate::types::{TypeCtx, TypeId, TypeKind};
+
+/// Evaluation steps granted to each constant or `#comptime` expression.
+pub const DEFAULT_FUEL: u64 = 1_000_000;
+
+/// Nesting limit for compile-time calls, protecting the host stack.
+const MAX_CALL_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstEvalErrorKind {
+    /// The expression depends on something only known at run time.
+    NotConstant,
+    /// Evaluation itself failed (division by zero, recursion too deep, ...).
+    Failed,
+    /// The fuel budget ran out.
+    FuelExhausted,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstEvalError {
+    pub kind: ConstEvalErrorKind,
+    pub message: String,
+    pub span: Span,
+    /// Compile-time calls active when the error occurred, innermost first,
+    /// as (callee, call site).
+    pub call_stack: Vec<(String, Span)>,
+}
+
+/// Non-local exits while interpreting; only `Error` escapes the evaluator.
+enum Flow {
+    Error(Box<ConstEvalError>),
+    Break(Option<String>),
+    Continue(Option<String>),
+    Return(Box<HirExpr>),
+}
+
+impl From<ConstEvalError> for Flow {
+    fn from(err: ConstEvalError) -> Self {
+        Flow::Error(Box::new(err))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    I32(i32),
+    F32(f32),
+}
+
+pub struct ConstEvaluator<'a> {
+    ctx: &'a TypeCtx,
+    strings: &'a [String],
+    /// Type-checked user functions with a HIR body, keyed by symbol.
+    functions: BTreeMap<&'a str, &'a HirFunction>,
+    /// `#wasm` bodies of user functions keyed by their HIR symbol, with parameter names.
+    raw_bodies: &'a BTreeMap<String, (Vec<String>, WasmBlock)>,
+    /// Type-checked initializers of every `const` item and `#comptime` expression.
+    inits: &'a BTreeMap<String, HirExpr>,
+    values: BTreeMap<String, HirExpr>,
+    active: Vec<String>,
+    fuel_limit: u64,
+    fuel: u64,
+    frames: Vec<(String, Span)>,
+}
+
+impl<'a> ConstEvaluator<'a> {
+    pub fn new(
+        ctx: &'a TypeCtx,
+        strings: &'a [String],
+        functions: &'a [HirFunction],
+        raw_bodies: &'a BTreeMap<String, (Vec<String>, WasmBlock)>,
+        inits: &'a BTreeMap<String, HirExpr>,
+    ) -> Self {
+        let functions = functions
+            .iter()
+            .filter(|f| matches!(f.body, HirBody::Block(_)))
+            .map(|f| (f.name.as_str(), f))
+            .collect();
+        Self {
+            ctx,
+            strings,
+            functions,
+            raw_bodies,
+            inits,
+            values: BTreeMap::new(),
+            active: Vec::new(),
+            fuel_limit: DEFAULT_FUEL,
+            fuel: DEFAULT_FUEL,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Overrides the per-item fuel budget.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel_limit = fuel;
+        self.fuel = fuel;
+        self
+    }
+
+    /// Folded values of every constant evaluated so far.
+    pub fn values(&self) -> &BTreeMap<String, HirExpr> {
+        &self.values
+    }
+
+    /// Evaluates the `const` item `name`, reusing earlier results.
+    pub fn eval_const(&mut self, name: &str, span: Span) -> Result<HirExpr, ConstEvalError> {
+        if let Some(v) = self.values.get(name) {
+            return Ok(v.clone());
+        }
+        if self.active.iter().any(|n| n == name) {
+            return Err(self.error(
+                ConstEvalErrorKind::NotConstant,
+                format!("constant '{}' depends on itself", name),
+                span,
+            ));
+        }
+        let Some(init) = self.inits.get(name) else {
+            return Err(self.error(
+                ConstEvalErrorKind::NotConstant,
+                format!("'{}' is not a constant", name),
+                span,
+            ));
+        };
+        self.active.push(name.to_string());
+        let outer_fuel = self.fuel;
+        self.fuel = self.fuel_limit;
+        let result = self.eval_top(init);
+        self.fuel = outer_fuel;
+        self.active.pop();
+        let value = result?;
+        self.values.insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+
+    /// Folds an arbitrary initializer expression (used for `static` items).
+    pub fn eval_expr(&mut self, expr: &HirExpr) -> Result<HirExpr, ConstEvalError> {
+        self.fuel = self.fuel_limit;
+        self.eval_top(expr)
+    }
+
+    /// Turns a folded value into the expression inlined at each use. Struct and
+    /// tuple values of 4-byte scalars are appended to `data` and loaded from there.
+    pub fn materialize(&self, value: &HirExpr, data: &mut Vec<Vec<u8>>) -> HirExpr {
+        if !matches!(
+            value.kind,
+            HirExprKind::StructConstruct { .. } | HirExprKind::TupleConstruct { .. }
+        ) {
+            return value.clone();
+        }
+        let mut bytes = Vec::new();
+        if !self.serialize(value, &mut bytes) {
+            return value.clone();
+        }
+        let id = data.len() as u32;
+        data.push(bytes);
+        HirExpr {
+            ty: value.ty,
+            kind: HirExprKind::Intrinsic {
+                name: String::from("load"),
+                type_args: vec![value.ty],
+                args: vec![HirExpr {
+                    ty: self.ctx.i32(),
+                    kind: HirExprKind::ConstData(id),
+                    span: value.span,
+                }],
+            },
+            span: value.span,
+        }
+    }
+
+    fn serialize(&self, value: &HirExpr, out: &mut Vec<u8>) -> bool {
+        match &value.kind {
+            HirExprKind::StructConstruct { fields: items, .. }
+            | HirExprKind::TupleConstruct { items } => {
+                items.iter().all(|item| self.serialize(item, out))
+            }
+            HirExprKind::LiteralI32(v) if self.is_kind(value.ty, |k| matches!(k, TypeKind::I32)) => {
+                out.extend_from_slice(&v.to_le_bytes());
+                true
+            }
+            HirExprKind::LiteralBool(b) => {
+                out.extend_from_slice(&(*b as i32).to_le_bytes());
+                true
+            }
+            HirExprKind::LiteralF32(v) => {
+                out.extend_from_slice(&v.to_le_bytes());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_kind(&self, ty: TypeId, pred: impl Fn(&TypeKind) -> bool) -> bool {
+        pred(&self.ctx.get(self.ctx.resolve_id(ty)))
+    }
+
+    fn eval_top(&mut self, expr: &HirExpr) -> Result<HirExpr, ConstEvalError> {
+        match self.eval(expr, &mut Vec::new()) {
+            Ok(value) => Ok(value),
+            Err(Flow::Return(value)) => Ok(*value),
+            Err(Flow::Error(err)) => Err(*err),
+            Err(Flow::Break(_)) | Err(Flow::Continue(_)) => Err(self.not_constant(expr.span)),
+        }
+    }
+
+    fn error(&self, kind: ConstEvalErrorKind, message: impl Into<String>, span: Span) -> ConstEvalError {
+        ConstEvalError {
+            kind,
+            message: message.into(),
+            span,
+            call_stack: self.frames.iter().rev().cloned().collect(),
+        }
+    }
+
+    fn not_constant(&self, span: Span) -> ConstEvalError {
+        self.error(
+            ConstEvalErrorKind::NotConstant,
+            "expression cannot be evaluated at compile time",
+            span,
+        )
+    }
+
+    fn consume_fuel(&mut self, span: Span) -> Result<(), ConstEvalError> {
+        if self.fuel == 0 {
+            return Err(self.error(
+                ConstEvalErrorKind::FuelExhausted,
+                format!(
+                    "compile-time evaluation did not finish within {} steps",
+                    self.fuel_limit
+                ),
+                span,
+            ));
+        }
+        self.fuel -= 1;
+        Ok(())
+    }
+
+    fn eval(&mut self, expr: &HirExpr, locals: &mut Vec<(String, HirExpr)>) -> Result<HirExpr, Flow> {
+        self.consume_fuel(expr.span)?;
+        let with_kind = |kind: HirExprKind| HirExpr {
+            ty: expr.ty,
+            kind,
+            span: expr.span,
+        };
+        match &expr.kind {
+            HirExprKind::LiteralI32(_)
+            | HirExprKind::LiteralF32(_)
+            | HirExprKind::LiteralBool(_)
+            | HirExprKind::LiteralStr(_)
+            | HirExprKind::Unit => Ok(expr.clone()),
+            HirExprKind::Var(name) => match locals.iter().rev().find(|(n, _)| n == name) {
+                Some((_, v)) => Ok(v.clone()),
+                None => Err(self
+                    .error(
+                        ConstEvalErrorKind::NotConstant,
+                        format!("'{}' is not known at compile time", name),
+                        expr.span,
+                    )
+                    .into()),
+            },
+            HirExprKind::GlobalGet(name) => {
+                if self.inits.contains_key(name) {
+                    let mut value = self.eval_const(name, expr.span)?;
+                    value.span = expr.span;
+                    Ok(value)
+                } else {
+                    Err(self
+                        .error(
+                            ConstEvalErrorKind::NotConstant,
+                            format!("static '{}' cannot be read in a constant expression", name),
+                            expr.span,
+                        )
+                        .into())
+                }
+            }
+            HirExprKind::Block(block) => {
+                let depth = locals.len();
+                let mut last = with_kind(HirExprKind::Unit);
+                for line in &block.lines {
+                    let value = self.eval(&line.expr, locals)?;
+                    if !line.drop_result {
+                        last = value;
+                    }
+                }
+                locals.truncate(depth);
+                Ok(last)
+            }
+            HirExprKind::Let { name, value, .. } => {
+                let value = self.eval(value, locals)?;
+                locals.push((name.clone(), value));
+                Ok(with_kind(HirExprKind::Unit))
+            }
+            HirExprKind::Set { name, value } => {
+                let value = self.eval(value, locals)?;
+                match locals.iter_mut().rev().find(|(n, _)| n == name) {
+                    Some(slot) => slot.1 = value,
+                    None => return Err(self.not_constant(expr.span).into()),
+                }
+                Ok(with_kind(HirExprKind::Unit))
+            }
+            HirExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => match self.eval(cond, locals)?.kind {
+                HirExprKind::LiteralBool(true) => self.eval(then_branch, locals),
+                HirExprKind::LiteralBool(false) => self.eval(else_branch, locals),
+                _ => Err(self.not_constant(cond.span).into()),
+            },
+            HirExprKind::While { cond, body, label } => {
+                loop {
+                    match self.eval(cond, locals)?.kind {
+                        HirExprKind::LiteralBool(true) => {}
+                        HirExprKind::LiteralBool(false) => break,
+                        _ => return Err(self.not_constant(cond.span).into()),
+                    }
+                    let depth = locals.len();
+                    match self.eval(body, locals) {
+                        Ok(_) => {}
+                        Err(Flow::Break(target)) if jump_targets(&target, label) => {
+                            locals.truncate(depth);
+                            break;
+                        }
+                        Err(Flow::Continue(target)) if jump_targets(&target, label) => {
+                            locals.truncate(depth);
+                        }
+                        Err(flow) => return Err(flow),
+                    }
+                }
+                Ok(with_kind(HirExprKind::Unit))
+            }
+            HirExprKind::Break { label } => Err(Flow::Break(label.clone())),
+            HirExprKind::Continue { label } => Err(Flow::Continue(label.clone())),
+            HirExprKind::Return(value) => {
+                let value = self.eval(value, locals)?;
+                Err(Flow::Return(Box::new(value)))
+            }
+            HirExprKind::Match { scrutinee, arms } => {
+                let value = self.eval(scrutinee, locals)?;
+                let HirExprKind::EnumConstruct {
+                    variant, payload, ..
+                } = &value.kind
+                else {
+                    return Err(self.not_constant(scrutinee.span).into());
+                };
+                let Some(arm) = arms
+                    .iter()
+                    .find(|arm| variant_name(&arm.variant) == variant_name(variant))
+                else {
+                    return Err(self.not_constant(expr.span).into());
+                };
+                let depth = locals.len();
+                if let (Some(bind), Some(payload)) = (&arm.bind_local, payload) {
+                    locals.push((bind.clone(), (**payload).clone()));
+                }
+                let result = self.eval(&arm.body, locals);
+                locals.truncate(depth);
+                result
+            }
+            HirExprKind::StructConstruct {
+                name,
+                type_args,
+                fields,
+            } => {
+                let mut out = Vec::new();
+                for f in fields {
+                    out.push(self.eval(f, locals)?);
+                }
+                Ok(with_kind(HirExprKind::StructConstruct {
+                    name: name.clone(),
+                    type_args: type_args.clone(),
+                    fields: out,
+                }))
+            }
+            HirExprKind::TupleConstruct { items } => {
+                let mut out = Vec::new();
+                for item in items {
+                    out.push(self.eval(item, locals)?);
+                }
+                Ok(with_kind(HirExprKind::TupleConstruct { items: out }))
+            }
+            HirExprKind::EnumConstruct {
+                name,
+                variant,
+                type_args,
+                payload,
+            } => {
+                let payload = match payload {
+                    Some(p) => Some(Box::new(self.eval(p, locals)?)),
+                    None => None,
+                };
+                Ok(with_kind(HirExprKind::EnumConstruct {
+                    name: name.clone(),
+                    variant: variant.clone(),
+                    type_args: type_args.clone(),
+                    payload,
+                }))
+            }
+            HirExprKind::Intrinsic { name, args, .. } if name == "get_field" && args.len() == 2 => {
+                let base = self.eval(&args[0], locals)?;
+                let index = self.eval(&args[1], locals)?;
+                self.field_of(&base, &index)
+                    .map(|mut v| {
+                        v.span = expr.span;
+                        v
+                    })
+                    .ok_or_else(|| self.not_constant(expr.span).into())
+            }
+            HirExprKind::Call {
+                callee: FuncRef::User(name, _),
+                args,
+            } => {
+                let mut arg_values = Vec::new();
+                for a in args {
+                    arg_values.push(self.eval(a, locals)?);
+                }
+                self.call(name, arg_values, expr)
+            }
+            HirExprKind::Drop { .. } => Ok(with_kind(HirExprKind::Unit)),
+            _ => Err(self.not_constant(expr.span).into()),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: Vec<HirExpr>, call: &HirExpr) -> Result<HirExpr, Flow> {
+        if let Some((params, body)) = self.raw_bodies.get(name) {
+            let mut scalars = Vec::new();
+            for a in &args {
+                scalars.push(scalar_of(a).ok_or_else(|| self.not_constant(a.span))?);
+            }
+            let result = run_wasm_arith(params, body, &scalars)
+                .map_err(|msg| self.error(ConstEvalErrorKind::Failed, msg, call.span))?;
+            return Ok(self.literal_of(call, result)?);
+        }
+        let Some(func) = self.functions.get(name).copied() else {
+            return Err(self.not_constant(call.span).into());
+        };
+        if !matches!(func.effect, Effect::Pure) {
+            return Err(self
+                .error(
+                    ConstEvalErrorKind::NotConstant,
+                    format!("impure function '{}' cannot be called at compile time", name),
+                    call.span,
+                )
+                .into());
+        }
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(self
+                .error(
+                    ConstEvalErrorKind::Failed,
+                    format!(
+                        "compile-time call depth exceeded {} while calling '{}'",
+                        MAX_CALL_DEPTH, name
+                    ),
+                    call.span,
+                )
+                .into());
+        }
+        let HirBody::Block(body) = &func.body else {
+            return Err(self.not_constant(call.span).into());
+        };
+        let mut locals: Vec<(String, HirExpr)> = func
+            .params
+            .iter()
+            .map(|p| p.name.clone())
+            .zip(args)
+            .collect();
+        // Report the source-level name rather than the mangled symbol.
+        let source_name = name.split_once("__").map_or(name, |(base, _)| base);
+        self.frames.push((source_name.to_string(), call.span));
+        let result = match self.eval_block_value(body, &mut locals) {
+            Ok(value) => Ok(value),
+            Err(Flow::Return(value)) => Ok(*value),
+            Err(Flow::Error(err)) => Err(Flow::Error(err)),
+            Err(Flow::Break(_)) | Err(Flow::Continue(_)) => Err(self.not_constant(call.span).into()),
+        };
+        self.frames.pop();
+        result.map(|mut value| {
+            value.span = call.span;
+            value
+        })
+    }
+
+    fn eval_block_value(
+        &mut self,
+        block: &HirBlock,
+        locals: &mut Vec<(String, HirExpr)>,
+    ) -> Result<HirExpr, Flow> {
+        let mut last = HirExpr {
+            ty: block.ty,
+            kind: HirExprKind::Unit,
+            span: block.span,
+        };
+        for line in &block.lines {
+            let value = self.eval(&line.expr, locals)?;
+            if !line.drop_result {
+                last = value;
+            }
+        }
+        Ok(last)
+    }
+
+    fn field_of(&self, base: &HirExpr, index: &HirExpr) -> Option<HirExpr> {
+        match (&base.kind, &index.kind) {
+            (HirExprKind::TupleConstruct { items }, HirExprKind::LiteralI32(i)) => {
+                items.get(usize::try_from(*i).ok()?).cloned()
+            }
+            (HirExprKind::StructConstruct { fields, .. }, HirExprKind::LiteralStr(id)) => {
+                let field = self.strings.get(*id as usize)?;
+                let names = struct_field_names(self.ctx, base.ty)?;
+                let pos = names.iter().position(|n| n == field)?;
+                fields.get(pos).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    fn literal_of(&self, expr: &HirExpr, value: Scalar) -> Result<HirExpr, ConstEvalError> {
+        let kind = match (self.ctx.get(self.ctx.resolve_id(expr.ty)), value) {
+            (TypeKind::Bool, Scalar::I32(v)) => HirExprKind::LiteralBool(v != 0),
+            (TypeKind::U8, Scalar::I32(v)) => HirExprKind::LiteralI32(v & 0xff),
+            (TypeKind::I32 | TypeKind::Var(_), Scalar::I32(v)) => HirExprKind::LiteralI32(v),
+            (TypeKind::F32 | TypeKind::Var(_), Scalar::F32(v)) => HirExprKind::LiteralF32(v),
+            _ => return Err(self.not_constant(expr.span)),
+        };
+        Ok(HirExpr {
+            ty: expr.ty,
+            kind,
+            span: expr.span,
+        })
+    }
+}
+
+/// Whether a jump with `target` label leaves or continues a loop labelled `label`.
+fn jump_targets(target: &Option<String>, label: &Option<String>) -> bool {
+    target.is_none() || target == label
+}
+
+fn variant_name(variant: &str) -> &str {
+    variant.rsplit("::").next().unwrap_or(variant)
+}
+
+fn struct_field_names(ctx: &TypeCtx, ty: TypeId) -> Option<Vec<String>> {
+    match ctx.get(ctx.resolve_id(ty)) {
+        TypeKind::Struct { field_names, .. } => Some(field_names),
+        TypeKind::Apply { base, .. } => struct_field_names(ctx, base),
+        _ => None,
+    }
+}
+
+fn scalar_of(expr: &HirExpr) -> Option<Scalar> {
+    match expr.kind {
+        HirExprKind::LiteralI32(v) => Some(Scalar::I32(v)),
+        HirExprKind::LiteralBool(b) => Some(Scalar::I32(b as i32)),
+        HirExprKind::LiteralF32(v) => Some(Scalar::F32(v)),
+        _ => None,
+    }
+}
+
+/// Runs a straight-line `#wasm` body over i32/f32 values.
+fn run_wasm_arith(params: &[String], body: &WasmBlock, args: &[Scalar]) -> Result<Scalar, String> {
+    let mut stack: Vec<Scalar> = Vec::new();
+    for line in &body.lines {
+        let insts = crate::wasm_shared::parse_wasm_line_with_lookup(line, |name| {
+            let name = name.strip_prefix('$').unwrap_or(name);
+            params.iter().position(|p| p == name).map(|i| i as u32)
+        })?;
+        for inst in insts {
+            step(&inst, args, &mut stack)?;
+        }
+    }
+    match stack.as_slice() {
+        [value] => Ok(*value),
+        _ => Err(String::from("function body does not produce a single value")),
+    }
+}
+
+fn step(inst: &Instruction<'static>, args: &[Scalar], stack: &mut Vec<Scalar>) -> Result<(), String> {
+    let unsupported = || String::from("instruction is not supported in constant evaluation");
+    let pop_i32 = |stack: &mut Vec<Scalar>| match stack.pop() {
+        Some(Scalar::I32(v)) => Ok(v),
+        _ => Err(String::from("operand type mismatch in constant evaluation")),
+    };
+    match inst {
+        Instruction::LocalGet(idx) => {
+            stack.push(*args.get(*idx as usize).ok_or_else(unsupported)?);
+            return Ok(());
+        }
+        Instruction::I32Const(v) => {
+            stack.push(Scalar::I32(*v));
+            return Ok(());
+        }
+        Instruction::F32Const(v) => {
+            stack.push(Scalar::F32(f32::from(*v)));
+            return Ok(());
+        }
+        Instruction::I32Eqz => {
+            let a = pop_i32(stack)?;
+            stack.push(Scalar::I32((a == 0) as i32));
+            return Ok(());
+        }
+        Instruction::F32Neg | Instruction::F32Abs => {
+            let Some(Scalar::F32(a)) = stack.pop() else {
+                return Err(unsupported());
+            };
+            let negate = matches!(inst, Instruction::F32Neg) || a < 0.0;
+            stack.push(Scalar::F32(if negate { -a } else { a }));
+            return Ok(());
+        }
+        _ => {}
+    }
+    let (b, a) = (stack.pop(), stack.pop());
+    let value = match (a, b) {
+        (Some(Scalar::I32(a)), Some(Scalar::I32(b))) => {
+            let div_zero = || String::from("division by zero in constant expression");
+            Scalar::I32(match inst {
+                Instruction::I32Add => a.wrapping_add(b),
+                Instruction::I32Sub => a.wrapping_sub(b),
+                Instruction::I32Mul => a.wrapping_mul(b),
+                Instruction::I32DivS => a.checked_div(b).ok_or_else(div_zero)?,
+                Instruction::I32DivU => (a as u32).checked_div(b as u32).ok_or_else(div_zero)? as i32,
+                Instruction::I32RemS => {
+                    if b == 0 {
+                        return Err(div_zero());
+                    }
+                    a.wrapping_rem(b)
+                }
+                Instruction::I32RemU => (a as u32).checked_rem(b as u32).ok_or_else(div_zero)? as i32,
+                Instruction::I32And => a & b,
+                Instruction::I32Or => a | b,
+                Instruction::I32Xor => a ^ b,
+                Instruction::I32Shl => a.wrapping_shl(b as u32),
+                Instruction::I32ShrS => a.wrapping_shr(b as u32),
+                Instruction::I32ShrU => (a as u32).wrapping_shr(b as u32) as i32,
+                Instruction::I32Eq => (a == b) as i32,
+                Instruction::I32Ne => (a != b) as i32,
+                Instruction::I32LtS => (a < b) as i32,
+                Instruction::I32LtU => ((a as u32) < (b as u32)) as i32,
+                Instruction::I32LeS => (a <= b) as i32,
+                Instruction::I32LeU => ((a as u32) <= (b as u32)) as i32,
+                Instruction::I32GtS => (a > b) as i32,
+                Instruction::I32GtU => ((a as u32) > (b as u32)) as i32,
+                Instruction::I32GeS => (a >= b) as i32,
+                Instruction::I32GeU => ((a as u32) >= (b as u32)) as i32,
+                _ => return Err(unsupported()),
+            })
+        }
+        (Some(Scalar::F32(a)), Some(Scalar::F32(b))) => match inst {
+            Instruction::F32Add => Scalar::F32(a + b),
+            Instruction::F32Sub => Scalar::F32(a - b),
+            Instruction::F32Mul => Scalar::F32(a * b),
+            Instruction::F32Div => Scalar::F32(a / b),
+            Instruction::F32Min => Scalar::F32(a.min(b)),
+            Instruction::F32Max => Scalar::F32(a.max(b)),
+            Instruction::F32Eq => Scalar::I32((a == b) as i32),
+            Instruction::F32Ne => Scalar::I32((a != b) as i32),
+            Instruction::F32Lt => Scalar::I32((a < b) as i32),
+            Instruction::F32Le => Scalar::I32((a <= b) as i32),
+            Instruction::F32Gt => Scalar::I32((a > b) as i32),
+            Instruction::F32Ge => Scalar::I32((a >= b) as i32),
+            _ => return Err(unsupported()),
+        },
+        _ => return Err(unsupported()),
+    };
+    stack.push(value);
+    Ok(())
+}
+
+/// Replaces every read of a constant with its folded value.
+pub fn inline_consts_in_expr(expr: &mut HirExpr, values: &BTreeMap<String, HirExpr>) {
+    if let HirExprKind::GlobalGet(name) = &expr.kind {
+        if let Some(value) = values.get(name) {
+            expr.kind = value.kind.clone();
+        }
+        return;
+    }
+    match &mut expr.kind {
+        HirExprKind::Call { args, .. } | HirExprKind::Intrinsic { args, .. } => {
+            for a in args {
+                inline_consts_in_expr(a, values);
+            }
+        }
+        HirExprKind::CallIndirect { callee, args, .. } => {
+            inline_consts_in_expr(callee, values);
+            for a in args {
+                inline_consts_in_expr(a, values);
+            }
+        }
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            inline_consts_in_expr(cond, values);
+            inline_consts_in_expr(then_branch, values);
+            inline_consts_in_expr(else_branch, values);
+        }
+        HirExprKind::While { cond, body, .. } => {
+            inline_consts_in_expr(cond, values);
+            inline_consts_in_expr(body, values);
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            inline_consts_in_expr(scrutinee, values);
+            for arm in arms {
+                inline_consts_in_expr(&mut arm.body, values);
+            }
+        }
+        HirExprKind::EnumConstruct { payload, .. } => {
+            if let Some(p) = payload {
+                inline_consts_in_expr(p, values);
+            }
+        }
+        HirExprKind::StructConstruct { fields: items, .. }
+        | HirExprKind::TupleConstruct { items } => {
+            for item in items {
+                inline_consts_in_expr(item, values);
+            }
+        }
+        HirExprKind::Block(block) => inline_consts_in_block(block, values),
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. }
+        | HirExprKind::AddrOf(value)
+        | HirExprKind::Deref(value)
+        | HirExprKind::Return(value) => inline_consts_in_expr(value, values),
+        HirExprKind::LiteralI32(_)
+        | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralBool(_)
+        | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
+        | HirExprKind::Unit
+        | HirExprKind::Var(_)
+        | HirExprKind::FnValue(_)
+        | HirExprKind::GlobalGet(_)
+        | HirExprKind::Break { .. }
+        | HirExprKind::Continue { .. }
+        | HirExprKind::Drop { .. } => {}
+    }
+}
+
+pub fn inline_consts_in_block(block: &mut HirBlock, values: &BTreeMap<String, HirExpr>) {
+    for line in &mut block.lines {
+        inline_consts_in_expr(&mut line.expr, values);
+    }
+}
@@ -0,0 +1,816 @@
+//! Source-level step debugging for wasm output.
+//!
+//! `instrument_module` rewrites HIR after monomorphization so that the
+//! generated wasm reports its progress to the host through imports of the
+//! `nepl_debug` module:
+//!
+//! - `enter(function)` / `leave()` around every function body (and before
+//!   each `return`), which gives the host an exact call stack;
+//! - `step(site) -> bool` before every statement. When the host answers
+//!   `true`, the statement's visible locals are sent through
+//!   `local_i32/i64/f32/f64(slot, value)` followed by `pause()`.
+//!
+//! The host suspends execution inside `pause` (wasmi resumable calls in
+//! `nepl-cli`) and inspects the program through a `DebugSession`, which
+//! owns the breakpoint/step logic and decodes locals from linear memory
+//! using the `TypeCtx` layouts. The session has no runtime dependency so the
+//! CLI and the web playground drive the same engine.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use wasm_encoder::ValType;
+
+use crate::ast::Effect;
+use crate::codegen_wasm::{is_aggregate_storage_type, type_storage_size_bytes, valtype};
+use crate::hir::*;
+use crate::loader::SourceMap;
+use crate::span::{FileId, Span};
+use crate::types::{TypeCtx, TypeId, TypeKind};
+
+/// Import module of the debug hooks.
+pub const DEBUG_IMPORT_MODULE: &str = "nepl_debug";
+
+const RET_LOCAL: &str = "__nepl_dbg_ret";
+
+/// Functions and statement sites of an instrumented module.
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    /// Indexed by the id passed to `enter`.
+    pub functions: Vec<DebugFunction>,
+    /// Indexed by the id passed to `step`.
+    pub sites: Vec<DebugSite>,
+    pub types: TypeCtx,
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugFunction {
+    /// Source-level name (`add`), without the mangled signature.
+    pub name: String,
+    /// Symbol of the monomorphized instance (`add__i32_i32__i32__pure`).
+    pub symbol: String,
+    pub span: Span,
+}
+
+/// A statement the debugger can stop before.
+#[derive(Debug, Clone)]
+pub struct DebugSite {
+    pub function: usize,
+    pub span: Span,
+    /// Locals in scope before the statement; the index is the `slot` of `local_*`.
+    pub locals: Vec<DebugLocal>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugLocal {
+    pub name: String,
+    pub ty: TypeId,
+}
+
+/// Raw value of a wasm local as reported by a `local_*` hook.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugValue {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+/// Strips the signature suffix that monomorphization appends to symbols.
+pub(crate) fn source_name(symbol: &str) -> String {
+    if symbol.starts_with("__") {
+        return symbol.to_string();
+    }
+    match symbol.find("__") {
+        Some(pos) => symbol[..pos].to_string(),
+        None => symbol.to_string(),
+    }
+}
+
+struct Instrumenter<'a> {
+    types: &'a TypeCtx,
+    /// Local name prefix of the hook externs (`__nepl_dbg_`).
+    prefix: &'static str,
+    /// Whether statements get `step` hooks; call tracking alone only needs `enter`/`leave`.
+    steps: bool,
+    function: usize,
+    scopes: Vec<Vec<DebugLocal>>,
+    sites: Vec<DebugSite>,
+    /// `local_i64` / `local_f64` hooks take the type of the first local that needed them.
+    wide_locals: BTreeMap<&'static str, TypeId>,
+}
+
+impl<'a> Instrumenter<'a> {
+    fn hook(&self, name: &str, args: Vec<HirExpr>, ty: TypeId) -> HirExpr {
+        HirExpr {
+            ty,
+            kind: HirExprKind::Call {
+                callee: FuncRef::User(format!("{}{}", self.prefix, name), Vec::new()),
+                args,
+            },
+            span: Span::dummy(),
+        }
+    }
+
+    fn unit(&self, kind: HirExprKind) -> HirExpr {
+        HirExpr {
+            ty: self.types.unit(),
+            kind,
+            span: Span::dummy(),
+        }
+    }
+
+    fn i32_lit(&self, value: usize) -> HirExpr {
+        HirExpr {
+            ty: self.types.i32(),
+            kind: HirExprKind::LiteralI32(value as i32),
+            span: Span::dummy(),
+        }
+    }
+
+    fn line(expr: HirExpr, drop_result: bool) -> HirLine {
+        HirLine { expr, drop_result }
+    }
+
+    /// Innermost binding of every visible name, in declaration order.
+    fn visible_locals(&self) -> Vec<DebugLocal> {
+        let mut out: Vec<DebugLocal> = Vec::new();
+        for local in self.scopes.iter().flatten() {
+            out.retain(|l| l.name != local.name);
+            out.push(local.clone());
+        }
+        out
+    }
+
+    fn declare(&mut self, name: &str, ty: TypeId) {
+        if name.starts_with("__") || valtype(&self.types.get(ty)).is_none() {
+            return;
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(DebugLocal {
+                name: name.to_string(),
+                ty,
+            });
+        }
+    }
+
+    /// `if step(site) { local_*(slot, x)...; pause() }`
+    fn step_hook(&mut self, span: Span) -> HirExpr {
+        let site = self.sites.len();
+        let locals = self.visible_locals();
+        let mut report = Vec::new();
+        for (slot, local) in locals.iter().enumerate() {
+            let hook = match valtype(&self.types.get(local.ty)) {
+                Some(ValType::I64) => "local_i64",
+                Some(ValType::F32) => "local_f32",
+                Some(ValType::F64) => "local_f64",
+                _ => "local_i32",
+            };
+            if matches!(hook, "local_i64" | "local_f64") {
+                self.wide_locals.entry(hook).or_insert(local.ty);
+            }
+            let value = HirExpr {
+                ty: local.ty,
+                kind: HirExprKind::Var(local.name.clone()),
+                span: Span::dummy(),
+            };
+            report.push(Self::line(
+                self.hook(hook, vec![self.i32_lit(slot), value], self.types.unit()),
+                true,
+            ));
+        }
+        report.push(Self::line(
+            self.hook("pause", Vec::new(), self.types.unit()),
+            true,
+        ));
+        self.sites.push(DebugSite {
+            function: self.function,
+            span,
+            locals,
+        });
+        let then_branch = self.unit(HirExprKind::Block(HirBlock {
+            lines: report,
+            ty: self.types.unit(),
+            span: Span::dummy(),
+        }));
+        self.unit(HirExprKind::If {
+            cond: Box::new(self.hook("step", vec![self.i32_lit(site)], self.types.bool())),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(self.unit(HirExprKind::Unit)),
+        })
+    }
+
+    /// `{ let ret value; leave(); ret }`, or `{ value; leave() }` for unit values.
+    fn leave_after(&self, value: HirExpr) -> HirExpr {
+        let ty = value.ty;
+        let leave = self.hook("leave", Vec::new(), self.types.unit());
+        let lines = if valtype(&self.types.get(ty)).is_none() || value.diverges() {
+            vec![Self::line(value, true), Self::line(leave, true)]
+        } else {
+            vec![
+                Self::line(
+                    self.unit(HirExprKind::Let {
+                        name: RET_LOCAL.to_string(),
+                        mutable: false,
+                        value: Box::new(value),
+                    }),
+                    true,
+                ),
+                Self::line(leave, true),
+                Self::line(
+                    HirExpr {
+                        ty,
+                        kind: HirExprKind::Var(RET_LOCAL.to_string()),
+                        span: Span::dummy(),
+                    },
+                    false,
+                ),
+            ]
+        };
+        HirExpr {
+            ty,
+            kind: HirExprKind::Block(HirBlock {
+                lines,
+                ty,
+                span: Span::dummy(),
+            }),
+            span: Span::dummy(),
+        }
+    }
+
+    fn block(&mut self, block: &mut HirBlock) {
+        self.scopes.push(Vec::new());
+        let lines = core::mem::take(&mut block.lines);
+        for mut line in lines {
+            let is_statement = self.steps
+                && !matches!(line.expr.kind, HirExprKind::Drop { .. })
+                && line.expr.span != Span::dummy();
+            if is_statement {
+                let hook = self.step_hook(line.expr.span);
+                block.lines.push(Self::line(hook, true));
+            }
+            self.expr(&mut line.expr);
+            if let HirExprKind::Let { name, value, .. } = &line.expr.kind {
+                self.declare(name, value.ty);
+            }
+            block.lines.push(line);
+        }
+        self.scopes.pop();
+    }
+
+    fn expr(&mut self, expr: &mut HirExpr) {
+        match &mut expr.kind {
+            HirExprKind::Block(block) => self.block(block),
+            HirExprKind::Return(value) => {
+                self.expr(value);
+                let inner = core::mem::replace(value.as_mut(), self.unit(HirExprKind::Unit));
+                **value = self.leave_after(inner);
+            }
+            HirExprKind::Call { args, .. } | HirExprKind::Intrinsic { args, .. } => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            HirExprKind::CallIndirect { callee, args, .. } => {
+                self.expr(callee);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            HirExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            HirExprKind::While { cond, body, .. } => {
+                self.expr(cond);
+                self.expr(body);
+            }
+            HirExprKind::Match { scrutinee, arms } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    if let Some(bind) = &arm.bind_local {
+                        if let Some(ty) = match_payload_ty(self.types, scrutinee.ty, &arm.variant) {
+                            self.declare(bind, ty);
+                        }
+                    }
+                    self.expr(&mut arm.body);
+                    self.scopes.pop();
+                }
+            }
+            HirExprKind::EnumConstruct {
+                payload: Some(payload),
+                ..
+            } => self.expr(payload),
+            HirExprKind::StructConstruct { fields: items, .. }
+            | HirExprKind::TupleConstruct { items } => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            HirExprKind::Let { value, .. }
+            | HirExprKind::Set { value, .. }
+            | HirExprKind::GlobalSet { value, .. } => self.expr(value),
+            HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => self.expr(inner),
+            _ => {}
+        }
+    }
+}
+
+fn match_payload_ty(types: &TypeCtx, enum_ty: TypeId, variant: &str) -> Option<TypeId> {
+    let variant = variant.rsplit("::").next().unwrap_or(variant);
+    match aggregate_shape(&mut types.clone(), enum_ty)? {
+        Aggregate::Enum { variants, .. } => variants
+            .into_iter()
+            .find(|(name, _)| name == variant)
+            .and_then(|(_, payload)| payload),
+        _ => None,
+    }
+}
+
+/// Calls tracked by `instrument_calls`.
+pub(crate) struct InstrumentedCalls {
+    pub functions: Vec<DebugFunction>,
+    pub sites: Vec<DebugSite>,
+    pub wide_locals: BTreeMap<&'static str, TypeId>,
+}
+
+/// Wraps every function body of `module` in `{prefix}enter(function)` / `{prefix}leave()`
+/// calls and, when `steps` is set, puts a `step` hook before each statement.
+/// `prologue` may add lines that run right after `enter`.
+pub(crate) fn instrument_calls(
+    types: &TypeCtx,
+    module: &mut HirModule,
+    prefix: &'static str,
+    steps: bool,
+    mut prologue: impl FnMut(&HirFunction) -> Vec<HirLine>,
+) -> InstrumentedCalls {
+    let mut functions = Vec::new();
+    let mut sites = Vec::new();
+    let mut wide_locals = BTreeMap::new();
+    for func in module.functions.iter_mut() {
+        if crate::wasm_shared::should_skip_wasm_codegen_for_generic(types, func)
+            || !matches!(func.body, HirBody::Block(_))
+        {
+            continue;
+        }
+        let function = functions.len();
+        functions.push(DebugFunction {
+            name: source_name(&func.name),
+            symbol: func.name.clone(),
+            span: func.span,
+        });
+        let extra = prologue(func);
+        let HirBody::Block(body) = &mut func.body else {
+            continue;
+        };
+        let mut inst = Instrumenter {
+            types,
+            prefix,
+            steps,
+            function,
+            scopes: vec![Vec::new()],
+            sites: core::mem::take(&mut sites),
+            wide_locals: core::mem::take(&mut wide_locals),
+        };
+        for param in &func.params {
+            inst.declare(&param.name, param.ty);
+        }
+        inst.block(body);
+        let original = HirExpr {
+            ty: body.ty,
+            kind: HirExprKind::Block(core::mem::replace(
+                body,
+                HirBlock {
+                    lines: Vec::new(),
+                    ty: body.ty,
+                    span: body.span,
+                },
+            )),
+            span: body.span,
+        };
+        let enter = inst.hook("enter", vec![inst.i32_lit(function)], types.unit());
+        let tail = inst.leave_after(original);
+        body.lines = vec![Instrumenter::line(enter, true)];
+        body.lines.extend(extra);
+        body.lines.push(Instrumenter::line(tail, false));
+        sites = inst.sites;
+        wide_locals = inst.wide_locals;
+    }
+    InstrumentedCalls {
+        functions,
+        sites,
+        wide_locals,
+    }
+}
+
+/// Inserts the debug hooks into every user function of `module`.
+pub fn instrument_module(types: &TypeCtx, module: &mut HirModule) -> DebugInfo {
+    let InstrumentedCalls {
+        functions,
+        sites,
+        wide_locals,
+    } = instrument_calls(types, module, "__nepl_dbg_", true, |_| Vec::new());
+
+    let unit = types.unit();
+    let i32_ty = types.i32();
+    let mut hooks: Vec<(&str, Vec<TypeId>, TypeId)> = vec![
+        ("enter", vec![i32_ty], unit),
+        ("leave", Vec::new(), unit),
+        ("step", vec![i32_ty], types.bool()),
+        ("pause", Vec::new(), unit),
+        ("local_i32", vec![i32_ty, i32_ty], unit),
+        ("local_f32", vec![i32_ty, types.f32()], unit),
+    ];
+    for (hook, ty) in &wide_locals {
+        hooks.push((hook, vec![i32_ty, *ty], unit));
+    }
+    for (name, params, result) in hooks {
+        module.externs.push(HirExtern {
+            module: DEBUG_IMPORT_MODULE.to_string(),
+            name: name.to_string(),
+            local_name: format!("__nepl_dbg_{}", name),
+            params,
+            result,
+            effect: Effect::Impure,
+            span: Span::dummy(),
+        });
+    }
+
+    DebugInfo {
+        functions,
+        sites,
+        types: types.clone(),
+    }
+}
+
+// ---------------------------------------------------------------------
+// Session
+// ---------------------------------------------------------------------
+
+/// How execution continues after a pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepCommand {
+    /// Run until a breakpoint.
+    Continue,
+    /// Stop at the next statement, entering calls.
+    StepInto,
+    /// Stop at the next statement of the current function or a caller.
+    StepOver,
+    /// Stop once the current function has returned.
+    StepOut,
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugFrame {
+    pub function: usize,
+    /// Statement the frame is executing, once it reached one.
+    pub site: Option<usize>,
+}
+
+/// Breakpoints, stepping state and the shadow call stack of one run.
+#[derive(Debug, Clone)]
+pub struct DebugSession {
+    info: DebugInfo,
+    breakpoints: BTreeSet<usize>,
+    /// Files stepping never stops in (breakpoints there still hit).
+    skipped_files: BTreeSet<u32>,
+    command: StepCommand,
+    /// Stack depth when `command` was issued.
+    anchor: usize,
+    stack: Vec<DebugFrame>,
+    locals: Vec<Option<DebugValue>>,
+    paused_at: Option<usize>,
+}
+
+impl DebugSession {
+    /// Starts a session that runs until the first breakpoint; call
+    /// `resume(StepCommand::StepInto)` to stop before the first statement instead.
+    pub fn new(info: DebugInfo) -> Self {
+        Self {
+            info,
+            breakpoints: BTreeSet::new(),
+            skipped_files: BTreeSet::new(),
+            command: StepCommand::Continue,
+            anchor: 0,
+            stack: Vec::new(),
+            locals: Vec::new(),
+            paused_at: None,
+        }
+    }
+
+    pub fn info(&self) -> &DebugInfo {
+        &self.info
+    }
+
+    /// Sites whose statement starts on `line` (0-based) of `file`.
+    pub fn sites_on_line(&self, source_map: &SourceMap, file: FileId, line: usize) -> Vec<usize> {
+        self.info
+            .sites
+            .iter()
+            .enumerate()
+            .filter(|(_, site)| {
+                site.span.file_id == file
+                    && source_map
+                        .line_col(file, site.span.start)
+                        .is_some_and(|(l, _)| l == line)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn set_breakpoint(&mut self, site: usize) {
+        self.breakpoints.insert(site);
+    }
+
+    pub fn clear_breakpoint(&mut self, site: usize) -> bool {
+        self.breakpoints.remove(&site)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Keeps stepping from stopping inside `file`, typically the stdlib.
+    pub fn skip_file(&mut self, file: FileId) {
+        self.skipped_files.insert(file.0);
+    }
+
+    /// Makes the next statement pause regardless of the current command.
+    pub fn interrupt(&mut self) {
+        self.command = StepCommand::StepInto;
+    }
+
+    /// Leaves the current pause; the next `on_step` decides where to stop.
+    pub fn resume(&mut self, command: StepCommand) {
+        self.command = command;
+        self.anchor = self.stack.len();
+        self.paused_at = None;
+    }
+
+    pub fn on_enter(&mut self, function: usize) {
+        self.stack.push(DebugFrame {
+            function,
+            site: None,
+        });
+    }
+
+    pub fn on_leave(&mut self) {
+        self.stack.pop();
+    }
+
+    /// Returns true when execution must pause before `site`.
+    pub fn on_step(&mut self, site: usize) -> bool {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.site = Some(site);
+        }
+        let depth = self.stack.len();
+        let skipped = self
+            .info
+            .sites
+            .get(site)
+            .is_some_and(|s| self.skipped_files.contains(&s.span.file_id.0));
+        let stop = self.breakpoints.contains(&site)
+            || !skipped && match self.command {
+                StepCommand::Continue => false,
+                StepCommand::StepInto => true,
+                StepCommand::StepOver => depth <= self.anchor,
+                StepCommand::StepOut => depth < self.anchor,
+            };
+        if stop {
+            self.paused_at = Some(site);
+            let count = self.info.sites.get(site).map(|s| s.locals.len()).unwrap_or(0);
+            self.locals = vec![None; count];
+        }
+        stop
+    }
+
+    pub fn on_local(&mut self, slot: usize, value: DebugValue) {
+        if let Some(entry) = self.locals.get_mut(slot) {
+            *entry = Some(value);
+        }
+    }
+
+    pub fn paused_at(&self) -> Option<&DebugSite> {
+        self.paused_at.and_then(|site| self.info.sites.get(site))
+    }
+
+    /// Frames from the innermost outwards.
+    pub fn call_stack(&self) -> impl Iterator<Item = &DebugFrame> + '_ {
+        self.stack.iter().rev()
+    }
+
+    pub fn function(&self, index: usize) -> Option<&DebugFunction> {
+        self.info.functions.get(index)
+    }
+
+    /// Locals of the paused statement as `(name, rendered value)`.
+    pub fn locals(&self, memory: &[u8]) -> Vec<(String, String)> {
+        let Some(site) = self.paused_at() else {
+            return Vec::new();
+        };
+        let mut types = self.info.types.clone();
+        site.locals
+            .iter()
+            .zip(self.locals.iter())
+            .map(|(local, value)| {
+                let rendered = match value {
+                    Some(value) => format_value(&mut types, local.ty, *value, memory, 0),
+                    None => String::from("<unavailable>"),
+                };
+                (local.name.clone(), rendered)
+            })
+            .collect()
+    }
+}
+
+// ---------------------------------------------------------------------
+// Value decoding
+// ---------------------------------------------------------------------
+
+const MAX_DEPTH: usize = 4;
+const MAX_STR_BYTES: usize = 256;
+
+enum Aggregate {
+    Struct {
+        name: String,
+        fields: Vec<(String, TypeId)>,
+    },
+    Tuple(Vec<TypeId>),
+    Enum {
+        name: String,
+        variants: Vec<(String, Option<TypeId>)>,
+    },
+}
+
+/// Fields of a struct/tuple/enum type with generic arguments substituted.
+fn aggregate_shape(types: &mut TypeCtx, ty: TypeId) -> Option<Aggregate> {
+    let ty = types.resolve_id(ty);
+    let (base, args) = match types.get(ty) {
+        TypeKind::Apply { base, args } => (types.resolve_id(base), args),
+        _ => (ty, Vec::new()),
+    };
+    let kind = types.get(base);
+    let type_params = match &kind {
+        TypeKind::Struct { type_params, .. } | TypeKind::Enum { type_params, .. } => {
+            type_params.clone()
+        }
+        _ => Vec::new(),
+    };
+    let mapping: BTreeMap<TypeId, TypeId> = type_params.into_iter().zip(args).collect();
+    match kind {
+        TypeKind::Struct {
+            name,
+            fields,
+            field_names,
+            ..
+        } => Some(Aggregate::Struct {
+            name,
+            fields: field_names
+                .into_iter()
+                .zip(fields)
+                .map(|(n, f)| (n, types.substitute(f, &mapping)))
+                .collect(),
+        }),
+        TypeKind::Tuple { items } => Some(Aggregate::Tuple(items)),
+        TypeKind::Enum { name, variants, .. } => Some(Aggregate::Enum {
+            name,
+            variants: variants
+                .into_iter()
+                .map(|v| (v.name, v.payload.map(|p| types.substitute(p, &mapping))))
+                .collect(),
+        }),
+        _ => None,
+    }
+}
+
+fn read_bytes(memory: &[u8], addr: u32, len: usize) -> Option<&[u8]> {
+    let start = addr as usize;
+    memory.get(start..start.checked_add(len)?)
+}
+
+fn read_value(memory: &[u8], addr: u32, vt: Option<ValType>, byte: bool) -> Option<DebugValue> {
+    let word = |n: usize| read_bytes(memory, addr, n);
+    Some(match vt? {
+        ValType::I32 if byte => DebugValue::I32(word(1)?[0] as i32),
+        ValType::I32 => DebugValue::I32(i32::from_le_bytes(word(4)?.try_into().ok()?)),
+        ValType::F32 => DebugValue::F32(f32::from_le_bytes(word(4)?.try_into().ok()?)),
+        ValType::I64 => DebugValue::I64(i64::from_le_bytes(word(8)?.try_into().ok()?)),
+        ValType::F64 => DebugValue::F64(f64::from_le_bytes(word(8)?.try_into().ok()?)),
+        _ => return None,
+    })
+}
+
+/// Renders a field stored at `addr`: aggregates live inline, scalars by value.
+fn format_stored(types: &mut TypeCtx, ty: TypeId, addr: u32, memory: &[u8], depth: usize) -> String {
+    if is_aggregate_storage_type(types, ty) {
+        return format_value(types, ty, DebugValue::I32(addr as i32), memory, depth);
+    }
+    let kind = types.get(types.resolve_id(ty));
+    match read_value(memory, addr, valtype(&kind), matches!(kind, TypeKind::U8)) {
+        Some(value) => format_value(types, ty, value, memory, depth),
+        None if valtype(&kind).is_none() => String::from("()"),
+        None => format!("<invalid address 0x{:x}>", addr),
+    }
+}
+
+/// Renders `value` of type `ty`; pointers are followed into `memory`.
+pub fn format_value(
+    types: &mut TypeCtx,
+    ty: TypeId,
+    value: DebugValue,
+    memory: &[u8],
+    depth: usize,
+) -> String {
+    let ty = types.resolve_id(ty);
+    let ptr = match value {
+        DebugValue::I32(v) => v as u32,
+        DebugValue::I64(v) => return format!("{}", v),
+        DebugValue::F32(v) => return format!("{}", v),
+        DebugValue::F64(v) => return format!("{}", v),
+    };
+    match types.get(ty) {
+        TypeKind::Unit | TypeKind::Never => return String::from("()"),
+        TypeKind::I32 | TypeKind::Named(_) => return format!("{}", ptr as i32),
+        TypeKind::U8 => return format!("{}", ptr & 0xff),
+        TypeKind::Bool => return String::from(if ptr != 0 { "true" } else { "false" }),
+        TypeKind::Str => {
+            let Some(len) = read_bytes(memory, ptr, 4) else {
+                return format!("<invalid str 0x{:x}>", ptr);
+            };
+            let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+            let shown = len.min(MAX_STR_BYTES);
+            return match read_bytes(memory, ptr + 4, shown) {
+                Some(bytes) => {
+                    let text = String::from_utf8_lossy(bytes);
+                    let ellipsis = if shown < len { "..." } else { "" };
+                    format!("{:?}{}", text, ellipsis)
+                }
+                None => format!("<invalid str 0x{:x}>", ptr),
+            };
+        }
+        TypeKind::Reference(_, is_mut) => {
+            return format!("&{}0x{:x}", if is_mut { "mut " } else { "" }, ptr)
+        }
+        TypeKind::Box(_) => return format!("box 0x{:x}", ptr),
+        TypeKind::Function { .. } => return format!("<fn #{}>", ptr),
+        _ => {}
+    }
+    if depth >= MAX_DEPTH {
+        return String::from("...");
+    }
+    match aggregate_shape(types, ty) {
+        Some(Aggregate::Struct { name, fields }) => {
+            let mut offset = 0;
+            let mut parts = Vec::new();
+            for (field, field_ty) in fields {
+                let rendered = format_stored(types, field_ty, ptr + offset, memory, depth + 1);
+                parts.push(format!("{}: {}", field, rendered));
+                offset += type_storage_size_bytes(types, field_ty);
+            }
+            format!("{} {{ {} }}", name, parts.join(", "))
+        }
+        Some(Aggregate::Tuple(items)) => {
+            let mut offset = 0;
+            let mut parts = Vec::new();
+            for item in items {
+                parts.push(format_stored(types, item, ptr + offset, memory, depth + 1));
+                offset += type_storage_size_bytes(types, item);
+            }
+            format!("({})", parts.join(", "))
+        }
+        Some(Aggregate::Enum { name, variants }) => {
+            let Some(tag) = read_value(memory, ptr, Some(ValType::I32), false) else {
+                return format!("<invalid {} 0x{:x}>", name, ptr);
+            };
+            let DebugValue::I32(tag) = tag else {
+                unreachable!()
+            };
+            let Some((variant, payload)) = variants.get(tag as usize).cloned() else {
+                return format!("<{} with tag {}>", name, tag);
+            };
+            let Some(payload) = payload else {
+                return format!("{}::{}", name, variant);
+            };
+            let vt = valtype(&types.get(types.resolve_id(payload)));
+            let offset = if matches!(vt, Some(ValType::I64 | ValType::F64)) { 8 } else { 4 };
+            let rendered = match read_value(memory, ptr + offset, vt, false) {
+                Some(value) => format_value(types, payload, value, memory, depth + 1),
+                None => String::from("()"),
+            };
+            format!("{}::{}({})", name, variant, rendered)
+        }
+        None => format!("0x{:x}", ptr),
+    }
+}
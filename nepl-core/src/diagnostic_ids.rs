@@ -17,6 +17,10 @@ pub enum DiagnosticId {
     LoaderFailure = 1003,
     /// open import が複数候補で曖昧。
     AmbiguousImport = 1101,
+    /// 名前解決で定義が見つからない。
+    ResolveUndefinedName = 1102,
+    /// 名前解決で同じスコープに型・定数の候補が複数ある。
+    ResolveAmbiguousName = 1103,
     /// 字句解析で未知のディレクティブ。
     LexerUnknownDirective = 1201,
     /// 字句解析で未知トークン。
@@ -219,6 +223,46 @@ pub enum DiagnosticId {
     TypeRawBodyTargetMismatch = 3095,
     /// trait capability 名が不正。
     TypeUnknownTraitCapability = 3096,
+    /// break / continue がループの外で使われた。
+    TypeJumpOutsideLoop = 3097,
+    /// break / continue のラベルに対応するループがない。
+    TypeUnknownLoopLabel = 3098,
+    /// return の引数の個数が不正。
+    TypeReturnArityMismatch = 3099,
+    /// 定数の初期化式をコンパイル時に評価できない。
+    TypeConstNotConstant = 3100,
+    /// static の型がスカラー型ではない。
+    TypeStaticTypeUnsupported = 3101,
+    /// 純粋な文脈で static mut を読み出した。
+    TypePureReadsMutableStatic = 3102,
+    /// コンパイル時評価が実行時エラー（ゼロ除算など）で失敗した。
+    TypeConstEvalFailed = 3103,
+    /// コンパイル時評価が燃料（評価ステップ数の上限）を使い切った。
+    TypeConstEvalFuelExhausted = 3104,
+    /// for の対象が Iterator を実装していない。
+    TypeForTargetNotIterator = 3105,
+    /// 型エイリアスが循環している。
+    TypeAliasCycle = 3106,
+    /// 型エイリアスの型引数の個数が不正。
+    TypeAliasArityMismatch = 3107,
+    /// type / newtype の型パラメータに境界が書かれている。
+    TypeDefTypeParamBoundsUnsupported = 3108,
+    /// 参照が参照先の生存範囲より長く使われる。
+    TypeBorrowOutlivesReferent = 3109,
+    /// 参照を返す関数に、その参照の借用元になる参照引数がない。
+    TypeReturnedReferenceWithoutSource = 3110,
+    /// 参照引数が複数あり、戻り値の借用元を省略から決められない。
+    TypeAmbiguousReturnRegion = 3111,
+    /// 戻り値の region と異なる region の引数から借りた参照を返している。
+    TypeReturnedReferenceFromWrongParam = 3112,
+    /// 宣言されていない region が使われている。
+    TypeUnknownRegion = 3113,
+    /// `try` の対象が Result / Option ではない。
+    TypeTryOperandNotFallible = 3114,
+    /// `try` を使う関数の戻り値が対象と同じ種類の Result / Option ではない。
+    TypeTryReturnTypeMismatch = 3115,
+    /// `try` で返すエラー型を関数のエラー型へ変換する `From` impl がない。
+    TypeTryErrorNotConvertible = 3116,
     /// WASM backend が extern シグネチャを lower できない。
     CodegenWasmUnsupportedExternSignature = 4001,
     /// WASM backend が関数シグネチャを lower できない。
@@ -264,6 +308,8 @@ impl DiagnosticId {
             1002 => Some(DiagnosticId::UnknownTargetDirective),
             1003 => Some(DiagnosticId::LoaderFailure),
             1101 => Some(DiagnosticId::AmbiguousImport),
+            1102 => Some(DiagnosticId::ResolveUndefinedName),
+            1103 => Some(DiagnosticId::ResolveAmbiguousName),
             1201 => Some(DiagnosticId::LexerUnknownDirective),
             1202 => Some(DiagnosticId::LexerUnknownToken),
             1203 => Some(DiagnosticId::LexerIndentTabsNotAllowed),
@@ -365,6 +411,26 @@ impl DiagnosticId {
             3094 => Some(DiagnosticId::TypeMultipleActiveRawBodies),
             3095 => Some(DiagnosticId::TypeRawBodyTargetMismatch),
             3096 => Some(DiagnosticId::TypeUnknownTraitCapability),
+            3097 => Some(DiagnosticId::TypeJumpOutsideLoop),
+            3098 => Some(DiagnosticId::TypeUnknownLoopLabel),
+            3099 => Some(DiagnosticId::TypeReturnArityMismatch),
+            3100 => Some(DiagnosticId::TypeConstNotConstant),
+            3101 => Some(DiagnosticId::TypeStaticTypeUnsupported),
+            3102 => Some(DiagnosticId::TypePureReadsMutableStatic),
+            3103 => Some(DiagnosticId::TypeConstEvalFailed),
+            3104 => Some(DiagnosticId::TypeConstEvalFuelExhausted),
+            3105 => Some(DiagnosticId::TypeForTargetNotIterator),
+            3106 => Some(DiagnosticId::TypeAliasCycle),
+            3107 => Some(DiagnosticId::TypeAliasArityMismatch),
+            3108 => Some(DiagnosticId::TypeDefTypeParamBoundsUnsupported),
+            3109 => Some(DiagnosticId::TypeBorrowOutlivesReferent),
+            3110 => Some(DiagnosticId::TypeReturnedReferenceWithoutSource),
+            3111 => Some(DiagnosticId::TypeAmbiguousReturnRegion),
+            3112 => Some(DiagnosticId::TypeReturnedReferenceFromWrongParam),
+            3113 => Some(DiagnosticId::TypeUnknownRegion),
+            3114 => Some(DiagnosticId::TypeTryOperandNotFallible),
+            3115 => Some(DiagnosticId::TypeTryReturnTypeMismatch),
+            3116 => Some(DiagnosticId::TypeTryErrorNotConvertible),
             4001 => Some(DiagnosticId::CodegenWasmUnsupportedExternSignature),
             4002 => Some(DiagnosticId::CodegenWasmUnsupportedFunctionSignature),
             4003 => Some(DiagnosticId::CodegenWasmMissingReturnValue),
@@ -391,6 +457,8 @@ impl DiagnosticId {
             DiagnosticId::UnknownTargetDirective => "unknown target in #target",
             DiagnosticId::LoaderFailure => "loader error",
             DiagnosticId::AmbiguousImport => "ambiguous import",
+            DiagnosticId::ResolveUndefinedName => "undefined name",
+            DiagnosticId::ResolveAmbiguousName => "ambiguous name",
             DiagnosticId::LexerUnknownDirective => "unknown directive",
             DiagnosticId::LexerUnknownToken => "unknown token",
             DiagnosticId::LexerIndentTabsNotAllowed => {
@@ -582,6 +650,36 @@ impl DiagnosticId {
                 "raw body does not match the active target"
             }
             DiagnosticId::TypeUnknownTraitCapability => "unknown trait capability",
+            DiagnosticId::TypeJumpOutsideLoop => "break or continue outside of a loop",
+            DiagnosticId::TypeUnknownLoopLabel => "unknown loop label",
+            DiagnosticId::TypeReturnArityMismatch => "return expects one argument",
+            DiagnosticId::TypeConstNotConstant => "initializer is not a compile-time constant",
+            DiagnosticId::TypeStaticTypeUnsupported => "static items must have a scalar type",
+            DiagnosticId::TypePureReadsMutableStatic => "pure context cannot read a mutable static",
+            DiagnosticId::TypeConstEvalFailed => "compile-time evaluation failed",
+            DiagnosticId::TypeConstEvalFuelExhausted => "compile-time evaluation ran out of fuel",
+            DiagnosticId::TypeForTargetNotIterator => "for target does not implement Iterator",
+            DiagnosticId::TypeAliasCycle => "type alias refers to itself",
+            DiagnosticId::TypeAliasArityMismatch => "wrong number of type arguments for type alias",
+            DiagnosticId::TypeDefTypeParamBoundsUnsupported => {
+                "type and newtype parameters cannot have bounds"
+            }
+            DiagnosticId::TypeBorrowOutlivesReferent => "borrowed value does not live long enough",
+            DiagnosticId::TypeReturnedReferenceWithoutSource => {
+                "returned reference has no parameter to borrow from"
+            }
+            DiagnosticId::TypeAmbiguousReturnRegion => {
+                "cannot infer which parameter the returned reference borrows from"
+            }
+            DiagnosticId::TypeReturnedReferenceFromWrongParam => {
+                "returned reference borrows from a parameter outside its region"
+            }
+            DiagnosticId::TypeUnknownRegion => "use of undeclared region",
+            DiagnosticId::TypeTryOperandNotFallible => "try expects a Result or Option",
+            DiagnosticId::TypeTryReturnTypeMismatch => {
+                "try needs the function to return the same kind of Result or Option"
+            }
+            DiagnosticId::TypeTryErrorNotConvertible => "no From impl converts the error type",
             DiagnosticId::CodegenWasmUnsupportedExternSignature => {
                 "unsupported extern signature for wasm"
             }
@@ -19,6 +19,32 @@ pub struct HirModule {
     pub string_literals: Vec<String>,
     pub traits: Vec<HirTrait>,
     pub impls: Vec<HirImpl>,
+    pub globals: Vec<HirGlobal>,
+    /// Data blobs of constants evaluated at compile time, addressed by `ConstData`.
+    pub const_data: Vec<Vec<u8>>,
+    /// Non-generic `pub` functions of the entry file. Only retained and exported
+    /// when bindings are requested; otherwise cleared before monomorphization.
+    pub exports: Vec<HirExport>,
+}
+
+/// A function exported from the wasm module under its source-level name.
+#[derive(Debug, Clone)]
+pub struct HirExport {
+    pub name: String,
+    pub symbol: String,
+}
+
+/// A `static` item. Constants never reach HIR as globals; their folded value is
+/// inlined at every use during type checking.
+#[derive(Debug, Clone)]
+pub struct HirGlobal {
+    pub doc: Option<String>,
+    pub name: String,
+    pub ty: TypeId,
+    pub mutable: bool,
+    /// Initial value, already folded to a literal.
+    pub init: HirExpr,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -28,6 +54,8 @@ pub struct HirFunction {
     pub func_ty: TypeId, // new
     pub params: Vec<HirParam>,
     pub result: TypeId,
+    /// Parameters the result may borrow from, when the result can hold a reference.
+    pub result_borrows: Option<Vec<usize>>,
     pub effect: Effect,
     pub body: HirBody,
     pub span: Span,
@@ -78,12 +106,39 @@ pub struct HirExpr {
     pub span: Span,
 }
 
+impl HirExpr {
+    /// Returns true when control never continues past this expression:
+    /// a jump, a block ending in one, or an `if`/`match` whose every path jumps.
+    pub fn diverges(&self) -> bool {
+        match &self.kind {
+            HirExprKind::Return(_) | HirExprKind::Break { .. } | HirExprKind::Continue { .. } => {
+                true
+            }
+            HirExprKind::Block(block) => block.lines.iter().any(|line| line.expr.diverges()),
+            HirExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => cond.diverges() || (then_branch.diverges() && else_branch.diverges()),
+            HirExprKind::Match { scrutinee, arms } => {
+                scrutinee.diverges() || (!arms.is_empty() && arms.iter().all(|arm| arm.body.diverges()))
+            }
+            HirExprKind::Let { value, .. }
+            | HirExprKind::Set { value, .. }
+            | HirExprKind::GlobalSet { value, .. } => value.diverges(),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum HirExprKind {
     LiteralI32(i32),
     LiteralF32(f32),
     LiteralBool(bool),
     LiteralStr(u32),
+    /// Address of `HirModule::const_data[id]` in linear memory.
+    ConstData(u32),
     Unit,
     Var(String),
     /// Explicit function-value reference created by `@fn_name`.
@@ -106,7 +161,18 @@ pub enum HirExprKind {
     While {
         cond: Box<HirExpr>,
         body: Box<HirExpr>,
+        label: Option<String>,
+    },
+    /// Leaves the innermost loop, or the loop named by `label`.
+    Break {
+        label: Option<String>,
     },
+    /// Jumps to the condition of the innermost loop, or the loop named by `label`.
+    Continue {
+        label: Option<String>,
+    },
+    /// Leaves the enclosing function with `value`.
+    Return(Box<HirExpr>),
     Match {
         scrutinee: Box<HirExpr>,
         arms: Vec<HirMatchArm>,
@@ -135,6 +201,13 @@ pub enum HirExprKind {
         name: String,
         value: Box<HirExpr>,
     },
+    /// Reads a `static` item.
+    GlobalGet(String),
+    /// Writes a `static mut` item.
+    GlobalSet {
+        name: String,
+        value: Box<HirExpr>,
+    },
     Intrinsic {
         name: String,
         type_args: Vec<TypeId>,
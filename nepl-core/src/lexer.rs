@@ -35,6 +35,7 @@ pub enum TokenKind {
     At,            // @
     Dot,
     Ampersand, // &
+    Region(String), // 'a (reference region; stored without the quote)
     Star,      // *
     Minus,     // -
     Equals,    // =
@@ -55,6 +56,10 @@ pub enum TokenKind {
     KwSet,
     KwIf,
     KwWhile,
+    KwBreak,
+    KwContinue,
+    KwReturn,
+    KwTry,
     KwCond,
     KwThen,
     KwElse,
@@ -66,6 +71,10 @@ pub enum TokenKind {
     KwImpl,
     KwFor,
     KwPub,
+    KwConst,
+    KwStatic,
+    KwType,
+    KwNewtype,
     KwBlock,
     KwTuple,
     KwMlstr,
@@ -89,6 +98,8 @@ pub enum TokenKind {
         signature: String,
     },
     DirIntrinsic,
+    /// `#comptime`: evaluates the following expression at compile time.
+    DirComptime,
     DirPrelude(String),
     DirNoPrelude,
 
@@ -678,6 +689,8 @@ impl LexState {
             // Lex the rest of the line as regular tokens (args)
             let rest_start = line_offset + 10; // length of "#intrinsic"
             self.lex_regular(rest, rest_start);
+        } else if body.starts_with("comptime") {
+            self.lex_regular(text, line_offset);
         } else {
             let span = Span::new(
                 self.file_id,
@@ -713,6 +726,15 @@ impl LexState {
                     self.push_token(TokenKind::Ampersand, offset + i, offset + i + 1);
                     i += 1;
                 }
+                b'\'' if bytes.get(i + 1).is_some_and(|b| is_ident_start(*b)) => {
+                    let start = i;
+                    i += 1;
+                    while i < bytes.len() && is_ident_continue(bytes[i]) {
+                        i += 1;
+                    }
+                    let name = text[start + 1..i].to_string();
+                    self.push_token(TokenKind::Region(name), offset + start, offset + i);
+                }
                 b',' => {
                     self.push_token(TokenKind::Comma, offset + i, offset + i + 1);
                     i += 1;
@@ -743,6 +765,18 @@ impl LexState {
                     self.push_token(TokenKind::At, offset + i, offset + i + 1);
                     i += 1;
                 }
+                b'#' => {
+                    let word_end = i + 1 + "comptime".len();
+                    if text[i + 1..].starts_with("comptime")
+                        && !bytes.get(word_end).is_some_and(|b| is_ident_continue(*b))
+                    {
+                        self.push_token(TokenKind::DirComptime, offset + i, offset + word_end);
+                        i = word_end;
+                    } else {
+                        self.unknown(offset + i, offset + i + 1);
+                        i += 1;
+                    }
+                }
                 b'<' => {
                     self.push_token(TokenKind::LAngle, offset + i, offset + i + 1);
                     i += 1;
@@ -1006,6 +1040,10 @@ fn keyword_token(lexeme: &str) -> Option<TokenKind> {
         "set" => Some(TokenKind::KwSet),
         "if" => Some(TokenKind::KwIf),
         "while" => Some(TokenKind::KwWhile),
+        "break" => Some(TokenKind::KwBreak),
+        "continue" => Some(TokenKind::KwContinue),
+        "return" => Some(TokenKind::KwReturn),
+        "try" => Some(TokenKind::KwTry),
         "cond" => Some(TokenKind::KwCond),
         "then" => Some(TokenKind::KwThen),
         "else" => Some(TokenKind::KwElse),
@@ -1017,6 +1055,10 @@ fn keyword_token(lexeme: &str) -> Option<TokenKind> {
         "impl" => Some(TokenKind::KwImpl),
         "for" => Some(TokenKind::KwFor),
         "pub" => Some(TokenKind::KwPub),
+        "const" => Some(TokenKind::KwConst),
+        "static" => Some(TokenKind::KwStatic),
+        "type" => Some(TokenKind::KwType),
+        "newtype" => Some(TokenKind::KwNewtype),
         "block" => Some(TokenKind::KwBlock),
         "Tuple" => Some(TokenKind::KwTuple),
         "mlstr" => Some(TokenKind::KwMlstr),
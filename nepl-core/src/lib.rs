@@ -20,19 +20,24 @@ pub mod error;
 pub mod span;
 
 pub mod ast;
+pub mod bindings;
 pub mod builtins;
 pub mod codegen_llvm;
 pub mod codegen_wasm;
 pub mod compiler;
+pub mod const_eval;
+pub mod debugger;
 pub mod hir;
 pub mod lexer;
 pub mod loader;
 pub mod log;
 pub mod monomorphize;
+pub mod name_resolve;
 pub mod module_graph;
 pub mod nm;
 pub mod parser;
 pub mod passes;
+pub mod profiler;
 pub mod resolve;
 pub mod wasm_shared;
 pub mod runtime_helpers;
@@ -41,7 +46,9 @@ pub mod typecheck;
 pub mod types;
 
 pub use compiler::{
-    compile_module, compile_module_with_source_map, compile_wasm, BuildProfile,
+    compile_module, compile_module_for_debugging, compile_module_for_profiling,
+    compile_module_with_bindings, compile_module_with_source_map, compile_wasm,
+    BuildProfile,
     CompilationArtifact, CompileOptions, CompileTarget,
 };
 pub use error::CoreError;
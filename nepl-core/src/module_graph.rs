@@ -72,6 +72,8 @@ pub enum ExportKind {
     Function,
     Struct,
     Enum,
+    TypeAlias,
+    Newtype,
 }
 
 #[derive(Debug, Clone)]
@@ -186,6 +188,13 @@ impl ModuleGraphBuilder {
                             node.id,
                         )?;
                     }
+                    crate::ast::Stmt::TypeDef(t) if t.vis == crate::ast::Visibility::Pub => {
+                        let kind = match t.kind {
+                            crate::ast::TypeDefKind::Alias => ExportKind::TypeAlias,
+                            crate::ast::TypeDefKind::Newtype => ExportKind::Newtype,
+                        };
+                        Self::insert_export(&mut exports, &t.name.name, kind, node.id)?;
+                    }
                     _ => {}
                 }
             }
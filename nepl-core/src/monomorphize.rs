@@ -5,6 +5,7 @@ extern crate std;
 use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::format;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
 
 use crate::hir::*;
@@ -24,9 +25,22 @@ fn monomorphize_internal(
 ) -> (HirModule, Vec<String>) {
     let mut impl_map: BTreeMap<(String, String, TypeId), String> = BTreeMap::new();
     let mut impl_entries: Vec<(String, Vec<TypeId>, String, TypeId, String)> = Vec::new();
+    let mut generic_impls: Vec<GenericImplMethod> = Vec::new();
     for imp in &module.impls {
         let ty = ctx.resolve_id(imp.target_ty);
         for m in &imp.methods {
+            if !imp.type_args.is_empty() {
+                if let Some(base) = &imp.trait_base_name {
+                    generic_impls.push(GenericImplMethod {
+                        trait_base_name: base.clone(),
+                        trait_args: imp.trait_args.clone(),
+                        type_params: imp.type_args.clone(),
+                        target_ty: ty,
+                        method: m.name.clone(),
+                        func_name: m.func.name.clone(),
+                    });
+                }
+            }
             impl_map.insert(
                 (imp.trait_name.clone(), m.name.clone(), ty),
                 m.func.name.clone(),
@@ -50,6 +64,7 @@ fn monomorphize_internal(
         queued: BTreeSet::new(),
         impl_map,
         impl_entries,
+        generic_impls,
     };
 
     for f in module.functions {
@@ -77,6 +92,12 @@ fn monomorphize_internal(
         }
     }
 
+    for export in &module.exports {
+        if !initial.contains(&export.symbol) {
+            initial.push(export.symbol.clone());
+        }
+    }
+
     // Ensure runtime-required helpers are retained even if not explicitly referenced.
     // Enum/struct/tuple codegen depends on allocator helper availability.
     for kind in [
@@ -132,6 +153,9 @@ fn monomorphize_internal(
             string_literals: module.string_literals,
             traits: module.traits,
             impls: module.impls,
+            globals: module.globals,
+            const_data: module.const_data,
+            exports: module.exports,
         },
         unresolved_trait_calls,
     )
@@ -145,6 +169,17 @@ struct Monomorphizer<'a> {
     queued: BTreeSet<String>,
     impl_map: BTreeMap<(String, String, TypeId), String>,
     impl_entries: Vec<(String, Vec<TypeId>, String, TypeId, String)>,
+    generic_impls: Vec<GenericImplMethod>,
+}
+
+/// A method of an `impl<...>` block whose target type still has type parameters.
+struct GenericImplMethod {
+    trait_base_name: String,
+    trait_args: Vec<TypeId>,
+    type_params: Vec<TypeId>,
+    target_ty: TypeId,
+    method: String,
+    func_name: String,
 }
 
 impl<'a> Monomorphizer<'a> {
@@ -248,7 +283,7 @@ impl<'a> Monomorphizer<'a> {
                     walk_expr(ctx, func_name, then_branch, out);
                     walk_expr(ctx, func_name, else_branch, out);
                 }
-                HirExprKind::While { cond, body } => {
+                HirExprKind::While { cond, body, .. } => {
                     walk_expr(ctx, func_name, cond, out);
                     walk_expr(ctx, func_name, body, out);
                 }
@@ -261,8 +296,10 @@ impl<'a> Monomorphizer<'a> {
                 HirExprKind::Block(block) => walk_block(ctx, func_name, block, out),
                 HirExprKind::Let { value, .. }
                 | HirExprKind::Set { value, .. }
+                | HirExprKind::GlobalSet { value, .. }
                 | HirExprKind::AddrOf(value)
-                | HirExprKind::Deref(value) => walk_expr(ctx, func_name, value, out),
+                | HirExprKind::Deref(value)
+                | HirExprKind::Return(value) => walk_expr(ctx, func_name, value, out),
                 HirExprKind::TupleConstruct { items }
                 | HirExprKind::Intrinsic { args: items, .. } => {
                     for item in items {
@@ -286,7 +323,11 @@ impl<'a> Monomorphizer<'a> {
                 | HirExprKind::LiteralF32(_)
                 | HirExprKind::LiteralBool(_)
                 | HirExprKind::LiteralStr(_)
-                | HirExprKind::Drop { .. } => {}
+                | HirExprKind::ConstData(_)
+                | HirExprKind::Drop { .. }
+                | HirExprKind::Break { .. }
+                | HirExprKind::Continue { .. }
+                | HirExprKind::GlobalGet(_) => {}
             }
         }
         fn walk_block(ctx: &TypeCtx, func_name: &str, block: &HirBlock, out: &mut Vec<String>) {
@@ -357,7 +398,7 @@ impl<'a> Monomorphizer<'a> {
                         _ => resolved,
                     };
                     *self_ty = dispatch_self_ty;
-                    if let Some(name) =
+                    if let Some((name, inst_args)) =
                         self.resolve_trait_impl_name(
                             trait_name.as_str(),
                             trait_args,
@@ -366,7 +407,7 @@ impl<'a> Monomorphizer<'a> {
                         )
                     {
                         *callee = FuncRef::User(
-                            self.request_instantiation(name, trait_args.clone()),
+                            self.request_instantiation(name, inst_args),
                             Vec::new(),
                         );
                     }
@@ -387,7 +428,7 @@ impl<'a> Monomorphizer<'a> {
                 self.resolve_trait_calls_in_expr(then_branch);
                 self.resolve_trait_calls_in_expr(else_branch);
             }
-            HirExprKind::While { cond, body } => {
+            HirExprKind::While { cond, body, .. } => {
                 self.resolve_trait_calls_in_expr(cond);
                 self.resolve_trait_calls_in_expr(body);
             }
@@ -400,8 +441,10 @@ impl<'a> Monomorphizer<'a> {
             HirExprKind::Block(block) => self.resolve_trait_calls_in_block(block),
             HirExprKind::Let { value, .. }
             | HirExprKind::Set { value, .. }
+            | HirExprKind::GlobalSet { value, .. }
             | HirExprKind::AddrOf(value)
-            | HirExprKind::Deref(value) => self.resolve_trait_calls_in_expr(value),
+            | HirExprKind::Deref(value)
+            | HirExprKind::Return(value) => self.resolve_trait_calls_in_expr(value),
             HirExprKind::TupleConstruct { items }
             | HirExprKind::Intrinsic { args: items, .. } => {
                 for item in items {
@@ -425,7 +468,11 @@ impl<'a> Monomorphizer<'a> {
             | HirExprKind::LiteralF32(_)
             | HirExprKind::LiteralBool(_)
             | HirExprKind::LiteralStr(_)
-            | HirExprKind::Drop { .. } => {}
+            | HirExprKind::ConstData(_)
+            | HirExprKind::Drop { .. }
+            | HirExprKind::Break { .. }
+            | HirExprKind::Continue { .. }
+            | HirExprKind::GlobalGet(_) => {}
         }
     }
 
@@ -435,17 +482,17 @@ impl<'a> Monomorphizer<'a> {
         trait_args: &[TypeId],
         method: &str,
         resolved_self_ty: TypeId,
-    ) -> Option<String> {
+    ) -> Option<(String, Vec<TypeId>)> {
         let key = (String::from(trait_name), String::from(method), resolved_self_ty);
         if let Some(name) = self.impl_map.get(&key) {
-            return Some(name.clone());
+            return Some((name.clone(), trait_args.to_vec()));
         }
         for ((tr, meth, target_ty), func_name) in self.impl_map.iter() {
             if tr != trait_name || meth != method {
                 continue;
             }
             if self.ctx.same_type(resolved_self_ty, *target_ty) {
-                return Some(func_name.clone());
+                return Some((func_name.clone(), trait_args.to_vec()));
             }
         }
         for (base, impl_trait_args, meth, target_ty, func_name) in self.impl_entries.iter() {
@@ -470,9 +517,39 @@ impl<'a> Monomorphizer<'a> {
                 }
             }
             if matched {
-                return Some(func_name.clone());
+                return Some((func_name.clone(), trait_args.to_vec()));
             }
         }
+        // Generic impls: bind the impl's type parameters by matching its target
+        // type and trait arguments against the call site.
+        for entry in self.generic_impls.iter() {
+            if entry.trait_base_name != trait_name
+                || entry.method != method
+                || entry.trait_args.len() != trait_args.len()
+            {
+                continue;
+            }
+            let mut pairs = vec![(entry.target_ty, resolved_self_ty)];
+            pairs.extend(
+                entry
+                    .trait_args
+                    .iter()
+                    .copied()
+                    .zip(trait_args.iter().map(|arg| self.ctx.resolve_id(*arg))),
+            );
+            let Some(bindings) = self.ctx.type_pattern_bindings(&pairs) else {
+                continue;
+            };
+            let inst_args = entry
+                .type_params
+                .iter()
+                .map(|tp| {
+                    let tp = self.ctx.resolve_id(*tp);
+                    bindings.get(&tp).copied().unwrap_or(tp)
+                })
+                .collect();
+            return Some((entry.func_name.clone(), inst_args));
+        }
         None
     }
 
@@ -659,7 +736,9 @@ impl<'a> Monomorphizer<'a> {
             | HirExprKind::LiteralI32(_)
             | HirExprKind::LiteralF32(_)
             | HirExprKind::LiteralBool(_)
-            | HirExprKind::LiteralStr(_) => {}
+            | HirExprKind::LiteralStr(_)
+            | HirExprKind::ConstData(_)
+            | HirExprKind::GlobalGet(_) => {}
             HirExprKind::Var(name) => {
                 if local_names.contains(name) {
                     return;
@@ -710,7 +789,7 @@ impl<'a> Monomorphizer<'a> {
                             _ => resolved,
                         };
                         *self_ty = dispatch_self_ty;
-                        if let Some(func_name) =
+                        if let Some((func_name, inst_args)) =
                             self.resolve_trait_impl_name(
                                 trait_name.as_str(),
                                 trait_args,
@@ -718,7 +797,7 @@ impl<'a> Monomorphizer<'a> {
                                 dispatch_self_ty,
                             )
                         {
-                            let inst = self.request_instantiation(func_name, trait_args.clone());
+                            let inst = self.request_instantiation(func_name, inst_args);
                             *callee = FuncRef::User(inst, Vec::new());
                         }
                     }
@@ -749,7 +828,7 @@ impl<'a> Monomorphizer<'a> {
                 self.substitute_expr(then_branch, mapping, local_names);
                 self.substitute_expr(else_branch, mapping, local_names);
             }
-            HirExprKind::While { cond, body } => {
+            HirExprKind::While { cond, body, .. } => {
                 self.substitute_expr(cond, mapping, local_names);
                 self.substitute_expr(body, mapping, local_names);
             }
@@ -789,10 +868,15 @@ impl<'a> Monomorphizer<'a> {
             }
             HirExprKind::Block(b) => self.substitute_block(b, mapping, local_names),
             HirExprKind::Let { value, .. } => self.substitute_expr(value, mapping, local_names),
-            HirExprKind::Set { value, .. } => self.substitute_expr(value, mapping, local_names),
+            HirExprKind::Set { value, .. } | HirExprKind::GlobalSet { value, .. } => {
+                self.substitute_expr(value, mapping, local_names)
+            }
             HirExprKind::AddrOf(inner) => self.substitute_expr(inner, mapping, local_names),
             HirExprKind::Deref(inner) => self.substitute_expr(inner, mapping, local_names),
-            HirExprKind::Drop { .. } => {}
+            HirExprKind::Return(inner) => self.substitute_expr(inner, mapping, local_names),
+            HirExprKind::Drop { .. }
+            | HirExprKind::Break { .. }
+            | HirExprKind::Continue { .. } => {}
             HirExprKind::Intrinsic {
                 type_args,
                 args,
@@ -821,7 +905,7 @@ fn collect_local_names_in_expr(expr: &HirExpr, out: &mut BTreeSet<String>) {
             out.insert(name.clone());
             collect_local_names_in_expr(value, out);
         }
-        HirExprKind::Set { value, .. } => {
+        HirExprKind::Set { value, .. } | HirExprKind::GlobalSet { value, .. } => {
             collect_local_names_in_expr(value, out);
         }
         HirExprKind::Call { args, .. } => {
@@ -844,7 +928,7 @@ fn collect_local_names_in_expr(expr: &HirExpr, out: &mut BTreeSet<String>) {
             collect_local_names_in_expr(then_branch, out);
             collect_local_names_in_expr(else_branch, out);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             collect_local_names_in_expr(cond, out);
             collect_local_names_in_expr(body, out);
         }
@@ -880,7 +964,7 @@ fn collect_local_names_in_expr(expr: &HirExpr, out: &mut BTreeSet<String>) {
                 collect_local_names_in_expr(arg, out);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
             collect_local_names_in_expr(inner, out);
         }
         HirExprKind::Unit
@@ -888,8 +972,12 @@ fn collect_local_names_in_expr(expr: &HirExpr, out: &mut BTreeSet<String>) {
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
         | HirExprKind::Var(_)
         | HirExprKind::FnValue(_)
-        | HirExprKind::Drop { .. } => {}
+        | HirExprKind::Drop { .. }
+        | HirExprKind::Break { .. }
+        | HirExprKind::Continue { .. }
+        | HirExprKind::GlobalGet(_) => {}
     }
 }
@@ -1,55 +1,824 @@
-//! Name resolution for NEPL (no_std, skeleton).
-//!
-//! This module is responsible for resolving names across namespaces,
-//! includes, imports, uses, enums, and structs.
-//!
-//! 現段階ではまだ実際の解決ロジックは実装せず、
-//! インタフェースとデータ構造だけを定義する。
-
-#![allow(dead_code)]
-
-use alloc::string::String;
-use alloc::vec::Vec;
-
-use crate::ast;
-use crate::diagnostic::Diagnostic;
-
-/// Kinds of symbols that can appear in the program.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum SymbolKind {
-    Value,      // variables, functions, constants
-    Type,       // enums, structs, type aliases
-    Namespace,  // namespace itself
-    EnumVariant,
-    StructField,
-}
-
-/// A resolved symbol reference.
-///
-/// 後でシンボルテーブルのインデックスやファイル位置などを持たせることを想定。
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ResolvedSymbol {
-    pub name: String,
-    pub kind: SymbolKind,
-    // TODO: module path, declaration span, etc.
-}
-
-/// Result of name resolution on a single AST root expression.
-///
-/// 現時点では diagnostics だけを返し、
-/// 実際の解決結果はほとんど空のままにしてある。
-#[derive(Debug)]
-pub struct NameResolveResult {
-    pub diagnostics: Vec<Diagnostic>,
-    // TODO: symbol tables, mapping from AST id -> ResolvedSymbol, etc.
-}
-
-/// Perform name resolution on a single AST root expression.
-///
-/// 将来は複数ファイルを跨いで `include` / `import` を処理するが、
-/// ここではひとまず 1 ファイル単位のインタフェースを用意する。
-pub fn resolve_names(_root: &ast::Expr) -> NameResolveResult {
-    NameResolveResult {
-        diagnostics: Vec::new(),
-    }
-}
+//! Name resolution for NEPL.
+//!
+//! 構文解析の後・型検査の前に走る独立したパス。識別子・`::` パス・enum variant・
+//! struct field を [`DefId`] で表す定義へ結び付け、定義元モジュール（[`FileId`]）と
+//! span を持つ [`SymbolTable`] を作る。型検査・LSP・Web 解析器はこの表を共有する。
+//!
+//! スコープ規則は型検査と同じで、ブロック内の `fn` / 型定義 / 非 mut の `let` は
+//! 巻き上げられ、内側のスコープの定義ほど優先される。型情報は使わないため、
+//! `p.x` のように型が決まらないと一意にならない参照は候補の列として残す。
+
+extern crate std;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use std::path::Path;
+
+use crate::ast::{
+    Block, Directive, FnBody, FnDef, GlobalKind, Ident, ImportClause, MatchArm, Module, PrefixExpr,
+    PrefixItem, Stmt, Symbol, TypeDefKind,
+};
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic_ids::DiagnosticId;
+use crate::loader::SourceMap;
+use crate::span::{FileId, Span};
+
+/// Stable index of a definition inside a [`SymbolTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DefId(pub u32);
+
+/// Namespaces a definition can live in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Value, // variables, functions, constants
+    Type,  // enums, structs, type aliases, traits
+    EnumVariant,
+    StructField,
+}
+
+/// What a definition introduces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Function,
+    FnAlias,
+    Extern,
+    Const,
+    Static,
+    Param,
+    /// 非 mut の `let`。ブロック先頭へ巻き上げられる。
+    Let,
+    LetMut,
+    MatchBind,
+    ForBind,
+    Struct,
+    StructField,
+    Enum,
+    EnumVariant,
+    TypeAlias,
+    Newtype,
+    Trait,
+    TraitMethod,
+}
+
+impl DefKind {
+    /// エディタ向け解析結果で使う短い名前を返します。
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            DefKind::Function => "fn",
+            DefKind::FnAlias => "fn_alias",
+            DefKind::Extern => "extern",
+            DefKind::Const => "const",
+            DefKind::Static => "static",
+            DefKind::Param => "param",
+            DefKind::Let => "let_hoisted",
+            DefKind::LetMut => "let_mut",
+            DefKind::MatchBind => "match_bind",
+            DefKind::ForBind => "for_bind",
+            DefKind::Struct => "struct",
+            DefKind::StructField => "field",
+            DefKind::Enum => "enum",
+            DefKind::EnumVariant => "variant",
+            DefKind::TypeAlias => "type",
+            DefKind::Newtype => "newtype",
+            DefKind::Trait => "trait",
+            DefKind::TraitMethod => "trait_method",
+        }
+    }
+
+    pub const fn symbol_kind(self) -> SymbolKind {
+        match self {
+            DefKind::Struct
+            | DefKind::Enum
+            | DefKind::TypeAlias
+            | DefKind::Newtype
+            | DefKind::Trait => SymbolKind::Type,
+            DefKind::EnumVariant => SymbolKind::EnumVariant,
+            DefKind::StructField => SymbolKind::StructField,
+            _ => SymbolKind::Value,
+        }
+    }
+
+    /// 同じスコープに複数あると曖昧になる種類か（関数は overload できる）。
+    fn is_unique_item(self) -> bool {
+        matches!(
+            self,
+            DefKind::Struct
+                | DefKind::Enum
+                | DefKind::TypeAlias
+                | DefKind::Newtype
+                | DefKind::Trait
+                | DefKind::Const
+                | DefKind::Static
+        )
+    }
+}
+
+/// A definition site.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    pub id: DefId,
+    pub name: String,
+    pub kind: DefKind,
+    pub span: Span,
+    /// 定義が書かれたファイル。
+    pub module: FileId,
+    pub scope_depth: usize,
+    /// field / variant / trait method を持つ struct / enum / trait。
+    pub parent: Option<DefId>,
+    pub doc: Option<String>,
+}
+
+/// A use of a name and what it resolved to.
+///
+/// `candidates` は近いスコープ順の候補で、`resolved` はそのうち選ばれたもの。
+/// overload や型の分からない field では候補が複数残る。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub scope_depth: usize,
+    pub resolved: Option<DefId>,
+    pub candidates: Vec<DefId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowKind {
+    /// 新しい定義が外側（または同じスコープ）の定義を隠した。
+    Definition,
+    /// 参照に複数の候補があり、最も近いものを選んだ。
+    Reference,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowEvent {
+    pub name: String,
+    pub kind: ShadowKind,
+    pub span: Span,
+    pub scope_depth: usize,
+    pub selected: Option<DefId>,
+    pub shadowed: Vec<DefId>,
+}
+
+/// `#import "path" as alias` の対応表: 宣言ファイル → alias → import 先ファイル。
+pub type ImportAliases = BTreeMap<u32, BTreeMap<String, BTreeSet<u32>>>;
+
+/// Every definition and reference of a module, indexed by [`DefId`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+    pub shadows: Vec<ShadowEvent>,
+    pub import_aliases: ImportAliases,
+}
+
+impl SymbolTable {
+    pub fn definition(&self, id: DefId) -> Option<&Definition> {
+        self.definitions.get(id.0 as usize)
+    }
+
+    /// `span` を含む参照のうち最も狭いものを返します。
+    ///
+    /// `Shape::Circle` は `Shape` 部分と全体の 2 つの参照を持つので、
+    /// トークン単位で引くと各部分の定義が得られる。
+    pub fn reference_at(&self, span: Span) -> Option<&Reference> {
+        self.references
+            .iter()
+            .filter(|r| {
+                r.span.file_id == span.file_id
+                    && r.span.start <= span.start
+                    && span.end <= r.span.end
+            })
+            .min_by_key(|r| r.span.len())
+    }
+}
+
+/// Result of name resolution on a loaded module.
+#[derive(Debug)]
+pub struct NameResolveResult {
+    pub symbols: SymbolTable,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Resolve every name of `module` (including items merged in by the loader).
+///
+/// 未定義名と曖昧な名前を診断として返す。型検査も同じ誤りを報告するため、
+/// コンパイルではこの診断を使わず、エディタ向け解析だけが表示する。
+pub fn resolve_names(module: &Module, source_map: Option<&SourceMap>) -> NameResolveResult {
+    let mut resolver = Resolver::new(build_import_aliases(module, source_map));
+
+    let mut seen = BTreeSet::new();
+    let directives = module
+        .directives
+        .iter()
+        .chain(module.root.items.iter().filter_map(|stmt| match stmt {
+            Stmt::Directive(d) => Some(d),
+            _ => None,
+        }))
+        .filter(|d| match d {
+            Directive::Entry { name } => seen.insert(span_key(name.span)),
+            Directive::Extern { span, .. } => seen.insert(span_key(*span)),
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    for directive in &directives {
+        if let Directive::Extern { func, .. } = directive {
+            resolver.define(func, DefKind::Extern, None, None);
+        }
+    }
+    resolver.resolve_block(&module.root, true);
+    for directive in &directives {
+        if let Directive::Entry { name } = directive {
+            resolver.resolve_plain(&name.name, name.span);
+        }
+    }
+
+    NameResolveResult {
+        symbols: resolver.table,
+        diagnostics: resolver.diagnostics,
+    }
+}
+
+fn span_key(span: Span) -> (u32, u32, u32) {
+    (span.file_id.0, span.start, span.end)
+}
+
+/// `if` のレイアウト形式で使う区切り語。名前ではない。
+fn is_layout_marker(name: &str) -> bool {
+    matches!(name, "cond" | "then" | "else" | "do" | "block")
+}
+
+struct Resolver {
+    table: SymbolTable,
+    diagnostics: Vec<Diagnostic>,
+    scopes: Vec<BTreeMap<String, Vec<DefId>>>,
+    /// struct field は値の名前空間に入らないので名前ごとに別に持つ。
+    fields: BTreeMap<String, Vec<DefId>>,
+    members: BTreeMap<DefId, Vec<DefId>>,
+}
+
+impl Resolver {
+    fn new(import_aliases: ImportAliases) -> Self {
+        Self {
+            table: SymbolTable {
+                import_aliases,
+                ..SymbolTable::default()
+            },
+            diagnostics: Vec::new(),
+            scopes: alloc::vec![BTreeMap::new()],
+            fields: BTreeMap::new(),
+            members: BTreeMap::new(),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.scopes.len() - 1
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(BTreeMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    fn def(&self, id: DefId) -> &Definition {
+        &self.table.definitions[id.0 as usize]
+    }
+
+    fn define(
+        &mut self,
+        name: &Ident,
+        kind: DefKind,
+        doc: Option<String>,
+        parent: Option<DefId>,
+    ) -> DefId {
+        // 複数の経路から import されたファイルの項目は、同じ span のまま重ねて合流する。
+        if let Some(existing) = self.scopes.last().and_then(|scope| scope.get(&name.name)) {
+            if let Some(id) = existing.iter().copied().find(|id| {
+                let def = self.def(*id);
+                def.span == name.span && def.kind == kind
+            }) {
+                return id;
+            }
+        }
+        let id = DefId(self.table.definitions.len() as u32);
+        let depth = self.depth();
+        self.table.definitions.push(Definition {
+            id,
+            name: name.name.clone(),
+            kind,
+            span: name.span,
+            module: name.span.file_id,
+            scope_depth: depth,
+            parent,
+            doc,
+        });
+        if let Some(parent) = parent {
+            self.members.entry(parent).or_default().push(id);
+        }
+        match kind {
+            DefKind::StructField => {
+                self.fields.entry(name.name.clone()).or_default().push(id);
+                return id;
+            }
+            DefKind::TraitMethod => return id,
+            _ => {}
+        }
+
+        let shadowed = self.lookup(&name.name);
+        if !shadowed.is_empty() {
+            self.table.shadows.push(ShadowEvent {
+                name: name.name.clone(),
+                kind: ShadowKind::Definition,
+                span: name.span,
+                scope_depth: depth,
+                selected: Some(id),
+                shadowed,
+            });
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.entry(name.name.clone()).or_default().push(id);
+        }
+        id
+    }
+
+    /// 近いスコープから順に、同じスコープ内では後の定義から順に候補を返す。
+    fn lookup(&self, name: &str) -> Vec<DefId> {
+        let mut out = Vec::new();
+        for scope in self.scopes.iter().rev() {
+            if let Some(ids) = scope.get(name) {
+                out.extend(ids.iter().rev().copied());
+            }
+        }
+        out
+    }
+
+    /// 巻き上げられた `let` は、参照より後ろにあれば同名の手前の定義を優先する。
+    fn select(&self, candidates: &[DefId], span: Span) -> Option<DefId> {
+        candidates
+            .iter()
+            .copied()
+            .find(|id| {
+                let def = self.def(*id);
+                !(def.kind == DefKind::Let
+                    && def.span.file_id == span.file_id
+                    && def.span.start > span.start)
+            })
+            .or_else(|| candidates.first().copied())
+    }
+
+    fn record(&mut self, name: &str, span: Span, candidates: Vec<DefId>, resolved: Option<DefId>) {
+        let depth = self.depth();
+        if candidates.len() > 1 {
+            self.table.shadows.push(ShadowEvent {
+                name: name.to_string(),
+                kind: ShadowKind::Reference,
+                span,
+                scope_depth: depth,
+                selected: resolved,
+                shadowed: candidates
+                    .iter()
+                    .copied()
+                    .filter(|id| Some(*id) != resolved)
+                    .collect(),
+            });
+        }
+        self.table.references.push(Reference {
+            name: name.to_string(),
+            span,
+            scope_depth: depth,
+            resolved,
+            candidates,
+        });
+    }
+
+    fn undefined(&mut self, message: String, span: Span) {
+        self.diagnostics
+            .push(Diagnostic::error(message, span).with_id(DiagnosticId::ResolveUndefinedName));
+    }
+
+    /// 最も近いスコープに型・定数の定義が複数あれば曖昧として報告する。
+    fn check_ambiguous(&mut self, name: &str, span: Span, candidates: &[DefId]) {
+        let Some(first) = candidates.first() else {
+            return;
+        };
+        let depth = self.def(*first).scope_depth;
+        let items = candidates
+            .iter()
+            .map(|id| self.def(*id))
+            .filter(|def| def.scope_depth == depth && def.kind.is_unique_item())
+            .map(|def| def.span)
+            .collect::<Vec<_>>();
+        if items.len() < 2 {
+            return;
+        }
+        let mut diag = Diagnostic::error(format!("ambiguous name '{}'", name), span)
+            .with_id(DiagnosticId::ResolveAmbiguousName);
+        for item in items {
+            diag = diag.with_secondary_label(item, Some(String::from("candidate defined here")));
+        }
+        self.diagnostics.push(diag);
+    }
+
+    fn resolve_plain(&mut self, name: &str, span: Span) -> Option<DefId> {
+        let candidates = self.lookup(name);
+        if candidates.is_empty() {
+            self.undefined(format!("undefined name '{}'", name), span);
+        } else {
+            self.check_ambiguous(name, span, &candidates);
+        }
+        let resolved = self.select(&candidates, span);
+        self.record(name, span, candidates, resolved);
+        resolved
+    }
+
+    fn resolve_ident(&mut self, id: &Ident) {
+        if id.name.contains("::") {
+            self.resolve_path(id, false);
+        } else if id.name.contains('.') {
+            self.resolve_dotted(id);
+        } else if !is_layout_marker(&id.name) {
+            self.resolve_plain(&id.name, id.span);
+        }
+    }
+
+    /// `Enum::Variant` / `Trait::method` / `Newtype::inner` / `alias::item` を解決する。
+    ///
+    /// 型検査と同じく、先頭が enum / trait ならその member、次に import alias、
+    /// どちらでもなければ `::` の後ろの名前そのものを探す。
+    fn resolve_path(&mut self, id: &Ident, variant_only: bool) {
+        let Some((head, member)) = id.name.split_once("::") else {
+            return;
+        };
+        let head_span = Span::new(
+            id.span.file_id,
+            id.span.start,
+            id.span.start + head.len() as u32,
+        );
+
+        let owners = self
+            .lookup(head)
+            .into_iter()
+            .filter(|d| self.def(*d).kind.symbol_kind() == SymbolKind::Type)
+            .collect::<Vec<_>>();
+        if let Some(owner) = owners.first().copied() {
+            let owner_kind = self.def(owner).kind;
+            let found = match owner_kind {
+                DefKind::Enum | DefKind::Trait => Some(self.member(owner, member)),
+                DefKind::Newtype if member == "inner" && !variant_only => Some(Some(owner)),
+                _ => None,
+            };
+            if let Some(found) = found {
+                self.check_ambiguous(head, head_span, &owners);
+                self.record(head, head_span, owners, Some(owner));
+                if found.is_none() {
+                    let what = if owner_kind == DefKind::Enum {
+                        "variant"
+                    } else {
+                        "method"
+                    };
+                    self.undefined(
+                        format!("'{}' has no {} named '{}'", head, what, member),
+                        id.span,
+                    );
+                }
+                self.record(&id.name, id.span, found.into_iter().collect(), found);
+                return;
+            }
+        }
+
+        if !variant_only {
+            let files = self
+                .table
+                .import_aliases
+                .get(&id.span.file_id.0)
+                .and_then(|aliases| aliases.get(head))
+                .cloned();
+            if let Some(files) = files {
+                let candidates = self.scopes[0]
+                    .get(member)
+                    .map(|ids| {
+                        ids.iter()
+                            .rev()
+                            .copied()
+                            .filter(|d| files.contains(&self.def(*d).module.0))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                if candidates.is_empty() {
+                    self.undefined(
+                        format!("module '{}' has no item named '{}'", head, member),
+                        id.span,
+                    );
+                }
+                let resolved = candidates.first().copied();
+                self.record(&id.name, id.span, candidates, resolved);
+                return;
+            }
+        }
+
+        let mut candidates = self.lookup(member);
+        if variant_only {
+            candidates.retain(|d| self.def(*d).kind == DefKind::EnumVariant);
+        }
+        if candidates.is_empty() {
+            self.undefined(format!("undefined name '{}'", id.name), id.span);
+        }
+        let resolved = candidates.first().copied();
+        self.record(&id.name, id.span, candidates, resolved);
+    }
+
+    fn member(&self, owner: DefId, name: &str) -> Option<DefId> {
+        self.members
+            .get(&owner)?
+            .iter()
+            .copied()
+            .find(|m| self.def(*m).name == name)
+    }
+
+    /// `p.x.y` は `p`（値）・`p.x`・`p.x.y`（field）の 3 つの参照になる。
+    fn resolve_dotted(&mut self, id: &Ident) {
+        let mut segments = id.name.split('.');
+        let Some(base) = segments.next() else {
+            return;
+        };
+        let start = id.span.start;
+        let mut end = start + base.len() as u32;
+        self.resolve_plain(base, Span::new(id.span.file_id, start, end));
+        for field in segments {
+            end += 1 + field.len() as u32;
+            let span = Span::new(id.span.file_id, start, end);
+            let candidates = self.fields.get(field).cloned().unwrap_or_default();
+            if candidates.is_empty() {
+                self.undefined(format!("no struct has a field named '{}'", field), span);
+            }
+            let resolved = if candidates.len() == 1 {
+                candidates.first().copied()
+            } else {
+                None
+            };
+            let path = &id.name[..(end - start) as usize];
+            self.record(path, span, candidates, resolved);
+        }
+    }
+
+    fn resolve_variant(&mut self, variant: &Ident) {
+        if variant.name == "_" {
+            return;
+        }
+        if variant.name.contains("::") {
+            self.resolve_path(variant, true);
+            return;
+        }
+        let candidates = self
+            .lookup(&variant.name)
+            .into_iter()
+            .filter(|d| self.def(*d).kind == DefKind::EnumVariant)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            self.undefined(
+                format!("undefined variant '{}'", variant.name),
+                variant.span,
+            );
+        }
+        let resolved = candidates.first().copied();
+        self.record(&variant.name, variant.span, candidates, resolved);
+    }
+
+    fn hoist(&mut self, block: &Block, top_level: bool) {
+        for stmt in &block.items {
+            match stmt {
+                Stmt::FnDef(def) => {
+                    self.define(&def.name, DefKind::Function, def.doc.clone(), None);
+                }
+                Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
+                    if let Some(PrefixItem::Symbol(Symbol::Let {
+                        name,
+                        mutable: false,
+                        ..
+                    })) = expr.items.first()
+                    {
+                        self.define(name, DefKind::Let, None, None);
+                    }
+                }
+                Stmt::StructDef(def) => {
+                    let owner = self.define(&def.name, DefKind::Struct, def.doc.clone(), None);
+                    for (field, _) in &def.fields {
+                        self.define(field, DefKind::StructField, None, Some(owner));
+                    }
+                }
+                Stmt::EnumDef(def) => {
+                    let owner = self.define(&def.name, DefKind::Enum, def.doc.clone(), None);
+                    for variant in &def.variants {
+                        self.define(&variant.name, DefKind::EnumVariant, None, Some(owner));
+                    }
+                }
+                Stmt::TypeDef(def) => {
+                    let kind = match def.kind {
+                        TypeDefKind::Alias => DefKind::TypeAlias,
+                        TypeDefKind::Newtype => DefKind::Newtype,
+                    };
+                    self.define(&def.name, kind, def.doc.clone(), None);
+                }
+                Stmt::Trait(def) => {
+                    let owner = self.define(&def.name, DefKind::Trait, def.doc.clone(), None);
+                    for method in &def.methods {
+                        self.define(
+                            &method.name,
+                            DefKind::TraitMethod,
+                            method.doc.clone(),
+                            Some(owner),
+                        );
+                    }
+                }
+                Stmt::Global(def) => {
+                    let kind = match def.kind {
+                        GlobalKind::Const => DefKind::Const,
+                        GlobalKind::Static { .. } => DefKind::Static,
+                    };
+                    self.define(&def.name, kind, def.doc.clone(), None);
+                }
+                // トップレベルの #extern は resolve_names がまとめて定義する。
+                Stmt::Directive(Directive::Extern { func, .. }) if !top_level => {
+                    self.define(func, DefKind::Extern, None, None);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve_block(&mut self, block: &Block, top_level: bool) {
+        self.hoist(block, top_level);
+        for stmt in &block.items {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::FnDef(def) => self.resolve_fn(def),
+            Stmt::FnAlias(alias) => {
+                self.resolve_ident(&alias.target);
+                self.define(&alias.name, DefKind::FnAlias, alias.doc.clone(), None);
+            }
+            Stmt::Trait(def) => {
+                for method in &def.methods {
+                    self.resolve_fn(method);
+                }
+            }
+            Stmt::Impl(def) => {
+                for method in &def.methods {
+                    self.resolve_fn(method);
+                }
+            }
+            Stmt::Global(def) => self.resolve_expr(&def.value),
+            Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => self.resolve_expr(expr),
+            _ => {}
+        }
+    }
+
+    fn resolve_fn(&mut self, def: &FnDef) {
+        let FnBody::Parsed(body) = &def.body else {
+            return;
+        };
+        self.push_scope();
+        for param in &def.params {
+            self.define(param, DefKind::Param, None, None);
+        }
+        self.resolve_block(body, false);
+        self.pop_scope();
+    }
+
+    fn resolve_match_arm(&mut self, arm: &MatchArm) {
+        self.resolve_variant(&arm.variant);
+        self.push_scope();
+        if let Some(bind) = &arm.bind {
+            self.define(bind, DefKind::MatchBind, None, None);
+        }
+        self.resolve_block(&arm.body, false);
+        self.pop_scope();
+    }
+
+    fn resolve_expr(&mut self, expr: &PrefixExpr) {
+        for (index, item) in expr.items.iter().enumerate() {
+            match item {
+                PrefixItem::Symbol(Symbol::Let { name, mutable, .. }) => {
+                    // 先頭の非 mut の let は hoist で定義済み。
+                    if *mutable {
+                        self.define(name, DefKind::LetMut, None, None);
+                    } else if index != 0 {
+                        self.define(name, DefKind::Let, None, None);
+                    }
+                }
+                PrefixItem::Symbol(Symbol::Set { name }) => self.resolve_ident(name),
+                PrefixItem::Symbol(Symbol::Ident(id, _, _)) => self.resolve_ident(id),
+                PrefixItem::Block(block, _) => {
+                    self.push_scope();
+                    self.resolve_block(block, false);
+                    self.pop_scope();
+                }
+                PrefixItem::Match(match_expr, _) => {
+                    self.resolve_expr(&match_expr.scrutinee);
+                    for arm in &match_expr.arms {
+                        self.resolve_match_arm(arm);
+                    }
+                }
+                PrefixItem::For(for_expr, _) => {
+                    self.resolve_expr(&for_expr.iterable);
+                    self.push_scope();
+                    self.define(&for_expr.binding, DefKind::ForBind, None, None);
+                    self.resolve_block(&for_expr.body, false);
+                    self.pop_scope();
+                }
+                PrefixItem::Tuple(items, _) => {
+                    for item in items {
+                        self.resolve_expr(item);
+                    }
+                }
+                PrefixItem::Group(inner, _) => self.resolve_expr(inner),
+                PrefixItem::Intrinsic(intrinsic, _) => {
+                    for arg in &intrinsic.args {
+                        self.resolve_expr(arg);
+                    }
+                }
+                PrefixItem::Literal(..)
+                | PrefixItem::TypeAnnotation(..)
+                | PrefixItem::Pipe(_)
+                | PrefixItem::Symbol(_) => {}
+            }
+        }
+    }
+}
+
+fn normalized_import_suffix(module: &str, ext: &str) -> String {
+    let mut s = module.replace('\\', "/");
+    if !s.starts_with('/') {
+        s.insert(0, '/');
+    }
+    s.push_str(ext);
+    s
+}
+
+fn build_import_aliases(module: &Module, source_map: Option<&SourceMap>) -> ImportAliases {
+    let Some(source_map) = source_map else {
+        return BTreeMap::new();
+    };
+    let mut directives: Vec<&Directive> = module.directives.iter().collect();
+    for item in &module.root.items {
+        if let Stmt::Directive(d) = item {
+            directives.push(d);
+        }
+    }
+    let mut out = ImportAliases::new();
+    for directive in directives {
+        let Directive::Import {
+            path, clause, span, ..
+        } = directive
+        else {
+            continue;
+        };
+        let aliases: Vec<String> = match clause {
+            ImportClause::DefaultAlias => Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| alloc::vec![name.to_string()])
+                .unwrap_or_default(),
+            ImportClause::Alias(alias) => alloc::vec![alias.clone()],
+            _ => Vec::new(),
+        };
+        if aliases.is_empty() {
+            continue;
+        }
+        let suffixes = [
+            normalized_import_suffix(path, ".nepl"),
+            normalized_import_suffix(path, ".n.md"),
+        ];
+        let target_files = source_map
+            .iter_paths()
+            .filter_map(|(file_id, path)| {
+                let normalized = path.to_string_lossy().replace('\\', "/");
+                if suffixes.iter().any(|suffix| normalized.ends_with(suffix)) {
+                    Some(file_id.0)
+                } else {
+                    None
+                }
+            })
+            .collect::<BTreeSet<_>>();
+        if target_files.is_empty() {
+            continue;
+        }
+        let file_aliases = out.entry(span.file_id.0).or_default();
+        for alias in aliases {
+            file_aliases
+                .entry(alias)
+                .or_default()
+                .extend(target_files.iter().copied());
+        }
+    }
+    out
+}
@@ -3,13 +3,21 @@
 //! This keeps document comments as structured data inside the Rust compiler
 //! pipeline so hover and documentation tooling can consume the same parsed
 //! representation instead of re-parsing in JavaScript.
+//!
+//! `render_document_html` produces the same markup as `nodesrc/html_gen.js`
+//! (plus a table of contents and NEPLg2 highlighting), so the published docs
+//! can be generated from Rust as well.
 
 #![no_std]
 extern crate alloc;
 
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
+use crate::lexer::{self, TokenKind};
+use crate::span::FileId;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Document {
     pub children: Vec<BlockNode>,
@@ -284,6 +292,106 @@ pub fn render_document_markdown(document: &Document) -> String {
     out.trim().to_string()
 }
 
+/// Options for [`render_document_html`].
+#[derive(Debug, Clone, Default)]
+pub struct HtmlOptions {
+    /// Put a table of contents of the sections before the body.
+    pub toc: bool,
+    /// Render a `neplg2:test` paragraph and the code block after it as the
+    /// doctest widget that the playground runtime adds a run button to.
+    pub playground: bool,
+    /// Added to every section level, for documents placed under an outer heading.
+    pub heading_offset: usize,
+    /// Prefix of the section ids, so several documents can share one page.
+    pub id_prefix: String,
+}
+
+pub fn render_document_html(document: &Document, options: &HtmlOptions) -> String {
+    let mut out = String::new();
+    if options.toc {
+        let toc = render_toc_html(document, options);
+        if !toc.is_empty() {
+            out.push_str("<nav class=\"nm-toc\">\n");
+            out.push_str(&toc);
+            out.push_str("\n</nav>\n");
+        }
+    }
+    render_blocks_html(&document.children, options, &options.id_prefix, &mut out);
+    out
+}
+
+/// Anchor-linked list of the sections, in the markup of `inpage_toc_helper.js`.
+/// Level-1 sections link to the top of the page.
+pub fn render_toc_html(document: &Document, options: &HtmlOptions) -> String {
+    let mut entries = Vec::new();
+    collect_toc(&document.children, &options.id_prefix, &mut entries);
+    let Some(min_level) = entries.iter().map(|(level, _, _)| *level).min() else {
+        return String::new();
+    };
+    let mut out = String::from("<ul class=\"inpage-toc-list\">");
+    for (level, id, heading) in entries {
+        let href = if level == 1 { String::from("#") } else { format!("#{}", escape_html(&id)) };
+        out.push_str(&format!(
+            "\n<li><a class=\"inpage-toc-link depth-{}\" href=\"{}\">{}</a></li>",
+            (level - min_level).min(6),
+            href,
+            render_inlines_html(heading)
+        ));
+    }
+    out.push_str("\n</ul>");
+    out
+}
+
+pub fn render_inlines_html(inlines: &[InlineNode]) -> String {
+    let mut out = String::new();
+    for inline in inlines {
+        match inline {
+            InlineNode::Text(text) => out.push_str(&escape_html(text).replace('\n', "<br/>")),
+            InlineNode::CodeInline(text) => {
+                out.push_str(&format!("<code class=\"nm-code-inline\">{}</code>", escape_html(text)))
+            }
+            InlineNode::Math { display, text } => {
+                let class = if *display { "math-display" } else { "math-inline" };
+                out.push_str(&format!("<span class=\"{}\">{}</span>", class, escape_html(text)));
+            }
+            InlineNode::Ruby { base, ruby } => out.push_str(&format!(
+                "<ruby class=\"nm-ruby\"><rb>{}</rb><rt>{}</rt></ruby>",
+                render_inlines_html(base),
+                render_inlines_html(ruby)
+            )),
+            InlineNode::Gloss { base, notes } => {
+                let mut rendered_notes = String::new();
+                for note in notes {
+                    rendered_notes.push_str(&format!(
+                        "<span class=\"nm-gloss-note\">{}</span>",
+                        render_inlines_html(note)
+                    ));
+                }
+                out.push_str(&format!(
+                    "<ruby class=\"nm-gloss\"><rb>{}</rb><rt>{}</rt></ruby>",
+                    render_inlines_html(base),
+                    rendered_notes
+                ));
+            }
+            InlineNode::Link { text, href } => out.push_str(&format!(
+                "<a href=\"{}\">{}</a>",
+                escape_html(&rewrite_doc_link(href)),
+                render_inlines_html(text)
+            )),
+        }
+    }
+    out
+}
+
+/// Highlights NEPLg2 source with `nm-syn-*` spans (the classes of the
+/// playground stylesheet), classifying text by the kinds `lexer::lex` assigns.
+pub fn highlight_neplg2(source: &str) -> String {
+    let classes = syntax_classes(source);
+    let mut out = String::new();
+    push_highlighted(source, &classes, 0, source.len(), &mut out);
+    out
+}
+
 enum Container {
     Document(Document),
     Section(SectionNode),
@@ -520,6 +628,409 @@ fn render_inlines_markdown(inlines: &[InlineNode]) -> String {
     out
 }
 
+fn render_blocks_html(blocks: &[BlockNode], options: &HtmlOptions, parent_id: &str, out: &mut String) {
+    let mut index = 0;
+    while index < blocks.len() {
+        if options.playground {
+            if let BlockNode::Paragraph(paragraph) = &blocks[index] {
+                if let Some(meta) = doctest_meta(&paragraph.inlines) {
+                    let code = match blocks.get(index + 1) {
+                        Some(BlockNode::Code(code)) => {
+                            index += 1;
+                            Some(code)
+                        }
+                        _ => None,
+                    };
+                    render_doctest_html(&meta, code, out);
+                    index += 1;
+                    continue;
+                }
+            }
+        }
+        match &blocks[index] {
+            BlockNode::Section(section) => {
+                let id = section_id(parent_id, &section.heading);
+                let level = (section.level + options.heading_offset).clamp(1, 6);
+                out.push_str(&format!(
+                    "<section class=\"nm-sec level-{}\" id=\"{}\"><h{}>{}</h{}>\n",
+                    level,
+                    escape_html(&id),
+                    level,
+                    render_inlines_html(&section.heading),
+                    level
+                ));
+                render_blocks_html(&section.children, options, &id, out);
+                out.push_str("</section>\n");
+            }
+            BlockNode::Paragraph(paragraph) => {
+                out.push_str(&format!("<p>{}</p>\n", render_inlines_html(&paragraph.inlines)))
+            }
+            BlockNode::List(list) => {
+                out.push_str("<ul>\n");
+                for item in &list.items {
+                    out.push_str(&format!("<li>{}</li>\n", render_inlines_html(item)));
+                }
+                out.push_str("</ul>\n");
+            }
+            BlockNode::Code(code) => out.push_str(&render_code_html(code)),
+            BlockNode::Hr => out.push_str("<hr/>\n"),
+        }
+        index += 1;
+    }
+}
+
+fn collect_toc<'a>(blocks: &'a [BlockNode], parent_id: &str, out: &mut Vec<(usize, String, &'a [InlineNode])>) {
+    for block in blocks {
+        if let BlockNode::Section(section) = block {
+            let id = section_id(parent_id, &section.heading);
+            out.push((section.level, id.clone(), &section.heading));
+            collect_toc(&section.children, &id, out);
+        }
+    }
+}
+
+/// Section ids nest like `html_gen.js`: `<parent id>-<slug of the heading>`.
+fn section_id(parent_id: &str, heading: &[InlineNode]) -> String {
+    let slug = make_slug(&inlines_plain_text(heading));
+    if parent_id.is_empty() {
+        slug
+    } else {
+        format!("{}-{}", parent_id, slug)
+    }
+}
+
+fn make_slug(text: &str) -> String {
+    let mut out = String::new();
+    for ch in text.chars().flat_map(char::to_lowercase) {
+        if ch.is_whitespace() {
+            if !out.ends_with('-') {
+                out.push('-');
+            }
+        } else if ch.is_ascii_alphanumeric()
+            || ch == '_'
+            || ch == '-'
+            || matches!(ch, '\u{3040}'..='\u{309f}' | '\u{30a0}'..='\u{30ff}' | '\u{4e00}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}')
+        {
+            out.push(ch);
+        }
+    }
+    out.chars().take(100).collect()
+}
+
+/// Heading text for slugs; ruby readings and gloss notes are dropped.
+fn inlines_plain_text(inlines: &[InlineNode]) -> String {
+    let mut out = String::new();
+    for inline in inlines {
+        match inline {
+            InlineNode::Text(text) | InlineNode::CodeInline(text) => out.push_str(text),
+            InlineNode::Math { text, .. } => out.push_str(text),
+            InlineNode::Ruby { base, .. } | InlineNode::Gloss { base, .. } => {
+                out.push_str(&inlines_plain_text(base))
+            }
+            InlineNode::Link { text, .. } => out.push_str(&inlines_plain_text(text)),
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Points relative `.n.md` / `.nepl` links at the generated `.html` pages.
+fn rewrite_doc_link(href: &str) -> String {
+    let href = href.trim();
+    if href.is_empty() || href.starts_with('#') || href.contains("://") || href.starts_with("mailto:") {
+        return href.to_string();
+    }
+    let split = href.find(['#', '?']).unwrap_or(href.len());
+    let (path, rest) = href.split_at(split);
+    for ext in [".n.md", ".nepl"] {
+        if let Some(stem) = path.strip_suffix(ext) {
+            return format!("{}.html{}", stem, rest);
+        }
+    }
+    href.to_string()
+}
+
+struct DoctestMeta {
+    flags: Vec<String>,
+    /// `stdin` / `stdout` / `ret` rows, values already unquoted.
+    rows: Vec<(String, String)>,
+}
+
+fn doctest_meta(inlines: &[InlineNode]) -> Option<DoctestMeta> {
+    let [InlineNode::Text(text)] = inlines else {
+        return None;
+    };
+    let mut lines = text.lines();
+    let rest = lines.next()?.trim().strip_prefix("neplg2:test")?;
+    let flags = match rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        Some(list) => list
+            .split(',')
+            .map(|flag| flag.trim().to_string())
+            .filter(|flag| !flag.is_empty())
+            .collect(),
+        None if rest.is_empty() => Vec::new(),
+        None => return None,
+    };
+    let mut rows = Vec::new();
+    while let Some(line) = lines.next() {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        if !matches!(key, "stdin" | "stdout" | "ret") {
+            continue;
+        }
+        // `parse_inlines` has already turned `\n` into line breaks, so a quoted
+        // value continues until its closing quote.
+        let mut value = value.trim().to_string();
+        while !quote_closed(&value) {
+            let Some(next) = lines.next() else { break };
+            value.push('\n');
+            value.push_str(next);
+        }
+        rows.push((key.to_string(), decode_doctest_value(value.trim())));
+    }
+    Some(DoctestMeta { flags, rows })
+}
+
+fn quote_closed(value: &str) -> bool {
+    let mut chars = value.chars();
+    let Some(quote) = chars.next().filter(|c| *c == '"' || *c == '\'') else {
+        return true;
+    };
+    let mut escaped = false;
+    for ch in chars {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == quote {
+            return true;
+        }
+    }
+    false
+}
+
+fn decode_doctest_value(raw: &str) -> String {
+    if let Some(body) = raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        return body.to_string();
+    }
+    let quoted = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"'));
+    let body = quoted.unwrap_or(raw);
+    let mut out = String::new();
+    let mut chars = body.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('u') if quoted.is_some() => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    out.push(c);
+                }
+            }
+            Some(other) if quoted.is_some() => out.push(other),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn render_doctest_html(meta: &DoctestMeta, code: Option<&CodeBlock>, out: &mut String) {
+    out.push_str("<div class=\"nm-code-container\"><div class=\"nm-code-header\"><span class=\"nm-badge-main\">TEST</span>");
+    for flag in &meta.flags {
+        out.push_str(&format!("<span class=\"nm-badge-flag\">{}</span>", escape_html(flag)));
+    }
+    out.push_str("</div><div class=\"nm-code-content\">");
+    if let Some(code) = code {
+        out.push_str(&render_code_html(code));
+    }
+    out.push_str("</div>");
+    if !meta.rows.is_empty() {
+        out.push_str("<div class=\"nm-code-footer\">");
+        for (key, value) in &meta.rows {
+            let value = if key == "ret" {
+                format!("<code class=\"nm-doctest-inline\">{}</code>", escape_html(value))
+            } else {
+                format!("<pre class=\"nm-doctest-pre\">{}</pre>", escape_html(value))
+            };
+            out.push_str(&format!(
+                "<div class=\"nm-doctest-row\"><span class=\"nm-doctest-badge\">{}</span>{}</div>",
+                escape_html(key),
+                value
+            ));
+        }
+        out.push_str("</div>");
+    }
+    out.push_str("</div>\n");
+}
+
+/// Code block; lines starting with `|` are the hidden preamble of an example.
+fn render_code_html(code: &CodeBlock) -> String {
+    let mut source = String::new();
+    let mut lines = Vec::new();
+    for (index, line) in code.text.split('\n').enumerate() {
+        if index > 0 {
+            source.push('\n');
+        }
+        let (hidden, text) = match line.strip_prefix('|') {
+            Some(rest) => (true, rest.strip_prefix(' ').unwrap_or(rest)),
+            None => (false, line),
+        };
+        let start = source.len();
+        source.push_str(text);
+        lines.push((hidden, start, source.len()));
+    }
+    let classes = if matches!(code.lang.as_str(), "neplg2" | "nepl") {
+        syntax_classes(&source)
+    } else {
+        alloc::vec![None; source.len()]
+    };
+    let mut body = String::new();
+    let count = lines.len();
+    for (index, (hidden, start, end)) in lines.into_iter().enumerate() {
+        let end = if index + 1 < count { end + 1 } else { end };
+        if hidden {
+            body.push_str("<span class=\"nm-hidden\">");
+        }
+        push_highlighted(&source, &classes, start, end, &mut body);
+        if hidden {
+            body.push_str("</span>");
+        }
+    }
+    let class = if code.lang.is_empty() {
+        String::new()
+    } else {
+        format!(" class=\"language-{}\"", escape_html(&code.lang))
+    };
+    format!("<pre class=\"nm-code\"><code{}>{}</code></pre>\n", class, body)
+}
+
+/// The `nm-syn-*` class of every byte of `source`.
+fn syntax_classes(source: &str) -> Vec<Option<&'static str>> {
+    let mut classes = alloc::vec![None; source.len()];
+    for token in lexer::lex(FileId(0), source).tokens {
+        let start = (token.span.start as usize).min(source.len());
+        let mut end = (token.span.end as usize).min(source.len());
+        let text = source.get(start..end).unwrap_or("");
+        let Some(class) = token_class(&token.kind, text) else {
+            continue;
+        };
+        // Directive spans cover the arguments too; only `#name` is a keyword.
+        if let Some(name) = text.strip_prefix('#') {
+            let len = name.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(name.len());
+            end = start + 1 + len;
+        }
+        for slot in &mut classes[start..end] {
+            *slot = Some(class);
+        }
+    }
+    // The lexer drops `//` comments; mark them the same way it finds them.
+    let mut line_start = 0;
+    for line in source.split('\n') {
+        if let Some(idx) = line.find("//") {
+            for slot in &mut classes[line_start + idx..line_start + line.len()] {
+                *slot = Some("nm-syn-comment");
+            }
+        }
+        line_start += line.len() + 1;
+    }
+    classes
+}
+
+fn token_class(kind: &TokenKind, text: &str) -> Option<&'static str> {
+    let class = match kind {
+        TokenKind::StringLiteral(_) | TokenKind::MlstrLine(_) => "nm-syn-string",
+        TokenKind::IntLiteral(_) | TokenKind::FloatLiteral(_) => "nm-syn-number",
+        TokenKind::BoolLiteral(_) => "nm-syn-boolean",
+        TokenKind::DocComment(_) => "nm-syn-comment",
+        TokenKind::Arrow(_) | TokenKind::Star | TokenKind::Minus | TokenKind::Equals | TokenKind::Ampersand => {
+            "nm-syn-operator"
+        }
+        TokenKind::Colon
+        | TokenKind::Semicolon
+        | TokenKind::Pipe
+        | TokenKind::LParen
+        | TokenKind::RParen
+        | TokenKind::Comma
+        | TokenKind::LAngle
+        | TokenKind::RAngle
+        | TokenKind::PathSep
+        | TokenKind::At
+        | TokenKind::Dot => "nm-syn-punctuation",
+        TokenKind::Region(_) => "nm-syn-type",
+        TokenKind::Ident(name) => {
+            let primitive = matches!(
+                name.as_str(),
+                "i32" | "i64" | "u8" | "u32" | "u64" | "f32" | "f64" | "bool" | "str" | "char"
+            );
+            if primitive || name.starts_with(|c: char| c.is_ascii_uppercase()) {
+                "nm-syn-type"
+            } else {
+                return None;
+            }
+        }
+        TokenKind::UnitLiteral
+        | TokenKind::Indent
+        | TokenKind::Dedent
+        | TokenKind::Newline
+        | TokenKind::Eof
+        | TokenKind::WasmText(_)
+        | TokenKind::LlvmIrText(_) => return None,
+        // Keywords and directives.
+        _ if !text.trim().is_empty() => "nm-syn-keyword",
+        _ => return None,
+    };
+    Some(class)
+}
+
+fn push_highlighted(source: &str, classes: &[Option<&'static str>], start: usize, end: usize, out: &mut String) {
+    let mut run_start = start;
+    let mut run_class = None;
+    for (offset, _) in source[start..end].char_indices() {
+        let pos = start + offset;
+        if classes[pos] != run_class {
+            push_run(&source[run_start..pos], run_class, out);
+            run_start = pos;
+            run_class = classes[pos];
+        }
+    }
+    push_run(&source[run_start..end], run_class, out);
+}
+
+fn push_run(text: &str, class: Option<&str>, out: &mut String) {
+    if text.is_empty() {
+        return;
+    }
+    match class {
+        Some(class) => out.push_str(&format!("<span class=\"{}\">{}</span>", class, escape_html(text))),
+        None => out.push_str(&escape_html(text)),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -545,4 +1056,39 @@ mod tests {
         assert!(rendered.contains("- a\n- b"));
         assert!(rendered.contains("```neplg2"));
     }
+
+    #[test]
+    fn render_html_has_ruby_and_toc() {
+        let doc = parse_document("# [漢字/かんじ] Guide\n\n## Next step\n\ntext [読/よ]む [next](./b.n.md#top)\n");
+        let options = HtmlOptions { toc: true, ..HtmlOptions::default() };
+        let html = render_document_html(&doc, &options);
+        assert!(html.contains("<ruby class=\"nm-ruby\"><rb>読</rb><rt>よ</rt></ruby>"));
+        assert!(html.contains("id=\"漢字-guide-next-step\""));
+        assert!(html.contains("<a href=\"./b.html#top\">next</a>"));
+        assert!(html.contains("<a class=\"inpage-toc-link depth-1\" href=\"#漢字-guide-next-step\">Next step</a>"));
+    }
+
+    #[test]
+    fn render_html_highlights_and_hides_preamble() {
+        let doc = parse_document("```neplg2\n| #entry main\nfn main <()->i32> (): 1 // one\n```\n");
+        let html = render_document_html(&doc, &HtmlOptions::default());
+        assert!(html.contains("<span class=\"nm-hidden\"><span class=\"nm-syn-keyword\">"));
+        assert!(html.contains("<span class=\"nm-syn-keyword\">fn</span>"));
+        assert!(html.contains("<span class=\"nm-syn-type\">i32</span>"));
+        assert!(html.contains("<span class=\"nm-syn-number\">1</span>"));
+        assert!(html.contains("<span class=\"nm-syn-comment\">// one</span>"));
+    }
+
+    #[test]
+    fn render_html_playground_doctest_widget() {
+        let source = "neplg2:test[should_panic]\nstdout: \"a\\nb\"\n```neplg2\nfn main ():\n```\n";
+        let doc = parse_document(source);
+        let plain = render_document_html(&doc, &HtmlOptions::default());
+        assert!(!plain.contains("nm-code-container"));
+        let options = HtmlOptions { playground: true, ..HtmlOptions::default() };
+        let html = render_document_html(&doc, &options);
+        assert!(html.contains("<span class=\"nm-badge-flag\">should_panic</span>"));
+        assert!(html.contains("<pre class=\"nm-doctest-pre\">a\nb</pre>"));
+        assert!(html.contains("<pre class=\"nm-code\"><code class=\"language-neplg2\">"));
+    }
 }
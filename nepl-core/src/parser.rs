@@ -164,6 +164,7 @@ impl Parser {
             vis: Visibility::Private,
             name: name_ident.clone(),
             no_shadow: false,
+            regions: Vec::new(),
             type_params: Vec::new(),
             signature: Self::infer_signature_from_params(params.len()),
             params,
@@ -692,6 +693,8 @@ impl Parser {
                     Some(TokenKind::KwFn) => self.parse_fn(),
                     Some(TokenKind::KwTrait) => self.parse_trait(),
                     Some(TokenKind::KwImpl) => self.parse_impl(),
+                    Some(TokenKind::KwConst) | Some(TokenKind::KwStatic) => self.parse_global(),
+                    Some(TokenKind::KwType) | Some(TokenKind::KwNewtype) => self.parse_type_def(),
                     _ => {
                         let span = self.peek_span().unwrap_or_else(Span::dummy);
                         self.push_error_with_id(
@@ -709,6 +712,8 @@ impl Parser {
             TokenKind::KwFn => self.parse_fn(),
             TokenKind::KwTrait => self.parse_trait(),
             TokenKind::KwImpl => self.parse_impl(),
+            TokenKind::KwConst | TokenKind::KwStatic => self.parse_global(),
+            TokenKind::KwType | TokenKind::KwNewtype => self.parse_type_def(),
             TokenKind::KwLet => {
                 if let Some(def) = self.parse_let_fn_def() {
                     Some(def)
@@ -727,6 +732,8 @@ impl Parser {
                     Stmt::FnAlias(d) => d.doc = Some(doc_str),
                     Stmt::StructDef(d) => d.doc = Some(doc_str),
                     Stmt::EnumDef(d) => d.doc = Some(doc_str),
+                    Stmt::TypeDef(d) => d.doc = Some(doc_str),
+                    Stmt::Global(d) => d.doc = Some(doc_str),
                     Stmt::Trait(d) => d.doc = Some(doc_str),
                     Stmt::Impl(d) => d.doc = Some(doc_str),
                     _ => {}
@@ -864,6 +871,7 @@ impl Parser {
             vis: Visibility::Private,
             name: Ident { name, span: nspan },
             no_shadow,
+            regions: Vec::new(),
             type_params: Vec::new(),
             signature: signature
                 .unwrap_or_else(|| Self::infer_signature_from_params(params.len())),
@@ -988,6 +996,77 @@ impl Parser {
         }))
     }
 
+    fn parse_global(&mut self) -> Option<Stmt> {
+        let vis = self.parse_visibility();
+        let (kind, kw_span) = if self.check(&TokenKind::KwConst) {
+            (GlobalKind::Const, self.expect_with_span(&TokenKind::KwConst)?)
+        } else {
+            let kw_span = self.expect_with_span(&TokenKind::KwStatic)?;
+            let mutable = self.consume_if(&TokenKind::KwMut);
+            (GlobalKind::Static { mutable }, kw_span)
+        };
+        let (name, nspan) = self.expect_ident()?;
+        self.expect(&TokenKind::LAngle)?;
+        let ty = self.parse_type_expr()?;
+        self.expect(&TokenKind::RAngle)?;
+        let value = self.parse_prefix_expr()?;
+        if value.items.is_empty() {
+            self.push_error_with_id(
+                DiagnosticId::ParserExpectedToken,
+                "expected an initializer expression",
+                nspan,
+            );
+            return None;
+        }
+        let span = kw_span.join(value.span).unwrap_or(kw_span);
+        Some(Stmt::Global(GlobalDef {
+            doc: None,
+            vis,
+            kind,
+            name: Ident { name, span: nspan },
+            ty,
+            value,
+            span,
+        }))
+    }
+
+    fn parse_type_def(&mut self) -> Option<Stmt> {
+        let vis = self.parse_visibility();
+        let (kind, kw_span) = if self.check(&TokenKind::KwType) {
+            (TypeDefKind::Alias, self.expect_with_span(&TokenKind::KwType)?)
+        } else {
+            (TypeDefKind::Newtype, self.expect_with_span(&TokenKind::KwNewtype)?)
+        };
+        let (name, nspan) = self.expect_ident()?;
+        let type_params = self.parse_generic_params();
+        if !self.consume_if(&TokenKind::Equals) {
+            let span = self.peek_span().unwrap_or(nspan);
+            self.push_error_with_id(
+                DiagnosticId::ParserExpectedToken,
+                "expected '=' after type name",
+                span,
+            );
+            return None;
+        }
+        let target = self.parse_type_expr()?;
+        let end_span = self
+            .pos
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map(|t| t.span)
+            .unwrap_or(nspan);
+        let span = kw_span.join(end_span).unwrap_or(kw_span);
+        Some(Stmt::TypeDef(TypeDef {
+            doc: None,
+            vis,
+            kind,
+            name: Ident { name, span: nspan },
+            type_params,
+            target,
+            span,
+        }))
+    }
+
     fn parse_fn(&mut self) -> Option<Stmt> {
         let vis = self.parse_visibility();
         let _fn_span = self.expect_with_span(&TokenKind::KwFn)?;
@@ -1023,6 +1102,7 @@ impl Parser {
 
         // If next is LAngle, it could be generics OR signature.
         let mut type_params = Vec::new();
+        let mut regions = Vec::new();
         if self.check(&TokenKind::LAngle) {
             // Peek further if needed?
             // In NEPL, if there are two < > blocks, the first is always generics.
@@ -1033,9 +1113,10 @@ impl Parser {
             // then we were correct.
             let saved_pos = self.pos;
             let saved_diags_len = self.diagnostics.len();
-            let tentative_params = self.parse_generic_params();
+            let (tentative_regions, tentative_params) = self.parse_generic_params_with_regions();
             if self.check(&TokenKind::LAngle) {
                 // Success, we had generics.
+                regions = tentative_regions;
                 type_params = tentative_params;
             } else {
                 // Not generics, backtrack.
@@ -1085,6 +1166,7 @@ impl Parser {
             vis,
             name,
             no_shadow,
+            regions,
             type_params,
             signature,
             params,
@@ -1449,7 +1531,7 @@ impl Parser {
                         }
                     } else if items
                         .iter()
-                        .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(_))))
+                        .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(..))))
                     {
                         let expected = if Self::while_layout_needs_cond(&items) {
                             2
@@ -1515,30 +1597,7 @@ impl Parser {
                     items.push(PrefixItem::TypeAnnotation(ty, span));
                 }
                 TokenKind::Minus => {
-                    let minus_span = self.next().unwrap().span;
-                    match self.peek_kind() {
-                        Some(TokenKind::IntLiteral(_)) => {
-                            let tok = self.next().unwrap();
-                            let v = if let TokenKind::IntLiteral(v) = tok.kind { v } else { unreachable!() };
-                            let combined = alloc::format!("-{}", v);
-                            items.push(PrefixItem::Literal(Literal::Int(combined), minus_span.join(tok.span).unwrap_or(minus_span)));
-                        }
-                        Some(TokenKind::FloatLiteral(v)) => {
-                            let tok = self.next().unwrap();
-                            let combined = alloc::format!("-{}", v);
-                            items.push(PrefixItem::Literal(Literal::Float(combined), minus_span.join(tok.span).unwrap_or(minus_span)));
-                        }
-                        _ => {
-                            items.push(PrefixItem::Symbol(Symbol::Ident(
-                                Ident {
-                                    name: "-".to_string(),
-                                    span: minus_span,
-                                },
-                                Vec::new(),
-                                false,
-                            )));
-                        }
-                    }
+                    items.push(self.parse_minus_item());
                 }
                 TokenKind::IntLiteral(_)
                 | TokenKind::FloatLiteral(_)
@@ -1654,7 +1713,14 @@ impl Parser {
                 }
                 TokenKind::KwWhile => {
                     let span = self.next().unwrap().span;
-                    items.push(PrefixItem::Symbol(Symbol::While(span)));
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::While(span, label)));
+                }
+                TokenKind::KwBreak
+                | TokenKind::KwContinue
+                | TokenKind::KwReturn
+                | TokenKind::KwTry => {
+                    items.push(self.parse_jump_symbol());
                 }
                 TokenKind::KwCond => {
                     items.push(self.parse_layout_marker_symbol("cond"));
@@ -1732,6 +1798,12 @@ impl Parser {
                     items.push(PrefixItem::Match(m, sp));
                     break;
                 }
+                TokenKind::KwFor => {
+                    let f = self.parse_for_expr()?;
+                    let sp = f.span;
+                    items.push(PrefixItem::For(f, sp));
+                    break;
+                }
                 TokenKind::At | TokenKind::Ident(_) => {
                     let ident_item = self.parse_ident_symbol_item(&items, true, true)?;
                     items.push(ident_item);
@@ -1740,6 +1812,10 @@ impl Parser {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::AddrOf(span)));
                 }
+                TokenKind::DirComptime => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Comptime(span)));
+                }
                 TokenKind::Star => {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::Deref(span)));
@@ -1993,7 +2069,7 @@ impl Parser {
                         }
                     } else if items
                         .iter()
-                        .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(_))))
+                        .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(..))))
                     {
                         let expected = if Self::while_layout_needs_cond(&items) {
                             2
@@ -2110,7 +2186,14 @@ impl Parser {
                 }
                 TokenKind::KwWhile => {
                     let span = self.next().unwrap().span;
-                    items.push(PrefixItem::Symbol(Symbol::While(span)));
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::While(span, label)));
+                }
+                TokenKind::KwBreak
+                | TokenKind::KwContinue
+                | TokenKind::KwReturn
+                | TokenKind::KwTry => {
+                    items.push(self.parse_jump_symbol());
                 }
                 TokenKind::KwCond => {
                     items.push(self.parse_layout_marker_symbol("cond"));
@@ -2155,6 +2238,12 @@ impl Parser {
                     items.push(PrefixItem::Match(m, sp));
                     break;
                 }
+                TokenKind::KwFor => {
+                    let f = self.parse_for_expr()?;
+                    let sp = f.span;
+                    items.push(PrefixItem::For(f, sp));
+                    break;
+                }
                 TokenKind::At | TokenKind::Ident(_) => {
                     let ident_item = self.parse_ident_symbol_item(&items, true, true)?;
                     items.push(ident_item);
@@ -2163,6 +2252,10 @@ impl Parser {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::AddrOf(span)));
                 }
+                TokenKind::DirComptime => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Comptime(span)));
+                }
                 TokenKind::Star => {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::Deref(span)));
@@ -2226,6 +2319,72 @@ impl Parser {
         })
     }
 
+    /// Reads `-` as the sign of a following numeric literal, or as the `-` identifier.
+    fn parse_minus_item(&mut self) -> PrefixItem {
+        let minus_span = self.next().unwrap().span;
+        match self.peek_kind() {
+            Some(TokenKind::IntLiteral(_)) => {
+                let tok = self.next().unwrap();
+                let v = if let TokenKind::IntLiteral(v) = tok.kind { v } else { unreachable!() };
+                let combined = alloc::format!("-{}", v);
+                PrefixItem::Literal(Literal::Int(combined), minus_span.join(tok.span).unwrap_or(minus_span))
+            }
+            Some(TokenKind::FloatLiteral(v)) => {
+                let tok = self.next().unwrap();
+                let combined = alloc::format!("-{}", v);
+                PrefixItem::Literal(Literal::Float(combined), minus_span.join(tok.span).unwrap_or(minus_span))
+            }
+            _ => PrefixItem::Symbol(Symbol::Ident(
+                Ident {
+                    name: "-".to_string(),
+                    span: minus_span,
+                },
+                Vec::new(),
+                false,
+            )),
+        }
+    }
+
+    fn parse_for_expr(&mut self) -> Option<ForExpr> {
+        let for_span = self.next()?.span;
+        let label = self.parse_loop_label();
+        let (name, name_span) = self.expect_ident()?;
+        match self.peek_kind() {
+            Some(TokenKind::Ident(kw)) if kw == "in" => {
+                self.next();
+            }
+            _ => {
+                let sp = self.peek_span().unwrap_or(name_span);
+                self.diagnostics.push(
+                    Diagnostic::error("expected 'in' after for binding", sp)
+                        .with_id(DiagnosticId::ParserExpectedToken),
+                );
+            }
+        }
+        let iterable = self.parse_prefix_expr_until_colon()?;
+        if !self.check(&TokenKind::Colon) {
+            let sp = self.peek_span().unwrap_or_else(Span::dummy);
+            self.diagnostics.push(
+                Diagnostic::error("expected ':' after for iterable", sp)
+                    .with_id(DiagnosticId::ParserExpectedToken),
+            );
+            return None;
+        }
+        self.next();
+        let body = self.parse_block_after_colon()?;
+        let span = for_span.join(body.span).unwrap_or(for_span);
+        Some(ForExpr {
+            label,
+            binding: Ident {
+                name,
+                span: name_span,
+            },
+            iterable,
+            body,
+            span,
+        })
+    }
+
     fn parse_prefix_expr_until_colon(&mut self) -> Option<PrefixExpr> {
         let start_span = self.peek_span().unwrap_or_else(Span::dummy);
         let mut items = Vec::new();
@@ -2291,6 +2450,9 @@ impl Parser {
                     let span = start.join(end).unwrap_or(start);
                     items.push(PrefixItem::TypeAnnotation(ty, span));
                 }
+                TokenKind::Minus => {
+                    items.push(self.parse_minus_item());
+                }
                 TokenKind::IntLiteral(_)
                 | TokenKind::FloatLiteral(_)
                 | TokenKind::BoolLiteral(_)
@@ -2349,7 +2511,14 @@ impl Parser {
                 }
                 TokenKind::KwWhile => {
                     let span = self.next().unwrap().span;
-                    items.push(PrefixItem::Symbol(Symbol::While(span)));
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::While(span, label)));
+                }
+                TokenKind::KwBreak
+                | TokenKind::KwContinue
+                | TokenKind::KwReturn
+                | TokenKind::KwTry => {
+                    items.push(self.parse_jump_symbol());
                 }
                 TokenKind::KwCond => {
                     items.push(self.parse_layout_marker_symbol("cond"));
@@ -2367,6 +2536,10 @@ impl Parser {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::AddrOf(span)));
                 }
+                TokenKind::DirComptime => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Comptime(span)));
+                }
                 TokenKind::Star => {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::Deref(span)));
@@ -2415,7 +2588,7 @@ impl Parser {
         });
         let has_while = items
             .iter()
-            .any(|item| matches!(item, PrefixItem::Symbol(Symbol::While(_))));
+            .any(|item| matches!(item, PrefixItem::Symbol(Symbol::While(..))));
         let mut i = 0;
         while i < items.len() {
             let remove = match &items[i] {
@@ -2698,7 +2871,7 @@ impl Parser {
             tail.pop();
         }
         match tail.as_slice() {
-            [.., PrefixItem::Symbol(Symbol::While(_))] => true,
+            [.., PrefixItem::Symbol(Symbol::While(..))] => true,
             _ => false,
         }
     }
@@ -3173,9 +3346,30 @@ impl Parser {
     }
 
     fn parse_generic_params(&mut self) -> Vec<TypeParam> {
+        let (regions, params) = self.parse_generic_params_with_regions();
+        for region in regions {
+            self.diagnostics.push(
+                Diagnostic::error("region parameters are only allowed on functions", region.span)
+                    .with_id(DiagnosticId::ParserInvalidTypeExpr),
+            );
+        }
+        params
+    }
+
+    /// Parses `<'a, .T: Bound, ...>`; region parameters (`'a`) are returned separately.
+    fn parse_generic_params_with_regions(&mut self) -> (Vec<Ident>, Vec<TypeParam>) {
+        let mut regions = Vec::new();
         let mut params = Vec::new();
         if self.consume_if(&TokenKind::LAngle) {
             loop {
+                if let Some(TokenKind::Region(name)) = self.peek_kind() {
+                    let span = self.next().map(|t| t.span).unwrap_or_else(Span::dummy);
+                    regions.push(Ident { name, span });
+                    if !self.consume_if(&TokenKind::Comma) {
+                        break;
+                    }
+                    continue;
+                }
                 let mut has_dot = false;
                 if self.consume_if(&TokenKind::Dot) {
                     has_dot = true;
@@ -3227,7 +3421,7 @@ impl Parser {
             }
             self.expect(&TokenKind::RAngle);
         }
-        params
+        (regions, params)
     }
 
     fn parse_path_ident(&mut self) -> Option<(String, Span)> {
@@ -3389,9 +3583,16 @@ impl Parser {
             }
             TokenKind::Ampersand => {
                 let _ = self.next();
+                let region = match self.peek_kind() {
+                    Some(TokenKind::Region(name)) => {
+                        let _ = self.next();
+                        Some(name)
+                    }
+                    _ => None,
+                };
                 let is_mut = self.consume_if(&TokenKind::KwMut);
                 let inner = self.parse_type_expr()?;
-                Some(TypeExpr::Reference(Box::new(inner), is_mut))
+                Some(TypeExpr::Reference(Box::new(inner), is_mut, region))
             }
             _ => {
                 let span = self.peek_span().unwrap_or_else(Span::dummy);
@@ -3580,7 +3781,7 @@ impl Parser {
         }
         let has_while = current_items
             .iter()
-            .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(_))));
+            .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(..))));
         has_while && matches!(name, "cond" | "do")
     }
 
@@ -3658,6 +3859,45 @@ impl Parser {
         }
     }
 
+    /// `while` / `break` / `continue` の直後に置かれた `<.label>` を読みます。
+    ///
+    /// 型注釈 `<T>` と区別するため、`<` `.` 識別子 `>` の並びだけをラベルとして扱います。
+    fn parse_loop_label(&mut self) -> Option<String> {
+        let is_label = matches!(self.peek_kind(), Some(TokenKind::LAngle))
+            && matches!(self.peek_kind_at(1), Some(TokenKind::Dot))
+            && matches!(self.peek_kind_at(2), Some(TokenKind::Ident(_)))
+            && matches!(self.peek_kind_at(3), Some(TokenKind::RAngle));
+        if !is_label {
+            return None;
+        }
+        self.next();
+        self.next();
+        let name = match self.next().map(|t| t.kind) {
+            Some(TokenKind::Ident(name)) => name,
+            _ => unreachable!(),
+        };
+        self.next();
+        Some(name)
+    }
+
+    /// `break` / `continue` / `return` / `try` を記号として読みます。
+    fn parse_jump_symbol(&mut self) -> PrefixItem {
+        let tok = self.next().unwrap();
+        let symbol = match tok.kind {
+            TokenKind::KwBreak => Symbol::Break {
+                label: self.parse_loop_label(),
+                span: tok.span,
+            },
+            TokenKind::KwContinue => Symbol::Continue {
+                label: self.parse_loop_label(),
+                span: tok.span,
+            },
+            TokenKind::KwTry => Symbol::Try(tok.span),
+            _ => Symbol::Return(tok.span),
+        };
+        PrefixItem::Symbol(symbol)
+    }
+
     fn reserved_keyword_token_name(kind: &TokenKind) -> Option<&'static str> {
         match kind {
             TokenKind::KwFn => Some("fn"),
@@ -3667,6 +3907,10 @@ impl Parser {
             TokenKind::KwSet => Some("set"),
             TokenKind::KwIf => Some("if"),
             TokenKind::KwWhile => Some("while"),
+            TokenKind::KwBreak => Some("break"),
+            TokenKind::KwContinue => Some("continue"),
+            TokenKind::KwReturn => Some("return"),
+            TokenKind::KwTry => Some("try"),
             TokenKind::KwCond => Some("cond"),
             TokenKind::KwThen => Some("then"),
             TokenKind::KwElse => Some("else"),
@@ -3678,6 +3922,10 @@ impl Parser {
             TokenKind::KwImpl => Some("impl"),
             TokenKind::KwFor => Some("for"),
             TokenKind::KwPub => Some("pub"),
+            TokenKind::KwConst => Some("const"),
+            TokenKind::KwStatic => Some("static"),
+            TokenKind::KwType => Some("type"),
+            TokenKind::KwNewtype => Some("newtype"),
             TokenKind::KwBlock => Some("block"),
             TokenKind::KwTuple => Some("Tuple"),
             TokenKind::KwMlstr => Some("mlstr"),
@@ -3943,12 +4191,17 @@ impl Parser {
             PrefixItem::Symbol(Symbol::Let { name, .. }) => name.span,
             PrefixItem::Symbol(Symbol::Set { name }) => name.span,
             PrefixItem::Symbol(Symbol::If(sp)) => *sp,
-            PrefixItem::Symbol(Symbol::While(sp)) => *sp,
+            PrefixItem::Symbol(Symbol::While(sp, _)) => *sp,
+            PrefixItem::Symbol(Symbol::Break { span, .. }) => *span,
+            PrefixItem::Symbol(Symbol::Continue { span, .. }) => *span,
+            PrefixItem::Symbol(Symbol::Return(sp)) | PrefixItem::Symbol(Symbol::Try(sp)) => *sp,
             PrefixItem::Symbol(Symbol::AddrOf(sp)) => *sp,
+            PrefixItem::Symbol(Symbol::Comptime(sp)) => *sp,
             PrefixItem::Symbol(Symbol::Deref(sp)) => *sp,
             PrefixItem::TypeAnnotation(_, sp) => *sp,
             PrefixItem::Block(_, sp) => *sp,
             PrefixItem::Match(_, sp) => *sp,
+            PrefixItem::For(_, sp) => *sp,
             PrefixItem::Pipe(sp) => *sp,
             PrefixItem::Tuple(_, sp) => *sp,
             PrefixItem::Group(_, sp) => *sp,
@@ -3975,6 +4228,8 @@ impl Parser {
             Stmt::FnAlias(a) => a.name.span,
             Stmt::StructDef(s) => s.name.span,
             Stmt::EnumDef(e) => e.name.span,
+            Stmt::TypeDef(t) => t.span,
+            Stmt::Global(g) => g.span,
             Stmt::Wasm(w) => w.span,
             Stmt::LlvmIr(l) => l.span,
             Stmt::Expr(e) => e.span,
@@ -146,7 +146,7 @@ fn check_llvm_expr(expr: &HirExpr, out: &mut Vec<Diagnostic>) {
             check_llvm_expr(then_branch, out);
             check_llvm_expr(else_branch, out);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             check_llvm_expr(cond, out);
             check_llvm_expr(body, out);
         }
@@ -157,7 +157,9 @@ fn check_llvm_expr(expr: &HirExpr, out: &mut Vec<Diagnostic>) {
             }
         }
         HirExprKind::Block(b) => precheck_llvm_expr_tree(b, out),
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             check_llvm_expr(value, out);
         }
         HirExprKind::EnumConstruct { payload, .. } => {
@@ -175,13 +177,17 @@ fn check_llvm_expr(expr: &HirExpr, out: &mut Vec<Diagnostic>) {
                 check_llvm_expr(item, out);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => check_llvm_expr(inner, out),
-        HirExprKind::Drop { .. } => {}
+        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => check_llvm_expr(inner, out),
+        HirExprKind::Drop { .. }
+            | HirExprKind::Break { .. }
+            | HirExprKind::Continue { .. }
+            | HirExprKind::GlobalGet(_) => {}
         HirExprKind::Unit
         | HirExprKind::LiteralI32(_)
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
         | HirExprKind::Var(_)
         | HirExprKind::FnValue(_) => {}
     }
@@ -266,7 +272,7 @@ fn check_indirect_sig_expr(
             check_indirect_sig_expr(ctx, then_branch, wasm_sig_set, out);
             check_indirect_sig_expr(ctx, else_branch, wasm_sig_set, out);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             check_indirect_sig_expr(ctx, cond, wasm_sig_set, out);
             check_indirect_sig_expr(ctx, body, wasm_sig_set, out);
         }
@@ -277,7 +283,9 @@ fn check_indirect_sig_expr(
             }
         }
         HirExprKind::Block(b) => precheck_wasm_indirect_signature(ctx, b, wasm_sig_set, out),
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             check_indirect_sig_expr(ctx, value, wasm_sig_set, out);
         }
         HirExprKind::EnumConstruct { payload, .. } => {
@@ -295,15 +303,19 @@ fn check_indirect_sig_expr(
                 check_indirect_sig_expr(ctx, it, wasm_sig_set, out);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
             check_indirect_sig_expr(ctx, inner, wasm_sig_set, out);
         }
-        HirExprKind::Drop { .. } => {}
+        HirExprKind::Drop { .. }
+            | HirExprKind::Break { .. }
+            | HirExprKind::Continue { .. }
+            | HirExprKind::GlobalGet(_) => {}
         HirExprKind::Unit
         | HirExprKind::LiteralI32(_)
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
         | HirExprKind::Var(_)
         | HirExprKind::FnValue(_) => {}
     }
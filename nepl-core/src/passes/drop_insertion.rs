@@ -30,11 +30,22 @@ struct DropPlan {
     unit_ty: TypeId,
 }
 
+struct LoopFrame {
+    label: Option<String>,
+    /// Number of scopes open outside the loop body.
+    depth: usize,
+    /// Variable states at each `break` targeting this loop.
+    break_states: Vec<BTreeMap<String, Vec<VarInfo>>>,
+}
+
 struct DropInsertionContext<'a> {
     types: &'a mut TypeCtx,
     plan: &'a DropPlan,
     var_stacks: BTreeMap<String, Vec<VarInfo>>,
     scopes: Vec<Vec<String>>,
+    /// Enclosing loops, innermost last.
+    loops: Vec<LoopFrame>,
+    next_tmp: usize,
 }
 
 impl<'a> DropInsertionContext<'a> {
@@ -44,6 +55,8 @@ impl<'a> DropInsertionContext<'a> {
             plan,
             var_stacks: BTreeMap::new(),
             scopes: Vec::new(),
+            loops: Vec::new(),
+            next_tmp: 0,
         }
     }
 
@@ -98,12 +111,24 @@ impl<'a> DropInsertionContext<'a> {
     }
 
     fn scope_drop_lines(&mut self, span: crate::span::Span) -> Vec<HirLine> {
+        let depth = self.scopes.len().saturating_sub(1);
+        self.exit_drop_lines(depth, span)
+    }
+
+    /// Drops for every live variable in scopes `depth..` (innermost first).
+    /// Used both at the end of a block and when a jump leaves several scopes.
+    fn exit_drop_lines(&mut self, depth: usize, span: crate::span::Span) -> Vec<HirLine> {
         let mut out = Vec::new();
-        let Some(scope) = self.scopes.last() else {
-            return out;
-        };
-        for name in scope.iter().rev() {
-            let Some(info) = self.get_var(name) else {
+        let mut names = Vec::new();
+        for scope in self.scopes.iter().skip(depth).rev() {
+            for name in scope.iter().rev() {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        for name in names {
+            let Some(info) = self.get_var(&name) else {
                 continue;
             };
             if info.state != VarState::Valid {
@@ -113,12 +138,34 @@ impl<'a> DropInsertionContext<'a> {
                 continue;
             }
             out.push(HirLine {
-                expr: drop_call_expr(self.types, self.plan, name.clone(), info.ty, span),
+                expr: drop_call_expr(self.types, self.plan, name, info.ty, span),
                 drop_result: true,
             });
         }
         out
     }
+
+    /// Drops live variables in scopes `depth..` before a jump and marks them
+    /// moved so the (unreachable) end-of-scope code does not drop them again.
+    fn jump_drop_lines(&mut self, depth: usize, span: crate::span::Span) -> Vec<HirLine> {
+        let out = self.exit_drop_lines(depth, span);
+        let names: Vec<String> = self.scopes.iter().skip(depth).flatten().cloned().collect();
+        for name in names {
+            self.set_state(&name, VarState::Moved);
+        }
+        out
+    }
+
+    fn target_loop(&mut self, label: &Option<String>) -> Option<&mut LoopFrame> {
+        match label {
+            None => self.loops.last_mut(),
+            Some(label) => self
+                .loops
+                .iter_mut()
+                .rev()
+                .find(|frame| frame.label.as_ref() == Some(label)),
+        }
+    }
 }
 
 pub fn insert_drops(module: &mut HirModule, types: &mut TypeCtx) {
@@ -195,8 +242,11 @@ fn insert_drops_in_block(block: &mut HirBlock, ctx: &mut DropInsertionContext<'_
             ctx.declare_var(name.clone(), value.ty);
         }
     }
-    let drops = ctx.scope_drop_lines(block.span);
-    block.lines.extend(drops);
+    let diverges = block.lines.iter().any(|line| line.expr.diverges());
+    if !diverges {
+        let drops = ctx.scope_drop_lines(block.span);
+        block.lines.extend(drops);
+    }
     ctx.pop_scope();
 }
 
@@ -208,10 +258,12 @@ fn insert_drops_in_expr(expr: &mut HirExpr, ctx: &mut DropInsertionContext<'_>)
             }
         }
         HirExprKind::FnValue(_)
+        | HirExprKind::GlobalGet(_)
         | HirExprKind::LiteralI32(_)
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
         | HirExprKind::Unit => {}
         HirExprKind::Call { callee, args } => match callee {
             FuncRef::Builtin(name) | FuncRef::User(name, _) if name == "get" => {
@@ -244,20 +296,91 @@ fn insert_drops_in_expr(expr: &mut HirExpr, ctx: &mut DropInsertionContext<'_>)
             insert_drops_in_expr(cond, ctx);
             let saved = ctx.var_stacks.clone();
             insert_drops_in_expr(then_branch, ctx);
-            let then_state = ctx.var_stacks.clone();
+            let mut then_state = ctx.var_stacks.clone();
             ctx.var_stacks = saved.clone();
             insert_drops_in_expr(else_branch, ctx);
-            let else_state = ctx.var_stacks.clone();
+            let mut else_state = ctx.var_stacks.clone();
             ctx.var_stacks = saved.clone();
+            // A diverging branch never reaches the join point.
+            if then_branch.diverges() {
+                then_state = else_state.clone();
+            } else if else_branch.diverges() {
+                else_state = then_state.clone();
+            }
             merge_outer_states(ctx, &saved, &then_state, &else_state);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, label } => {
             insert_drops_in_expr(cond, ctx);
             let saved = ctx.var_stacks.clone();
+            ctx.loops.push(LoopFrame {
+                label: label.clone(),
+                depth: ctx.scopes.len(),
+                break_states: Vec::new(),
+            });
             insert_drops_in_expr(body, ctx);
-            let body_state = ctx.var_stacks.clone();
+            let frame = ctx.loops.pop();
+            let mut exit_states = vec![ctx.var_stacks.clone()];
+            if let Some(frame) = frame {
+                exit_states.extend(frame.break_states);
+            }
             ctx.var_stacks = saved.clone();
-            merge_outer_states(ctx, &saved, &saved, &body_state);
+            merge_many_outer_states(ctx, &saved, &exit_states);
+        }
+        HirExprKind::Break { label } | HirExprKind::Continue { label } => {
+            let label = label.clone();
+            let is_break = matches!(expr.kind, HirExprKind::Break { .. });
+            let depth = ctx
+                .target_loop(&label)
+                .map(|frame| frame.depth)
+                .unwrap_or(ctx.scopes.len());
+            let drops = ctx.jump_drop_lines(depth, expr.span);
+            if is_break {
+                let state = ctx.var_stacks.clone();
+                if let Some(frame) = ctx.target_loop(&label) {
+                    frame.break_states.push(state);
+                }
+            }
+            prepend_drop_lines_to_expr(expr, drops);
+        }
+        HirExprKind::Return(value) => {
+            insert_drops_in_expr(value, ctx);
+            let drops = ctx.jump_drop_lines(0, expr.span);
+            if !drops.is_empty() {
+                let tmp = alloc::format!("__ret_tmp{}", ctx.next_tmp);
+                ctx.next_tmp += 1;
+                let value_ty = value.ty;
+                let span = value.span;
+                let original = core::mem::replace(
+                    value.as_mut(),
+                    HirExpr {
+                        ty: value_ty,
+                        kind: HirExprKind::Var(tmp.clone()),
+                        span,
+                    },
+                );
+                let mut lines = vec![HirLine {
+                    expr: HirExpr {
+                        ty: ctx.plan.unit_ty,
+                        kind: HirExprKind::Let {
+                            name: tmp,
+                            mutable: false,
+                            value: Box::new(original),
+                        },
+                        span,
+                    },
+                    drop_result: false,
+                }];
+                lines.extend(drops);
+                lines.push(HirLine {
+                    expr: expr.clone(),
+                    drop_result: false,
+                });
+                expr.kind = HirExprKind::Block(HirBlock {
+                    lines,
+                    ty: expr.ty,
+                    span: expr.span,
+                });
+            }
         }
         HirExprKind::Match { scrutinee, arms } => {
             insert_drops_in_expr(scrutinee, ctx);
@@ -266,7 +389,9 @@ fn insert_drops_in_expr(expr: &mut HirExpr, ctx: &mut DropInsertionContext<'_>)
             for arm in arms {
                 ctx.var_stacks = saved.clone();
                 process_match_arm(arm, ctx);
-                arm_states.push(ctx.var_stacks.clone());
+                if !arm.body.diverges() {
+                    arm_states.push(ctx.var_stacks.clone());
+                }
             }
             ctx.var_stacks = saved.clone();
             merge_many_outer_states(ctx, &saved, &arm_states);
@@ -294,6 +419,7 @@ fn insert_drops_in_expr(expr: &mut HirExpr, ctx: &mut DropInsertionContext<'_>)
             insert_drops_in_expr(value, ctx);
             ctx.set_state(name, VarState::Valid);
         }
+        HirExprKind::GlobalSet { value, .. } => insert_drops_in_expr(value, ctx),
         HirExprKind::Intrinsic {
             name,
             type_args,
@@ -349,6 +475,23 @@ fn process_match_arm(arm: &mut HirMatchArm, ctx: &mut DropInsertionContext<'_>)
     ctx.pop_scope();
 }
 
+fn prepend_drop_lines_to_expr(expr: &mut HirExpr, drops: Vec<HirLine>) {
+    if drops.is_empty() {
+        return;
+    }
+    let original = expr.clone();
+    let mut lines = drops;
+    lines.push(HirLine {
+        expr: original,
+        drop_result: false,
+    });
+    expr.kind = HirExprKind::Block(HirBlock {
+        lines,
+        ty: expr.ty,
+        span: expr.span,
+    });
+}
+
 fn append_drop_lines_to_expr(expr: &mut HirExpr, drops: Vec<HirLine>) {
     if drops.is_empty() {
         return;
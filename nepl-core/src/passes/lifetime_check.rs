@@ -0,0 +1,390 @@
+#![no_std]
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic_ids::DiagnosticId;
+use crate::hir::{FuncRef, HirBlock, HirBody, HirExpr, HirExprKind, HirFunction, HirModule};
+use crate::span::Span;
+use crate::types::TypeCtx;
+
+/// What a reference-carrying value may point into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Origin {
+    /// Borrow of a local, indexing `LifetimeContext::borrows`.
+    Local(usize),
+    /// Whatever the caller passed in parameter `index` points into.
+    Param(usize),
+}
+
+type Origins = BTreeSet<Origin>;
+
+struct Local {
+    name: String,
+    /// Index of the scope the local belongs to.
+    depth: usize,
+    /// Origins of the references the local currently holds.
+    holds: Origins,
+}
+
+struct Scope {
+    /// Where the locals of this scope are dropped.
+    end: Span,
+    /// Visible names, later declarations shadow earlier ones.
+    names: Vec<(String, usize)>,
+}
+
+struct LifetimeContext<'a> {
+    types: &'a TypeCtx,
+    result_borrows: &'a BTreeMap<String, Option<Vec<usize>>>,
+    locals: Vec<Local>,
+    /// Borrowed local and the span of the `&` expression, per borrow.
+    borrows: Vec<(usize, Span)>,
+    scopes: Vec<Scope>,
+    /// Parameters the current function may return borrows of, if restricted.
+    allowed_params: Option<Vec<usize>>,
+    param_names: Vec<String>,
+    /// Locals already reported, so one dangling borrow yields one error.
+    reported: BTreeSet<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> LifetimeContext<'a> {
+    fn push_scope(&mut self, end: Span) {
+        self.scopes.push(Scope {
+            end,
+            names: Vec::new(),
+        });
+    }
+
+    fn declare(&mut self, name: String, holds: Origins) -> usize {
+        let id = self.locals.len();
+        let depth = self.scopes.len() - 1;
+        self.locals.push(Local {
+            name: name.clone(),
+            depth,
+            holds,
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.names.push((name, id));
+        }
+        id
+    }
+
+    fn borrow(&mut self, id: usize, span: Span) -> Origin {
+        self.borrows.push((id, span));
+        Origin::Local(self.borrows.len() - 1)
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|s| s.names.iter().rev())
+            .find(|(n, _)| n == name)
+            .map(|(_, id)| *id)
+    }
+
+    /// Closes the innermost scope. Borrows of its locals must not survive in `value`
+    /// (the value the scope evaluates to) nor in any local of an enclosing scope.
+    fn pop_scope(&mut self, value: &Origins) {
+        let depth = self.scopes.len() - 1;
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let dies = |o: &Origin| match o {
+            Origin::Local(borrow) => self.locals[self.borrows[*borrow].0].depth == depth,
+            Origin::Param(_) => false,
+        };
+        let mut dangling: Vec<Origin> = value.iter().copied().filter(|o| dies(o)).collect();
+        for local in self.locals.iter().filter(|l| l.depth < depth) {
+            dangling.extend(local.holds.iter().copied().filter(|o| dies(o)));
+        }
+        for origin in dangling {
+            self.report_dangling(origin, scope.end);
+        }
+        // Locals of a closed scope can no longer be named; keep their ids alive for
+        // origins that are already reported.
+        for (_, id) in scope.names {
+            self.locals[id].depth = usize::MAX;
+            self.locals[id].holds.clear();
+        }
+    }
+
+    fn report_dangling(&mut self, origin: Origin, dropped_at: Span) {
+        let Origin::Local(borrow) = origin else {
+            return;
+        };
+        let (id, borrow) = self.borrows[borrow];
+        if !self.reported.insert(id) {
+            return;
+        }
+        let name = self.locals[id].name.clone();
+        self.diagnostics.push(
+            Diagnostic::error(alloc::format!("`{}` does not live long enough", name), borrow)
+                .with_id(DiagnosticId::TypeBorrowOutlivesReferent)
+                .with_secondary_label(
+                    dropped_at,
+                    Some(alloc::format!("`{}` is dropped here while still borrowed", name)),
+                ),
+        );
+    }
+
+    /// Checks a value leaving the function through `return` or the body's tail.
+    fn check_result(&mut self, value: &Origins, span: Span, body_end: Span) {
+        for origin in value.iter().copied() {
+            match origin {
+                Origin::Local(_) => self.report_dangling(origin, body_end),
+                Origin::Param(index) => {
+                    let Some(allowed) = &self.allowed_params else {
+                        continue;
+                    };
+                    if !allowed.contains(&index) {
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                alloc::format!(
+                                    "returned reference borrows from `{}`, whose region differs from the result's",
+                                    self.param_names[index]
+                                ),
+                                span,
+                            )
+                            .with_id(DiagnosticId::TypeReturnedReferenceFromWrongParam),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Origins of every reference `expr` may hold once evaluated.
+    fn visit_expr(&mut self, expr: &HirExpr, body_end: Span) -> Origins {
+        match &expr.kind {
+            HirExprKind::Var(name) => match self.lookup(name) {
+                Some(id) => self.locals[id].holds.clone(),
+                None => Origins::new(),
+            },
+            HirExprKind::AddrOf(inner) => {
+                let span = expr.span.join(inner.span).unwrap_or(expr.span);
+                self.visit_addr_of(inner, span, body_end)
+            }
+            HirExprKind::Deref(inner) => {
+                let held = self.visit_expr(inner, body_end);
+                self.if_reference(expr, held)
+            }
+            HirExprKind::Call { callee, args } => {
+                let arg_origins: Vec<Origins> =
+                    args.iter().map(|a| self.visit_expr(a, body_end)).collect();
+                let borrows = match callee {
+                    FuncRef::User(name, _) => self.result_borrows.get(name).cloned().flatten(),
+                    _ => None,
+                };
+                match borrows {
+                    Some(indices) => indices
+                        .iter()
+                        .filter_map(|i| arg_origins.get(*i))
+                        .flatten()
+                        .copied()
+                        .collect(),
+                    None => self.if_reference(expr, arg_origins.into_iter().flatten().collect()),
+                }
+            }
+            HirExprKind::CallIndirect { callee, args, .. } => {
+                self.visit_expr(callee, body_end);
+                let held = args
+                    .iter()
+                    .flat_map(|a| self.visit_expr(a, body_end))
+                    .collect();
+                self.if_reference(expr, held)
+            }
+            HirExprKind::Intrinsic { args, .. } => {
+                let held = args
+                    .iter()
+                    .flat_map(|a| self.visit_expr(a, body_end))
+                    .collect();
+                self.if_reference(expr, held)
+            }
+            HirExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.visit_expr(cond, body_end);
+                let mut held = self.visit_expr(then_branch, body_end);
+                held.extend(self.visit_expr(else_branch, body_end));
+                held
+            }
+            HirExprKind::While { cond, body, .. } => {
+                self.visit_expr(cond, body_end);
+                self.visit_expr(body, body_end);
+                Origins::new()
+            }
+            HirExprKind::Return(value) => {
+                let held = self.visit_expr(value, body_end);
+                self.check_result(&held, value.span, body_end);
+                Origins::new()
+            }
+            HirExprKind::Match { scrutinee, arms } => {
+                let scrutinee_holds = self.visit_expr(scrutinee, body_end);
+                let mut held = Origins::new();
+                for arm in arms {
+                    self.push_scope(Span::empty(arm.body.span.file_id, arm.body.span.end));
+                    if let Some(bind) = &arm.bind_local {
+                        self.declare(bind.clone(), scrutinee_holds.clone());
+                    }
+                    let arm_holds = self.visit_expr(&arm.body, body_end);
+                    self.pop_scope(&arm_holds);
+                    held.extend(arm_holds);
+                }
+                held
+            }
+            HirExprKind::EnumConstruct { payload, .. } => match payload {
+                Some(payload) => self.visit_expr(payload, body_end),
+                None => Origins::new(),
+            },
+            HirExprKind::StructConstruct { fields: items, .. }
+            | HirExprKind::TupleConstruct { items } => items
+                .iter()
+                .flat_map(|item| self.visit_expr(item, body_end))
+                .collect(),
+            HirExprKind::Block(block) => self.visit_block(block, body_end),
+            HirExprKind::Let { name, value, .. } => {
+                let held = self.visit_expr(value, body_end);
+                self.declare(name.clone(), held);
+                Origins::new()
+            }
+            HirExprKind::Set { name, value } => {
+                let held = self.visit_expr(value, body_end);
+                if let Some(id) = self.lookup(name) {
+                    self.locals[id].holds.extend(held);
+                }
+                Origins::new()
+            }
+            HirExprKind::GlobalSet { value, .. } => {
+                self.visit_expr(value, body_end);
+                Origins::new()
+            }
+            HirExprKind::LiteralI32(_)
+            | HirExprKind::LiteralF32(_)
+            | HirExprKind::LiteralBool(_)
+            | HirExprKind::LiteralStr(_)
+            | HirExprKind::ConstData(_)
+            | HirExprKind::Unit
+            | HirExprKind::FnValue(_)
+            | HirExprKind::Break { .. }
+            | HirExprKind::Continue { .. }
+            | HirExprKind::GlobalGet(_)
+            | HirExprKind::Drop { .. } => Origins::new(),
+        }
+    }
+
+    /// `&place` borrows the local the place is rooted in; `&*r` reborrows what `r` points
+    /// into; any other operand is a temporary owned by the current scope.
+    fn visit_addr_of(&mut self, inner: &HirExpr, span: Span, body_end: Span) -> Origins {
+        let mut place = inner;
+        loop {
+            match &place.kind {
+                HirExprKind::Call {
+                    callee: FuncRef::Builtin(name) | FuncRef::User(name, _),
+                    args,
+                } if name == "get" && !args.is_empty() => place = &args[0],
+                _ => break,
+            }
+        }
+        match &place.kind {
+            HirExprKind::Var(name) => {
+                if !core::ptr::eq(place, inner) {
+                    self.visit_expr(inner, body_end);
+                }
+                match self.lookup(name) {
+                    Some(id) => Origins::from([self.borrow(id, span)]),
+                    None => Origins::new(),
+                }
+            }
+            HirExprKind::Deref(target) if core::ptr::eq(place, inner) => {
+                self.visit_expr(target, body_end)
+            }
+            _ => {
+                let mut held = self.visit_expr(inner, body_end);
+                let id = self.declare(String::from("temporary value"), Origins::new());
+                held.insert(self.borrow(id, span));
+                held
+            }
+        }
+    }
+
+    fn visit_block(&mut self, block: &HirBlock, body_end: Span) -> Origins {
+        self.push_scope(Span::empty(block.span.file_id, block.span.end));
+        let mut value = Origins::new();
+        for line in &block.lines {
+            let held = self.visit_expr(&line.expr, body_end);
+            if !line.drop_result {
+                value = held;
+            } else if !matches!(line.expr.kind, HirExprKind::Drop { .. }) {
+                value = Origins::new();
+            }
+        }
+        self.pop_scope(&value);
+        value
+    }
+
+    fn if_reference(&self, expr: &HirExpr, held: Origins) -> Origins {
+        if self.types.contains_reference(expr.ty) {
+            held
+        } else {
+            Origins::new()
+        }
+    }
+}
+
+fn check_function(
+    func: &HirFunction,
+    types: &TypeCtx,
+    result_borrows: &BTreeMap<String, Option<Vec<usize>>>,
+) -> Vec<Diagnostic> {
+    let HirBody::Block(body) = &func.body else {
+        return Vec::new();
+    };
+    let body_end = Span::empty(body.span.file_id, body.span.end);
+    let mut ctx = LifetimeContext {
+        types,
+        result_borrows,
+        locals: Vec::new(),
+        borrows: Vec::new(),
+        scopes: Vec::new(),
+        allowed_params: func.result_borrows.clone(),
+        param_names: func.params.iter().map(|p| p.name.clone()).collect(),
+        reported: BTreeSet::new(),
+        diagnostics: Vec::new(),
+    };
+    ctx.push_scope(body_end);
+    for (index, param) in func.params.iter().enumerate() {
+        let holds = if types.contains_reference(param.ty) {
+            Origins::from([Origin::Param(index)])
+        } else {
+            Origins::new()
+        };
+        ctx.declare(param.name.clone(), holds);
+    }
+    let value = ctx.visit_block(body, body_end);
+    ctx.check_result(&value, body.span, body_end);
+    ctx.diagnostics
+}
+
+/// Rejects references that outlive the value they borrow: borrows of locals escaping
+/// their block or the function, and returned borrows of parameters the signature
+/// does not name.
+pub fn run(module: &HirModule, types: &TypeCtx) -> Vec<Diagnostic> {
+    let result_borrows: BTreeMap<String, Option<Vec<usize>>> = module
+        .functions
+        .iter()
+        .map(|f| (f.name.clone(), f.result_borrows.clone()))
+        .collect();
+    module
+        .functions
+        .iter()
+        .flat_map(|f| check_function(f, types, &result_borrows))
+        .collect()
+}
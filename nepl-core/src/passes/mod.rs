@@ -1,5 +1,6 @@
 pub mod codegen_precheck;
 pub mod drop_insertion;
+pub mod lifetime_check;
 pub mod move_check;
 
 pub use drop_insertion::insert_drops;
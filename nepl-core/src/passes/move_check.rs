@@ -39,6 +39,15 @@ struct MoveCheckContext {
     scopes: Vec<BTreeSet<String>>,
     /// History of changes for undoing/merging branches
     history: Vec<BTreeMap<String, VarState>>,
+    /// Enclosing loops, innermost last.
+    loops: Vec<LoopExits>,
+}
+
+/// Variable states observed at the `break`/`continue` sites of one loop.
+struct LoopExits {
+    label: Option<String>,
+    breaks: BTreeMap<String, VarState>,
+    continues: BTreeMap<String, VarState>,
 }
 
 impl MoveCheckContext {
@@ -48,6 +57,7 @@ impl MoveCheckContext {
             diagnostics: Vec::new(),
             scopes: Vec::new(),
             history: Vec::new(),
+            loops: Vec::new(),
         }
     }
 
@@ -100,6 +110,38 @@ impl MoveCheckContext {
         }
     }
 
+    /// Records the current non-valid variable states at a jump to the loop named by `label`.
+    fn record_jump(&mut self, label: &Option<String>, is_break: bool) {
+        let target = match label {
+            None => self.loops.last_mut(),
+            Some(label) => self
+                .loops
+                .iter_mut()
+                .rev()
+                .find(|l| l.label.as_ref() == Some(label)),
+        };
+        let Some(target) = target else {
+            return;
+        };
+        let exits = if is_break {
+            &mut target.breaks
+        } else {
+            &mut target.continues
+        };
+        for (name, stack) in &self.var_stacks {
+            let Some(state) = stack.last().copied() else {
+                continue;
+            };
+            if matches!(state, VarState::Moved | VarState::PossiblyMoved) {
+                let merged = match exits.get(name) {
+                    Some(prev) => Self::merge_state_pair(*prev, state),
+                    None => state,
+                };
+                exits.insert(name.clone(), merged);
+            }
+        }
+    }
+
     fn push_history(&mut self) {
         self.history.push(BTreeMap::new());
     }
@@ -467,6 +509,9 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
             let mut all_modified: BTreeSet<String> = then_diff.keys().cloned().collect();
             all_modified.extend(else_diff.keys().cloned());
 
+            // A diverging branch never reaches the join point, so only the other one counts.
+            let then_diverges = then_branch.diverges();
+            let else_diverges = else_branch.diverges();
             for name in all_modified {
                 let start_state = then_diff
                     .get(&name)
@@ -477,18 +522,37 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
                 let then_state = then_final.get(&name).copied().unwrap_or(start_state);
                 let else_state = else_final.get(&name).copied().unwrap_or(start_state);
 
-                let merged = MoveCheckContext::merge_state_pair(then_state, else_state);
+                let merged = if then_diverges && !else_diverges {
+                    else_state
+                } else if else_diverges && !then_diverges {
+                    then_state
+                } else {
+                    MoveCheckContext::merge_state_pair(then_state, else_state)
+                };
                 ctx.set_state(&name, merged);
             }
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, label } => {
             visit_expr(cond, ctx, tctx);
             ctx.push_history();
+            ctx.loops.push(LoopExits {
+                label: label.clone(),
+                breaks: BTreeMap::new(),
+                continues: BTreeMap::new(),
+            });
             visit_expr(body, ctx, tctx);
+            let exits = ctx.loops.pop();
             let body_diff = ctx.pop_history();
+            let body_diverges = body.diverges();
 
             for (name, start_state) in body_diff {
-                let end_state = ctx.get_state(&name).unwrap_or(start_state);
+                let mut end_state = ctx.get_state(&name).unwrap_or(start_state);
+                if body_diverges {
+                    end_state = start_state;
+                }
+                if let Some(state) = exits.as_ref().and_then(|e| e.continues.get(&name)) {
+                    end_state = MoveCheckContext::merge_state_pair(end_state, *state);
+                }
                 let merged = MoveCheckContext::merge_state_pair(start_state, end_state);
                 ctx.set_state(&name, merged);
                 if matches!(merged, VarState::PossiblyMoved)
@@ -502,7 +566,16 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
                 }
             }
             visit_expr(cond, ctx, tctx);
+            if let Some(exits) = exits {
+                for (name, state) in exits.breaks {
+                    let current = ctx.get_state(&name).unwrap_or(VarState::Valid);
+                    ctx.set_state(&name, MoveCheckContext::merge_state_pair(current, state));
+                }
+            }
         }
+        HirExprKind::Break { label } => ctx.record_jump(label, true),
+        HirExprKind::Continue { label } => ctx.record_jump(label, false),
+        HirExprKind::Return(value) => visit_expr(value, ctx, tctx),
         HirExprKind::Match { scrutinee, arms } => {
             visit_expr(scrutinee, ctx, tctx);
 
@@ -525,7 +598,9 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
                 }
                 ctx.undo_history(&diff);
                 all_branch_diffs.push(diff);
-                all_branch_finals.push(final_states);
+                if !arm.body.diverges() {
+                    all_branch_finals.push(final_states);
+                }
             }
 
             let mut all_modified = BTreeSet::new();
@@ -544,6 +619,9 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
                 for branch_final in &all_branch_finals {
                     states.push(branch_final.get(&name).copied().unwrap_or(start_state));
                 }
+                if states.is_empty() {
+                    states.push(start_state);
+                }
                 let merged = MoveCheckContext::merge_states(&states);
                 ctx.set_state(&name, merged);
             }
@@ -557,6 +635,7 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
             visit_expr(value, ctx, tctx);
             ctx.check_assign(name, expr.span);
         }
+        HirExprKind::GlobalSet { value, .. } => visit_expr(value, ctx, tctx),
         HirExprKind::Let { name, value, .. } => {
             visit_expr(value, ctx, tctx);
 
@@ -622,10 +701,12 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
         HirExprKind::Drop { name } => {
             ctx.check_drop(name, expr.span);
         }
-        HirExprKind::LiteralI32(_)
+        HirExprKind::GlobalGet(_)
+        | HirExprKind::LiteralI32(_)
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
         | HirExprKind::Unit => {}
     }
 }
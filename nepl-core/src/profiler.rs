@@ -0,0 +1,301 @@
+//! Call-level execution profiling for wasm output.
+//!
+//! `instrument_module` wraps every function body in `enter(function)` /
+//! `leave()` calls imported from the `nepl_profile` module and reports the
+//! size passed to `alloc_raw` through `alloc(bytes)`. The host reads the
+//! runtime's instruction counter (wasmi fuel in `nepl-cli`) at every hook
+//! and feeds it to a `ProfileSession`, which charges the instructions
+//! executed since the previous hook to the current call stack.
+//!
+//! Results are keyed by source-level function names, so all monomorphized
+//! instances of a generic function are reported together.
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::ast::Effect;
+use crate::debugger::instrument_calls;
+use crate::hir::*;
+use crate::span::Span;
+use crate::types::TypeCtx;
+
+/// Import module of the profiling hooks.
+pub const PROFILE_IMPORT_MODULE: &str = "nepl_profile";
+
+const HOOK_PREFIX: &str = "__nepl_prof_";
+
+/// Allocation entry point whose size argument is reported through `alloc`.
+const ALLOC_FUNCTION: &str = "alloc_raw";
+
+/// Source-level names of the instrumented functions, indexed by the id passed to `enter`.
+#[derive(Debug, Clone)]
+pub struct ProfileInfo {
+    pub functions: Vec<String>,
+}
+
+/// Inserts the profiling hooks into every user function of `module`.
+pub fn instrument_module(types: &TypeCtx, module: &mut HirModule) -> ProfileInfo {
+    let calls = instrument_calls(types, module, HOOK_PREFIX, false, |func| {
+        let Some(size) = func.params.first() else {
+            return Vec::new();
+        };
+        if crate::debugger::source_name(&func.name) != ALLOC_FUNCTION {
+            return Vec::new();
+        }
+        let report = HirExpr {
+            ty: types.unit(),
+            kind: HirExprKind::Call {
+                callee: FuncRef::User(format!("{HOOK_PREFIX}alloc"), Vec::new()),
+                args: vec![HirExpr {
+                    ty: size.ty,
+                    kind: HirExprKind::Var(size.name.clone()),
+                    span: Span::dummy(),
+                }],
+            },
+            span: Span::dummy(),
+        };
+        vec![HirLine {
+            expr: report,
+            drop_result: true,
+        }]
+    });
+
+    let unit = types.unit();
+    let i32_ty = types.i32();
+    for (name, params) in [
+        ("enter", vec![i32_ty]),
+        ("leave", Vec::new()),
+        ("alloc", vec![i32_ty]),
+    ] {
+        module.externs.push(HirExtern {
+            module: PROFILE_IMPORT_MODULE.to_string(),
+            name: name.to_string(),
+            local_name: format!("{HOOK_PREFIX}{name}"),
+            params,
+            result: unit,
+            effect: Effect::Impure,
+            span: Span::dummy(),
+        });
+    }
+
+    ProfileInfo {
+        functions: calls.functions.into_iter().map(|f| f.name).collect(),
+    }
+}
+
+/// Totals of one source-level function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileRow {
+    pub name: String,
+    pub calls: u64,
+    /// Instructions executed in the function's own body.
+    pub self_instructions: u64,
+    /// Instructions executed while the function was on the stack.
+    pub total_instructions: u64,
+    /// Bytes allocated while the function was on the stack.
+    pub alloc_bytes: u64,
+    pub allocs: u64,
+}
+
+#[derive(Clone)]
+struct Frame {
+    function: usize,
+    entered_at: u64,
+}
+
+/// Shadow call stack and counters of one profiled run.
+#[derive(Clone)]
+pub struct ProfileSession {
+    info: ProfileInfo,
+    stack: Vec<Frame>,
+    /// Instruction counter at the previous hook.
+    last: u64,
+    /// Self instructions and allocated bytes per call path (function ids, outermost first).
+    paths: BTreeMap<Vec<usize>, (u64, u64)>,
+    rows: BTreeMap<String, ProfileRow>,
+}
+
+impl ProfileSession {
+    pub fn new(info: ProfileInfo) -> Self {
+        Self {
+            info,
+            stack: Vec::new(),
+            last: 0,
+            paths: BTreeMap::new(),
+            rows: BTreeMap::new(),
+        }
+    }
+
+    fn name(&self, function: usize) -> String {
+        self.info
+            .functions
+            .get(function)
+            .cloned()
+            .unwrap_or_else(|| format!("<fn {function}>"))
+    }
+
+    fn row(&mut self, function: usize) -> &mut ProfileRow {
+        let name = self.name(function);
+        self.rows.entry(name.clone()).or_insert_with(|| ProfileRow {
+            name,
+            ..ProfileRow::default()
+        })
+    }
+
+    fn path(&self) -> Vec<usize> {
+        self.stack.iter().map(|f| f.function).collect()
+    }
+
+    /// Charges the instructions since the previous hook to the current stack.
+    fn charge(&mut self, counter: u64) {
+        let delta = counter.saturating_sub(self.last);
+        self.last = counter;
+        let Some(top) = self.stack.last().map(|f| f.function) else {
+            return;
+        };
+        if delta == 0 {
+            return;
+        }
+        let path = self.path();
+        self.paths.entry(path).or_default().0 += delta;
+        self.row(top).self_instructions += delta;
+    }
+
+    /// Names on the stack, each once, so recursion is not counted twice.
+    fn distinct_names_on_stack(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for frame in &self.stack {
+            let name = self.name(frame.function);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    pub fn on_enter(&mut self, function: usize, counter: u64) {
+        self.charge(counter);
+        self.row(function).calls += 1;
+        self.stack.push(Frame {
+            function,
+            entered_at: counter,
+        });
+    }
+
+    pub fn on_leave(&mut self, counter: u64) {
+        self.charge(counter);
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        let name = self.name(frame.function);
+        if self.stack.iter().all(|f| self.name(f.function) != name) {
+            self.row(frame.function).total_instructions += counter - frame.entered_at;
+        }
+    }
+
+    pub fn on_alloc(&mut self, bytes: i32) {
+        let bytes = bytes.max(0) as u64;
+        if self.stack.is_empty() {
+            return;
+        }
+        let path = self.path();
+        self.paths.entry(path).or_default().1 += bytes;
+        for name in self.distinct_names_on_stack() {
+            if let Some(row) = self.rows.get_mut(&name) {
+                row.alloc_bytes += bytes;
+                row.allocs += 1;
+            }
+        }
+    }
+
+    /// Instruction counter seen at the latest hook.
+    pub fn counter(&self) -> u64 {
+        self.last
+    }
+
+    /// Closes the frames still open when the program stopped (exit or trap).
+    pub fn finish(&mut self, counter: u64) {
+        while !self.stack.is_empty() {
+            self.on_leave(counter);
+        }
+    }
+
+    /// Per-function totals, hottest (by self instructions) first.
+    pub fn rows(&self) -> Vec<ProfileRow> {
+        let mut rows: Vec<ProfileRow> = self.rows.values().cloned().collect();
+        rows.sort_by(|a, b| {
+            b.self_instructions
+                .cmp(&a.self_instructions)
+                .then(b.total_instructions.cmp(&a.total_instructions))
+                .then(a.name.cmp(&b.name))
+        });
+        rows
+    }
+
+    fn folded(&self, pick: impl Fn(&(u64, u64)) -> u64) -> String {
+        let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+        for (path, counts) in &self.paths {
+            let value = pick(counts);
+            if value == 0 {
+                continue;
+            }
+            let names: Vec<String> = path.iter().map(|f| self.name(*f)).collect();
+            *stacks.entry(names.join(";")).or_default() += value;
+        }
+        let mut out = String::new();
+        for (stack, value) in stacks {
+            out.push_str(&format!("{stack} {value}\n"));
+        }
+        out
+    }
+
+    /// Self instructions per call stack in the folded format of flamegraph tools
+    /// (`main;solve;push 1234`).
+    pub fn folded_instructions(&self) -> String {
+        self.folded(|c| c.0)
+    }
+
+    /// Allocated bytes per call stack in the folded format.
+    pub fn folded_alloc_bytes(&self) -> String {
+        self.folded(|c| c.1)
+    }
+
+    /// Sorted text table of `rows`.
+    pub fn table(&self) -> String {
+        let rows = self.rows();
+        let total: u64 = rows.iter().map(|r| r.self_instructions).sum();
+        let width = rows
+            .iter()
+            .map(|r| r.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("function".len());
+        let mut out = format!(
+            "{:<width$} {:>10} {:>14} {:>7} {:>14} {:>12} {:>8}\n",
+            "function", "calls", "self instr", "self%", "total instr", "alloc bytes", "allocs"
+        );
+        for row in rows {
+            let percent = if total == 0 {
+                0.0
+            } else {
+                row.self_instructions as f64 * 100.0 / total as f64
+            };
+            out.push_str(&format!(
+                "{:<width$} {:>10} {:>14} {:>6.2}% {:>14} {:>12} {:>8}\n",
+                row.name,
+                row.calls,
+                row.self_instructions,
+                percent,
+                row.total_instructions,
+                row.alloc_bytes,
+                row.allocs
+            ));
+        }
+        out
+    }
+}
@@ -8,7 +8,7 @@ extern crate alloc;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 
-use crate::ast::{EnumDef, FnAlias, FnDef, StructDef, Visibility};
+use crate::ast::{EnumDef, FnAlias, FnDef, StructDef, TypeDef, TypeDefKind, Visibility};
 use crate::module_graph::{ExportEntry, ExportKind, ExportTable, ModuleGraph, ModuleId};
 use crate::ast::{ImportClause, ImportItem};
 use alloc::vec::Vec;
@@ -25,6 +25,8 @@ pub enum DefKind {
     Function,
     Struct,
     Enum,
+    TypeAlias,
+    Newtype,
 }
 
 #[derive(Debug, Clone)]
@@ -111,6 +113,23 @@ pub fn collect_defs(graph: &ModuleGraph) -> DefTable {
                         },
                     );
                 }
+                crate::ast::Stmt::TypeDef(TypeDef {
+                    name, vis, kind, ..
+                }) if *vis == Visibility::Pub => {
+                    let id = DefId(next_id);
+                    next_id += 1;
+                    map.insert(
+                        name.name.clone(),
+                        DefInfo {
+                            id,
+                            kind: match kind {
+                                TypeDefKind::Alias => DefKind::TypeAlias,
+                                TypeDefKind::Newtype => DefKind::Newtype,
+                            },
+                            module: node.id,
+                        },
+                    );
+                }
                 _ => {}
             }
         }
@@ -8,7 +8,6 @@ use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
-use std::path::Path;
 
 use crate::ast::*;
 use crate::builtins::BuiltinKind;
@@ -18,6 +17,7 @@ use crate::diagnostic_ids::DiagnosticId;
 use crate::effects::{intrinsic_effect, raw_body_effect};
 use crate::hir::*;
 use crate::loader::SourceMap;
+use crate::name_resolve::{ImportAliases, SymbolTable};
 use crate::span::Span;
 use crate::types::{EnumVariantInfo, TypeCtx, TypeId, TypeKind};
 
@@ -59,6 +59,8 @@ pub struct TypeCheckResult {
     pub module: Option<HirModule>,
     pub diagnostics: Vec<Diagnostic>,
     pub types: TypeCtx,
+    /// 型検査の前に作った名前解決の結果。エディタ向け解析もこれを使う。
+    pub symbols: SymbolTable,
 }
 
 #[derive(Debug)]
@@ -84,6 +86,20 @@ struct StructInfo {
     field_names: Vec<String>,
 }
 
+#[derive(Debug, Clone)]
+struct GlobalInfo {
+    ty: TypeId,
+    kind: GlobalKind,
+}
+
+/// Module-level `const`/`static` items, plus the `#comptime` expressions met
+/// while checking bodies. Both are folded once every function is checked.
+#[derive(Debug, Default)]
+struct GlobalTable {
+    items: BTreeMap<String, GlobalInfo>,
+    comptime: Vec<(String, HirExpr)>,
+}
+
 #[derive(Debug, Clone)]
 struct TraitInfo {
     doc: Option<String>,
@@ -558,7 +574,8 @@ pub fn typecheck(
     let mut rejected_copy_targets: Vec<TypeId> = Vec::new();
     let mut pending_copy_clone_checks: Vec<(TypeId, Span)> = Vec::new();
     let mut duplicate_impl_spans: BTreeSet<(u32, u32, u32)> = BTreeSet::new();
-    let qualified_import_targets = build_qualified_import_targets(module, source_map);
+    let symbols = crate::name_resolve::resolve_names(module, source_map).symbols;
+    let qualified_import_targets = &symbols.import_aliases;
 
     let mut entry: Option<(String, Span)> = None;
     let mut externs: Vec<HirExtern> = Vec::new();
@@ -675,6 +692,140 @@ pub fn typecheck(
 
     // Builtins are defined in stdlib (e.g. std/mem) or via #extern.
 
+    // Register `type` aliases and `newtype` declarations before any other item so that
+    // signatures, struct fields and enum payloads can refer to them.
+    let mut pending_if: Option<bool> = None;
+    let mut type_defs: Vec<&TypeDef> = Vec::new();
+    for item in &module.root.items {
+        if let Stmt::Directive(d) = item {
+            if let Some(allowed) = gate_allows(d, target, profile) {
+                pending_if = Some(allowed);
+                continue;
+            }
+        }
+        let allowed = pending_if.unwrap_or(true);
+        pending_if = None;
+        if !allowed {
+            continue;
+        }
+        let Stmt::TypeDef(t) = item else {
+            continue;
+        };
+        if type_defs.iter().any(|d| d.name.name == t.name.name) {
+            continue;
+        }
+        let clashes = module.root.items.iter().any(|other| match other {
+            Stmt::StructDef(s) => s.name.name == t.name.name,
+            Stmt::EnumDef(e) => e.name.name == t.name.name,
+            _ => false,
+        });
+        if clashes {
+            diagnostics.push(
+                Diagnostic::error("name already used by another item", t.name.span)
+                    .with_id(DiagnosticId::TypeItemNameConflict),
+            );
+            continue;
+        }
+        for p in &t.type_params {
+            if !p.bounds.is_empty() {
+                diagnostics.push(
+                    Diagnostic::error(
+                        "type and newtype parameters cannot have bounds",
+                        p.name.span,
+                    )
+                    .with_id(DiagnosticId::TypeDefTypeParamBoundsUnsupported),
+                );
+            }
+        }
+        type_defs.push(t);
+    }
+    // Aliases are expanded eagerly, so each one is registered after the aliases it mentions.
+    let mut pending_aliases: Vec<&TypeDef> = type_defs
+        .iter()
+        .copied()
+        .filter(|t| t.kind == TypeDefKind::Alias)
+        .collect();
+    loop {
+        let ready = pending_aliases.iter().position(|t| {
+            let mut refs = Vec::new();
+            collect_type_expr_names(&t.target, &mut refs);
+            !refs
+                .iter()
+                .any(|r| pending_aliases.iter().any(|p| &p.name.name == r))
+        });
+        let Some(idx) = ready else {
+            break;
+        };
+        let t = pending_aliases.remove(idx);
+        let mut t_labels = LabelEnv::new();
+        let mut tps = Vec::new();
+        for p in &t.type_params {
+            let id = ctx.fresh_var(Some(p.name.name.clone()));
+            t_labels.insert(p.name.name.clone(), id);
+            tps.push(id);
+        }
+        let target_ty = type_from_expr(&mut ctx, &mut t_labels, &t.target);
+        ctx.register_alias(t.name.name.clone(), tps, target_ty);
+    }
+    for t in pending_aliases {
+        diagnostics.push(
+            Diagnostic::error("type alias refers to itself", t.name.span)
+                .with_id(DiagnosticId::TypeAliasCycle),
+        );
+    }
+    for t in type_defs.iter().filter(|t| t.kind == TypeDefKind::Newtype) {
+        let mut t_labels = LabelEnv::new();
+        let mut tps = Vec::new();
+        for p in &t.type_params {
+            let id = ctx.fresh_var(Some(p.name.name.clone()));
+            t_labels.insert(p.name.name.clone(), id);
+            tps.push(id);
+        }
+        let inner = type_from_expr(&mut ctx, &mut t_labels, &t.target);
+        let ty = ctx.register_named(
+            t.name.name.clone(),
+            TypeKind::Newtype {
+                doc: t.doc.clone(),
+                name: t.name.name.clone(),
+                type_params: tps.clone(),
+                inner,
+            },
+        );
+        label_env.insert(t.name.name.clone(), ty);
+        let outer = if tps.is_empty() {
+            ty
+        } else {
+            ctx.apply(ty, tps.clone())
+        };
+        // `Name x` wraps and `Name::inner x` unwraps; both are free at runtime.
+        let conversions = [
+            (t.name.name.clone(), inner, outer),
+            (format!("{}::inner", t.name.name), outer, inner),
+        ];
+        for (fname, from, to) in conversions {
+            let func_ty = ctx.function(tps.clone(), vec![from], to, Effect::Pure);
+            env.insert_global(Binding {
+                name: fname.clone(),
+                ty: func_ty,
+                mutable: false,
+                no_shadow: false,
+                defined: true,
+                moved: false,
+                span: t.name.span,
+                kind: BindingKind::Func {
+                    symbol: fname,
+                    effect: Effect::Pure,
+                    arity: 1,
+                    builtin: None,
+                    field_accessor: None,
+                    type_param_bounds: BTreeMap::new(),
+                    captures: Vec::new(),
+                },
+            });
+        }
+    }
+    check_alias_arity_in_items(&ctx, &module.root.items, &mut diagnostics);
+
     // Collect top-level function signatures (hoist)
     // Also hoist struct/enum definitions
     let mut pending_if: Option<bool> = None;
@@ -1053,17 +1204,6 @@ pub fn typecheck(
                 }
                 trait_self_ty = traits.get(tn).map(|info| info.self_ty);
             }
-            if !i.type_params.is_empty()
-                && !trait_semantics.has_copy_capability(trait_self_ty)
-                && !trait_semantics.has_clone_capability(trait_self_ty)
-                && !trait_semantics.has_drop_capability(trait_self_ty)
-            {
-                diagnostics.push(
-                    Diagnostic::error("impl type parameters are not supported yet", i.span)
-                        .with_id(DiagnosticId::TypeImplTypeParamsUnsupported),
-                );
-                continue;
-            }
             let mut f_labels = LabelEnv::new();
             let (tps, _bounds_vec, impl_bounds_map) =
                 collect_type_params(&mut ctx, &mut f_labels, &i.type_params, &traits, &mut diagnostics);
@@ -1095,18 +1235,6 @@ pub fn typecheck(
                 trait_name.clone().unwrap_or_default()
             };
             f_labels.insert(String::from("Self"), target_ty);
-            let generic_impl_target = type_contains_unbound_var(&ctx, target_ty);
-            if generic_impl_target
-                && !trait_semantics.has_copy_capability(trait_self_ty)
-                && !trait_semantics.has_clone_capability(trait_self_ty)
-                && !trait_semantics.has_drop_capability(trait_self_ty)
-            {
-                diagnostics.push(
-                    Diagnostic::error("impl target type must be concrete", i.target_ty.span())
-                        .with_id(DiagnosticId::TypeImplTargetMustBeConcrete),
-                );
-                continue;
-            }
             if trait_semantics.has_copy_capability(trait_self_ty) {
                 if !ctx.is_copy_impl_eligible(target_ty) {
                     diagnostics.push(
@@ -1551,7 +1679,129 @@ pub fn typecheck(
         }
     }
 
+    // Hoist const/static items so function bodies can refer to them.
+    let mut globals = GlobalTable::default();
+    let mut global_defs: Vec<&GlobalDef> = Vec::new();
+    pending_if = None;
+    for item in &module.root.items {
+        if let Stmt::Directive(d) = item {
+            if let Some(allowed) = gate_allows(d, target, profile) {
+                pending_if = Some(allowed);
+                continue;
+            }
+        }
+        let allowed = pending_if.unwrap_or(true);
+        pending_if = None;
+        let Stmt::Global(g) = item else {
+            continue;
+        };
+        if !allowed {
+            continue;
+        }
+        if env.lookup_any_defined(&g.name.name).is_some()
+            || structs.contains_key(&g.name.name)
+            || enums.contains_key(&g.name.name)
+        {
+            diagnostics.push(
+                Diagnostic::error("name already used by another item", g.name.span)
+                    .with_id(DiagnosticId::TypeItemNameConflict),
+            );
+            continue;
+        }
+        let ty = type_from_expr(&mut ctx, &mut label_env, &g.ty);
+        if matches!(g.kind, GlobalKind::Static { .. })
+            && !matches!(
+                ctx.get(ctx.resolve_id(ty)),
+                TypeKind::I32 | TypeKind::U8 | TypeKind::F32 | TypeKind::Bool
+            )
+        {
+            diagnostics.push(
+                Diagnostic::error("static items must have a scalar type", g.name.span)
+                    .with_id(DiagnosticId::TypeStaticTypeUnsupported),
+            );
+            continue;
+        }
+        env.insert_global(Binding {
+            name: g.name.name.clone(),
+            ty,
+            mutable: matches!(g.kind, GlobalKind::Static { mutable: true }),
+            no_shadow: false,
+            defined: true,
+            moved: false,
+            span: g.name.span,
+            kind: BindingKind::Var,
+        });
+        globals
+            .items
+            .insert(g.name.name.clone(), GlobalInfo { ty, kind: g.kind });
+        global_defs.push(g);
+    }
+
+    // Initializers are checked like the body of a pure `fn <()->T> ()`.
+    let mut global_inits: BTreeMap<String, HirExpr> = BTreeMap::new();
+    for g in &global_defs {
+        let ty = globals.items[&g.name.name].ty;
+        let init_fn = FnDef {
+            doc: None,
+            vis: Visibility::Private,
+            name: g.name.clone(),
+            no_shadow: false,
+            regions: Vec::new(),
+            type_params: Vec::new(),
+            signature: TypeExpr::Function {
+                params: Vec::new(),
+                result: Box::new(g.ty.clone()),
+                effect: Effect::Pure,
+            },
+            params: Vec::new(),
+            body: FnBody::Parsed(Block {
+                items: vec![Stmt::Expr(g.value.clone())],
+                span: g.span,
+            }),
+        };
+        let init_ty = ctx.function(Vec::new(), Vec::new(), ty, Effect::Pure);
+        let mut nested_functions = Vec::new();
+        match check_function(
+            &init_fn,
+            init_ty,
+            false,
+            target,
+            profile,
+            &[],
+            &mut ctx,
+            &mut env,
+            &mut label_env,
+            &mut strings,
+            &enums,
+            &structs,
+            &mut globals,
+            &mut instantiations,
+            BTreeMap::new(),
+            &traits,
+            &impls,
+            &mut nested_functions,
+            qualified_import_targets,
+        ) {
+            Ok(checked) => {
+                diagnostics.extend(checked.diagnostics);
+                if let HirBody::Block(block) = checked.function.body {
+                    global_inits.insert(
+                        g.name.name.clone(),
+                        HirExpr {
+                            ty,
+                            span: g.value.span,
+                            kind: HirExprKind::Block(block),
+                        },
+                    );
+                }
+            }
+            Err(mut diags) => diagnostics.append(&mut diags),
+        }
+    }
+
     let mut functions = Vec::new();
+    let mut exports = Vec::new();
+    let mut raw_wasm_bodies: BTreeMap<String, (Vec<String>, WasmBlock)> = BTreeMap::new();
     let mut pending_if = None;
     for item in &module.root.items {
         if let Stmt::Directive(d) = item {
@@ -1679,15 +1929,30 @@ pub fn typecheck(
                 &mut strings,
                 &enums,
                 &structs,
+                &mut globals,
                 &mut instantiations,
                 type_param_bounds,
                 &traits,
                 &impls,
                 &mut nested_functions,
-                &qualified_import_targets,
+                qualified_import_targets,
             ) {
                 Ok(checked) => {
                     diagnostics.extend(checked.diagnostics);
+                    if let Some(wb) = fn_wasm_body(f) {
+                        let params = f.params.iter().map(|p| p.name.clone()).collect();
+                        raw_wasm_bodies.insert(checked.function.name.clone(), (params, wb.clone()));
+                    }
+                    if f.vis == Visibility::Pub
+                        && f.type_params.is_empty()
+                        && f.name.span.file_id == module.root.span.file_id
+                        && entry.as_ref().map(|(n, _)| n != &f.name.name).unwrap_or(true)
+                    {
+                        exports.push(HirExport {
+                            name: f.name.name.clone(),
+                            symbol: checked.function.name.clone(),
+                        });
+                    }
                     functions.push(checked.function);
                     functions.extend(nested_functions);
                 }
@@ -1752,17 +2017,6 @@ pub fn typecheck(
                     continue;
                 }
             };
-            if !i.type_params.is_empty()
-                && !trait_semantics.has_copy_capability(Some(trait_info.self_ty))
-                && !trait_semantics.has_clone_capability(Some(trait_info.self_ty))
-                && !trait_semantics.has_drop_capability(Some(trait_info.self_ty))
-            {
-                diagnostics.push(
-                    Diagnostic::error("impl type parameters are not supported yet", i.span)
-                        .with_id(DiagnosticId::TypeImplTypeParamsUnsupported),
-                );
-                continue;
-            }
 
             let mut impl_methods = Vec::new();
             let mut f_labels = LabelEnv::new();
@@ -1790,17 +2044,6 @@ pub fn typecheck(
                 .map(|arg| type_from_expr(&mut ctx, &mut f_labels, arg))
                 .collect();
             let applied_trait_name = format_trait_ref_name(&trait_name, &trait_args, &ctx);
-            if type_contains_unbound_var(&ctx, target_ty)
-                && !trait_semantics.has_copy_capability(Some(trait_info.self_ty))
-                && !trait_semantics.has_clone_capability(Some(trait_info.self_ty))
-                && !trait_semantics.has_drop_capability(Some(trait_info.self_ty))
-            {
-                diagnostics.push(
-                    Diagnostic::error("impl target type must be concrete", i.target_ty.span())
-                        .with_id(DiagnosticId::TypeImplTargetMustBeConcrete),
-                );
-                continue;
-            }
             if trait_semantics.has_copy_capability(Some(trait_info.self_ty)) {
                 if contains_same_type(&ctx, &rejected_copy_targets, target_ty) {
                     continue;
@@ -1881,12 +2124,13 @@ pub fn typecheck(
                     &mut strings,
                     &enums,
                     &structs,
+                    &mut globals,
                     &mut instantiations,
                     impl_bounds_map.clone(),
                     &traits,
                     &impls,
                     &mut nested_functions,
-                    &qualified_import_targets,
+                    qualified_import_targets,
                 ) {
                     Ok(checked) => checked,
                     Err(mut diags) => {
@@ -1937,6 +2181,72 @@ pub fn typecheck(
         }
     }
 
+    // Fold const/static initializers and `#comptime` expressions, then inline
+    // every constant read.
+    let mut hir_globals = Vec::new();
+    let mut const_data = Vec::new();
+    {
+        let mut const_inits: BTreeMap<String, HirExpr> = global_inits
+            .iter()
+            .filter(|(name, _)| matches!(globals.items[*name].kind, GlobalKind::Const))
+            .map(|(name, init)| (name.clone(), init.clone()))
+            .collect();
+        const_inits.extend(globals.comptime.iter().cloned());
+        let mut evaluator = crate::const_eval::ConstEvaluator::new(
+            &ctx,
+            &strings.items,
+            &functions,
+            &raw_wasm_bodies,
+            &const_inits,
+        );
+        for g in &global_defs {
+            let Some(init) = global_inits.get(&g.name.name) else {
+                continue;
+            };
+            let folded = match g.kind {
+                GlobalKind::Const => evaluator.eval_const(&g.name.name, g.name.span),
+                GlobalKind::Static { .. } => evaluator.eval_expr(init),
+            };
+            match (folded, g.kind) {
+                (Ok(_), GlobalKind::Const) => {}
+                (Ok(init), GlobalKind::Static { mutable }) => hir_globals.push(HirGlobal {
+                    doc: g.doc.clone(),
+                    name: g.name.name.clone(),
+                    ty: globals.items[&g.name.name].ty,
+                    mutable,
+                    init,
+                    span: g.name.span,
+                }),
+                (Err(err), _) => diagnostics.push(
+                    const_eval_diagnostic(err)
+                        .with_secondary_label(g.name.span, Some("in the initializer of this item".into())),
+                ),
+            }
+        }
+        for (name, expr) in &globals.comptime {
+            if let Err(err) = evaluator.eval_const(name, expr.span) {
+                diagnostics.push(const_eval_diagnostic(err));
+            }
+        }
+        let values: BTreeMap<String, HirExpr> = evaluator
+            .values()
+            .iter()
+            .map(|(name, value)| (name.clone(), evaluator.materialize(value, &mut const_data)))
+            .collect();
+        for func in functions.iter_mut() {
+            if let HirBody::Block(block) = &mut func.body {
+                crate::const_eval::inline_consts_in_block(block, &values);
+            }
+        }
+        for imp in final_impls.iter_mut() {
+            for m in imp.methods.iter_mut() {
+                if let HirBody::Block(block) = &mut m.func.body {
+                    crate::const_eval::inline_consts_in_block(block, &values);
+                }
+            }
+        }
+    }
+
     let resolved_entry = if let Some((name, entry_span)) = entry {
         let bindings = env.lookup_all_callables(&name);
         let mut func_symbols = Vec::new();
@@ -1976,17 +2286,48 @@ pub fn typecheck(
                 string_literals: strings.into_vec(),
                 traits: final_traits,
                 impls: final_impls,
+                globals: hir_globals,
+                const_data,
+                exports,
             })
         },
         diagnostics,
         types: ctx,
+        symbols,
+    }
+}
+
+/// Reports a failed compile-time evaluation, listing the calls that led to it.
+fn const_eval_diagnostic(err: crate::const_eval::ConstEvalError) -> Diagnostic {
+    use crate::const_eval::ConstEvalErrorKind;
+    let id = match err.kind {
+        ConstEvalErrorKind::NotConstant => DiagnosticId::TypeConstNotConstant,
+        ConstEvalErrorKind::Failed => DiagnosticId::TypeConstEvalFailed,
+        ConstEvalErrorKind::FuelExhausted => DiagnosticId::TypeConstEvalFuelExhausted,
+    };
+    let mut diag = Diagnostic::error(err.message, err.span).with_id(id);
+    for (callee, site) in err.call_stack {
+        diag = diag.with_secondary_label(site, Some(format!("in compile-time call to '{}'", callee)));
     }
+    diag
 }
 
 // ---------------------------------------------------------------------
 // Function checking
 // ---------------------------------------------------------------------
 
+/// The `#wasm` body of `f`, whether written directly or behind target gates.
+fn fn_wasm_body(f: &FnDef) -> Option<&WasmBlock> {
+    match &f.body {
+        FnBody::Wasm(wb) => Some(wb),
+        FnBody::Parsed(block) => block.items.iter().find_map(|item| match item {
+            Stmt::Wasm(wb) => Some(wb),
+            _ => None,
+        }),
+        FnBody::LlvmIr(_) => None,
+    }
+}
+
 fn check_function(
     f: &FnDef,
     func_ty: TypeId,
@@ -2000,12 +2341,13 @@ fn check_function(
     strings: &mut StringTable,
     enums: &BTreeMap<String, EnumInfo>,
     structs: &BTreeMap<String, StructInfo>,
+    globals: &mut GlobalTable,
     instantiations: &mut BTreeMap<String, Vec<Vec<TypeId>>>,
     type_param_bounds: BTreeMap<TypeId, Vec<TraitBoundRef>>,
     traits: &BTreeMap<String, TraitInfo>,
     impls: &Vec<ImplInfo>,
     generated_functions: &mut Vec<HirFunction>,
-    qualified_import_targets: &BTreeMap<u32, BTreeMap<String, BTreeSet<u32>>>,
+    qualified_import_targets: &ImportAliases,
 ) -> Result<CheckedFunction, Vec<Diagnostic>> {
     let mut diags = Vec::new();
     let func_ty_snapshot = ctx.snapshot_type_var_bindings(func_ty);
@@ -2082,6 +2424,7 @@ fn check_function(
             current_effect: effect,
             enums,
             structs,
+            globals,
             instantiations,
             type_param_bounds: type_param_bounds.clone(),
             qualified_import_targets,
@@ -2090,6 +2433,8 @@ fn check_function(
             generated_functions,
             target,
             profile,
+            return_ty: result_ty,
+            loop_labels: BTreeMap::new(),
         };
 
         let body_res = match &f.body {
@@ -2103,6 +2448,7 @@ fn check_function(
                 } else {
                     match checker.check_block(b, 0, true, Some(result_ty)) {
                         Some((blk, _val)) => {
+                            check_jump_targets_in_block(&blk, &mut Vec::new(), &mut checker.diagnostics);
                             if checker.ctx.unify(blk.ty, result_ty).is_err() {
                                 checker.diagnostics.push(Diagnostic::error(
                                     "return type does not match signature",
@@ -2172,6 +2518,19 @@ fn check_function(
         }
     }
     env.pop_scope();
+    let result_borrows = infer_result_borrows(
+        f,
+        &params_ty[captured_params.len().min(params_ty.len())..],
+        result_ty,
+        ctx,
+        &mut diag_out,
+    )
+    .map(|borrows| {
+        borrows
+            .into_iter()
+            .map(|i| i + captured_params.len())
+            .collect()
+    });
     let has_error = diag_out
         .iter()
         .any(|d| matches!(d.severity, crate::diagnostic::Severity::Error));
@@ -2212,6 +2571,7 @@ fn check_function(
                 out
             },
             result: result_ty,
+            result_borrows,
             effect,
             body,
             span: f.name.span,
@@ -2264,17 +2624,39 @@ struct BlockChecker<'a> {
     current_effect: Effect,
     enums: &'a BTreeMap<String, EnumInfo>,
     structs: &'a BTreeMap<String, StructInfo>,
+    /// Module-level `const`/`static` items, visible wherever no local shadows them.
+    globals: &'a mut GlobalTable,
     instantiations: &'a mut BTreeMap<String, Vec<Vec<TypeId>>>, // new
     type_param_bounds: BTreeMap<TypeId, Vec<TraitBoundRef>>,
-    qualified_import_targets: &'a BTreeMap<u32, BTreeMap<String, BTreeSet<u32>>>,
+    qualified_import_targets: &'a ImportAliases,
     traits: &'a BTreeMap<String, TraitInfo>,
     impls: &'a Vec<ImplInfo>,
     generated_functions: &'a mut Vec<HirFunction>,
     target: CompileTarget,
     profile: BuildProfile,
+    /// Declared result type of the function being checked; `return` arguments unify with it.
+    return_ty: TypeId,
+    /// Labels attached to `while` keywords, keyed by the keyword span.
+    loop_labels: BTreeMap<(u32, u32), String>,
 }
 
 impl<'a> BlockChecker<'a> {
+    /// Lowers a read of `name` when it resolves to a module-level `const`/`static`
+    /// rather than a local. Constants are inlined once their value is folded.
+    fn global_read(&mut self, name: &str, span: Span) -> Option<HirExprKind> {
+        let (_, scope) = self.env.lookup_value_with_scope(name)?;
+        let info = self.globals.items.get(name).filter(|_| scope == 0)?;
+        if matches!(info.kind, GlobalKind::Static { mutable: true })
+            && matches!(self.current_effect, Effect::Pure)
+        {
+            self.diagnostics.push(
+                Diagnostic::error("pure context cannot read a mutable static", span)
+                    .with_id(DiagnosticId::TypePureReadsMutableStatic),
+            );
+        }
+        Some(HirExprKind::GlobalGet(name.to_string()))
+    }
+
     fn lookup_qualified_bindings(&self, id: &Ident) -> Option<(String, Vec<Binding>)> {
         let (ns, member) = parse_variant_name(&id.name)?;
         if self.enums.contains_key(ns) || self.traits.contains_key(ns) {
@@ -2387,6 +2769,10 @@ impl<'a> BlockChecker<'a> {
                         Self::collect_bound_names_from_block(&arm.body, out);
                     }
                 }
+                PrefixItem::For(f, _) => {
+                    out.insert(f.binding.name.clone());
+                    Self::collect_bound_names_from_block(&f.body, out);
+                }
                 _ => {}
             }
         }
@@ -2421,6 +2807,10 @@ impl<'a> BlockChecker<'a> {
                         Self::collect_ref_names_from_block(&arm.body, out);
                     }
                 }
+                PrefixItem::For(f, _) => {
+                    Self::collect_ref_names_from_prefix(&f.iterable, out);
+                    Self::collect_ref_names_from_block(&f.body, out);
+                }
                 PrefixItem::Tuple(items, _) => {
                     for it in items {
                         Self::collect_ref_names_from_prefix(it, out);
@@ -2652,6 +3042,21 @@ impl<'a> BlockChecker<'a> {
             return None;
         };
         if entry.type_args.is_empty() {
+            if type_params.is_empty() {
+                return Some((params, result, effect));
+            }
+            // Work on a fresh instantiation so that peeking at a generic callee's
+            // parameters never binds the declaration's own type variables.
+            let (inst_ty, _fresh, _mapping) = self.ctx.instantiate(rty);
+            let TypeKind::Function {
+                params,
+                result,
+                effect,
+                ..
+            } = self.ctx.get(inst_ty)
+            else {
+                return None;
+            };
             return Some((params, result, effect));
         }
         if type_params.len() != entry.type_args.len() {
@@ -3038,6 +3443,92 @@ impl<'a> BlockChecker<'a> {
         inferred
     }
 
+    /// Replaces trait arguments that could not be inferred from the call with the
+    /// call site's own type variables, and links inferred ones to them.
+    fn bind_call_site_trait_args(
+        &mut self,
+        trait_info: &TraitInfo,
+        inferred: Vec<TypeId>,
+        type_args: &[TypeId],
+    ) -> Vec<TypeId> {
+        inferred
+            .into_iter()
+            .zip(trait_info.type_params.iter())
+            .enumerate()
+            .map(|(idx, (arg, tp))| {
+                let Some(site_var) = type_args.get(idx + 1).copied() else {
+                    return arg;
+                };
+                if self.ctx.resolve_id(arg) == self.ctx.resolve_id(*tp) {
+                    site_var
+                } else {
+                    let _ = self.ctx.unify(site_var, arg);
+                    arg
+                }
+            })
+            .collect()
+    }
+
+    /// Treats trait type arguments as associated items of the implementing type:
+    /// when a call leaves some of them open, they are taken from the single impl
+    /// (or type parameter bound) that applies to `self_ty`.
+    fn resolve_associated_trait_args(
+        &mut self,
+        trait_name: &str,
+        self_ty: TypeId,
+        trait_args: &mut [TypeId],
+    ) {
+        if !trait_args
+            .iter()
+            .any(|arg| type_contains_unbound_var(self.ctx, *arg))
+        {
+            return;
+        }
+        let resolved_self = self.ctx.resolve_id(self_ty);
+        let mut candidates: Vec<Vec<TypeId>> = Vec::new();
+        for (tp, bounds) in self.type_param_bounds.iter() {
+            if self.ctx.resolve_id(*tp) != resolved_self {
+                continue;
+            }
+            for b in bounds {
+                if b.trait_base_name == trait_name && b.trait_args.len() == trait_args.len() {
+                    candidates.push(b.trait_args.clone());
+                }
+            }
+        }
+        if candidates.is_empty() {
+            for imp in self.impls.iter() {
+                if imp.trait_base_name.as_deref() != Some(trait_name)
+                    || imp.trait_args.len() != trait_args.len()
+                {
+                    continue;
+                }
+                let Some(bindings) = self
+                    .ctx
+                    .type_pattern_bindings(&[(imp.target_ty, resolved_self)])
+                else {
+                    continue;
+                };
+                let applied: Vec<TypeId> = imp
+                    .trait_args
+                    .iter()
+                    .map(|arg| self.ctx.substitute(*arg, &bindings))
+                    .collect();
+                if trait_application_matches(self.ctx, trait_name, trait_args, trait_name, &applied) {
+                    candidates.push(applied);
+                }
+            }
+        }
+        if candidates.len() != 1 {
+            return;
+        }
+        for (arg, assoc) in trait_args.iter_mut().zip(candidates[0].iter()) {
+            if self.ctx.unify(*arg, *assoc).is_ok() {
+                *arg = self.ctx.resolve_id(*arg);
+            }
+        }
+    }
+
     fn resolve_field_access(
         &mut self,
         base_ty: TypeId,
@@ -3708,6 +4199,7 @@ impl<'a> BlockChecker<'a> {
                         self.string_table,
                         self.enums,
                         self.structs,
+                        &mut *self.globals,
                         self.instantiations,
                         nested_bounds,
                         self.traits,
@@ -3736,6 +4228,18 @@ impl<'a> BlockChecker<'a> {
                         block.span,
                     ));
                 }
+                Stmt::Global(g) => {
+                    self.diagnostics.push(Diagnostic::error(
+                        "const and static items are only allowed at module level",
+                        g.span,
+                    ));
+                }
+                Stmt::TypeDef(t) => {
+                    self.diagnostics.push(Diagnostic::error(
+                        "type and newtype declarations are only allowed at module level",
+                        t.span,
+                    ));
+                }
                 Stmt::Trait(_) | Stmt::Impl(_) => {}
             }
         }
@@ -4051,7 +4555,10 @@ impl<'a> BlockChecker<'a> {
                                     {
                                         HirExprKind::FnValue(symbol.clone())
                                     }
-                                    _ => HirExprKind::Var(id.name.clone()),
+                                    BindingKind::Func { .. } => HirExprKind::Var(id.name.clone()),
+                                    BindingKind::Var => self
+                                        .global_read(&id.name, id.span)
+                                        .unwrap_or_else(|| HirExprKind::Var(id.name.clone())),
                                 };
                                 let explicit_args = match binding.kind {
                                     BindingKind::Func { .. } => {
@@ -4235,11 +4742,14 @@ impl<'a> BlockChecker<'a> {
                                         ).with_id(DiagnosticId::TypeVariableTypeArgsNotAllowed));
                                     }
                                     let ty = binding.ty;
+                                    let kind = self
+                                        .global_read(&lookup_name, id.span)
+                                        .unwrap_or_else(|| HirExprKind::Var(lookup_name.clone()));
                                     stack.push(StackEntry {
                                         ty,
                                         expr: HirExpr {
                                             ty,
-                                            kind: HirExprKind::Var(lookup_name.clone()),
+                                            kind,
                                             span: id.span,
                                         },
                                         type_args: Vec::new(),
@@ -4461,6 +4971,14 @@ impl<'a> BlockChecker<'a> {
                                                 self.ctx.resolve_id(trait_info.self_ty),
                                                 method_self,
                                             );
+                                            // Each call site gets its own trait arguments so that
+                                            // inferring them does not bind the trait declaration.
+                                            let mut method_type_args = vec![method_self];
+                                            for tp in &trait_info.type_params {
+                                                let fresh = self.ctx.fresh_var(None);
+                                                mapping.insert(self.ctx.resolve_id(*tp), fresh);
+                                                method_type_args.push(fresh);
+                                            }
                                             let inst_ty = self.ctx.substitute(*sig, &mapping);
                                             stack.push(StackEntry {
                                                 ty: inst_ty,
@@ -4473,7 +4991,7 @@ impl<'a> BlockChecker<'a> {
                                                     },
                                                     span: id.span,
                                                 },
-                                                type_args: vec![method_self],
+                                                type_args: method_type_args,
                                                 assign: None,
                                                 auto_call: !*forced_value,
                                             });
@@ -4680,6 +5198,22 @@ impl<'a> BlockChecker<'a> {
                         });
                         last_expr = Some(stack.last().unwrap().expr.clone());
                     }
+                    Symbol::Comptime(sp) => {
+                        let a = self.ctx.fresh_var(None);
+                        let func_ty = self.ctx.function(Vec::new(), vec![a], a, Effect::Pure);
+                        stack.push(StackEntry {
+                            ty: func_ty,
+                            expr: HirExpr {
+                                ty: func_ty,
+                                kind: HirExprKind::Var("#comptime".to_string()),
+                                span: *sp,
+                            },
+                            type_args: Vec::new(),
+                            assign: None,
+                            auto_call: true,
+                        });
+                        last_expr = Some(stack.last().unwrap().expr.clone());
+                    }
                     Symbol::If(sp) => {
                         let t_cond = self.ctx.bool();
                         let t_branch = self.ctx.fresh_var(None);
@@ -4703,7 +5237,10 @@ impl<'a> BlockChecker<'a> {
                         // defer applying ascription until the expression is complete
                         last_expr = Some(stack.last().unwrap().expr.clone());
                     }
-                    Symbol::While(sp) => {
+                    Symbol::While(sp, label) => {
+                        if let Some(label) = label {
+                            self.loop_labels.insert((sp.start, sp.end), label.clone());
+                        }
                         let t_cond = self.ctx.bool();
                         let func_ty = self.ctx.function(
                             Vec::new(),
@@ -4725,29 +5262,87 @@ impl<'a> BlockChecker<'a> {
                         // defer applying ascription until the expression is complete
                         last_expr = Some(stack.last().unwrap().expr.clone());
                     }
-                },
-                PrefixItem::Intrinsic(intrin, sp) => {
-                    let intrin_effect = intrinsic_effect(&intrin.name);
-                    if matches!(self.current_effect, Effect::Pure) && matches!(intrin_effect, Effect::Impure)
-                    {
-                        self.diagnostics.push(Diagnostic::error(
-                            "pure context cannot call impure function",
-                            *sp,
-                        ).with_id(DiagnosticId::TypePureCallsImpureFunction));
-                        return None;
-                    }
-
-                    let mut type_args = Vec::new();
-                    for t in &intrin.type_args {
-                        type_args.push(type_from_expr(self.ctx, self.labels, t));
-                    }
-                    
-                    let mut args = Vec::new();
-                    for arg in &intrin.args {
-                        let mut arg_stack = Vec::new();
-                        if let Some((hexpr, _)) =
-                            self.check_prefix(arg, 0, &mut arg_stack, None)
-                        {
+                    Symbol::Break { label, span } | Symbol::Continue { label, span } => {
+                        // Targets are validated once the body is built (see `check_jump_targets`).
+                        let never = self.ctx.never();
+                        let kind = if matches!(sym, Symbol::Break { .. }) {
+                            HirExprKind::Break { label: label.clone() }
+                        } else {
+                            HirExprKind::Continue { label: label.clone() }
+                        };
+                        stack.push(StackEntry {
+                            ty: never,
+                            expr: HirExpr {
+                                ty: never,
+                                kind,
+                                span: *span,
+                            },
+                            type_args: Vec::new(),
+                            assign: None,
+                            auto_call: true,
+                        });
+                        last_expr = Some(stack.last().unwrap().expr.clone());
+                    }
+                    Symbol::Try(sp) => {
+                        let a = self.ctx.fresh_var(None);
+                        let b = self.ctx.fresh_var(None);
+                        let func_ty = self.ctx.function(Vec::new(), vec![a], b, Effect::Pure);
+                        stack.push(StackEntry {
+                            ty: func_ty,
+                            expr: HirExpr {
+                                ty: func_ty,
+                                kind: HirExprKind::Var("try".to_string()),
+                                span: *sp,
+                            },
+                            type_args: Vec::new(),
+                            assign: None,
+                            auto_call: true,
+                        });
+                        last_expr = Some(stack.last().unwrap().expr.clone());
+                    }
+                    Symbol::Return(sp) => {
+                        let func_ty = self.ctx.function(
+                            Vec::new(),
+                            vec![self.return_ty],
+                            self.ctx.never(),
+                            Effect::Pure,
+                        );
+                        stack.push(StackEntry {
+                            ty: func_ty,
+                            expr: HirExpr {
+                                ty: func_ty,
+                                kind: HirExprKind::Var("return".to_string()),
+                                span: *sp,
+                            },
+                            type_args: Vec::new(),
+                            assign: None,
+                            auto_call: true,
+                        });
+                        last_expr = Some(stack.last().unwrap().expr.clone());
+                    }
+                },
+                PrefixItem::Intrinsic(intrin, sp) => {
+                    let intrin_effect = intrinsic_effect(&intrin.name);
+                    if matches!(self.current_effect, Effect::Pure) && matches!(intrin_effect, Effect::Impure)
+                    {
+                        self.diagnostics.push(Diagnostic::error(
+                            "pure context cannot call impure function",
+                            *sp,
+                        ).with_id(DiagnosticId::TypePureCallsImpureFunction));
+                        return None;
+                    }
+
+                    let mut type_args = Vec::new();
+                    for t in &intrin.type_args {
+                        type_args.push(type_from_expr(self.ctx, self.labels, t));
+                    }
+                    
+                    let mut args = Vec::new();
+                    for arg in &intrin.args {
+                        let mut arg_stack = Vec::new();
+                        if let Some((hexpr, _)) =
+                            self.check_prefix(arg, 0, &mut arg_stack, None)
+                        {
                             args.push(hexpr);
                         } else {
                              return None;
@@ -5052,6 +5647,17 @@ impl<'a> BlockChecker<'a> {
                     // record target type and current stack depth; do NOT treat as an expression
                     pending_ascription = Some((ty, stack.len()));
                 }
+                PrefixItem::For(fexpr, _sp) => {
+                    let hexpr = self.check_for_expr(fexpr)?;
+                    stack.push(StackEntry {
+                        ty: hexpr.ty,
+                        expr: hexpr,
+                        type_args: Vec::new(),
+                        assign: None,
+                        auto_call: true,
+                    });
+                    last_expr = Some(stack.last().unwrap().expr.clone());
+                }
                 PrefixItem::Match(mexpr, _sp) => {
                     if let Some((hexpr, ty)) = self.check_match_expr(mexpr) {
                         stack.push(StackEntry {
@@ -5775,6 +6381,309 @@ impl<'a> BlockChecker<'a> {
                 break;
             }
         }
+    }
+
+    /// Checks `for x in it: body` as the block produced by `desugar_for_expr`,
+    /// rejecting iterables without an `Iterator` impl before the loop is checked.
+    fn check_for_expr(&mut self, f: &ForExpr) -> Option<HirExpr> {
+        let (iter_name, init, step) = Self::desugar_for_expr(f);
+        self.env.push_scope();
+        let lines = self.check_block(&init, 0, false, None).and_then(|(init_blk, _)| {
+            let iter_ty = self.env.lookup_value(&iter_name)?.ty;
+            if !self.implements_trait(iter_ty, "Iterator") {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!(
+                            "'{}' does not implement Iterator",
+                            self.ctx.type_to_string(iter_ty)
+                        ),
+                        f.iterable.span,
+                    )
+                    .with_id(DiagnosticId::TypeForTargetNotIterator),
+                );
+                return None;
+            }
+            let (step_blk, _) = self.check_block(&step, 0, false, None)?;
+            Some(init_blk.lines.into_iter().chain(step_blk.lines).collect::<Vec<_>>())
+        });
+        self.env.pop_scope();
+        let unit = self.ctx.unit();
+        Some(HirExpr {
+            ty: unit,
+            kind: HirExprKind::Block(HirBlock {
+                lines: lines?,
+                ty: unit,
+                span: f.span,
+            }),
+            span: f.span,
+        })
+    }
+
+    /// Whether `ty` has an impl of (or a type parameter bound on) `trait_name`,
+    /// whatever the trait arguments are.
+    fn implements_trait(&self, ty: TypeId, trait_name: &str) -> bool {
+        let resolved = self.ctx.resolve_id(ty);
+        self.type_param_bounds.iter().any(|(tp, bounds)| {
+            self.ctx.resolve_id(*tp) == resolved
+                && bounds.iter().any(|b| b.trait_base_name == trait_name)
+        }) || self.impls.iter().any(|imp| {
+            imp.trait_base_name.as_deref() == Some(trait_name)
+                && self.ctx.type_pattern_matches(imp.target_ty, resolved)
+        })
+    }
+
+    /// Lowers `try <operand>` into
+    ///
+    /// ```text
+    /// match <operand>:
+    ///     Ok v: v
+    ///     Err e: return Err (From::from e)
+    /// ```
+    ///
+    /// (`Some`/`None` for `Option`). The error is converted through `From`
+    /// only when the operand and function error types differ and an impl
+    /// exists, so the early return goes through ordinary `Return` lowering
+    /// and drop insertion.
+    fn lower_try(&mut self, span: Span, operand: HirExpr) -> Option<HirExpr> {
+        let operand_ty = self.ctx.resolve_id(operand.ty);
+        let fallible = match self.ctx.get(operand_ty) {
+            TypeKind::Apply { base, args } => match self.ctx.get(base) {
+                TypeKind::Enum { name, .. } if name == "Result" || name == "Option" => {
+                    Some((name, args))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some((enum_name, operand_args)) = fallible else {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!(
+                        "try expects a Result or Option, found {}",
+                        self.ctx.type_to_string(operand_ty)
+                    ),
+                    operand.span,
+                )
+                .with_id(DiagnosticId::TypeTryOperandNotFallible),
+            );
+            return None;
+        };
+        let return_ty = self.ctx.resolve_id(self.return_ty);
+        let return_args = match self.ctx.get(return_ty) {
+            TypeKind::Apply { base, args } => match self.ctx.get(base) {
+                TypeKind::Enum { name, .. } if name == enum_name => Some(args),
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some(return_args) = return_args else {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!(
+                        "try on {} requires the enclosing function to return {}, found {}",
+                        self.ctx.type_to_string(operand_ty),
+                        enum_name,
+                        self.ctx.type_to_string(return_ty)
+                    ),
+                    span,
+                )
+                .with_id(DiagnosticId::TypeTryReturnTypeMismatch),
+            );
+            return None;
+        };
+        let is_result = enum_name == "Result";
+        let ok_ty = operand_args[0];
+        let ok_name = format!("__try_ok{}", span.start);
+        let ok_arm = HirMatchArm {
+            variant: String::from(if is_result { "Ok" } else { "Some" }),
+            bind_local: Some(ok_name.clone()),
+            body: HirExpr {
+                ty: ok_ty,
+                kind: HirExprKind::Var(ok_name),
+                span,
+            },
+        };
+        let (err_variant, err_bind, err_payload) = if is_result {
+            let err_ty = operand_args[1];
+            let target_ty = return_args[1];
+            let err_name = format!("__try_err{}", span.start);
+            let err_var = HirExpr {
+                ty: err_ty,
+                kind: HirExprKind::Var(err_name.clone()),
+                span,
+            };
+            let payload = if self.ctx.same_type(err_ty, target_ty) {
+                err_var
+            } else if self.has_from_impl(target_ty, err_ty) {
+                HirExpr {
+                    ty: target_ty,
+                    kind: HirExprKind::Call {
+                        callee: FuncRef::Trait {
+                            trait_name: String::from("From"),
+                            trait_args: vec![err_ty],
+                            method: String::from("from"),
+                            self_ty: target_ty,
+                        },
+                        args: vec![err_var],
+                    },
+                    span,
+                }
+            } else if self.ctx.unify(err_ty, target_ty).is_ok() {
+                err_var
+            } else {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!(
+                            "try cannot convert error {} into {}; add `impl From<{}> for {}`",
+                            self.ctx.type_to_string(err_ty),
+                            self.ctx.type_to_string(target_ty),
+                            self.ctx.type_to_string(err_ty),
+                            self.ctx.type_to_string(target_ty)
+                        ),
+                        span,
+                    )
+                    .with_id(DiagnosticId::TypeTryErrorNotConvertible),
+                );
+                return None;
+            };
+            ("Err", Some(err_name), Some(Box::new(payload)))
+        } else {
+            ("None", None, None)
+        };
+        let never = self.ctx.never();
+        let err_arm = HirMatchArm {
+            variant: String::from(err_variant),
+            bind_local: err_bind,
+            body: HirExpr {
+                ty: never,
+                kind: HirExprKind::Return(Box::new(HirExpr {
+                    ty: return_ty,
+                    kind: HirExprKind::EnumConstruct {
+                        name: enum_name,
+                        variant: String::from(err_variant),
+                        type_args: return_args,
+                        payload: err_payload,
+                    },
+                    span,
+                })),
+                span,
+            },
+        };
+        let span = span.join(operand.span).unwrap_or(span);
+        Some(HirExpr {
+            ty: ok_ty,
+            kind: HirExprKind::Match {
+                scrutinee: Box::new(operand),
+                arms: vec![ok_arm, err_arm],
+            },
+            span,
+        })
+    }
+
+    /// Whether `impl From<source> for target` is visible.
+    fn has_from_impl(&self, target: TypeId, source: TypeId) -> bool {
+        let target = self.ctx.resolve_id(target);
+        self.impls.iter().any(|imp| {
+            imp.trait_base_name.as_deref() == Some("From")
+                && trait_application_matches(self.ctx, "From", &[source], "From", &imp.trait_args)
+                && self.ctx.type_pattern_matches(imp.target_ty, target)
+        })
+    }
+
+    /// Rewrites `for x in it: body` into a hidden binding of the iterator and
+    ///
+    /// ```text
+    /// while <label> true:
+    ///     match Iterator::next <hidden>:
+    ///         Some x: body
+    ///         None: break
+    /// ```
+    ///
+    /// so the loop checks as an ordinary `while` whose `next` call is resolved
+    /// statically by monomorphization.
+    fn desugar_for_expr(f: &ForExpr) -> (String, Block, Block) {
+        let sp = f.span;
+        let iter_name = format!("__for_iter{}", sp.start);
+        let ident = |name: &str, span: Span| Ident {
+            name: name.to_string(),
+            span,
+        };
+        let line = |items: Vec<PrefixItem>, span: Span| PrefixExpr {
+            items,
+            trailing_semis: 0,
+            trailing_semi_span: None,
+            span,
+        };
+
+        let mut let_items = alloc::vec![PrefixItem::Symbol(Symbol::Let {
+            name: ident(&iter_name, f.iterable.span),
+            mutable: false,
+            no_shadow: false,
+        })];
+        let_items.extend(f.iterable.items.iter().cloned());
+        let mut let_line = line(let_items, f.iterable.span);
+        let_line.trailing_semis = 1;
+
+        let scrutinee = line(
+            alloc::vec![
+                PrefixItem::Symbol(Symbol::Ident(
+                    ident("Iterator::next", sp),
+                    Vec::new(),
+                    false,
+                )),
+                PrefixItem::Symbol(Symbol::Ident(ident(&iter_name, sp), Vec::new(), false)),
+            ],
+            sp,
+        );
+        let break_block = Block {
+            items: alloc::vec![Stmt::Expr(line(
+                alloc::vec![PrefixItem::Symbol(Symbol::Break {
+                    label: None,
+                    span: sp,
+                })],
+                sp,
+            ))],
+            span: sp,
+        };
+        let step = MatchExpr {
+            scrutinee,
+            arms: alloc::vec![
+                MatchArm {
+                    variant: ident("Some", f.binding.span),
+                    bind: Some(f.binding.clone()),
+                    body: f.body.clone(),
+                    span: f.body.span,
+                },
+                MatchArm {
+                    variant: ident("None", sp),
+                    bind: None,
+                    body: break_block,
+                    span: sp,
+                },
+            ],
+            span: sp,
+        };
+        let loop_body = Block {
+            items: alloc::vec![Stmt::Expr(line(alloc::vec![PrefixItem::Match(step, sp)], sp))],
+            span: f.body.span,
+        };
+        let while_line = line(
+            alloc::vec![
+                PrefixItem::Symbol(Symbol::While(sp, f.label.clone())),
+                PrefixItem::Literal(Literal::Bool(true), sp),
+                PrefixItem::Block(loop_body, f.body.span),
+            ],
+            sp,
+        );
+        let init = Block {
+            items: alloc::vec![Stmt::ExprSemi(let_line, None)],
+            span: f.iterable.span,
+        };
+        let step = Block {
+            items: alloc::vec![Stmt::Expr(while_line)],
+            span: sp,
+        };
+        (iter_name, init, step)
     }
 
     fn check_match_expr(&mut self, m: &MatchExpr) -> Option<(HirExpr, TypeId)> {
@@ -6162,6 +7071,12 @@ impl<'a> BlockChecker<'a> {
             // For assignments we must find hoisted (possibly undefined)
             // bindings as well, so use a mutable lookup that returns
             // bindings regardless of `defined` state.
+            let is_global = self.globals.items.contains_key(&name)
+                && self
+                    .env
+                    .lookup_value_with_scope(&name)
+                    .map(|(_, scope)| scope == 0)
+                    .unwrap_or(false);
             if let Some(b) = self.env.lookup_mut(&name) {
                 let b_ty = b.ty;
                 let b_mut = b.mutable;
@@ -6206,13 +7121,21 @@ impl<'a> BlockChecker<'a> {
                                     .with_id(DiagnosticId::TypeImmutableMutation),
                             );
                         }
+                        let value = Box::new(args[0].expr.clone());
                         return Some(StackEntry {
                             ty: self.ctx.unit(),
                             expr: HirExpr {
                                 ty: self.ctx.unit(),
-                                kind: HirExprKind::Set {
-                                    name: name.clone(),
-                                    value: Box::new(args[0].expr.clone()),
+                                kind: if is_global && matches!(assign, AssignKind::Set) {
+                                    HirExprKind::GlobalSet {
+                                        name: name.clone(),
+                                        value,
+                                    }
+                                } else {
+                                    HirExprKind::Set {
+                                        name: name.clone(),
+                                        value,
+                                    }
                                 },
                                 span: func.expr.span,
                             },
@@ -6266,6 +7189,10 @@ impl<'a> BlockChecker<'a> {
                 });
             }
             HirExprKind::Var(name) if name == "while" => {
+                let label = self
+                    .loop_labels
+                    .get(&(func.expr.span.start, func.expr.span.end))
+                    .cloned();
                 if args.len() != 2 {
                     self.diagnostics.push(Diagnostic::error(
                         "while expects two arguments",
@@ -6292,6 +7219,7 @@ impl<'a> BlockChecker<'a> {
                         kind: HirExprKind::While {
                             cond: Box::new(args[0].expr.clone()),
                             body: Box::new(args[1].expr.clone()),
+                            label,
                         },
                         span: func.expr.span,
                     },
@@ -6300,6 +7228,67 @@ impl<'a> BlockChecker<'a> {
                             auto_call: true,
                 });
             }
+            HirExprKind::Var(name) if name == "return" => {
+                if args.len() != 1 {
+                    self.diagnostics.push(Diagnostic::error(
+                        "return expects one argument",
+                        func.expr.span,
+                    ).with_id(DiagnosticId::TypeReturnArityMismatch));
+                    return None;
+                }
+                if self.ctx.unify(args[0].ty, self.return_ty).is_err() {
+                    self.diagnostics.push(Diagnostic::error(
+                        "return value does not match the function result type",
+                        args[0].expr.span,
+                    ).with_id(DiagnosticId::TypeReturnTypeMismatch));
+                }
+                let never = self.ctx.never();
+                return Some(StackEntry {
+                    ty: never,
+                    expr: HirExpr {
+                        ty: never,
+                        kind: HirExprKind::Return(Box::new(args[0].expr.clone())),
+                        span: func.expr.span,
+                    },
+                    type_args: Vec::new(),
+                    assign: None,
+                    auto_call: true,
+                });
+            }
+            HirExprKind::Var(name) if name == "try" => {
+                if args.len() != 1 {
+                    return None;
+                }
+                let expr = self.lower_try(func.expr.span, args[0].expr.clone())?;
+                return Some(StackEntry {
+                    ty: expr.ty,
+                    expr,
+                    type_args: Vec::new(),
+                    assign: None,
+                    auto_call: true,
+                });
+            }
+            HirExprKind::Var(name) if name == "#comptime" => {
+                if args.len() != 1 {
+                    return None;
+                }
+                // The value is folded after every function has been checked,
+                // since the expression may call functions defined later.
+                let name = format!("#comptime{}", self.globals.comptime.len());
+                self.globals.comptime.push((name.clone(), args[0].expr.clone()));
+                let span = func.expr.span.join(args[0].expr.span).unwrap_or(func.expr.span);
+                return Some(StackEntry {
+                    ty: args[0].ty,
+                    expr: HirExpr {
+                        ty: args[0].ty,
+                        kind: HirExprKind::GlobalGet(name),
+                        span,
+                    },
+                    type_args: Vec::new(),
+                    assign: None,
+                    auto_call: true,
+                });
+            }
             HirExprKind::Var(name) if name == "let" || name == "set" => {
                 // handled elsewhere
             }
@@ -7064,6 +8053,32 @@ impl<'a> BlockChecker<'a> {
                         });
                     }
 
+                    // Newtype constructor `Name x` and unwrap `Name::inner x` only change
+                    // the static type; the value is passed through untouched.
+                    let newtype_base = parse_variant_name(name)
+                        .filter(|(_, member)| *member == "inner")
+                        .map(|(base, _)| base)
+                        .unwrap_or(name.as_str());
+                    let is_newtype_conversion = self
+                        .ctx
+                        .lookup_named(newtype_base)
+                        .map(|ty| matches!(self.ctx.get(ty), TypeKind::Newtype { .. }))
+                        .unwrap_or(false);
+                    if is_newtype_conversion && args.len() == 1 {
+                        let arg = args.into_iter().next().unwrap();
+                        return Some(StackEntry {
+                            ty: c_result,
+                            expr: HirExpr {
+                                ty: c_result,
+                                kind: arg.expr.kind,
+                                span: func.expr.span,
+                            },
+                            type_args: Vec::new(),
+                            assign: None,
+                            auto_call: true,
+                        });
+                    }
+
                     let mut final_args: Vec<HirExpr> = Vec::new();
                     for (cap_name, cap_ty) in captures.iter() {
                         let resolved_cap_ty = self
@@ -7267,8 +8282,13 @@ impl<'a> BlockChecker<'a> {
                                 &args,
                                 expected_ret,
                             );
-                            let applied_trait_args =
+                            let inferred_trait_args =
                                 self.infer_trait_application_args(trait_info, *sig, &args, expected_ret);
+                            let mut applied_trait_args = self.bind_call_site_trait_args(
+                                trait_info,
+                                inferred_trait_args,
+                                &type_args,
+                            );
                             let mut inferred_self_ty = None;
                             if let (Some(self_hint), Some(first_param), Some(arg)) =
                                 (type_args.first().copied(), params.first().copied(), args.first())
@@ -7331,6 +8351,11 @@ impl<'a> BlockChecker<'a> {
                                 ).with_id(DiagnosticId::TypeTraitBoundUnsatisfied));
                                 return None;
                             };
+                            self.resolve_associated_trait_args(
+                                trait_name,
+                                self_ty,
+                                &mut applied_trait_args,
+                            );
                                 let trait_ok = self.type_param_has_bound_ref(
                                     self_ty,
                                     trait_name,
@@ -7510,6 +8535,95 @@ enum BindingKind {
     },
 }
 
+/// Reports `break`/`continue` that do not sit inside the body of a matching `while`.
+/// A jump in a loop condition belongs to the enclosing loop, not the one being tested.
+fn check_jump_targets_in_block(
+    block: &HirBlock,
+    loops: &mut Vec<Option<String>>,
+    diags: &mut Vec<Diagnostic>,
+) {
+    for line in &block.lines {
+        check_jump_targets(&line.expr, loops, diags);
+    }
+}
+
+fn check_jump_targets(expr: &HirExpr, loops: &mut Vec<Option<String>>, diags: &mut Vec<Diagnostic>) {
+    match &expr.kind {
+        HirExprKind::Break { label } | HirExprKind::Continue { label } => {
+            if loops.is_empty() {
+                diags.push(
+                    Diagnostic::error("break or continue outside of a loop", expr.span)
+                        .with_id(DiagnosticId::TypeJumpOutsideLoop),
+                );
+            } else if let Some(name) = label {
+                if !loops.iter().any(|l| l.as_ref() == Some(name)) {
+                    diags.push(
+                        Diagnostic::error(format!("unknown loop label `<.{}>`", name), expr.span)
+                            .with_id(DiagnosticId::TypeUnknownLoopLabel),
+                    );
+                }
+            }
+        }
+        HirExprKind::While { cond, body, label } => {
+            check_jump_targets(cond, loops, diags);
+            loops.push(label.clone());
+            check_jump_targets(body, loops, diags);
+            loops.pop();
+        }
+        HirExprKind::Call { args, .. }
+        | HirExprKind::TupleConstruct { items: args }
+        | HirExprKind::StructConstruct { fields: args, .. }
+        | HirExprKind::Intrinsic { args, .. } => {
+            for arg in args {
+                check_jump_targets(arg, loops, diags);
+            }
+        }
+        HirExprKind::CallIndirect { callee, args, .. } => {
+            check_jump_targets(callee, loops, diags);
+            for arg in args {
+                check_jump_targets(arg, loops, diags);
+            }
+        }
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            check_jump_targets(cond, loops, diags);
+            check_jump_targets(then_branch, loops, diags);
+            check_jump_targets(else_branch, loops, diags);
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            check_jump_targets(scrutinee, loops, diags);
+            for arm in arms {
+                check_jump_targets(&arm.body, loops, diags);
+            }
+        }
+        HirExprKind::EnumConstruct { payload, .. } => {
+            if let Some(payload) = payload {
+                check_jump_targets(payload, loops, diags);
+            }
+        }
+        HirExprKind::Block(block) => check_jump_targets_in_block(block, loops, diags),
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. }
+        | HirExprKind::AddrOf(value)
+        | HirExprKind::Deref(value)
+        | HirExprKind::Return(value) => check_jump_targets(value, loops, diags),
+        HirExprKind::FnValue(_)
+        | HirExprKind::Var(_)
+        | HirExprKind::GlobalGet(_)
+        | HirExprKind::Unit
+        | HirExprKind::LiteralI32(_)
+        | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralBool(_)
+        | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
+        | HirExprKind::Drop { .. } => {}
+    }
+}
+
 fn resolve_type_ids_in_function(ctx: &TypeCtx, function: &mut HirFunction) {
     function.func_ty = ctx.resolve_id(function.func_ty);
     function.result = ctx.resolve_id(function.result);
@@ -7579,7 +8693,7 @@ fn resolve_type_ids_in_expr(ctx: &TypeCtx, expr: &mut HirExpr) {
             resolve_type_ids_in_expr(ctx, then_branch);
             resolve_type_ids_in_expr(ctx, else_branch);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             resolve_type_ids_in_expr(ctx, cond);
             resolve_type_ids_in_expr(ctx, body);
         }
@@ -7592,8 +8706,10 @@ fn resolve_type_ids_in_expr(ctx: &TypeCtx, expr: &mut HirExpr) {
         HirExprKind::Block(block) => resolve_type_ids_in_block(ctx, block),
         HirExprKind::Let { value, .. }
         | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. }
         | HirExprKind::AddrOf(value)
-        | HirExprKind::Deref(value) => resolve_type_ids_in_expr(ctx, value),
+        | HirExprKind::Deref(value)
+        | HirExprKind::Return(value) => resolve_type_ids_in_expr(ctx, value),
         HirExprKind::TupleConstruct { items }
         | HirExprKind::Intrinsic { args: items, .. } => {
             for item in items {
@@ -7622,7 +8738,11 @@ fn resolve_type_ids_in_expr(ctx: &TypeCtx, expr: &mut HirExpr) {
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
-        | HirExprKind::Drop { .. } => {}
+        | HirExprKind::ConstData(_)
+        | HirExprKind::Drop { .. }
+        | HirExprKind::Break { .. }
+        | HirExprKind::Continue { .. }
+        | HirExprKind::GlobalGet(_) => {}
     }
 }
 
@@ -8075,7 +9195,7 @@ fn type_shape_specificity(ctx: &TypeCtx, ty: TypeId) -> usize {
         | TypeKind::Str
         | TypeKind::Never
         | TypeKind::Named(_) => 2,
-        TypeKind::Enum { .. } | TypeKind::Struct { .. } => 3,
+        TypeKind::Enum { .. } | TypeKind::Struct { .. } | TypeKind::Newtype { .. } => 3,
         TypeKind::Apply { base, args } => {
             4 + type_shape_specificity(ctx, base)
                 + args
@@ -8137,74 +9257,6 @@ fn detect_field_accessor_fn(def: &FnDef) -> Option<FieldAccessorKind> {
     }
 }
 
-fn normalized_import_suffix(module: &str, ext: &str) -> String {
-    let mut s = module.replace('\\', "/");
-    if !s.starts_with('/') {
-        s.insert(0, '/');
-    }
-    s.push_str(ext);
-    s
-}
-
-fn build_qualified_import_targets(
-    module: &crate::ast::Module,
-    source_map: Option<&SourceMap>,
-) -> BTreeMap<u32, BTreeMap<String, BTreeSet<u32>>> {
-    let Some(source_map) = source_map else {
-        return BTreeMap::new();
-    };
-    let mut directives: Vec<&Directive> = module.directives.iter().collect();
-    for item in &module.root.items {
-        if let Stmt::Directive(d) = item {
-            directives.push(d);
-        }
-    }
-    let mut out: BTreeMap<u32, BTreeMap<String, BTreeSet<u32>>> = BTreeMap::new();
-    for directive in directives {
-        let Directive::Import { path, clause, span, .. } = directive else {
-            continue;
-        };
-        let aliases: Vec<String> = match clause {
-            ImportClause::DefaultAlias => Path::new(path)
-                .file_name()
-                .and_then(|name| name.to_str())
-                .map(|name| vec![name.to_string()])
-                .unwrap_or_default(),
-            ImportClause::Alias(alias) => vec![alias.clone()],
-            _ => Vec::new(),
-        };
-        if aliases.is_empty() {
-            continue;
-        }
-        let suffixes = [
-            normalized_import_suffix(path, ".nepl"),
-            normalized_import_suffix(path, ".n.md"),
-        ];
-        let target_files = source_map
-            .iter_paths()
-            .filter_map(|(file_id, path)| {
-                let normalized = path.to_string_lossy().replace('\\', "/");
-                if suffixes.iter().any(|suffix| normalized.ends_with(suffix)) {
-                    Some(file_id.0)
-                } else {
-                    None
-                }
-            })
-            .collect::<BTreeSet<_>>();
-        if target_files.is_empty() {
-            continue;
-        }
-        let file_aliases = out.entry(span.file_id.0).or_default();
-        for alias in aliases {
-            file_aliases
-                .entry(alias)
-                .or_default()
-                .extend(target_files.iter().copied());
-        }
-    }
-    out
-}
-
 type LabelEnv = BTreeMap<String, TypeId>;
 
 #[derive(Debug)]
@@ -8262,6 +9314,9 @@ fn type_from_expr(ctx: &mut TypeCtx, labels: &mut LabelEnv, t: &TypeExpr) -> Typ
                     if let Some(id) = labels.get(name) {
                         return *id;
                     }
+                    if let Some(id) = ctx.expand_alias(name, &[]) {
+                        return id;
+                    }
                     if let Some(id) = ctx.lookup_named(name) {
                         id
                     } else {
@@ -8271,6 +9326,17 @@ fn type_from_expr(ctx: &mut TypeCtx, labels: &mut LabelEnv, t: &TypeExpr) -> Typ
             }
         }
         TypeExpr::Apply(base, args) => {
+            if let TypeExpr::Named(name) = base.as_ref() {
+                if !labels.contains_key(name) && ctx.lookup_alias(name).is_some() {
+                    let arg_tys = args
+                        .iter()
+                        .map(|a| type_from_expr(ctx, labels, a))
+                        .collect::<Vec<_>>();
+                    if let Some(id) = ctx.expand_alias(name, &arg_tys) {
+                        return id;
+                    }
+                }
+            }
             let b = type_from_expr(ctx, labels, base);
             let mut arg_tys = Vec::new();
             for a in args {
@@ -8314,13 +9380,120 @@ fn type_from_expr(ctx: &mut TypeCtx, labels: &mut LabelEnv, t: &TypeExpr) -> Typ
             let i = type_from_expr(ctx, labels, inner);
             ctx.box_ty(i)
         }
-        TypeExpr::Reference(inner, is_mut) => {
+        TypeExpr::Reference(inner, is_mut, _) => {
             let i = type_from_expr(ctx, labels, inner);
             ctx.reference(i, *is_mut)
         }
     }
 }
 
+fn collect_type_expr_names(t: &TypeExpr, out: &mut Vec<String>) {
+    match t {
+        TypeExpr::Named(name) => out.push(name.clone()),
+        TypeExpr::Apply(base, args) => {
+            collect_type_expr_names(base, out);
+            for a in args {
+                collect_type_expr_names(a, out);
+            }
+        }
+        TypeExpr::Boxed(inner) | TypeExpr::Reference(inner, _, _) => {
+            collect_type_expr_names(inner, out)
+        }
+        TypeExpr::Tuple(items) => {
+            for ty in items {
+                collect_type_expr_names(ty, out);
+            }
+        }
+        TypeExpr::Function { params, result, .. } => {
+            for ty in params {
+                collect_type_expr_names(ty, out);
+            }
+            collect_type_expr_names(result, out);
+        }
+        _ => {}
+    }
+}
+
+fn check_alias_arity(ctx: &TypeCtx, t: &TypeExpr, span: Span, diags: &mut Vec<Diagnostic>) {
+    let mismatch = |name: &str, given: usize| {
+        ctx.lookup_alias(name)
+            .map(|alias| alias.params.len() != given)
+            .unwrap_or(false)
+    };
+    match t {
+        TypeExpr::Named(name) if mismatch(name, 0) => {
+            diags.push(
+                Diagnostic::error("wrong number of type arguments for type alias", span)
+                    .with_id(DiagnosticId::TypeAliasArityMismatch),
+            );
+        }
+        TypeExpr::Apply(base, args) => {
+            if let TypeExpr::Named(name) = base.as_ref() {
+                if mismatch(name, args.len()) {
+                    diags.push(
+                        Diagnostic::error("wrong number of type arguments for type alias", span)
+                            .with_id(DiagnosticId::TypeAliasArityMismatch),
+                    );
+                }
+            } else {
+                check_alias_arity(ctx, base, span, diags);
+            }
+            for a in args {
+                check_alias_arity(ctx, a, span, diags);
+            }
+        }
+        TypeExpr::Boxed(inner) | TypeExpr::Reference(inner, _, _) => {
+            check_alias_arity(ctx, inner, span, diags)
+        }
+        TypeExpr::Tuple(items) => {
+            for ty in items {
+                check_alias_arity(ctx, ty, span, diags);
+            }
+        }
+        TypeExpr::Function { params, result, .. } => {
+            for ty in params {
+                check_alias_arity(ctx, ty, span, diags);
+            }
+            check_alias_arity(ctx, result, span, diags);
+        }
+        _ => {}
+    }
+}
+
+fn check_alias_arity_in_items(ctx: &TypeCtx, items: &[Stmt], diags: &mut Vec<Diagnostic>) {
+    for item in items {
+        match item {
+            Stmt::FnDef(f) => check_alias_arity(ctx, &f.signature, f.name.span, diags),
+            Stmt::StructDef(s) => {
+                for (ident, ty) in &s.fields {
+                    check_alias_arity(ctx, ty, ident.span, diags);
+                }
+            }
+            Stmt::EnumDef(e) => {
+                for v in &e.variants {
+                    if let Some(p) = &v.payload {
+                        check_alias_arity(ctx, p, v.name.span, diags);
+                    }
+                }
+            }
+            Stmt::TypeDef(t) => check_alias_arity(ctx, &t.target, t.name.span, diags),
+            Stmt::Global(g) => check_alias_arity(ctx, &g.ty, g.name.span, diags),
+            Stmt::Trait(t) => {
+                for m in &t.methods {
+                    check_alias_arity(ctx, &m.signature, m.name.span, diags);
+                }
+            }
+            Stmt::Impl(i) => {
+                check_alias_arity(ctx, &i.target_ty, i.span, diags);
+                for m in &i.methods {
+                    check_alias_arity(ctx, &m.signature, m.name.span, diags);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 fn func_arity(ctx: &TypeCtx, ty: TypeId) -> usize {
     match ctx.get(ty) {
         TypeKind::Function { params, .. } => params.len(),
@@ -8739,6 +9912,11 @@ fn signature_type_string(ctx: &TypeCtx, ty: TypeId, generics: &BTreeMap<TypeId,
             name,
             type_params,
             ..
+        }
+        | TypeKind::Newtype {
+            name,
+            type_params,
+            ..
         } => {
             if type_params.is_empty() {
                 name
@@ -8767,6 +9945,126 @@ fn push_unique_type(ctx: &TypeCtx, list: &mut Vec<TypeId>, ty: TypeId) {
     }
 }
 
+fn collect_type_expr_regions(ty: &TypeExpr, out: &mut Vec<String>) {
+    match ty {
+        TypeExpr::Reference(inner, _, region) => {
+            if let Some(region) = region {
+                out.push(region.clone());
+            }
+            collect_type_expr_regions(inner, out);
+        }
+        TypeExpr::Apply(base, args) => {
+            collect_type_expr_regions(base, out);
+            for arg in args {
+                collect_type_expr_regions(arg, out);
+            }
+        }
+        TypeExpr::Boxed(inner) => collect_type_expr_regions(inner, out),
+        TypeExpr::Tuple(items) => {
+            for item in items {
+                collect_type_expr_regions(item, out);
+            }
+        }
+        TypeExpr::Function { params, result, .. } => {
+            for param in params {
+                collect_type_expr_regions(param, out);
+            }
+            collect_type_expr_regions(result, out);
+        }
+        _ => {}
+    }
+}
+
+/// Decides which parameters (indices into `f.params`) the result of `f` may borrow from.
+///
+/// Returns `None` when the result type cannot hold a reference. An explicit region on the
+/// result selects the parameters sharing that region; without one, the single
+/// reference-typed parameter is used (elision).
+fn infer_result_borrows(
+    f: &FnDef,
+    params_ty: &[TypeId],
+    result_ty: TypeId,
+    ctx: &TypeCtx,
+    diags: &mut Vec<Diagnostic>,
+) -> Option<Vec<usize>> {
+    let (param_exprs, result_expr): (&[TypeExpr], Option<&TypeExpr>) = match &f.signature {
+        TypeExpr::Function { params, result, .. } => (params.as_slice(), Some(result.as_ref())),
+        _ => (&[], None),
+    };
+    let mut used = Vec::new();
+    collect_type_expr_regions(&f.signature, &mut used);
+    for region in &used {
+        if !f.regions.iter().any(|r| &r.name == region) {
+            diags.push(
+                Diagnostic::error(format!("use of undeclared region `'{}`", region), f.name.span)
+                    .with_id(DiagnosticId::TypeUnknownRegion),
+            );
+        }
+    }
+    if !ctx.contains_reference(result_ty) {
+        return None;
+    }
+    let mut result_regions = Vec::new();
+    if let Some(result_expr) = result_expr {
+        collect_type_expr_regions(result_expr, &mut result_regions);
+    }
+    if !result_regions.is_empty() {
+        let borrows: Vec<usize> = param_exprs
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                let mut regions = Vec::new();
+                collect_type_expr_regions(p, &mut regions);
+                regions.iter().any(|r| result_regions.contains(r))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if borrows.is_empty() {
+            diags.push(
+                Diagnostic::error(
+                    format!(
+                        "no parameter of `{}` has the region `'{}` of the returned reference",
+                        f.name.name, result_regions[0]
+                    ),
+                    f.name.span,
+                )
+                .with_id(DiagnosticId::TypeReturnedReferenceWithoutSource),
+            );
+        }
+        return Some(borrows);
+    }
+    let ref_params: Vec<usize> = params_ty
+        .iter()
+        .enumerate()
+        .filter(|(_, ty)| ctx.contains_reference(**ty))
+        .map(|(i, _)| i)
+        .collect();
+    match ref_params.len() {
+        0 => diags.push(
+            Diagnostic::error(
+                format!(
+                    "`{}` returns a reference but takes no reference to borrow it from",
+                    f.name.name
+                ),
+                f.name.span,
+            )
+            .with_id(DiagnosticId::TypeReturnedReferenceWithoutSource),
+        ),
+        1 => {}
+        _ => diags.push(
+            Diagnostic::error(
+                format!(
+                    "`{}` takes several references; name the one its result borrows from with a region such as `&'a`",
+                    f.name.name
+                ),
+                f.name.span,
+            )
+            .with_id(DiagnosticId::TypeAmbiguousReturnRegion),
+        ),
+    }
+    Some(ref_params)
+}
+
 fn type_contains_unbound_var(ctx: &TypeCtx, ty: TypeId) -> bool {
     let ty = ctx.resolve_id(ty);
     match ctx.get(ty) {
@@ -8779,9 +10077,9 @@ fn type_contains_unbound_var(ctx: &TypeCtx, ty: TypeId) -> bool {
         | TypeKind::Never
         | TypeKind::Named(_) => false,
         TypeKind::Var(tv) => tv.binding.is_none(),
-        TypeKind::Enum { type_params, .. } | TypeKind::Struct { type_params, .. } => {
-            !type_params.is_empty()
-        }
+        TypeKind::Enum { type_params, .. }
+        | TypeKind::Struct { type_params, .. }
+        | TypeKind::Newtype { type_params, .. } => !type_params.is_empty(),
         TypeKind::Function {
             type_params,
             params,
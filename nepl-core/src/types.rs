@@ -37,6 +37,13 @@ pub enum TypeKind {
         fields: Vec<TypeId>,
         field_names: Vec<String>,
     },
+    /// Nominal wrapper around `inner`; erased to `inner` before codegen.
+    Newtype {
+        doc: Option<String>,
+        name: String,
+        type_params: Vec<TypeId>, // TypeId(Var)
+        inner: TypeId,
+    },
     Tuple {
         items: Vec<TypeId>,
     },
@@ -85,6 +92,14 @@ pub struct TypeCtx {
     copy_impl_targets: Vec<TypeId>,
     copy_trait_enabled: bool,
     drop_impl_targets: Vec<TypeId>,
+    aliases: alloc::collections::BTreeMap<alloc::string::String, TypeAlias>,
+}
+
+/// `type Name<.T> = target`: `params` are the `.T` variables occurring in `target`.
+#[derive(Debug, Clone)]
+pub struct TypeAlias {
+    pub params: Vec<TypeId>,
+    pub target: TypeId,
 }
 
 static GLOBAL_UNIFY_DEPTH: AtomicUsize = AtomicUsize::new(0);
@@ -127,6 +142,7 @@ impl TypeCtx {
             copy_impl_targets: Vec::new(),
             copy_trait_enabled: false,
             drop_impl_targets: Vec::new(),
+            aliases: alloc::collections::BTreeMap::new(),
         }
     }
 
@@ -247,6 +263,14 @@ impl TypeCtx {
                     self.collect_type_var_bindings(*field, seen, out);
                 }
             }
+            TypeKind::Newtype {
+                type_params, inner, ..
+            } => {
+                for tp in type_params {
+                    self.collect_type_var_bindings(*tp, seen, out);
+                }
+                self.collect_type_var_bindings(*inner, seen, out);
+            }
             TypeKind::Function {
                 type_params,
                 params,
@@ -301,6 +325,79 @@ impl TypeCtx {
         self.named.get(name).copied()
     }
 
+    pub fn register_alias(&mut self, name: alloc::string::String, params: Vec<TypeId>, target: TypeId) {
+        self.aliases.insert(name, TypeAlias { params, target });
+    }
+
+    pub fn lookup_alias(&self, name: &str) -> Option<&TypeAlias> {
+        self.aliases.get(name)
+    }
+
+    /// Expands `name<args>` to the alias target. Returns `None` when `name` is not an
+    /// alias or the argument count does not match.
+    pub fn expand_alias(&mut self, name: &str, args: &[TypeId]) -> Option<TypeId> {
+        let alias = self.aliases.get(name)?.clone();
+        if alias.params.len() != args.len() {
+            return None;
+        }
+        if args.is_empty() {
+            return Some(alias.target);
+        }
+        let mapping = alias
+            .params
+            .iter()
+            .map(|p| self.resolve_id(*p))
+            .zip(args.iter().copied())
+            .collect::<BTreeMap<_, _>>();
+        Some(self.substitute(alias.target, &mapping))
+    }
+
+    /// Replaces every newtype with its inner type so that the backends see the
+    /// inner layout. Runs after monomorphization, which still needs the nominal
+    /// types to pick trait impls.
+    pub fn erase_newtypes(&mut self) {
+        let mut applied = Vec::new();
+        let mut i = 0;
+        while i < self.arena.len() {
+            if let TypeKind::Apply { base, args } = self.arena[i].clone() {
+                let base = self.resolve_id(base);
+                if let TypeKind::Newtype {
+                    type_params, inner, ..
+                } = self.arena[base.0].clone()
+                {
+                    if type_params.len() == args.len() {
+                        let mapping = type_params
+                            .iter()
+                            .map(|p| self.resolve_id(*p))
+                            .zip(args.iter().copied())
+                            .collect::<BTreeMap<_, _>>();
+                        let erased = self.substitute(inner, &mapping);
+                        applied.push((i, erased));
+                    }
+                }
+            }
+            i += 1;
+        }
+        for (slot, erased) in applied {
+            self.arena[slot] = Self::forwarding_var(erased);
+        }
+        for slot in 0..self.arena.len() {
+            if let TypeKind::Newtype { inner, .. } = self.arena[slot] {
+                self.arena[slot] = Self::forwarding_var(inner);
+            }
+        }
+    }
+
+    fn forwarding_var(target: TypeId) -> TypeKind {
+        TypeKind::Var(TypeVar {
+            label: None,
+            binding: Some(target),
+            copy_cap: false,
+            clone_cap: false,
+            drop_cap: false,
+        })
+    }
+
     pub fn function(
         &mut self,
         type_params: Vec<TypeId>,
@@ -394,6 +491,27 @@ impl TypeCtx {
         )
     }
 
+    /// Matches every `(pattern, actual)` pair against one shared set of
+    /// bindings and returns what each unbound pattern variable was bound to.
+    pub fn type_pattern_bindings(
+        &self,
+        pairs: &[(TypeId, TypeId)],
+    ) -> Option<BTreeMap<TypeId, TypeId>> {
+        let mut mapping = BTreeMap::new();
+        for (pattern, actual) in pairs {
+            let mut seen = BTreeSet::new();
+            if !self.type_pattern_matches_inner(
+                self.resolve_id(*pattern),
+                self.resolve_id(*actual),
+                &mut mapping,
+                &mut seen,
+            ) {
+                return None;
+            }
+        }
+        Some(mapping)
+    }
+
     fn type_pattern_matches_inner(
         &self,
         pattern: TypeId,
@@ -498,6 +616,28 @@ impl TypeCtx {
                         .zip(b_fields.iter())
                         .all(|(x, y)| self.type_pattern_matches_inner(*x, *y, mapping, seen))
             }
+            (
+                TypeKind::Newtype {
+                    name: a_name,
+                    type_params: a_tps,
+                    inner: a_inner,
+                    ..
+                },
+                TypeKind::Newtype {
+                    name: b_name,
+                    type_params: b_tps,
+                    inner: b_inner,
+                    ..
+                },
+            ) => {
+                a_name == b_name
+                    && a_tps.len() == b_tps.len()
+                    && a_tps
+                        .iter()
+                        .zip(b_tps.iter())
+                        .all(|(x, y)| self.type_pattern_matches_inner(*x, *y, mapping, seen))
+                    && self.type_pattern_matches_inner(*a_inner, *b_inner, mapping, seen)
+            }
             (
                 TypeKind::Enum {
                     name: a_name,
@@ -582,8 +722,17 @@ impl TypeCtx {
             TypeKind::Named(_) => self.has_copy_impl_target(resolved),
             TypeKind::Tuple { items } => items.iter().all(|t| self.is_copy(*t)),
             TypeKind::Struct { .. } | TypeKind::Enum { .. } => self.has_copy_impl_target(resolved),
-            TypeKind::Apply { base, .. } => match self.get_ref(self.resolve_id(*base)) {
+            TypeKind::Newtype { inner, .. } => {
+                self.has_copy_impl_target(resolved) || self.is_copy(*inner)
+            }
+            TypeKind::Apply { base, args } => match self.get_ref(self.resolve_id(*base)) {
                 TypeKind::Struct { .. } | TypeKind::Enum { .. } => self.has_copy_impl_target(resolved),
+                TypeKind::Newtype {
+                    type_params, inner, ..
+                } => {
+                    self.has_copy_impl_target(resolved)
+                        || self.newtype_inner_is_copy(type_params, *inner, args)
+                }
                 _ => self.has_copy_impl_target(resolved),
             },
             TypeKind::Var(v) => v.binding.map(|b| self.is_copy(b)).unwrap_or(v.copy_cap),
@@ -592,6 +741,15 @@ impl TypeCtx {
         }
     }
 
+    /// A newtype is `Copy` whenever its inner type is; `args` instantiate `type_params`.
+    fn newtype_inner_is_copy(&self, type_params: &[TypeId], inner: TypeId, args: &[TypeId]) -> bool {
+        let inner = self.resolve_id(inner);
+        match type_params.iter().position(|tp| self.resolve_id(*tp) == inner) {
+            Some(pos) => args.get(pos).map(|a| self.is_copy(*a)).unwrap_or(false),
+            None => self.is_copy(inner),
+        }
+    }
+
     pub fn has_drop(&self, id: TypeId) -> bool {
         let resolved = self.resolve_id(id);
         match self.get_ref(resolved) {
@@ -607,13 +765,54 @@ impl TypeCtx {
             TypeKind::Tuple { items } => {
                 items.iter().any(|t| self.has_drop(*t)) || self.has_drop_impl_target(resolved)
             }
-            TypeKind::Struct { .. } | TypeKind::Enum { .. } => self.has_drop_impl_target(resolved),
+            TypeKind::Struct { .. } | TypeKind::Enum { .. } | TypeKind::Newtype { .. } => {
+                self.has_drop_impl_target(resolved)
+            }
             TypeKind::Apply { .. } | TypeKind::Box(_) => self.has_drop_impl_target(resolved),
             TypeKind::Function { .. } => false,
             TypeKind::Var(v) => v.binding.map(|b| self.has_drop(b)).unwrap_or(v.drop_cap),
         }
     }
 
+    /// Returns true when a value of this type can hold a reference, directly or in a field.
+    /// Unbound type variables do not count.
+    pub fn contains_reference(&self, id: TypeId) -> bool {
+        self.contains_reference_inner(id, &mut BTreeSet::new())
+    }
+
+    fn contains_reference_inner(&self, id: TypeId, visiting: &mut BTreeSet<TypeId>) -> bool {
+        let resolved = self.resolve_id(id);
+        if !visiting.insert(resolved) {
+            return false;
+        }
+        match self.get_ref(resolved) {
+            TypeKind::Reference(_, _) => true,
+            TypeKind::Struct { fields, .. } => fields
+                .iter()
+                .any(|f| self.contains_reference_inner(*f, visiting)),
+            TypeKind::Enum { variants, .. } => variants.iter().any(|v| {
+                v.payload
+                    .map(|p| self.contains_reference_inner(p, visiting))
+                    .unwrap_or(false)
+            }),
+            TypeKind::Tuple { items } => items
+                .iter()
+                .any(|t| self.contains_reference_inner(*t, visiting)),
+            TypeKind::Apply { base, args } => {
+                self.contains_reference_inner(*base, visiting)
+                    || args.iter().any(|a| self.contains_reference_inner(*a, visiting))
+            }
+            TypeKind::Newtype { inner, .. } | TypeKind::Box(inner) => {
+                self.contains_reference_inner(*inner, visiting)
+            }
+            TypeKind::Var(v) => v
+                .binding
+                .map(|b| self.contains_reference_inner(b, visiting))
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
     fn is_copy_eligible_inner(
         &self,
         id: TypeId,
@@ -648,6 +847,9 @@ impl TypeCtx {
             TypeKind::Struct { fields, .. } => fields
                 .iter()
                 .all(|f| self.is_copy_eligible_inner(*f, visiting, mapping, allow_opaque_named)),
+            TypeKind::Newtype { inner, .. } => {
+                self.is_copy_eligible_inner(*inner, visiting, mapping, allow_opaque_named)
+            }
             TypeKind::Tuple { items } => items
                 .iter()
                 .all(|t| self.is_copy_eligible_inner(*t, visiting, mapping, allow_opaque_named)),
@@ -676,6 +878,23 @@ impl TypeCtx {
                                 .all(|f| self.is_copy_eligible_inner(*f, visiting, &nested, allow_opaque_named))
                         }
                     }
+                    TypeKind::Newtype {
+                        type_params, inner, ..
+                    } => {
+                        if type_params.len() != args.len() {
+                            false
+                        } else {
+                            let mut nested = mapping.clone();
+                            for (tp, arg) in type_params.iter().zip(args.iter()) {
+                                let rhs = mapping
+                                    .get(&self.resolve_id(*arg))
+                                    .copied()
+                                    .unwrap_or_else(|| self.resolve_id(*arg));
+                                nested.insert(self.resolve_id(*tp), rhs);
+                            }
+                            self.is_copy_eligible_inner(*inner, visiting, &nested, allow_opaque_named)
+                        }
+                    }
                     TypeKind::Enum {
                         type_params,
                         variants,
@@ -848,6 +1067,28 @@ impl TypeCtx {
                             }
                     })
             }
+            (
+                TypeKind::Newtype {
+                    name: na,
+                    type_params: tpa,
+                    inner: ia,
+                    ..
+                },
+                TypeKind::Newtype {
+                    name: nb,
+                    type_params: tpb,
+                    inner: ib,
+                    ..
+                },
+            ) => {
+                na == nb
+                    && tpa.len() == tpb.len()
+                    && tpa
+                        .iter()
+                        .zip(tpb.iter())
+                        .all(|(ta, tb)| self.same_type_inner(*ta, *tb, seen))
+                    && self.same_type_inner(*ia, *ib, seen)
+            }
             (
                 TypeKind::Apply { base: ba, args: aa },
                 TypeKind::Apply { base: bb, args: ab },
@@ -1051,6 +1292,24 @@ impl TypeCtx {
                 }
                 Ok(a)
             }
+            (
+                TypeKind::Newtype {
+                    name: na,
+                    inner: ia,
+                    ..
+                },
+                TypeKind::Newtype {
+                    name: nb,
+                    inner: ib,
+                    ..
+                },
+            ) => {
+                if na != nb {
+                    return Err(UnifyError::Mismatch);
+                }
+                self.unify(ia, ib)?;
+                Ok(a)
+            }
             (TypeKind::Tuple { items: ta }, TypeKind::Tuple { items: tb }) => {
                 if ta.len() != tb.len() {
                     return Err(UnifyError::Mismatch);
@@ -1095,7 +1354,9 @@ impl TypeCtx {
                 }
             }
             (TypeKind::Named(na), TypeKind::Struct { name: nb, .. })
-            | (TypeKind::Struct { name: na, .. }, TypeKind::Named(nb)) => {
+            | (TypeKind::Struct { name: na, .. }, TypeKind::Named(nb))
+            | (TypeKind::Named(na), TypeKind::Newtype { name: nb, .. })
+            | (TypeKind::Newtype { name: na, .. }, TypeKind::Named(nb)) => {
                 if na == nb {
                     Ok(a)
                 } else {
@@ -1178,13 +1439,17 @@ impl TypeCtx {
                 }
                 Ok(a)
             }
-            (TypeKind::Struct { name: na, type_params: ta, .. }, TypeKind::Apply { base: bb, args: ab }) => {
+            (
+                TypeKind::Struct { name: na, type_params: ta, .. }
+                | TypeKind::Newtype { name: na, type_params: ta, .. },
+                TypeKind::Apply { base: bb, args: ab },
+            ) => {
                 if ta.len() != ab.len() {
                     return Err(UnifyError::Mismatch);
                 }
                 let resolved_base = self.resolve_id(bb);
                 match &self.arena[resolved_base.0] {
-                    TypeKind::Struct { name: nb, .. } => {
+                    TypeKind::Struct { name: nb, .. } | TypeKind::Newtype { name: nb, .. } => {
                         if *na != *nb {
                             return Err(UnifyError::Mismatch);
                         }
@@ -1201,13 +1466,17 @@ impl TypeCtx {
                 }
                 Ok(a)
             }
-            (TypeKind::Apply { base: ba, args: aa }, TypeKind::Struct { name: nb, type_params: tb, .. }) => {
+            (
+                TypeKind::Apply { base: ba, args: aa },
+                TypeKind::Struct { name: nb, type_params: tb, .. }
+                | TypeKind::Newtype { name: nb, type_params: tb, .. },
+            ) => {
                 if aa.len() != tb.len() {
                     return Err(UnifyError::Mismatch);
                 }
                 let resolved_base = self.resolve_id(ba);
                 match &self.arena[resolved_base.0] {
-                    TypeKind::Struct { name: na, .. } => {
+                    TypeKind::Struct { name: na, .. } | TypeKind::Newtype { name: na, .. } => {
                         if *na != *nb {
                             return Err(UnifyError::Mismatch);
                         }
@@ -1379,6 +1648,32 @@ impl TypeCtx {
                     ty
                 }
             }
+            TypeKind::Newtype {
+                doc,
+                name,
+                type_params,
+                inner,
+            } => {
+                let mut new_tps = Vec::new();
+                let mut changed = false;
+                for tp in type_params {
+                    let nt = self.substitute_inner(tp, mapping, seen);
+                    if nt != tp { changed = true; }
+                    new_tps.push(nt);
+                }
+                let new_inner = self.substitute_inner(inner, mapping, seen);
+                if new_inner != inner { changed = true; }
+                if changed {
+                    self.store(TypeKind::Newtype {
+                        doc,
+                        name,
+                        type_params: new_tps,
+                        inner: new_inner,
+                    })
+                } else {
+                    ty
+                }
+            }
             TypeKind::Tuple { items } => {
                 let mut new_items = Vec::new();
                 let mut changed = false;
@@ -1559,6 +1854,11 @@ impl TypeCtx {
                 name,
                 type_params,
                 ..
+            }
+            | TypeKind::Newtype {
+                name,
+                type_params,
+                ..
             } => {
                 if type_params.is_empty() {
                     name.clone()
@@ -1654,6 +1954,7 @@ impl TypeCtx {
             TypeKind::Apply { base, args } => match self.get(base) {
                 TypeKind::Enum { type_params, .. }
                 | TypeKind::Struct { type_params, .. }
+                | TypeKind::Newtype { type_params, .. }
                 | TypeKind::Function { type_params, .. } => type_params.len() != args.len(),
                 _ => false,
             },
@@ -1721,6 +2022,16 @@ impl TypeCtx {
                 }
                 false
             }
+            TypeKind::Newtype {
+                type_params, inner, ..
+            } => {
+                for tp in type_params {
+                    if self.occurs_in(var, tp, seen) {
+                        return true;
+                    }
+                }
+                self.occurs_in(var, inner, seen)
+            }
             TypeKind::Tuple { items } => {
                 for item in items {
                     if self.occurs_in(var, item, seen) {
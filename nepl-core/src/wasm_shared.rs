@@ -180,7 +180,7 @@ fn collect_called_functions_from_expr(
             collect_called_functions_from_expr(then_branch, out, has_indirect);
             collect_called_functions_from_expr(else_branch, out, has_indirect);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             collect_called_functions_from_expr(cond, out, has_indirect);
             collect_called_functions_from_expr(body, out, has_indirect);
         }
@@ -210,7 +210,9 @@ fn collect_called_functions_from_expr(
                 collect_called_functions_from_expr(&line.expr, out, has_indirect);
             }
         }
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             collect_called_functions_from_expr(value, out, has_indirect);
         }
         HirExprKind::Intrinsic { args, .. } => {
@@ -218,7 +220,9 @@ fn collect_called_functions_from_expr(
                 collect_called_functions_from_expr(a, out, has_indirect);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+        HirExprKind::AddrOf(inner)
+        | HirExprKind::Deref(inner)
+        | HirExprKind::Return(inner) => {
             collect_called_functions_from_expr(inner, out, has_indirect);
         }
         HirExprKind::Var(name) | HirExprKind::FnValue(name) => {
@@ -229,7 +233,11 @@ fn collect_called_functions_from_expr(
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
-        | HirExprKind::Drop { .. } => {}
+        | HirExprKind::ConstData(_)
+        | HirExprKind::Drop { .. }
+        | HirExprKind::Break { .. }
+        | HirExprKind::Continue { .. }
+        | HirExprKind::GlobalGet(_) => {}
     }
 }
 
@@ -352,7 +360,7 @@ fn collect_indirect_sigs(expr: &HirExpr, out: &mut Vec<(Vec<ValType>, Vec<ValTyp
             collect_indirect_sigs(then_branch, out, ctx);
             collect_indirect_sigs(else_branch, out, ctx);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             collect_indirect_sigs(cond, out, ctx);
             collect_indirect_sigs(body, out, ctx);
         }
@@ -382,7 +390,9 @@ fn collect_indirect_sigs(expr: &HirExpr, out: &mut Vec<(Vec<ValType>, Vec<ValTyp
                 collect_indirect_sigs(&line.expr, out, ctx);
             }
         }
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             collect_indirect_sigs(value, out, ctx);
         }
         HirExprKind::Intrinsic { args, .. } => {
@@ -390,7 +400,9 @@ fn collect_indirect_sigs(expr: &HirExpr, out: &mut Vec<(Vec<ValType>, Vec<ValTyp
                 collect_indirect_sigs(a, out, ctx);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+        HirExprKind::AddrOf(inner)
+        | HirExprKind::Deref(inner)
+        | HirExprKind::Return(inner) => {
             collect_indirect_sigs(inner, out, ctx);
         }
         HirExprKind::Unit
@@ -398,9 +410,13 @@ fn collect_indirect_sigs(expr: &HirExpr, out: &mut Vec<(Vec<ValType>, Vec<ValTyp
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
         | HirExprKind::Var(_)
         | HirExprKind::FnValue(_)
-        | HirExprKind::Drop { .. } => {}
+        | HirExprKind::Drop { .. }
+        | HirExprKind::Break { .. }
+        | HirExprKind::Continue { .. }
+        | HirExprKind::GlobalGet(_) => {}
     }
 }
 
@@ -0,0 +1,161 @@
+use std::path::PathBuf;
+
+use nepl_core::bindings::{Bindings, ALLOC_EXPORT};
+use nepl_core::error::CoreError;
+use nepl_core::loader::Loader;
+use nepl_core::{
+    compile_module_with_bindings, compile_module_with_source_map, CompilationArtifact,
+    CompileOptions, CompileTarget,
+};
+use wasmi::{Engine, Instance, Linker, Module, Store};
+
+const SRC: &str = r#"#entry main
+#indent 4
+#target core
+#import "core/math" as *
+#import "core/option" as *
+
+struct Point:
+    x <i32>
+    y <i32>
+
+enum Shape:
+    Dot
+    Circle <i32>
+
+pub fn sum <(i32,i32)->i32> (a, b):
+    add a b
+
+pub fn norm1 <(Point)->i32> (p):
+    add get p "x" get p "y"
+
+pub fn radius <(Shape)->Option<i32>> (s):
+    match s:
+        Shape::Dot:
+            Option<i32>::None
+        Shape::Circle r:
+            Option<i32>::Some r
+
+fn helper <(i32)->i32> (v):
+    v
+
+fn main <()->i32> ():
+    0
+"#;
+
+fn options() -> CompileOptions {
+    CompileOptions {
+        target: Some(CompileTarget::Wasm),
+        verbose: false,
+        profile: None,
+    }
+}
+
+fn load(src: &str) -> (nepl_core::ast::Module, nepl_core::loader::SourceMap) {
+    let mut loader = Loader::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../stdlib"));
+    let loaded = loader
+        .load_inline(PathBuf::from("test.nepl"), src.to_string())
+        .expect("load");
+    (loaded.module, loaded.source_map)
+}
+
+fn compile(src: &str) -> Result<(CompilationArtifact, Bindings), CoreError> {
+    let (module, source_map) = load(src);
+    compile_module_with_bindings(module, Some(&source_map), options())
+}
+
+fn instantiate(wasm: &[u8]) -> (Store<()>, Instance) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("module");
+    let mut store = Store::new(&engine, ());
+    let instance = Linker::<()>::new(&engine)
+        .instantiate(&mut store, &module)
+        .expect("instantiate")
+        .start(&mut store)
+        .expect("start");
+    (store, instance)
+}
+
+#[test]
+fn pub_functions_are_exported_and_callable() {
+    let (artifact, _) = compile(SRC).expect("compile");
+    let (mut store, instance) = instantiate(&artifact.wasm);
+    let sum = instance
+        .get_typed_func::<(i32, i32), i32>(&store, "sum")
+        .expect("sum export");
+    assert_eq!(sum.call(&mut store, (2, 3)).unwrap(), 5);
+    assert!(instance.get_func(&store, "helper").is_none());
+
+    // Pass a struct the way the JS runtime does: allocate, then write the fields.
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&store, ALLOC_EXPORT)
+        .expect("allocator export");
+    let memory = instance.get_memory(&store, "memory").unwrap();
+    let ptr = alloc.call(&mut store, 8).unwrap();
+    memory
+        .write(&mut store, ptr as usize, &3i32.to_le_bytes())
+        .unwrap();
+    memory
+        .write(&mut store, ptr as usize + 4, &4i32.to_le_bytes())
+        .unwrap();
+    let norm1 = instance
+        .get_typed_func::<i32, i32>(&store, "norm1")
+        .unwrap();
+    assert_eq!(norm1.call(&mut store, ptr).unwrap(), 7);
+}
+
+#[test]
+fn declarations_describe_structs_enums_and_signatures() {
+    let (_, bindings) = compile(SRC).expect("compile");
+    assert!(bindings
+        .dts
+        .contains("export interface Point {\n  x: number;\n  y: number;\n}"));
+    assert!(bindings.dts.contains(
+        "export type Option_i32 =\n  | { tag: \"None\" }\n  | { tag: \"Some\"; value: number }\n;"
+    ));
+    assert!(bindings.dts.contains("  radius(s: Shape): Option_i32;\n"));
+    assert!(bindings
+        .dts
+        .contains("  sum(a: number, b: number): number;\n"));
+    assert!(!bindings.dts.contains("helper"));
+    assert!(bindings.js.contains(
+        "Point: { kind: \"struct\", size: 8, fields: [[\"x\", 0, 0, \"i32\"], [\"y\", 4, 0, \"i32\"]] }"
+    ));
+    assert!(bindings.js.contains(
+        "Shape: { kind: \"enum\", variants: [[\"Dot\", 0, 4, null], [\"Circle\", 4, 8, \"i32\"]] }"
+    ));
+    assert!(bindings.js.contains("export function bind(instance)"));
+}
+
+#[test]
+fn plain_compilation_does_not_export_pub_functions() {
+    let (module, source_map) = load(SRC);
+    let artifact =
+        compile_module_with_source_map(module, Some(&source_map), options()).expect("compile");
+    let (store, instance) = instantiate(&artifact.wasm);
+    assert!(instance.get_func(&store, "sum").is_none());
+    assert!(instance.get_func(&store, ALLOC_EXPORT).is_none());
+}
+
+#[test]
+fn overloaded_pub_functions_are_rejected() {
+    let src = r#"#entry main
+#indent 4
+#target core
+
+pub fn pick <(i32)->i32> (v):
+    v
+
+pub fn pick <(i32,i32)->i32> (a, b):
+    a
+
+fn main <()->i32> ():
+    0
+"#;
+    let Err(CoreError::Diagnostics(diags)) = compile(src) else {
+        panic!("expected diagnostics");
+    };
+    assert!(diags.iter().any(|d| d
+        .message
+        .contains("cannot export overloaded function 'pick'")));
+}
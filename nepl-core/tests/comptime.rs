@@ -0,0 +1,157 @@
+use nepl_core::diagnostic::Diagnostic;
+use nepl_core::diagnostic_ids::DiagnosticId;
+
+mod harness;
+use harness::{compile_src, run_main_i32, try_compile_src};
+
+fn expect_diag(src: &str, id: DiagnosticId) -> Diagnostic {
+    let diags = match try_compile_src(src) {
+        Ok(_) => panic!("expected {:?}, but compilation succeeded", id),
+        Err(diags) => diags,
+    };
+    diags
+        .into_iter()
+        .find(|d| d.id == Some(id))
+        .unwrap_or_else(|| panic!("expected {:?}", id))
+}
+
+#[test]
+fn const_calls_recursive_pure_function() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+fn fib <(i32)->i32> (n):
+    if lt n 2 n add fib sub n 1 fib sub n 2
+
+const FIB20 <i32> fib 20
+
+fn main <()->i32> ():
+    FIB20
+"#;
+    assert_eq!(run_main_i32(src), 6765);
+}
+
+#[test]
+fn comptime_runs_loops_and_early_return() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+fn sum_to <(i32)->i32> (n):
+    let mut i <i32> 0;
+    let mut acc <i32> 0;
+    while true:
+        do:
+            if gt i n return acc ();
+            set acc add acc i;
+            set i add i 1;
+    0
+
+fn main <()->i32> ():
+    let total <i32> #comptime sum_to 100;
+    total
+"#;
+    assert_eq!(run_main_i32(src), 5050);
+}
+
+#[test]
+fn struct_constant_is_materialized_into_a_data_segment() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/field" as *
+
+struct Pair:
+    a <i32>
+    b <i32>
+
+fn make <(i32)->Pair> (n):
+    Pair mul n 0x1000 0x5A5A5A5A
+
+const P <Pair> make 0x11223
+
+fn main <()->i32> ():
+    let p <Pair> P;
+    get p "a"
+"#;
+    let wasm = compile_src(src);
+    let expected = [0x00, 0x30, 0x22, 0x11, 0x5A, 0x5A, 0x5A, 0x5A];
+    assert!(
+        wasm.windows(expected.len()).any(|w| w == expected),
+        "constant bytes were not found in the data section"
+    );
+    assert_eq!(run_main_i32(src), 0x11223000);
+}
+
+#[test]
+fn runaway_evaluation_runs_out_of_fuel() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+
+fn spin <()->i32> ():
+    while true:
+        ()
+    0
+
+const X <i32> spin
+
+fn main <()->i32> ():
+    X
+"#;
+    expect_diag(src, DiagnosticId::TypeConstEvalFuelExhausted);
+}
+
+#[test]
+fn evaluation_error_reports_the_call_stack() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+fn inner <(i32)->i32> (d):
+    div_s 10 d
+
+fn outer <(i32)->i32> (d):
+    inner sub d 1
+
+fn main <()->i32> ():
+    #comptime outer 1
+"#;
+    let diag = expect_diag(src, DiagnosticId::TypeConstEvalFailed);
+    let labels: Vec<_> = diag
+        .secondary
+        .iter()
+        .filter_map(|l| l.message.clone())
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            "in compile-time call to 'inner'".to_string(),
+            "in compile-time call to 'outer'".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn comptime_rejects_impure_calls() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#extern "env" "tick" fn tick <()*>i32>
+
+fn main <()*>i32> ():
+    #comptime tick
+"#;
+    expect_diag(src, DiagnosticId::TypeConstNotConstant);
+}
@@ -0,0 +1,204 @@
+use nepl_core::diagnostic_ids::DiagnosticId;
+
+mod harness;
+use harness::{run_llvm_main_i32, run_main_i32, run_main_tick_trace, try_compile_src};
+
+// Plain loop/return semantics are covered by tests/compiler/control_flow.n.md;
+// this file keeps the cases that need drop tracing, LLVM execution or diagnostics.
+
+#[test]
+fn return_drops_live_locals_once() {
+    let src = r#"
+#target wasm
+#indent 4
+#entry main
+#no_prelude
+#import "core/traits/drop" as *
+#extern "env" "tick" fn tick <(i32)*>()>
+
+struct Guard:
+    id <i32>
+
+impl Drop for Guard:
+    fn drop <(&Guard)*>()> (self):
+        tick 7;
+        ()
+
+fn pick <(bool)*>i32> (early):
+    let g <Guard> Guard 1;
+    if early return 1 ();
+    2
+
+fn main <()*>i32> ():
+    pick true
+"#;
+    assert_eq!(run_main_tick_trace(src), (1, vec![7]));
+}
+
+#[test]
+fn break_drops_loop_body_locals() {
+    let src = r#"
+#target wasm
+#indent 4
+#entry main
+#no_prelude
+#import "core/traits/drop" as *
+#extern "env" "tick" fn tick <(i32)*>()>
+
+struct Guard:
+    id <i32>
+
+impl Drop for Guard:
+    fn drop <(&Guard)*>()> (self):
+        tick 3;
+        ()
+
+fn main <()*>i32> ():
+    while true:
+        do:
+            let g <Guard> Guard 1;
+            break;
+    0
+"#;
+    assert_eq!(run_main_tick_trace(src), (0, vec![3]));
+}
+
+#[test]
+fn tail_return_is_the_function_result() {
+    let literal = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn main <()->i32> ():
+    return 42
+"#;
+    assert_eq!(run_main_i32(literal), 42);
+    if let Some(code) = run_llvm_main_i32(&literal.replace("#target wasm", "#target llvm")) {
+        assert_eq!(code, 42);
+    }
+
+    let via_local = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+fn bump <(i32)->i32> (x):
+    let y <i32> add x 1;
+    return y
+
+fn main <()->i32> ():
+    bump 6
+"#;
+    assert_eq!(run_main_i32(via_local), 7);
+    if let Some(code) = run_llvm_main_i32(&via_local.replace("#target wasm", "#target llvm")) {
+        assert_eq!(code, 7);
+    }
+}
+
+#[test]
+fn labelled_continue_restarts_outer_loop() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+fn main <()->i32> ():
+    let mut i <i32> 0;
+    let mut hits <i32> 0;
+    while <.outer> lt i 4:
+        do:
+            set i add i 1;
+            let mut j <i32> 0;
+            while lt j 10:
+                do:
+                    set j add j 1;
+                    if gt j i continue <.outer> ();
+                    set hits add hits 1;
+            set hits add hits 100;
+    hits
+"#;
+    // Every inner loop exits through `continue <.outer>`, so the +100 is never reached.
+    assert_eq!(run_main_i32(src), 10);
+    if let Some(code) = run_llvm_main_i32(&src.replace("#target wasm", "#target llvm")) {
+        assert_eq!(code, 10);
+    }
+}
+
+#[test]
+fn break_inside_match_arm() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+enum Step:
+    Stop
+    Go <i32>
+
+fn step_at <(i32)->Step> (i):
+    if ge i 3 Step::Stop Step::Go i
+
+fn main <()->i32> ():
+    let mut i <i32> 0;
+    let mut sum <i32> 0;
+    while true:
+        do:
+            match step_at i:
+                Stop:
+                    break
+                Go v:
+                    set sum add sum v;
+            set i add i 1;
+    sum
+"#;
+    assert_eq!(run_main_i32(src), 3);
+}
+
+#[test]
+fn llvm_runs_break_and_return() {
+    let src = r#"
+#target llvm
+#entry main
+#indent 4
+#import "core/math" as *
+
+fn first_multiple <(i32, i32)->i32> (start, k):
+    let mut n <i32> start;
+    while <.outer> true:
+        do:
+            if eq 0 rem_s n k return n ();
+            if gt n 100 break <.outer> ();
+            set n add n 1;
+    -1
+
+fn main <()->i32> ():
+    add first_multiple 10 7 first_multiple 200 7
+"#;
+    if let Some(code) = run_llvm_main_i32(src) {
+        assert_eq!(code, 13);
+    }
+}
+
+#[test]
+fn break_in_while_condition_is_rejected() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+fn main <()->i32> ():
+    let mut i <i32> 0;
+    while <.a> (if ge i 3 break true):
+        set i add i 1;
+    i
+"#;
+    let errs = try_compile_src(src).unwrap_err();
+    assert!(errs
+        .iter()
+        .any(|d| d.id == Some(DiagnosticId::TypeJumpOutsideLoop)));
+}
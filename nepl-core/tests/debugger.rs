@@ -0,0 +1,209 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use nepl_core::debugger::{DebugSession, DebugValue, StepCommand, DEBUG_IMPORT_MODULE};
+use nepl_core::loader::{Loader, SourceMap};
+use nepl_core::span::FileId;
+use nepl_core::{compile_module_for_debugging, CompileOptions, CompileTarget};
+use wasmi::core::{Trap, F32};
+use wasmi::{Engine, Linker, Module, ResumableCall, Store, Value};
+
+const SRC: &str = r#"#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+struct Point:
+    x <i32>
+    y <i32>
+
+enum Shape:
+    Dot
+    Circle <i32>
+
+fn scale <(i32)->i32> (n):
+    let doubled mul n 2
+    doubled
+
+fn main <()->i32> ():
+    let p Point 3 4
+    let s Shape::Circle 7
+    let label "hi"
+    let r scale 5
+    add r 1
+"#;
+
+/// What the host saw at one pause.
+#[derive(Debug)]
+struct Stop {
+    line: usize,
+    stack: Vec<String>,
+    locals: Vec<(String, String)>,
+}
+
+/// Runs `SRC` under a `DebugSession`, answering every pause with the next command.
+fn debug_run(
+    setup: impl FnOnce(&mut DebugSession, &SourceMap, FileId),
+    mut commands: impl FnMut(&Stop) -> StepCommand,
+) -> (Vec<Stop>, i32) {
+    let mut loader = Loader::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../stdlib"));
+    let loaded = loader
+        .load_inline(PathBuf::from("test.nepl"), SRC.to_string())
+        .expect("load");
+    let source_map = loaded.source_map;
+    let (artifact, info) = compile_module_for_debugging(
+        loaded.module,
+        Some(&source_map),
+        CompileOptions {
+            target: Some(CompileTarget::Wasm),
+            verbose: false,
+            profile: None,
+        },
+    )
+    .expect("compile");
+    let entry = source_map
+        .iter_paths()
+        .find(|(_, path)| path.ends_with("test.nepl"))
+        .map(|(id, _)| id)
+        .expect("entry file");
+
+    let mut session = DebugSession::new(info);
+    for (id, _) in source_map.iter_paths() {
+        if id != entry {
+            session.skip_file(id);
+        }
+    }
+    setup(&mut session, &source_map, entry);
+    let session = Arc::new(Mutex::new(session));
+
+    let engine = Engine::default();
+    let module = Module::new(&engine, &*artifact.wasm).expect("module");
+    let mut linker = Linker::<()>::new(&engine);
+    let s = session.clone();
+    linker
+        .func_wrap(DEBUG_IMPORT_MODULE, "enter", move |f: i32| {
+            s.lock().unwrap().on_enter(f as usize)
+        })
+        .unwrap();
+    let s = session.clone();
+    linker
+        .func_wrap(DEBUG_IMPORT_MODULE, "leave", move || s.lock().unwrap().on_leave())
+        .unwrap();
+    let s = session.clone();
+    linker
+        .func_wrap(DEBUG_IMPORT_MODULE, "step", move |site: i32| -> i32 {
+            s.lock().unwrap().on_step(site as usize) as i32
+        })
+        .unwrap();
+    let s = session.clone();
+    linker
+        .func_wrap(DEBUG_IMPORT_MODULE, "local_i32", move |slot: i32, v: i32| {
+            s.lock().unwrap().on_local(slot as usize, DebugValue::I32(v))
+        })
+        .unwrap();
+    linker
+        .func_wrap(DEBUG_IMPORT_MODULE, "local_f32", |_: i32, _: F32| {})
+        .unwrap();
+    linker
+        .func_wrap(DEBUG_IMPORT_MODULE, "pause", || -> Result<(), Trap> {
+            Err(Trap::new("pause"))
+        })
+        .unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .expect("instantiate")
+        .start(&mut store)
+        .expect("start");
+    let main = instance.get_func(&store, "main").expect("main");
+    let memory = instance.get_memory(&store, "memory").expect("memory");
+
+    let mut stops = Vec::new();
+    let mut outputs = [Value::I32(0)];
+    let mut call = main
+        .call_resumable(&mut store, &[], &mut outputs)
+        .expect("call");
+    while let ResumableCall::Resumable(invocation) = call {
+        let stop = {
+            let session = session.lock().unwrap();
+            let site = session.paused_at().expect("paused");
+            Stop {
+                line: source_map
+                    .line_col(site.span.file_id, site.span.start)
+                    .unwrap()
+                    .0
+                    + 1,
+                stack: session
+                    .call_stack()
+                    .map(|frame| session.function(frame.function).unwrap().name.clone())
+                    .collect(),
+                locals: session.locals(memory.data(&store)),
+            }
+        };
+        let command = commands(&stop);
+        stops.push(stop);
+        session.lock().unwrap().resume(command);
+        call = invocation
+            .resume(&mut store, &[], &mut outputs)
+            .expect("resume");
+    }
+    (stops, outputs[0].i32().unwrap())
+}
+
+fn local<'a>(stop: &'a Stop, name: &str) -> &'a str {
+    stop.locals
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+        .unwrap_or_else(|| panic!("no local `{name}` in {:?}", stop.locals))
+}
+
+#[test]
+fn step_into_visits_every_statement_of_user_code() {
+    let (stops, ret) = debug_run(
+        |session, _, _| session.resume(StepCommand::StepInto),
+        |_| StepCommand::StepInto,
+    );
+    assert_eq!(ret, 11);
+    let lines: Vec<usize> = stops.iter().map(|s| s.line).collect();
+    assert_eq!(lines, vec![19, 20, 21, 22, 15, 16, 23]);
+    assert_eq!(stops[4].stack, vec!["scale", "main"]);
+    assert_eq!(stops[6].stack, vec!["main"]);
+}
+
+#[test]
+fn breakpoint_shows_struct_enum_and_str_locals() {
+    let (stops, _) = debug_run(
+        |session, source_map, entry| {
+            for site in session.sites_on_line(source_map, entry, 21) {
+                session.set_breakpoint(site);
+            }
+        },
+        |_| StepCommand::Continue,
+    );
+    assert_eq!(stops.len(), 1);
+    let stop = &stops[0];
+    assert_eq!(stop.line, 22);
+    assert_eq!(local(stop, "p"), "Point { x: 3, y: 4 }");
+    assert_eq!(local(stop, "s"), "Shape::Circle(7)");
+    assert_eq!(local(stop, "label"), "\"hi\"");
+}
+
+#[test]
+fn step_over_skips_calls_and_step_out_returns_to_the_caller() {
+    let mut commands = vec![StepCommand::StepOut, StepCommand::StepOver].into_iter();
+    let (stops, ret) = debug_run(
+        |session, source_map, entry| {
+            for site in session.sites_on_line(source_map, entry, 14) {
+                session.set_breakpoint(site);
+            }
+        },
+        |_| commands.next().unwrap_or(StepCommand::Continue),
+    );
+    assert_eq!(ret, 11);
+    let lines: Vec<usize> = stops.iter().map(|s| s.line).collect();
+    assert_eq!(lines, vec![15, 23]);
+    assert_eq!(local(&stops[0], "n"), "5");
+    assert_eq!(stops[1].stack, vec!["main"]);
+    assert_eq!(local(&stops[1], "r"), "10");
+}
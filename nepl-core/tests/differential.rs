@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+
+mod harness;
+use harness::{collect_doc_cases, diff_backends, BackendDiff, DocCase};
+
+// Every `neplg2:test` case is compiled through both `compile_module` (wasm) and
+// `emit_ll_from_module_for_target` (LLVM) and the observable results are compared.
+
+/// Doctest files whose cases already behave identically on both backends.
+/// Add a file here once its last divergence is fixed.
+const AGREEING_FILES: &[&str] = &[
+    "block_semicolon_return.n.md",
+    "comptime.n.md",
+    "control_flow.n.md",
+    "globals.n.md",
+    "sizeof.n.md",
+];
+
+fn compiler_doctest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../tests/compiler")
+}
+
+/// Run the cases on a few threads (each one loads the stdlib twice) and collect failures.
+fn divergences(cases: &[DocCase]) -> Vec<String> {
+    let per_thread = cases.len().div_ceil(8).max(1);
+    let results: Vec<(String, BackendDiff)> = std::thread::scope(|s| {
+        let handles: Vec<_> = cases
+            .chunks(per_thread)
+            .map(|chunk| {
+                s.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|case| (case.name.clone(), diff_backends(case)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("differential worker"))
+            .collect()
+    });
+    results
+        .into_iter()
+        .filter_map(|(name, diff)| match diff {
+            BackendDiff::Diverged(detail) => Some(format!("{name}\n{detail}")),
+            BackendDiff::Agree | BackendDiff::Skipped(_) => None,
+        })
+        .collect()
+}
+
+fn assert_no_divergence(cases: &[DocCase]) {
+    let failures = divergences(cases);
+    assert!(
+        failures.is_empty(),
+        "{} of {} cases diverge between wasm and llvm:\n\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("\n\n")
+    );
+}
+
+#[test]
+fn agreeing_doctest_files_match_on_wasm_and_llvm() {
+    let cases: Vec<DocCase> = AGREEING_FILES
+        .iter()
+        .flat_map(|f| collect_doc_cases(&compiler_doctest_dir().join(f)))
+        .collect();
+    assert!(!cases.is_empty());
+    assert_no_divergence(&cases);
+}
+
+/// Full sweep over `tests/compiler`; slow, and still lists known LLVM backend gaps.
+/// Run with `cargo test -p nepl-core --test differential -- --ignored`.
+#[test]
+#[ignore]
+fn all_compiler_doctests_match_on_wasm_and_llvm() {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(compiler_doctest_dir())
+        .expect("tests/compiler")
+        .map(|entry| entry.expect("dir entry").path())
+        .filter(|p| p.to_string_lossy().ends_with(".n.md"))
+        .collect();
+    files.sort();
+    let cases: Vec<DocCase> = files.iter().flat_map(|f| collect_doc_cases(f)).collect();
+    assert_no_divergence(&cases);
+}
+
+fn write_doc(dir: &Path, body: &str) -> Vec<DocCase> {
+    let path = dir.join("case.n.md");
+    std::fs::write(&path, body).expect("write doctest");
+    collect_doc_cases(&path)
+}
+
+#[test]
+fn doc_cases_follow_the_node_doctest_format() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let cases = write_doc(
+        tmp.path(),
+        r#"# sample
+
+neplg2:test[compile_fail]
+diag_id: 3004
+```neplg2
+| #entry main
+fn main <()->i32> ():
+    0
+```
+
+neplg2:test
+stdin: "1 2\n"
+ret: 3
+```neplg2
+#entry main
+```
+"#,
+    );
+    assert_eq!(cases.len(), 2);
+    assert_eq!(cases[0].name, "case.n.md#0");
+    assert!(cases[0].has_tag("compile_fail"));
+    assert!(cases[0].source.starts_with("#entry main\nfn main"));
+    assert_eq!(cases[1].stdin, "1 2\n");
+    assert!(cases[1].tags.is_empty());
+}
+
+#[test]
+fn target_dependent_results_are_reported_as_divergence() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let cases = write_doc(
+        tmp.path(),
+        r#"neplg2:test
+```neplg2
+#entry main
+#indent 4
+
+#if[target=wasm]
+fn pick <()->i32> ():
+    1
+
+#if[target=llvm]
+fn pick <()->i32> ():
+    2
+
+fn main <()->i32> ():
+    pick
+```
+"#,
+    );
+    match diff_backends(&cases[0]) {
+        BackendDiff::Diverged(detail) => assert!(detail.contains("ret: 1"), "{detail}"),
+        BackendDiff::Skipped(reason) => eprintln!("skipped: {reason}"),
+        BackendDiff::Agree => panic!("different results must not agree"),
+    }
+}
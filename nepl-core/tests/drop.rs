@@ -1,71 +1,5 @@
-use nepl_core::diagnostic::Diagnostic;
-use nepl_core::loader::Loader;
-use nepl_core::{compile_module, CompileOptions, CompileTarget};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use wasmi::{Engine, Linker, Module, Store};
-
 mod harness;
-
-fn stdlib_root() -> PathBuf {
-    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("..")
-        .join("stdlib")
-}
-
-fn compile_drop_test(source: &str) -> Result<Vec<u8>, Vec<Diagnostic>> {
-    let mut loader = Loader::new(stdlib_root());
-    let loaded = loader
-        .load_inline(PathBuf::from("drop_test.nepl"), source.to_string())
-        .expect("load");
-    match compile_module(
-        loaded.module,
-        CompileOptions {
-            target: Some(CompileTarget::Wasm),
-            verbose: false,
-            profile: None,
-        },
-    ) {
-        Ok(artifact) => Ok(artifact.wasm),
-        Err(nepl_core::error::CoreError::Diagnostics(ds)) => Err(ds),
-        Err(other) => panic!("unexpected compile error: {other:?}"),
-    }
-}
-
-fn run_drop_trace(source: &str) -> Vec<i32> {
-    let wasm = harness::compile_src_with_options(
-        source,
-        CompileOptions {
-            target: Some(CompileTarget::Wasm),
-            verbose: false,
-            profile: None,
-        },
-    );
-    let engine = Engine::default();
-    let module = Module::new(&engine, &*wasm).expect("module");
-    let trace = Arc::new(Mutex::new(Vec::<i32>::new()));
-    let mut linker = Linker::new(&engine);
-    let host_trace = Arc::clone(&trace);
-    linker
-        .func_wrap("env", "tick", move |value: i32| {
-            host_trace.lock().unwrap().push(value);
-        })
-        .unwrap();
-    let mut store = Store::new(&engine, ());
-    let instance = linker
-        .instantiate(&mut store, &module)
-        .and_then(|pre| pre.start(&mut store))
-        .expect("instantiate");
-    if let Ok(main) = instance.get_typed_func::<(), i32>(&store, "main") {
-        let _ = main.call(&mut store, ()).expect("call");
-    } else if let Ok(main) = instance.get_typed_func::<(), ()>(&store, "main") {
-        main.call(&mut store, ()).expect("call");
-    } else {
-        panic!("main not found");
-    }
-    let out = trace.lock().unwrap().clone();
-    out
-}
+use harness::{run_main_tick_trace, try_compile_src};
 
 #[test]
 fn drop_capability_parses_and_compiles() {
@@ -87,7 +21,7 @@ fn main <()->i32> ():
     let g <Guard> Guard 1;
     0
 "#;
-    let artifact = compile_drop_test(source).expect("drop trait should compile");
+    let artifact = try_compile_src(source).expect("drop trait should compile");
     assert!(!artifact.is_empty(), "generated wasm should not be empty");
 }
 
@@ -113,7 +47,7 @@ fn main <()->i32> ():
     let g <Guard> Guard 0;
     0
 "#;
-    assert_eq!(run_drop_trace(source), vec![7]);
+    assert_eq!(run_main_tick_trace(source).1, vec![7]);
 }
 
 #[test]
@@ -159,7 +93,7 @@ fn main <()->i32> ():
             0
     0
 "#;
-    assert_eq!(run_drop_trace(source), vec![3, 2, 1]);
+    assert_eq!(run_main_tick_trace(source).1, vec![3, 2, 1]);
 }
 
 #[test]
@@ -198,7 +132,7 @@ fn main <()->i32> ():
             2
     0
 "#;
-    assert_eq!(run_drop_trace(source), vec![10]);
+    assert_eq!(run_main_tick_trace(source).1, vec![10]);
 }
 
 #[test]
@@ -236,7 +170,7 @@ fn main <()->i32> ():
             0
     0
 "#;
-    assert_eq!(run_drop_trace(source), vec![2, 1]);
+    assert_eq!(run_main_tick_trace(source).1, vec![2, 1]);
 }
 
 #[test]
@@ -269,7 +203,7 @@ fn main <()*>i32> ():
             0
     0
 "#;
-    compile_drop_test(source).expect("conditional move should not trigger auto-drop diagnostics");
+    try_compile_src(source).expect("conditional move should not trigger auto-drop diagnostics");
 }
 
 #[test]
@@ -292,6 +226,6 @@ fn main <()->i32> ():
     let g <Guard> Guard 9;
     0
 "#;
-    let artifact = compile_drop_test(source).expect("loader-based compile should resolve Drop");
+    let artifact = try_compile_src(source).expect("loader-based compile should resolve Drop");
     assert!(!artifact.is_empty(), "generated wasm should not be empty");
 }
@@ -0,0 +1,152 @@
+use nepl_core::diagnostic::Severity;
+use nepl_core::hir::{FuncRef, HirBody, HirExpr, HirExprKind};
+use nepl_core::loader::Loader;
+use nepl_core::monomorphize::monomorphize;
+use nepl_core::typecheck;
+use nepl_core::{BuildProfile, CompileTarget};
+use std::path::PathBuf;
+
+mod harness;
+use harness::run_main_i32;
+
+// Loop semantics are covered by tests/compiler/for_loops.n.md; this file checks
+// the lowered HIR and the generic-impl path that the doctests cannot observe.
+
+fn visit(expr: &HirExpr, f: &mut dyn FnMut(&HirExpr)) {
+    f(expr);
+    match &expr.kind {
+        HirExprKind::Call { args, .. } | HirExprKind::Intrinsic { args, .. } => {
+            for arg in args {
+                visit(arg, f);
+            }
+        }
+        HirExprKind::CallIndirect { callee, args, .. } => {
+            visit(callee, f);
+            for arg in args {
+                visit(arg, f);
+            }
+        }
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            visit(cond, f);
+            visit(then_branch, f);
+            visit(else_branch, f);
+        }
+        HirExprKind::While { cond, body, .. } => {
+            visit(cond, f);
+            visit(body, f);
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            visit(scrutinee, f);
+            for arm in arms {
+                visit(&arm.body, f);
+            }
+        }
+        HirExprKind::Block(block) => {
+            for line in &block.lines {
+                visit(&line.expr, f);
+            }
+        }
+        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => visit(value, f),
+        _ => {}
+    }
+}
+
+#[test]
+fn for_loop_lowers_to_while_with_a_static_next_call() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+#import "core/iter" as *
+
+fn main <()->i32> ():
+    let mut acc <i32> 0;
+    for i in range 0 4:
+        set acc add acc i
+    acc
+"#;
+    let mut loader = Loader::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../stdlib"));
+    let loaded = loader
+        .load_inline(PathBuf::from("for_loops.nepl"), src.to_string())
+        .expect("load");
+    let mut checked = typecheck::typecheck(
+        &loaded.module,
+        CompileTarget::Wasm,
+        BuildProfile::Debug,
+        Some(&loaded.source_map),
+    );
+    assert!(
+        checked
+            .diagnostics
+            .iter()
+            .all(|d| !matches!(d.severity, Severity::Error)),
+        "{:?}",
+        checked.diagnostics
+    );
+    let module = monomorphize(&mut checked.types, checked.module.expect("module"));
+    let entry = module.entry.clone().expect("entry");
+    let main = module
+        .functions
+        .iter()
+        .find(|f| f.name == entry)
+        .expect("main");
+    let HirBody::Block(body) = &main.body else {
+        panic!("main has no parsed body");
+    };
+
+    let mut loops = 0;
+    let mut next_calls = Vec::new();
+    for line in &body.lines {
+        visit(&line.expr, &mut |e| match &e.kind {
+            HirExprKind::While { .. } => loops += 1,
+            HirExprKind::CallIndirect { .. } => panic!("for loop left an indirect call"),
+            HirExprKind::Call {
+                callee: FuncRef::Trait { .. },
+                ..
+            } => panic!("for loop left an unresolved trait call"),
+            HirExprKind::Call {
+                callee: FuncRef::User(name, _),
+                ..
+            } if name.contains("next") => next_calls.push(name.clone()),
+            _ => {}
+        });
+    }
+    assert_eq!(loops, 1);
+    assert_eq!(next_calls.len(), 1, "{:?}", next_calls);
+}
+
+#[test]
+fn generic_iterator_impl_is_instantiated_per_element_type() {
+    let src = r#"
+#entry main
+#indent 4
+#target std
+#import "alloc/collections/vec" as *
+#import "core/math" as *
+
+fn main <()*>i32> ():
+    let nums <Vec<i32>>:
+        unwrap_ok new<i32>
+        |> push 5 |> uwok
+        |> push 7 |> uwok
+    let flags <Vec<bool>>:
+        unwrap_ok new<bool>
+        |> push true |> uwok
+        |> push false |> uwok
+        |> push true |> uwok
+    let mut acc <i32> 0;
+    for n in iter nums:
+        set acc add acc n
+    for b in iter flags:
+        if b:
+            then set acc add acc 100
+            else ()
+    acc
+"#;
+    assert_eq!(run_main_i32(src), 212);
+}
@@ -0,0 +1,146 @@
+use nepl_core::diagnostic_ids::DiagnosticId;
+
+mod harness;
+use harness::{run_llvm_main_i32, run_main_i32, try_compile_src};
+
+fn assert_diag(src: &str, id: DiagnosticId) {
+    let diags = match try_compile_src(src) {
+        Ok(_) => panic!("expected {:?}, but compilation succeeded", id),
+        Err(diags) => diags,
+    };
+    assert!(
+        diags.iter().any(|d| d.id == Some(id)),
+        "expected {:?}, got {:?}",
+        id,
+        diags
+    );
+}
+
+#[test]
+fn const_is_folded_through_arithmetic() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+const BASE <i32> 40
+const ANSWER <i32> add BASE sub 5 3
+
+fn main <()->i32> ():
+    ANSWER
+"#;
+    assert_eq!(run_main_i32(src), 42);
+}
+
+#[test]
+fn const_struct_value_is_usable_at_runtime() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+struct Pair:
+    a <i32>
+    b <i32>
+
+const ORIGIN <Pair> Pair 3 mul 4 10
+
+fn main <()->i32> ():
+    let p <Pair> ORIGIN;
+    add get p "a" get p "b"
+"#;
+    assert_eq!(run_main_i32(src), 43);
+}
+
+#[test]
+fn static_mut_counter_is_shared_across_calls() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+static mut COUNTER <i32> 10
+
+fn bump <()*>i32> ():
+    set COUNTER add COUNTER 1;
+    COUNTER
+
+fn main <()*>i32> ():
+    bump;
+    bump;
+    bump
+"#;
+    assert_eq!(run_main_i32(src), 13);
+    if let Some(code) = run_llvm_main_i32(&src.replace("#target wasm", "#target llvm")) {
+        assert_eq!(code, 13);
+    }
+}
+
+#[test]
+fn pure_function_cannot_read_static_mut() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+
+static mut COUNTER <i32> 0
+
+fn main <()->i32> ():
+    COUNTER
+"#;
+    assert_diag(src, DiagnosticId::TypePureReadsMutableStatic);
+}
+
+#[test]
+fn const_initializer_must_be_constant() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#extern "env" "now" fn now <()->i32>
+
+const START <i32> now
+
+fn main <()->i32> ():
+    START
+"#;
+    assert_diag(src, DiagnosticId::TypeConstNotConstant);
+}
+
+#[test]
+fn const_cycle_is_reported() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+
+const A <i32> B
+const B <i32> A
+
+fn main <()->i32> ():
+    A
+"#;
+    assert_diag(src, DiagnosticId::TypeConstNotConstant);
+}
+
+#[test]
+fn static_must_have_scalar_type() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+
+struct Pair:
+    a <i32>
+    b <i32>
+
+static mut P <Pair> Pair 1 2
+
+fn main <()->i32> ():
+    0
+"#;
+    assert_diag(src, DiagnosticId::TypeStaticTypeUnsupported);
+}
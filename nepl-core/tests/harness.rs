@@ -1,8 +1,8 @@
 use nepl_core::loader::Loader;
 use nepl_core::{compile_module, CompileOptions, CompileTarget};
-use std::collections::BTreeMap;
+use nepl_wasi::{SharedBuffer, WasiCtx};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use wasmi::{Caller, Engine, Extern, Linker, Module, Store};
 
@@ -32,15 +32,95 @@ pub fn compile_src_with_options(src: &str, options: CompileOptions) -> Vec<u8> {
     artifact.wasm
 }
 
-struct WasiFile {
-    data: Vec<u8>,
-    pos: usize,
+/// Compile source for the wasm target, returning diagnostics instead of panicking.
+pub fn try_compile_src(src: &str) -> Result<Vec<u8>, Vec<nepl_core::diagnostic::Diagnostic>> {
+    let mut loader = Loader::new(stdlib_root());
+    let loaded = loader.load_inline(PathBuf::from("test.nepl"), src.to_string()).expect("load");
+    match compile_module(
+        loaded.module,
+        CompileOptions {
+            target: Some(CompileTarget::Wasm),
+            verbose: false,
+            profile: None,
+        },
+    ) {
+        Ok(artifact) => Ok(artifact.wasm),
+        Err(nepl_core::error::CoreError::Diagnostics(ds)) => Err(ds),
+        Err(other) => panic!("unexpected compile error: {other:?}"),
+    }
+}
+
+/// Compile and run `main`, recording every `env.tick` call.
+/// Returns main's result (0 if main is ()->()) and the recorded values.
+pub fn run_main_tick_trace(src: &str) -> (i32, Vec<i32>) {
+    let wasm = try_compile_src(src).expect("compile failure");
+    let engine = Engine::default();
+    let module = Module::new(&engine, &*wasm).expect("module");
+    let trace = Arc::new(Mutex::new(Vec::<i32>::new()));
+    let mut linker = Linker::new(&engine);
+    let host_trace = Arc::clone(&trace);
+    linker
+        .func_wrap("env", "tick", move |value: i32| {
+            host_trace.lock().unwrap().push(value);
+        })
+        .unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|pre| pre.start(&mut store))
+        .expect("instantiate");
+    let ret = if let Ok(main) = instance.get_typed_func::<(), i32>(&store, "main") {
+        main.call(&mut store, ()).expect("call")
+    } else if let Ok(main) = instance.get_typed_func::<(), ()>(&store, "main") {
+        main.call(&mut store, ()).expect("call");
+        0
+    } else {
+        panic!("main not found");
+    };
+    let out = trace.lock().unwrap().clone();
+    (ret, out)
 }
 
-struct WasiState {
-    args: Vec<Vec<u8>>,
-    files: BTreeMap<i32, WasiFile>,
-    next_fd: i32,
+/// Lower source through the LLVM backend and run `main` with `lli`.
+/// Returns `None` when `lli` is not installed so callers can skip.
+pub fn run_llvm_main_i32(src: &str) -> Option<i32> {
+    let mut loader = Loader::new(stdlib_root());
+    let loaded = loader.load_inline(PathBuf::from("test.nepl"), src.to_string()).expect("load");
+    let ir = nepl_core::codegen_llvm::emit_ll_from_module(&loaded.module).expect("llvm ir");
+    let mut child = match std::process::Command::new("lli")
+        .arg("-")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(_) => {
+            eprintln!("lli not found; skipping llvm execution");
+            return None;
+        }
+    };
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(ir.as_bytes())
+        .expect("write ir");
+    let out = child.wait_with_output().expect("lli");
+    let code = out.status.code().unwrap_or_else(|| {
+        panic!(
+            "lli terminated by signal\n{}\n{}",
+            String::from_utf8_lossy(&out.stderr),
+            ir
+        )
+    });
+    assert!(
+        out.stderr.is_empty(),
+        "lli reported errors:\n{}\n{}",
+        String::from_utf8_lossy(&out.stderr),
+        ir
+    );
+    Some(code)
 }
 
 /// Compile and run `main` returning i32 (or 0 if main is ())->()).
@@ -272,279 +352,15 @@ pub fn run_main_wasi_i32(src: &str) -> i32 {
     );
     let engine = Engine::default();
     let module = Module::new(&engine, &*wasm).expect("module");
-    let mut linker: Linker<WasiState> = Linker::new(&engine);
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "args_sizes_get",
-            |mut caller: Caller<'_, WasiState>, argc_ptr: i32, argv_buf_size_ptr: i32| -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if argc_ptr < 0 || argv_buf_size_ptr < 0 {
-                    return 21;
-                }
-                let argc = caller.data().args.len() as u32;
-                let buf_size: u32 = caller
-                    .data()
-                    .args
-                    .iter()
-                    .map(|a| a.len() as u32)
-                    .sum();
-                let mem_len = memory.data(&caller).len();
-                let argc_offset = argc_ptr as usize;
-                let buf_offset = argv_buf_size_ptr as usize;
-                if argc_offset + 4 > mem_len || buf_offset + 4 > mem_len {
-                    return 21;
-                }
-                if memory
-                    .write(&mut caller, argc_offset, &argc.to_le_bytes())
-                    .is_err()
-                {
-                    return 21;
-                }
-                if memory
-                    .write(&mut caller, buf_offset, &buf_size.to_le_bytes())
-                    .is_err()
-                {
-                    return 21;
-                }
-                0
-            },
-        )
-        .unwrap();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "args_get",
-            |mut caller: Caller<'_, WasiState>, argv: i32, argv_buf: i32| -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if argv < 0 || argv_buf < 0 {
-                    return 21;
-                }
-                let mem_len = memory.data(&caller).len();
-                let args = caller.data().args.clone();
-                let mut argv_offset = argv as usize;
-                let mut buf_offset = argv_buf as usize;
-                for arg in args.iter() {
-                    if argv_offset + 4 > mem_len {
-                        return 21;
-                    }
-                    let ptr_bytes = (buf_offset as u32).to_le_bytes();
-                    if memory
-                        .write(&mut caller, argv_offset, &ptr_bytes)
-                        .is_err()
-                    {
-                        return 21;
-                    }
-                    if buf_offset + arg.len() > mem_len {
-                        return 21;
-                    }
-                    if memory.write(&mut caller, buf_offset, arg).is_err() {
-                        return 21;
-                    }
-                    argv_offset += 4;
-                    buf_offset += arg.len();
-                }
-                0
-            },
-        )
-        .unwrap();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "path_open",
-            |mut caller: Caller<'_, WasiState>,
-             _dirfd: i32,
-             _dirflags: i32,
-             path_ptr: i32,
-             path_len: i32,
-             _oflags: i32,
-             _rights_base: i64,
-             _rights_inherit: i64,
-             _fdflags: i32,
-             fd_out: i32|
-             -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if path_ptr < 0 || path_len < 0 || fd_out < 0 {
-                    return 21;
-                }
-                let mem = memory.data(&caller);
-                let start = path_ptr as usize;
-                let end = start.saturating_add(path_len as usize);
-                if end > mem.len() || (fd_out as usize) + 4 > mem.len() {
-                    return 21;
-                }
-                let path = std::str::from_utf8(&mem[start..end]).unwrap_or("");
-                let content = if path == "test.nepl" {
-                    b"selfhost".to_vec()
-                } else {
-                    return 44;
-                };
-                let fd = caller.data().next_fd;
-                caller.data_mut().next_fd += 1;
-                caller.data_mut().files.insert(
-                    fd,
-                    WasiFile {
-                        data: content,
-                        pos: 0,
-                    },
-                );
-                let fd_bytes = (fd as u32).to_le_bytes();
-                if memory
-                    .write(&mut caller, fd_out as usize, &fd_bytes)
-                    .is_err()
-                {
-                    return 21;
-                }
-                0
-            },
-        )
-        .unwrap();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_read",
-            |mut caller: Caller<'_, WasiState>,
-             fd: i32,
-             iovs: i32,
-             iovs_len: i32,
-             nread: i32|
-             -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if fd < 0 || iovs < 0 || iovs_len < 0 || nread < 0 {
-                    return 21;
-                }
-                let data_snapshot = memory.data(&caller).to_vec();
-                let mut total = 0usize;
-                let mut offset = iovs as usize;
-                let count = if iovs_len > 0 { iovs_len as usize } else { 0 };
-                for _ in 0..count {
-                    if offset + 8 > data_snapshot.len() {
-                        return 21;
-                    }
-                    let base = u32::from_le_bytes(
-                        data_snapshot[offset..offset + 4].try_into().unwrap(),
-                    ) as usize;
-                    let len = u32::from_le_bytes(
-                        data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
-                    ) as usize;
-                    offset += 8;
-                    if base + len > data_snapshot.len() {
-                        return 21;
-                    }
-                    let (take, chunk) = {
-                        let file = match caller.data_mut().files.get_mut(&fd) {
-                            Some(f) => f,
-                            None => return 8,
-                        };
-                        if file.pos >= file.data.len() {
-                            (0, Vec::new())
-                        } else {
-                            let avail = file.data.len() - file.pos;
-                            let take = if len < avail { len } else { avail };
-                            let chunk = file.data[file.pos..file.pos + take].to_vec();
-                            file.pos += take;
-                            (take, chunk)
-                        }
-                    };
-                    if take == 0 {
-                        break;
-                    }
-                    memory.write(&mut caller, base, &chunk).ok();
-                    total += take;
-                }
-                if let Some(mem) = caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    let bytes = (total as u32).to_le_bytes();
-                    if (nread as usize) + 4 <= mem.data(&caller).len() {
-                        mem.write(&mut caller, nread as usize, &bytes).ok();
-                    }
-                }
-                0
-            },
-        )
-        .unwrap();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_close",
-            |mut caller: Caller<'_, WasiState>, fd: i32| -> i32 {
-                if caller.data_mut().files.remove(&fd).is_none() {
-                    return 8;
-                }
-                0
-            },
-        )
-        .unwrap();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_write",
-            |mut caller: Caller<'_, WasiState>,
-             fd: i32,
-             iovs: i32,
-             iovs_len: i32,
-             nwritten: i32|
-             -> i32 {
-                if fd != 1 {
-                    return 8;
-                }
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                let data_snapshot = memory.data(&caller).to_vec();
-                let mut total = 0usize;
-                let mut offset = iovs as usize;
-                let mut stdout = std::io::stdout().lock();
-                for _ in 0..iovs_len {
-                    if offset + 8 > data_snapshot.len() {
-                        return 21;
-                    }
-                    let base = u32::from_le_bytes(
-                        data_snapshot[offset..offset + 4].try_into().unwrap(),
-                    ) as usize;
-                    let len = u32::from_le_bytes(
-                        data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
-                    ) as usize;
-                    offset += 8;
-                    if base + len > data_snapshot.len() {
-                        return 21;
-                    }
-                    let slice = &data_snapshot[base..base + len];
-                    if stdout.write_all(slice).is_err() {
-                        return 21;
-                    }
-                    total += len;
-                }
-                let _ = stdout.flush();
-                if let Some(mem) = caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    let bytes = (total as u32).to_le_bytes();
-                    if (nwritten as usize) + 4 <= mem.data(&caller).len() {
-                        mem.write(&mut caller, nwritten as usize, &bytes).ok();
-                    }
-                }
-                0
-            },
-        )
-        .unwrap();
+    let mut linker: Linker<WasiCtx> = Linker::new(&engine);
+    nepl_wasi::add_to_linker(&mut linker).unwrap();
 
     // Provide simple host allocator (nepl_alloc) for tests
     linker
         .func_wrap(
             "nepl_alloc",
             "alloc",
-            |mut caller: Caller<'_, WasiState>, size: i32| -> i32 {
+            |mut caller: Caller<'_, WasiCtx>, size: i32| -> i32 {
                 let header = 8u32;
                 let size = size as u32;
                 let total = ((size + header + 7) / 8) * 8;
@@ -610,7 +426,7 @@ pub fn run_main_wasi_i32(src: &str) -> i32 {
         .func_wrap(
             "nepl_alloc",
             "dealloc",
-            |mut caller: Caller<'_, WasiState>, ptr: i32, size: i32| {
+            |mut caller: Caller<'_, WasiCtx>, ptr: i32, size: i32| {
                 let header = 8u32;
                 let ptr = ptr as u32;
                 let _size = size as u32;
@@ -644,7 +460,7 @@ pub fn run_main_wasi_i32(src: &str) -> i32 {
         .func_wrap(
             "nepl_alloc",
             "realloc",
-            |mut caller: Caller<'_, WasiState>, ptr: i32, old_size: i32, new_size: i32| -> i32 {
+            |mut caller: Caller<'_, WasiCtx>, ptr: i32, old_size: i32, new_size: i32| -> i32 {
                 let header = 8u32;
                 let ptr = ptr as u32;
                 let old = old_size as u32;
@@ -705,11 +521,7 @@ pub fn run_main_wasi_i32(src: &str) -> i32 {
 
     let mut store = Store::new(
         &engine,
-        WasiState {
-            args: Vec::new(),
-            files: BTreeMap::new(),
-            next_fd: 4,
-        },
+        WasiCtx::builder().file("test.nepl", "selfhost").build(),
     );
     let instance = linker
         .instantiate(&mut store, &module)
@@ -737,84 +549,14 @@ pub fn run_main_capture_stdout(src: &str) -> String {
     );
     let engine = Engine::default();
     let module = Module::new(&engine, &*wasm).expect("module");
-    let output = Arc::new(Mutex::new(String::new()));
-    let mut linker = Linker::new(&engine);
-    let output_buf = output.clone();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_read",
-            move |mut caller: Caller<'_, ()>,
-                  fd: i32,
-                  _iovs_ptr: i32,
-                  _iovs_len: i32,
-                  nread_ptr: i32|
-                  -> i32 {
-                if fd != 0 {
-                    return 8;
-                }
-                if let Some(Extern::Memory(mem)) = caller.get_export("memory") {
-                    if nread_ptr != 0 {
-                        mem.write(&mut caller, nread_ptr as usize, &0u32.to_le_bytes())
-                            .ok();
-                    }
-                }
-                0
-            },
-        )
-        .unwrap();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_write",
-            move |mut caller: Caller<'_, ()>,
-                  fd: i32,
-                  iovs_ptr: i32,
-                  iovs_len: i32,
-                  nwritten_ptr: i32|
-                  -> i32 {
-                if fd != 1 && fd != 2 {
-                    return 8;
-                }
-                if let Some(Extern::Memory(mem)) = caller.get_export("memory") {
-                    let data = mem.data(&caller);
-                    let mut written = 0u32;
-                    let count = if iovs_len > 0 { iovs_len as usize } else { 0 };
-                    let base = if iovs_ptr > 0 { iovs_ptr as usize } else { 0 };
-                    let mut out = output_buf.lock().unwrap();
-                    for idx in 0..count {
-                        let off = base.saturating_add(idx.saturating_mul(8));
-                        if off + 8 > data.len() {
-                            break;
-                        }
-                        let ptr =
-                            u32::from_le_bytes(data[off..off + 4].try_into().unwrap()) as usize;
-                        let len =
-                            u32::from_le_bytes(data[off + 4..off + 8].try_into().unwrap()) as usize;
-                        if ptr + len > data.len() {
-                            break;
-                        }
-                        let bytes = &data[ptr..ptr + len];
-                        match std::str::from_utf8(bytes) {
-                            Ok(s) => out.push_str(s),
-                            Err(_) => out.push_str("<utf8-error>"),
-                        }
-                        written = written.saturating_add(len as u32);
-                    }
-                    if nwritten_ptr != 0 {
-                        mem.write(&mut caller, nwritten_ptr as usize, &written.to_le_bytes())
-                            .ok();
-                    }
-                }
-                0
-            },
-        )
-        .unwrap();
+    let output = SharedBuffer::new();
+    let mut linker: Linker<WasiCtx> = Linker::new(&engine);
+    nepl_wasi::add_to_linker(&mut linker).unwrap();
     linker
         .func_wrap(
             "nepl_alloc",
             "alloc",
-            |mut caller: Caller<'_, ()>, size: i32| -> i32 {
+            |mut caller: Caller<'_, WasiCtx>, size: i32| -> i32 {
                 let header = 8u32;
                 let size = size as u32;
                 let total = ((size + header + 7) / 8) * 8;
@@ -880,7 +622,7 @@ pub fn run_main_capture_stdout(src: &str) -> String {
         .func_wrap(
             "nepl_alloc",
             "dealloc",
-            |mut caller: Caller<'_, ()>, ptr: i32, size: i32| {
+            |mut caller: Caller<'_, WasiCtx>, ptr: i32, size: i32| {
                 let header = 8u32;
                 let ptr = ptr as u32;
                 let _size = size as u32;
@@ -914,7 +656,7 @@ pub fn run_main_capture_stdout(src: &str) -> String {
         .func_wrap(
             "nepl_alloc",
             "realloc",
-            |mut caller: Caller<'_, ()>, ptr: i32, old_size: i32, new_size: i32| -> i32 {
+            |mut caller: Caller<'_, WasiCtx>, ptr: i32, old_size: i32, new_size: i32| -> i32 {
                 let header = 8u32;
                 let ptr = ptr as u32;
                 let old = old_size as u32;
@@ -972,7 +714,13 @@ pub fn run_main_capture_stdout(src: &str) -> String {
             },
         )
         .unwrap();
-    let mut store = Store::new(&engine, ());
+    let mut store = Store::new(
+        &engine,
+        WasiCtx::builder()
+            .stdout(output.clone())
+            .stderr(output.clone())
+            .build(),
+    );
     let instance = linker
         .instantiate(&mut store, &module)
         .and_then(|pre| pre.start(&mut store))
@@ -984,8 +732,7 @@ pub fn run_main_capture_stdout(src: &str) -> String {
     } else {
         panic!("main not found")
     }
-    let captured = output.lock().unwrap().clone();
-    captured
+    output.to_string_lossy()
 }
 
 /// Compile and run `main`, capturing stdout and providing stdin bytes via WASI fd_read.
@@ -1000,118 +747,14 @@ pub fn run_main_capture_stdout_with_stdin(src: &str, stdin: &[u8]) -> String {
     );
     let engine = Engine::default();
     let module = Module::new(&engine, &*wasm).expect("module");
-    let output = Arc::new(Mutex::new(String::new()));
-    let stdin_state = Arc::new(Mutex::new((stdin.to_vec(), 0usize)));
-    let mut linker = Linker::new(&engine);
-    let output_buf = output.clone();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_write",
-            move |mut caller: Caller<'_, ()>,
-                  fd: i32,
-                  iovs_ptr: i32,
-                  iovs_len: i32,
-                  nwritten_ptr: i32|
-                  -> i32 {
-                if fd != 1 && fd != 2 {
-                    return 8;
-                }
-                if let Some(Extern::Memory(mem)) = caller.get_export("memory") {
-                    let data = mem.data(&caller);
-                    let mut written = 0u32;
-                    let count = if iovs_len > 0 { iovs_len as usize } else { 0 };
-                    let base = if iovs_ptr > 0 { iovs_ptr as usize } else { 0 };
-                    let mut out = output_buf.lock().unwrap();
-                    for idx in 0..count {
-                        let off = base.saturating_add(idx.saturating_mul(8));
-                        if off + 8 > data.len() {
-                            break;
-                        }
-                        let ptr =
-                            u32::from_le_bytes(data[off..off + 4].try_into().unwrap()) as usize;
-                        let len =
-                            u32::from_le_bytes(data[off + 4..off + 8].try_into().unwrap()) as usize;
-                        if ptr + len > data.len() {
-                            break;
-                        }
-                        let bytes = &data[ptr..ptr + len];
-                        match std::str::from_utf8(bytes) {
-                            Ok(s) => out.push_str(s),
-                            Err(_) => out.push_str("<utf8-error>"),
-                        }
-                        written = written.saturating_add(len as u32);
-                    }
-                    if nwritten_ptr != 0 {
-                        mem.write(&mut caller, nwritten_ptr as usize, &written.to_le_bytes())
-                            .ok();
-                    }
-                }
-                0
-            },
-        )
-        .unwrap();
-    let stdin_buf = stdin_state.clone();
-    linker
-        .func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_read",
-            move |mut caller: Caller<'_, ()>,
-                  fd: i32,
-                  iovs_ptr: i32,
-                  iovs_len: i32,
-                  nread_ptr: i32|
-                  -> i32 {
-                if fd != 0 {
-                    return 8;
-                }
-                if let Some(Extern::Memory(mem)) = caller.get_export("memory") {
-                    let data_snapshot = mem.data(&caller).to_vec();
-                    let mut total = 0u32;
-                    let count = if iovs_len > 0 { iovs_len as usize } else { 0 };
-                    let base = if iovs_ptr > 0 { iovs_ptr as usize } else { 0 };
-                    let mut state = stdin_buf.lock().unwrap();
-                    for idx in 0..count {
-                        let off = base.saturating_add(idx.saturating_mul(8));
-                        if off + 8 > data_snapshot.len() {
-                            break;
-                        }
-                        let ptr = u32::from_le_bytes(
-                            data_snapshot[off..off + 4].try_into().unwrap(),
-                        ) as usize;
-                        let len = u32::from_le_bytes(
-                            data_snapshot[off + 4..off + 8].try_into().unwrap(),
-                        ) as usize;
-                        if ptr + len > data_snapshot.len() {
-                            break;
-                        }
-                        if state.1 >= state.0.len() {
-                            break;
-                        }
-                        let avail = state.0.len() - state.1;
-                        let take = if len < avail { len } else { avail };
-                        if take == 0 {
-                            break;
-                        }
-                        mem.write(&mut caller, ptr, &state.0[state.1..state.1 + take])
-                            .ok();
-                        state.1 += take;
-                        total = total.saturating_add(take as u32);
-                    }
-                    if nread_ptr != 0 {
-                        mem.write(&mut caller, nread_ptr as usize, &total.to_le_bytes())
-                            .ok();
-                    }
-                }
-                0
-            },
-        )
-        .unwrap();
+    let output = SharedBuffer::new();
+    let mut linker: Linker<WasiCtx> = Linker::new(&engine);
+    nepl_wasi::add_to_linker(&mut linker).unwrap();
     linker
         .func_wrap(
             "nepl_alloc",
             "alloc",
-            |mut caller: Caller<'_, ()>, size: i32| -> i32 {
+            |mut caller: Caller<'_, WasiCtx>, size: i32| -> i32 {
                 let header = 8u32;
                 let size = size as u32;
                 let total = ((size + header + 7) / 8) * 8;
@@ -1177,7 +820,7 @@ pub fn run_main_capture_stdout_with_stdin(src: &str, stdin: &[u8]) -> String {
         .func_wrap(
             "nepl_alloc",
             "dealloc",
-            |mut caller: Caller<'_, ()>, ptr: i32, size: i32| {
+            |mut caller: Caller<'_, WasiCtx>, ptr: i32, size: i32| {
                 let header = 8u32;
                 let ptr = ptr as u32;
                 let _size = size as u32;
@@ -1211,7 +854,7 @@ pub fn run_main_capture_stdout_with_stdin(src: &str, stdin: &[u8]) -> String {
         .func_wrap(
             "nepl_alloc",
             "realloc",
-            |mut caller: Caller<'_, ()>, ptr: i32, old_size: i32, new_size: i32| -> i32 {
+            |mut caller: Caller<'_, WasiCtx>, ptr: i32, old_size: i32, new_size: i32| -> i32 {
                 let header = 8u32;
                 let ptr = ptr as u32;
                 let old = old_size as u32;
@@ -1270,7 +913,14 @@ pub fn run_main_capture_stdout_with_stdin(src: &str, stdin: &[u8]) -> String {
             },
         )
         .unwrap();
-    let mut store = Store::new(&engine, ());
+    let mut store = Store::new(
+        &engine,
+        WasiCtx::builder()
+            .stdin_bytes(stdin)
+            .stdout(output.clone())
+            .stderr(output.clone())
+            .build(),
+    );
     let instance = linker
         .instantiate(&mut store, &module)
         .expect("instantiate")
@@ -1283,8 +933,7 @@ pub fn run_main_capture_stdout_with_stdin(src: &str, stdin: &[u8]) -> String {
     } else {
         panic!("main not found");
     }
-    let captured = output.lock().unwrap().clone();
-    captured
+    output.to_string_lossy()
 }
 
 fn stdlib_root() -> std::path::PathBuf {
@@ -1292,3 +941,352 @@ fn stdlib_root() -> std::path::PathBuf {
         .join("..")
         .join("stdlib")
 }
+
+/// A `neplg2:test` case extracted from a `.n.md` file.
+///
+/// Extraction follows `nodesrc/parser.js`: the metadata lines between the marker and the
+/// next ```` ```neplg2 ```` fence are read, and the `|` hidden-line prefix is stripped.
+#[derive(Debug, Clone)]
+pub struct DocCase {
+    pub name: String,
+    pub dir: PathBuf,
+    pub tags: Vec<String>,
+    pub stdin: String,
+    pub source: String,
+}
+
+impl DocCase {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+/// Collect every `neplg2:test` case of one `.n.md` file.
+pub fn collect_doc_cases(path: &Path) -> Vec<DocCase> {
+    let text = std::fs::read_to_string(path).expect("read doctest file");
+    let lines: Vec<&str> = text.lines().collect();
+    let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+    let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let mut cases = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        i += 1;
+        let Some(rest) = line.strip_prefix("neplg2:test") else {
+            continue;
+        };
+        let tags: Vec<String> = match rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            Some(list) => list
+                .split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            None if rest.is_empty() => Vec::new(),
+            None => continue,
+        };
+        let mut stdin = String::new();
+        while i < lines.len() && lines[i].trim() != "```neplg2" {
+            if let Some(value) = lines[i].trim().strip_prefix("stdin:") {
+                stdin = parse_meta_string(value.trim());
+            }
+            i += 1;
+        }
+        i += 1;
+        let mut source = String::new();
+        while i < lines.len() && lines[i].trim() != "```" {
+            let line = lines[i];
+            let line = match line.strip_prefix('|') {
+                Some(hidden) => hidden.strip_prefix(' ').unwrap_or(hidden),
+                None => line,
+            };
+            source.push_str(line);
+            source.push('\n');
+            i += 1;
+        }
+        i += 1;
+        cases.push(DocCase {
+            name: format!("{file_name}#{}", cases.len()),
+            dir: dir.clone(),
+            tags,
+            stdin,
+            source,
+        });
+    }
+    cases
+}
+
+/// Decode a metadata value written as a JSON string (`"a\nb"`); bare values are kept as is.
+fn parse_meta_string(raw: &str) -> String {
+    let Some(body) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) else {
+        return raw.to_string();
+    };
+    let mut out = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    out.push(c);
+                }
+            }
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Observable result of one backend for one case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendOutcome {
+    /// Error diagnostic IDs (sorted, deduplicated; `0` for errors without an ID).
+    CompileFailed(Vec<u32>),
+    Returned { ret: i32, stdout: String },
+    Trapped { stdout: String },
+}
+
+/// Result of running a case on both backends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendDiff {
+    Agree,
+    /// The case cannot be compared (no native toolchain, unsupported host import, ...).
+    Skipped(String),
+    Diverged(String),
+}
+
+/// Compile a doctest case with `compile_module` and `emit_ll_from_module_for_target`,
+/// run both results and compare return value, stdout, traps and diagnostic IDs.
+pub fn diff_backends(case: &DocCase) -> BackendDiff {
+    if case.has_tag("skip") {
+        return BackendDiff::Skipped(String::from("tagged skip"));
+    }
+    let mut loader = Loader::new(stdlib_root());
+    let loaded = match loader.load_inline(case.dir.join("doctest.nepl"), case.source.clone()) {
+        Ok(loaded) => loaded,
+        // Loading is shared by both backends, so its diagnostics cannot diverge.
+        Err(_) => return BackendDiff::Agree,
+    };
+    let wasm = match run_case_on_wasm(&loaded.module, &case.stdin) {
+        Ok(outcome) => outcome,
+        Err(reason) => return BackendDiff::Skipped(reason),
+    };
+    let llvm = match run_case_on_llvm(&loaded.module, &case.stdin) {
+        Some(Ok(outcome)) => outcome,
+        Some(Err(reason)) => {
+            return BackendDiff::Diverged(format!("wasm: {wasm:?}\nllvm: {reason}"));
+        }
+        None => return BackendDiff::Skipped(String::from("no lli or clang found")),
+    };
+    if wasm == llvm {
+        BackendDiff::Agree
+    } else {
+        BackendDiff::Diverged(format!("wasm: {wasm:?}\nllvm: {llvm:?}"))
+    }
+}
+
+fn error_ids(diags: &[nepl_core::diagnostic::Diagnostic]) -> Vec<u32> {
+    let ids: std::collections::BTreeSet<u32> = diags
+        .iter()
+        .filter(|d| matches!(d.severity, nepl_core::diagnostic::Severity::Error))
+        .map(|d| d.id.map(|id| id.as_u32()).unwrap_or(0))
+        .collect();
+    ids.into_iter().collect()
+}
+
+fn run_case_on_wasm(module: &nepl_core::ast::Module, stdin: &str) -> Result<BackendOutcome, String> {
+    let compiled = std::panic::catch_unwind(|| {
+        compile_module(
+            module.clone(),
+            CompileOptions {
+                target: None,
+                verbose: false,
+                profile: Some(nepl_core::BuildProfile::Debug),
+            },
+        )
+    });
+    let Ok(compiled) = compiled else {
+        return Err(String::from("wasm compile panicked"));
+    };
+    let artifact = match compiled {
+        Ok(artifact) => artifact,
+        Err(nepl_core::error::CoreError::Diagnostics(ds)) => {
+            return Ok(BackendOutcome::CompileFailed(error_ids(&ds)));
+        }
+        Err(other) => return Err(format!("wasm compile error: {other:?}")),
+    };
+    let engine = Engine::default();
+    let wasm_module = Module::new(&engine, &*artifact.wasm).map_err(|e| e.to_string())?;
+    for import in wasm_module.imports() {
+        let known = import.module() == "wasi_snapshot_preview1"
+            && matches!(import.name(), "fd_write" | "fd_read" | "fd_close" | "path_open");
+        if !known {
+            return Err(format!("unsupported import {}::{}", import.module(), import.name()));
+        }
+    }
+    let stdout = SharedBuffer::new();
+    let mut linker: Linker<WasiCtx> = Linker::new(&engine);
+    nepl_wasi::add_to_linker(&mut linker).unwrap();
+    let mut store = Store::new(
+        &engine,
+        WasiCtx::builder()
+            .stdin_bytes(stdin)
+            .stdout(stdout.clone())
+            .build(),
+    );
+    let instance = linker
+        .instantiate(&mut store, &wasm_module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(|e| e.to_string())?;
+    let main = instance
+        .get_func(&store, "main")
+        .ok_or_else(|| String::from("main is not exported"))?;
+    let mut results = match main.ty(&store).results() {
+        [] => Vec::new(),
+        [wasmi::core::ValueType::I32] => vec![wasmi::Value::I32(0)],
+        other => return Err(format!("main returns {other:?}")),
+    };
+    let called = main.call(&mut store, &[], &mut results);
+    let stdout = stdout.to_string_lossy();
+    Ok(match called {
+        Ok(()) => BackendOutcome::Returned {
+            ret: results.first().and_then(|v| v.i32()).unwrap_or(0),
+            stdout,
+        },
+        Err(_) => BackendOutcome::Trapped { stdout },
+    })
+}
+
+/// Marker the LLVM-side wrapper prints to stderr after `main` returns.
+const LLVM_RET_MARKER: &str = "__nepl_diff_ret=";
+
+/// Lower through LLVM and run with `lli` (or `clang` when `lli` is missing).
+/// Returns `None` when neither tool is installed.
+fn run_case_on_llvm(
+    module: &nepl_core::ast::Module,
+    stdin: &str,
+) -> Option<Result<BackendOutcome, String>> {
+    let runner = llvm_runner()?;
+    let emitted = std::panic::catch_unwind(|| {
+        nepl_core::codegen_llvm::emit_ll_from_module_for_target(
+            module,
+            CompileTarget::Llvm,
+            nepl_core::BuildProfile::Debug,
+            false,
+        )
+    });
+    let Ok(emitted) = emitted else {
+        return Some(Err(String::from("llvm codegen panicked")));
+    };
+    let ir = match emitted {
+        Ok(ir) => ir,
+        Err(nepl_core::codegen_llvm::LlvmCodegenError::TypecheckFailed { diagnostics, .. })
+            if !diagnostics.is_empty() =>
+        {
+            return Some(Ok(BackendOutcome::CompileFailed(error_ids(&diagnostics))));
+        }
+        Err(other) => return Some(Err(format!("llvm codegen error: {other}"))),
+    };
+    // Report main's full i32 result through stderr; exit codes are truncated to 8 bits.
+    let Some(main_def) = ["define i32 @main() {", "define i32 @\"main\"() {"]
+        .into_iter()
+        .find(|def| ir.contains(def))
+    else {
+        return Some(Err(String::from("llvm module has no i32 @main")));
+    };
+    let ir = ir.replacen(main_def, "define i32 @__nepl_diff_main() {", 1);
+    let fmt_len = LLVM_RET_MARKER.len() + 5;
+    let ir = format!(
+        "{ir}\n@__nepl_diff_fmt = private unnamed_addr constant [{fmt_len} x i8] c\"\\0A{LLVM_RET_MARKER}%d\\0A\\00\"\n\
+         declare i32 @dprintf(i32, ptr, ...)\n\
+         define i32 @main() {{\nentry:\n  %r = call i32 @__nepl_diff_main()\n  \
+         %p = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @__nepl_diff_fmt, i32 %r)\n  ret i32 0\n}}\n"
+    );
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let ll_path = tmp.path().join("case.ll");
+    std::fs::write(&ll_path, &ir).expect("write ir");
+    let mut command = match &runner {
+        LlvmRunner::Lli { opaque_pointers_flag } => {
+            let mut c = std::process::Command::new("lli");
+            if *opaque_pointers_flag {
+                c.arg("-opaque-pointers");
+            }
+            c.arg(&ll_path);
+            c
+        }
+        LlvmRunner::Clang(clang) => {
+            let exe = tmp.path().join("case");
+            let built = std::process::Command::new(clang)
+                .arg("-O0")
+                .arg(&ll_path)
+                .arg("-o")
+                .arg(&exe)
+                .output()
+                .expect("clang");
+            if !built.status.success() {
+                return Some(Err(format!(
+                    "clang failed:\n{}",
+                    String::from_utf8_lossy(&built.stderr)
+                )));
+            }
+            std::process::Command::new(exe)
+        }
+    };
+    let mut child = command
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("spawn llvm runner");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).ok();
+    let out = child.wait_with_output().expect("llvm runner");
+    let stdout = String::from_utf8_lossy(&out.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if out.status.code().is_none() {
+        return Some(Ok(BackendOutcome::Trapped { stdout }));
+    }
+    let ret = stderr
+        .lines()
+        .rev()
+        .find_map(|l| l.strip_prefix(LLVM_RET_MARKER))
+        .and_then(|v| v.trim().parse::<i32>().ok());
+    Some(match ret {
+        Some(ret) => Ok(BackendOutcome::Returned { ret, stdout }),
+        None => Err(format!("llvm run failed ({}):\n{stderr}", out.status)),
+    })
+}
+
+enum LlvmRunner {
+    Lli { opaque_pointers_flag: bool },
+    Clang(String),
+}
+
+/// The stdlib `#llvmir` blocks use opaque `ptr`, which `lli` before LLVM 15 only accepts
+/// behind `-opaque-pointers`.
+fn llvm_runner() -> Option<LlvmRunner> {
+    if let Ok(out) = std::process::Command::new("lli").arg("--version").output() {
+        let text = String::from_utf8_lossy(&out.stdout);
+        let major = text
+            .split("LLVM version ")
+            .nth(1)
+            .and_then(|v| v.split('.').next())
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .unwrap_or(0);
+        return Some(LlvmRunner::Lli {
+            opaque_pointers_flag: major < 15,
+        });
+    }
+    let clang = std::env::var("NEPL_LLVM_CLANG_BIN").unwrap_or_else(|_| String::from("clang"));
+    std::process::Command::new(&clang)
+        .arg("--version")
+        .output()
+        .ok()
+        .map(|_| LlvmRunner::Clang(clang))
+}
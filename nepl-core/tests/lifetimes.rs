@@ -0,0 +1,83 @@
+use nepl_core::diagnostic_ids::DiagnosticId;
+
+mod harness;
+use harness::{run_main_i32, try_compile_src};
+
+// Accept/reject cases live in tests/compiler/lifetimes.n.md; this file checks
+// where the diagnostics point.
+
+#[test]
+fn dangling_borrow_points_at_the_borrow_and_where_the_referent_dies() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+
+fn main <()->i32> ():
+    let mut r &0
+    block:
+        let inner 2
+        set r &inner
+    *r
+"#;
+    let diags = try_compile_src(src).expect_err("dangling borrow must be rejected");
+    let diag = diags
+        .iter()
+        .find(|d| d.id == Some(DiagnosticId::TypeBorrowOutlivesReferent))
+        .expect("outlives diagnostic");
+    assert_eq!(
+        &src[diag.primary.span.start as usize..diag.primary.span.end as usize],
+        "&inner"
+    );
+    assert_eq!(diag.secondary.len(), 1);
+    let dropped_at = diag.secondary[0].span.start as usize;
+    assert!(
+        dropped_at >= src.find("set r &inner").unwrap() && dropped_at <= src.find("*r").unwrap(),
+        "referent should die at the end of the inner block, got offset {dropped_at}"
+    );
+}
+
+#[test]
+fn returned_borrow_of_a_local_dies_when_the_function_returns() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+fn dangle <(&i32)->&i32> (r):
+    let x 5
+    if eq *r 0 return &x ();
+    r
+
+fn main <()->i32> ():
+    let z 1
+    *dangle &z
+"#;
+    let diags = try_compile_src(src).expect_err("returned local borrow must be rejected");
+    let diag = diags
+        .iter()
+        .find(|d| d.id == Some(DiagnosticId::TypeBorrowOutlivesReferent))
+        .expect("outlives diagnostic");
+    assert!(diag.message.contains("`x`"), "{}", diag.message);
+    let dropped_at = diag.secondary[0].span.start as usize;
+    assert!(dropped_at > src.find("    r\n").unwrap());
+    assert!(dropped_at < src.find("fn main").unwrap());
+}
+
+#[test]
+fn reborrow_through_deref_keeps_the_parameter_as_source() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+
+fn same <(&i32)->&i32> (r):
+    &*r
+
+fn main <()->i32> ():
+    let x 9
+    *same &x
+"#;
+    assert_eq!(run_main_i32(src), 9);
+}
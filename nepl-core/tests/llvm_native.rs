@@ -0,0 +1,70 @@
+use nepl_core::codegen_llvm::{emit_ll_from_module_for_triple, LlvmTargetSpec, LIBC_SHIM_MODULE};
+use nepl_core::loader::Loader;
+use nepl_core::{BuildProfile, CompileTarget};
+use std::path::PathBuf;
+
+fn emit_for_triple(src: &str, triple: &str) -> String {
+    let mut loader = Loader::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../stdlib"));
+    let loaded = loader
+        .load_inline(PathBuf::from("llvm_native.nepl"), src.to_string())
+        .expect("load");
+    let spec = LlvmTargetSpec::from_triple(triple).expect("supported triple");
+    emit_ll_from_module_for_triple(
+        &loaded.module,
+        CompileTarget::Llvm,
+        BuildProfile::Debug,
+        false,
+        &spec,
+    )
+    .expect("llvm ir")
+}
+
+#[test]
+fn triple_and_datalayout_header_is_prepended() {
+    let src = r#"
+#entry main
+#indent 4
+#target llvm
+
+fn main <()->i32> ():
+    3
+"#;
+    let ir = emit_for_triple(src, "aarch64-unknown-linux-gnu");
+    let mut lines = ir.lines();
+    assert!(
+        lines.next().unwrap_or("").starts_with("target datalayout = \"e-m:e-"),
+        "{ir}"
+    );
+    assert_eq!(
+        lines.next(),
+        Some("target triple = \"aarch64-unknown-linux-gnu\"")
+    );
+}
+
+#[test]
+fn unsupported_triple_is_rejected() {
+    let err = LlvmTargetSpec::from_triple("i686-pc-linux-gnu").unwrap_err();
+    assert!(err.to_string().contains("i686-pc-linux-gnu"), "{err}");
+}
+
+#[test]
+fn stdio_lowers_to_the_libc_shim_instead_of_syscalls() {
+    let src = r#"
+#entry main
+#indent 4
+#target llvm
+#import "std/stdio" as *
+
+fn main <()*>()> ():
+    print "hello"
+"#;
+    let ir = emit_for_triple(src, "x86_64-pc-linux-gnu");
+    assert!(ir.contains("define i32 @__nepl_rt_fd_write("), "{ir}");
+    assert!(ir.contains("declare i64 @write("), "{ir}");
+    assert!(
+        !ir.lines()
+            .any(|l| l.starts_with("declare") && l.contains(&format!("@__{LIBC_SHIM_MODULE}_"))),
+        "shim functions must be defined, not declared:\n{ir}"
+    );
+    assert!(!ir.contains("@syscall"), "{ir}");
+}
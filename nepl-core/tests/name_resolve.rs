@@ -0,0 +1,224 @@
+use std::path::PathBuf;
+
+use nepl_core::diagnostic::Diagnostic;
+use nepl_core::diagnostic_ids::DiagnosticId;
+use nepl_core::loader::{LoadResult, Loader, LoaderError};
+use nepl_core::name_resolve::{resolve_names, DefKind, Definition, SymbolTable};
+use nepl_core::span::Span;
+use nepl_core::typecheck::typecheck;
+use nepl_core::{BuildProfile, CompileTarget};
+
+fn load(src: &str, deps: &[(&str, &str)]) -> LoadResult {
+    let mut provider = |path: &PathBuf| -> Result<String, LoaderError> {
+        deps.iter()
+            .find(|(name, _)| path.ends_with(format!("{name}.nepl")))
+            .map(|(_, src)| src.to_string())
+            .ok_or_else(|| LoaderError::Io(format!("missing source: {}", path.display())))
+    };
+    let mut loader = Loader::new(PathBuf::from("/stdlib"));
+    loader
+        .load_inline_with_provider(
+            PathBuf::from("/work/main.nepl"),
+            src.to_string(),
+            &mut provider,
+        )
+        .expect("load")
+}
+
+fn resolve(src: &str, deps: &[(&str, &str)]) -> (LoadResult, SymbolTable, Vec<Diagnostic>) {
+    let loaded = load(src, deps);
+    let result = resolve_names(&loaded.module, Some(&loaded.source_map));
+    (loaded, result.symbols, result.diagnostics)
+}
+
+/// `text` の `nth` 番目の出現位置にある参照が解決した定義。
+fn resolved_at<'a>(
+    loaded: &LoadResult,
+    symbols: &'a SymbolTable,
+    text: &str,
+    nth: usize,
+) -> Option<&'a Definition> {
+    let entry = loaded.module.root.span.file_id;
+    let source = loaded.source_map.get(entry).unwrap();
+    let start = source
+        .match_indices(text)
+        .nth(nth)
+        .expect("text in source")
+        .0 as u32;
+    let span = Span::new(entry, start, start + text.len() as u32);
+    let reference = symbols.reference_at(span).expect("reference");
+    symbols.definition(reference.resolved?)
+}
+
+fn line_of(loaded: &LoadResult, definition: &Definition) -> usize {
+    loaded
+        .source_map
+        .line_col(definition.span.file_id, definition.span.start)
+        .unwrap()
+        .0
+}
+
+#[test]
+fn locals_resolve_to_the_nearest_definition() {
+    let src = r#"#no_prelude
+#entry main
+#indent 4
+
+fn value <()->i32> ():
+    1
+
+fn pick <(i32)->i32> (value):
+    value
+
+fn main <()->i32> ():
+    let mut total 0
+    set total pick value
+    total
+"#;
+    let (loaded, symbols, diags) = resolve(src, &[]);
+    assert!(diags.is_empty(), "{diags:?}");
+
+    let param = resolved_at(&loaded, &symbols, "value", 2).unwrap();
+    assert_eq!(param.kind, DefKind::Param);
+    let global = resolved_at(&loaded, &symbols, "value", 3).unwrap();
+    assert_eq!(global.kind, DefKind::Function);
+    assert_eq!(line_of(&loaded, global), 4);
+
+    let total = resolved_at(&loaded, &symbols, "total", 1).unwrap();
+    assert_eq!(total.kind, DefKind::LetMut);
+    let entry = symbols
+        .references
+        .iter()
+        .find(|r| r.name == "main")
+        .unwrap();
+    assert_eq!(
+        symbols.definition(entry.resolved.unwrap()).unwrap().kind,
+        DefKind::Function
+    );
+}
+
+#[test]
+fn paths_variants_and_fields_resolve_to_their_owner() {
+    let src = r#"#no_prelude
+#entry main
+#indent 4
+
+struct Point:
+    x <i32>
+    y <i32>
+
+enum Shape:
+    Dot
+    Circle <i32>
+
+fn area <(Shape)->i32> (s):
+    match s:
+        Shape::Dot:
+            0
+        Circle r:
+            r
+
+fn main <()->i32> ():
+    let p Point 1 2
+    area Shape::Circle p.y
+"#;
+    let (loaded, symbols, diags) = resolve(src, &[]);
+    assert!(diags.is_empty(), "{diags:?}");
+
+    let shape = resolved_at(&loaded, &symbols, "Shape", 2).unwrap();
+    assert_eq!(shape.kind, DefKind::Enum);
+    let dot = resolved_at(&loaded, &symbols, "Dot", 1).unwrap();
+    assert_eq!(dot.kind, DefKind::EnumVariant);
+    assert_eq!(dot.parent, Some(shape.id));
+
+    let circle = resolved_at(&loaded, &symbols, "Circle", 2).unwrap();
+    assert_eq!(circle.kind, DefKind::EnumVariant);
+    assert_eq!(resolved_at(&loaded, &symbols, "Circle", 1), Some(circle));
+    assert_eq!(resolved_at(&loaded, &symbols, "Shape", 3), Some(shape));
+
+    let y = resolved_at(&loaded, &symbols, "p.y", 0).unwrap();
+    assert_eq!(y.kind, DefKind::StructField);
+    let point = resolved_at(&loaded, &symbols, "Point", 1).unwrap();
+    assert_eq!(y.parent, Some(point.id));
+    let base = symbols
+        .references
+        .iter()
+        .find(|r| r.name == "p")
+        .and_then(|r| symbols.definition(r.resolved?))
+        .unwrap();
+    assert_eq!(base.kind, DefKind::Let);
+}
+
+#[test]
+fn undefined_and_ambiguous_names_are_reported() {
+    let dep = "#no_prelude\nstruct Pair:\n    a <i32>\n";
+    let src = r#"#no_prelude
+#entry main
+#indent 4
+#import "dep" as *
+
+struct Pair:
+    b <i32>
+
+enum Shape:
+    Dot
+
+fn main <()->i32> ():
+    let s Shape::Square
+    let p Pair 1
+    missing
+"#;
+    let (loaded, _, diags) = resolve(src, &[("dep", dep)]);
+    let messages = diags.iter().map(|d| d.message.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "'Shape' has no variant named 'Square'",
+            "ambiguous name 'Pair'",
+            "undefined name 'missing'",
+        ]
+    );
+    assert_eq!(diags[0].id, Some(DiagnosticId::ResolveUndefinedName));
+    assert_eq!(diags[1].id, Some(DiagnosticId::ResolveAmbiguousName));
+    let candidate_files = diags[1]
+        .secondary
+        .iter()
+        .map(|label| loaded.source_map.path(label.span.file_id).unwrap().clone())
+        .collect::<Vec<_>>();
+    assert!(candidate_files.iter().any(|p| p.ends_with("dep.nepl")));
+    assert!(candidate_files.iter().any(|p| p.ends_with("main.nepl")));
+}
+
+#[test]
+fn qualified_imports_resolve_into_the_aliased_module() {
+    let dep = "#no_prelude\n//: from dep\nfn seven <()->i32> ():\n    7\n";
+    let src = r#"#no_prelude
+#entry main
+#indent 4
+#import "dep" as d
+
+fn seven <()->i32> ():
+    0
+
+fn main <()->i32> ():
+    d::seven
+"#;
+    let (loaded, symbols, diags) = resolve(src, &[("dep", dep)]);
+    assert!(diags.is_empty(), "{diags:?}");
+    let seven = resolved_at(&loaded, &symbols, "d::seven", 0).unwrap();
+    assert_eq!(seven.doc.as_deref(), Some("from dep"));
+    assert!(loaded
+        .source_map
+        .path(seven.module)
+        .unwrap()
+        .ends_with("dep.nepl"));
+
+    // 型検査は同じ表の import alias を使い、結果にも表を含める。
+    let tc = typecheck(
+        &loaded.module,
+        CompileTarget::Wasm,
+        BuildProfile::Debug,
+        Some(&loaded.source_map),
+    );
+    assert_eq!(tc.symbols, symbols);
+}
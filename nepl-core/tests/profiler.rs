@@ -0,0 +1,64 @@
+use nepl_core::profiler::{ProfileInfo, ProfileSession};
+
+fn session(names: &[&str]) -> ProfileSession {
+    ProfileSession::new(ProfileInfo {
+        functions: names.iter().map(|n| n.to_string()).collect(),
+    })
+}
+
+#[test]
+fn instructions_are_charged_to_the_running_frame() {
+    // main(0) -> solve(1) -> push(2), then back in main.
+    let mut s = session(&["main", "solve", "push"]);
+    s.on_enter(0, 10);
+    s.on_enter(1, 15);
+    s.on_enter(2, 40);
+    s.on_alloc(16);
+    s.on_leave(100);
+    s.on_leave(130);
+    s.on_leave(132);
+
+    assert_eq!(
+        s.folded_instructions(),
+        "main 7\nmain;solve 55\nmain;solve;push 60\n"
+    );
+    assert_eq!(s.folded_alloc_bytes(), "main;solve;push 16\n");
+    let rows = s.rows();
+    let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, vec!["push", "solve", "main"]);
+    assert_eq!(rows[2].total_instructions, 122);
+    assert_eq!(rows[1].total_instructions, 115);
+    assert!(rows.iter().all(|r| r.alloc_bytes == 16 && r.allocs == 1));
+}
+
+#[test]
+fn recursion_is_counted_once_in_totals() {
+    let mut s = session(&["fib"]);
+    s.on_enter(0, 0);
+    s.on_enter(0, 10);
+    s.on_alloc(8);
+    s.on_leave(30);
+    s.on_leave(35);
+
+    let row = &s.rows()[0];
+    assert_eq!(row.calls, 2);
+    assert_eq!(row.self_instructions, 35);
+    assert_eq!(row.total_instructions, 35);
+    assert_eq!(row.alloc_bytes, 8);
+    assert_eq!(s.folded_instructions(), "fib 15\nfib;fib 20\n");
+}
+
+#[test]
+fn finish_closes_frames_left_open_by_a_trap() {
+    let mut s = session(&["main", "loop_forever"]);
+    s.on_enter(0, 0);
+    s.on_enter(1, 5);
+    s.finish(1005);
+
+    let rows = s.rows();
+    assert_eq!(rows[0].name, "loop_forever");
+    assert_eq!(rows[0].self_instructions, 1000);
+    assert_eq!(rows[1].total_instructions, 1005);
+    let table = s.table();
+    assert!(table.lines().nth(1).unwrap().contains("99.50%"), "{table}");
+}
@@ -0,0 +1,93 @@
+use nepl_core::diagnostic_ids::DiagnosticId;
+
+mod harness;
+use harness::{run_main_tick_trace, try_compile_src};
+
+// `try` semantics are covered by tests/compiler/try_operator.n.md; this file
+// checks drops on the early return and the diagnostics.
+
+#[test]
+fn try_drops_live_locals_on_early_return() {
+    let src = r#"
+#target wasm
+#indent 4
+#entry main
+#no_prelude
+#import "core/option" as *
+#import "core/traits/copy" as *
+#import "core/traits/drop" as *
+#extern "env" "tick" fn tick <(i32)*>()>
+
+struct Guard:
+    id <i32>
+
+impl Drop for Guard:
+    fn drop <(&Guard)*>()> (self):
+        tick 5;
+        ()
+
+fn first <(Option<i32>)*>Option<i32>> (o):
+    let g <Guard> Guard 1;
+    let v try o;
+    some<i32> v
+
+fn main <()*>i32> ():
+    match first none<i32>:
+        Option::Some v:
+            v
+        Option::None:
+            9
+"#;
+    assert_eq!(run_main_tick_trace(src), (9, vec![5]));
+}
+
+#[test]
+fn try_without_from_impl_reports_unconvertible_error() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/result" as *
+
+enum AppError:
+    Failed
+
+fn parse <(i32)->Result<i32, i32>> (x):
+    Result::Err<i32, i32> x
+
+fn run <(i32)->Result<i32, AppError>> (x):
+    let v try parse x;
+    Result::Ok<i32, AppError> v
+
+fn main <()->i32> ():
+    match run 1:
+        Result::Ok v:
+            v
+        Result::Err e:
+            0
+"#;
+    let errs = try_compile_src(src).unwrap_err();
+    assert!(errs
+        .iter()
+        .any(|d| d.id == Some(DiagnosticId::TypeTryErrorNotConvertible)));
+}
+
+#[test]
+fn try_on_plain_value_is_rejected() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/option" as *
+
+fn get <(i32)->Option<i32>> (x):
+    some<i32> try x
+
+fn main <()->i32> ():
+    0
+"#;
+    let errs = try_compile_src(src).unwrap_err();
+    assert!(errs
+        .iter()
+        .any(|d| d.id == Some(DiagnosticId::TypeTryOperandNotFallible)));
+}
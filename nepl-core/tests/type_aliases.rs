@@ -0,0 +1,103 @@
+use nepl_core::compiler::prepare_module_for_codegen;
+use nepl_core::loader::Loader;
+use nepl_core::module_graph::{ExportKind, ModuleGraphBuilder};
+use nepl_core::types::TypeKind;
+use nepl_core::{BuildProfile, CompileTarget};
+use std::fs;
+use std::path::PathBuf;
+
+mod harness;
+use harness::{run_llvm_main_i32, run_main_i32};
+
+// Alias and newtype semantics are covered by tests/compiler/type_aliases.n.md; this
+// file checks erasure, the LLVM backend and the export table.
+
+#[test]
+fn newtype_is_erased_to_its_inner_type_before_codegen() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+newtype Meters = i32
+
+fn double <(Meters)->Meters> (m):
+    Meters mul 2 Meters::inner m
+
+fn main <()->i32> ():
+    Meters::inner double Meters 21
+"#;
+    let mut loader = Loader::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../stdlib"));
+    let loaded = loader
+        .load_inline(PathBuf::from("type_aliases.nepl"), src.to_string())
+        .expect("load");
+    let prepared =
+        prepare_module_for_codegen(&loaded.module, CompileTarget::Wasm, BuildProfile::Debug)
+            .expect("prepare");
+    let double = prepared
+        .hir_module
+        .functions
+        .iter()
+        .find(|f| f.name.starts_with("double"))
+        .expect("double");
+    let types = &prepared.types;
+    assert!(matches!(
+        types.get(types.resolve_id(double.params[0].ty)),
+        TypeKind::I32
+    ));
+    assert!(matches!(
+        types.get(types.resolve_id(double.result)),
+        TypeKind::I32
+    ));
+    assert_eq!(run_main_i32(src), 42);
+}
+
+#[test]
+fn newtype_over_f32_keeps_the_float_layout_on_llvm() {
+    let src = r#"
+#entry main
+#indent 4
+#target core
+#import "core/math" as *
+
+newtype Celsius = f32
+
+fn pick <(bool, Celsius, Celsius)->Celsius> (first, a, b):
+    if first a b
+
+fn main <()->i32> ():
+    trunc_s_f32_to_i32 Celsius::inner pick false Celsius 1.5 Celsius 4.5
+"#;
+    assert_eq!(run_main_i32(src), 4);
+    if let Some(code) = run_llvm_main_i32(src) {
+        assert_eq!(code, 4);
+    }
+}
+
+#[test]
+fn pub_type_items_are_exported() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("main.nepl");
+    let dep = dir.path().join("units.nepl");
+    fs::write(
+        &root,
+        "#import \"./units\" as *\n#entry main\nfn main <()*> ()> ():\n    ()\n",
+    )
+    .unwrap();
+    fs::write(
+        &dep,
+        "pub type Count = i32\npub newtype Meters = i32\ntype Hidden = i32\n",
+    )
+    .unwrap();
+
+    let builder = ModuleGraphBuilder::new(dir.path().to_path_buf());
+    let graph = builder.build(&root).unwrap();
+    let exports = ModuleGraphBuilder::build_exports(&graph).unwrap();
+    let dep_path = fs::canonicalize(&dep).unwrap();
+    let dep_id = graph.nodes.iter().find(|n| n.path == dep_path).unwrap().id;
+    let dep_exports = exports.map.get(&dep_id).unwrap();
+    assert!(matches!(dep_exports["Count"].kind, ExportKind::TypeAlias));
+    assert!(matches!(dep_exports["Meters"].kind, ExportKind::Newtype));
+    assert!(!dep_exports.contains_key("Hidden"));
+}
@@ -1,18 +1,20 @@
 //! エディタ拡張向けの共通解析ライブラリ。
 //!
-//! `nepl-web` の Web 向け API とは別に、Zed / VSCode / 将来の LSP 実装が
-//! 共有できる Rust ネイティブな解析結果を提供します。
+//! Zed / VSCode / LSP 実装と `nepl-web` の Web 向け API が共有する
+//! Rust ネイティブな解析結果を提供します。`serde` feature を有効にすると
+//! 解析結果をそのまま JSON / JS 値へシリアライズできます。
 
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
-use nepl_core::ast::{Block, Directive, FnBody, MatchArm, Module, PrefixExpr, PrefixItem, Stmt, Symbol};
+use nepl_core::ast::{Directive, Module, Stmt};
 use nepl_core::compiler::BuildProfile;
 use nepl_core::diagnostic::{Diagnostic, Severity};
 use nepl_core::diagnostic_ids::DiagnosticId;
 use nepl_core::hir::{HirBlock, HirExpr, HirExprKind, HirLine, HirModule};
 use nepl_core::lexer::{lex, Token, TokenKind};
 use nepl_core::loader::{LoadResult, Loader, LoaderError, SourceMap};
+use nepl_core::name_resolve::{resolve_names, Definition, Reference, ShadowKind, SymbolTable};
 use nepl_core::nm::Document as NmDocument;
 use nepl_core::parser::parse_tokens;
 use nepl_core::span::{FileId, Span};
@@ -27,6 +29,10 @@ pub struct TextPosition {
     pub column: usize,
 }
 
+/// ソース上の範囲。
+///
+/// `serde` feature 有効時は `{ file_id, start, end, start_line, start_col, end_line, end_col, file_path }`
+/// の平坦な形にシリアライズされ、Web playground の span 表現と一致します。
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TextRange {
     pub file_id: u32,
@@ -36,23 +42,39 @@ pub struct TextRange {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct EditorDiagnostic {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_severity"))]
     pub severity: Severity,
     pub id: Option<u32>,
+    pub id_message: Option<&'static str>,
     pub code: Option<&'static str>,
     pub message: String,
+    #[cfg_attr(feature = "serde", serde(rename = "span"))]
     pub range: TextRange,
+    pub secondary: Vec<EditorLabel>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EditorLabel {
+    #[cfg_attr(feature = "serde", serde(rename = "span"))]
+    pub range: TextRange,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TokenInfo {
     pub kind: String,
     pub value: Option<String>,
     pub debug: String,
+    #[cfg_attr(feature = "serde", serde(rename = "span"))]
     pub range: TextRange,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LexAnalysis {
     pub ok: bool,
     pub indent_width: usize,
@@ -74,19 +96,24 @@ impl Default for NameResolutionOptions {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameDefinitionInfo {
     pub id: usize,
     pub name: String,
     pub kind: &'static str,
+    #[cfg_attr(feature = "serde", serde(rename = "span"))]
     pub range: TextRange,
     pub scope_depth: usize,
     pub doc: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub doc_ast: Option<NmDocument>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameReferenceInfo {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "span"))]
     pub range: TextRange,
     pub scope_depth: usize,
     pub resolved_def_id: Option<usize>,
@@ -96,9 +123,11 @@ pub struct NameReferenceInfo {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameShadowInfo {
     pub name: String,
     pub event_kind: &'static str,
+    #[cfg_attr(feature = "serde", serde(rename = "span"))]
     pub range: TextRange,
     pub scope_depth: usize,
     pub selected_def_id: Option<usize>,
@@ -108,12 +137,14 @@ pub struct NameShadowInfo {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameIndexEntry {
     pub definitions: Vec<usize>,
     pub references: Vec<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameResolutionPolicy {
     pub selection: &'static str,
     pub hoist: &'static str,
@@ -121,6 +152,7 @@ pub struct NameResolutionPolicy {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameResolutionAnalysis {
     pub ok: bool,
     pub diagnostics: Vec<EditorDiagnostic>,
@@ -133,10 +165,12 @@ pub struct NameResolutionAnalysis {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SemanticExpressionInfo {
     pub id: usize,
     pub function_name: String,
     pub kind: &'static str,
+    #[cfg_attr(feature = "serde", serde(rename = "span"))]
     pub range: TextRange,
     pub inferred_type: String,
     pub parent_id: Option<usize>,
@@ -144,6 +178,7 @@ pub struct SemanticExpressionInfo {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SemanticTokenInfo {
     pub token_index: usize,
     pub inferred_expr_id: Option<usize>,
@@ -154,13 +189,16 @@ pub struct SemanticTokenInfo {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SemanticFunctionInfo {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "span"))]
     pub range: TextRange,
     pub signature: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TokenHintInfo {
     pub token_index: usize,
     pub inferred_expr_id: Option<usize>,
@@ -169,6 +207,7 @@ pub struct TokenHintInfo {
     pub arg_index: Option<usize>,
     pub arg_range: Option<TextRange>,
     pub name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(rename = "ref_span"))]
     pub ref_range: Option<TextRange>,
     pub resolved_def_id: Option<usize>,
     pub candidate_def_ids: Vec<usize>,
@@ -176,7 +215,17 @@ pub struct TokenHintInfo {
     pub candidate_definitions: Vec<NameDefinitionInfo>,
 }
 
+/// token 単位の名前解決結果。参照を含む token だけが列挙されます。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TokenResolutionInfo {
+    pub token_index: usize,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub reference: NameReferenceInfo,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SemanticsAnalysis {
     pub ok: bool,
     pub tokens: Vec<TokenInfo>,
@@ -186,38 +235,39 @@ pub struct SemanticsAnalysis {
     pub token_hints: Vec<TokenHintInfo>,
     pub functions: Vec<SemanticFunctionInfo>,
     pub name_resolution: Option<NameResolutionAnalysis>,
-    pub token_resolution: Vec<NameReferenceInfo>,
-}
-
-#[derive(Clone)]
-struct NameDefTrace {
-    id: usize,
-    name: String,
-    kind: &'static str,
-    span: Span,
-    scope_depth: usize,
-    doc: Option<String>,
-}
-
-#[derive(Clone)]
-struct NameRefTrace {
-    name: String,
-    span: Span,
-    scope_depth: usize,
-    resolved_def_id: Option<usize>,
-    candidate_def_ids: Vec<usize>,
+    pub token_resolution: Vec<TokenResolutionInfo>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TextRange {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("TextRange", 8)?;
+        state.serialize_field("file_id", &self.file_id)?;
+        state.serialize_field("start", &self.start.byte)?;
+        state.serialize_field("end", &self.end.byte)?;
+        state.serialize_field("start_line", &self.start.line)?;
+        state.serialize_field("start_col", &self.start.column)?;
+        state.serialize_field("end_line", &self.end.line)?;
+        state.serialize_field("end_col", &self.end.column)?;
+        match &self.path {
+            Some(path) => state.serialize_field("file_path", &path.to_string_lossy())?,
+            None => state.skip_field("file_path")?,
+        }
+        state.end()
+    }
 }
 
-#[derive(Clone)]
-struct NameShadowTrace {
-    name: String,
-    event_kind: &'static str,
-    span: Span,
-    scope_depth: usize,
-    selected_def_id: Option<usize>,
-    shadowed_def_ids: Vec<usize>,
-    severity: &'static str,
-    message: String,
+#[cfg(feature = "serde")]
+fn serialize_severity<S: serde::Serializer>(
+    severity: &Severity,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    })
 }
 
 #[derive(Clone)]
@@ -241,138 +291,6 @@ struct SemanticTokenTrace {
     arg_span: Option<Span>,
 }
 
-#[derive(Default)]
-struct NameResolutionTrace {
-    defs: Vec<NameDefTrace>,
-    refs: Vec<NameRefTrace>,
-    shadows: Vec<NameShadowTrace>,
-    scopes: Vec<BTreeMap<String, Vec<usize>>>,
-    warn_important_shadow: bool,
-}
-
-impl NameResolutionTrace {
-    fn new_with_options(warn_important_shadow: bool) -> Self {
-        Self {
-            defs: Vec::new(),
-            refs: Vec::new(),
-            shadows: Vec::new(),
-            scopes: vec![BTreeMap::new()],
-            warn_important_shadow,
-        }
-    }
-
-    fn current_depth(&self) -> usize {
-        self.scopes.len().saturating_sub(1)
-    }
-
-    fn push_scope(&mut self) {
-        self.scopes.push(BTreeMap::new());
-    }
-
-    fn pop_scope(&mut self) {
-        if self.scopes.len() > 1 {
-            self.scopes.pop();
-        }
-    }
-
-    fn define(&mut self, name: String, kind: &'static str, span: Span, doc: Option<String>) -> usize {
-        let existing_candidates = self.lookup_candidates(&name);
-        let id = self.defs.len();
-        let depth = self.current_depth();
-        self.defs.push(NameDefTrace {
-            id,
-            name: name.clone(),
-            kind,
-            span,
-            scope_depth: depth,
-            doc,
-        });
-
-        if !existing_candidates.is_empty() {
-            let severity = if self.warn_important_shadow
-                && is_important_shadow_symbol(&name)
-                && is_variable_def_kind(kind)
-            {
-                "warning"
-            } else {
-                "info"
-            };
-            let message = if severity == "warning" {
-                format!("important symbol '{}' is shadowed by {} definition", name, kind)
-            } else {
-                format!("'{}' shadows an outer definition", name)
-            };
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "definition_shadow",
-                span,
-                scope_depth: depth,
-                selected_def_id: Some(id),
-                shadowed_def_ids: existing_candidates,
-                severity,
-                message,
-            });
-        } else if self.warn_important_shadow
-            && is_important_shadow_symbol(&name)
-            && is_variable_def_kind(kind)
-        {
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "important_name",
-                span,
-                scope_depth: depth,
-                selected_def_id: Some(id),
-                shadowed_def_ids: Vec::new(),
-                severity: "warning",
-                message: format!("definition '{}' may shadow important stdlib symbol", name),
-            });
-        }
-
-        if let Some(scope) = self.scopes.last_mut() {
-            scope.entry(name).or_default().push(id);
-        }
-        id
-    }
-
-    fn lookup_candidates(&self, name: &str) -> Vec<usize> {
-        let mut out = Vec::new();
-        for scope in self.scopes.iter().rev() {
-            if let Some(ids) = scope.get(name) {
-                out.extend(ids.iter().rev().copied());
-            }
-        }
-        out
-    }
-
-    fn reference(&mut self, name: String, span: Span) {
-        let candidates = self.lookup_candidates(&name);
-        let resolved = candidates.first().copied();
-        if candidates.len() > 1 {
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "reference_shadow",
-                span,
-                scope_depth: self.current_depth(),
-                selected_def_id: resolved,
-                shadowed_def_ids: candidates[1..].to_vec(),
-                severity: "info",
-                message: format!(
-                    "'{}' resolved to nearest definition with {} shadowed candidate(s)",
-                    name,
-                    candidates.len().saturating_sub(1)
-                ),
-            });
-        }
-        self.refs.push(NameRefTrace {
-            name,
-            span,
-            scope_depth: self.current_depth(),
-            resolved_def_id: resolved,
-            candidate_def_ids: candidates,
-        });
-    }
-}
-
 pub fn load_module_from_fs(
     stdlib_root: impl Into<PathBuf>,
     entry_path: impl Into<PathBuf>,
@@ -425,17 +343,21 @@ pub fn analyze_name_resolution(
     let parse_result = parse_tokens(file_id, lex_result);
     match parse_result.module {
         Some(module) => {
-            let mut trace = NameResolutionTrace::new_with_options(options.warn_important_shadow);
-            trace_block(&mut trace, &module.root);
+            let resolved = resolve_names(&module, None);
+            let mut diagnostics = parse_result.diagnostics;
+            // import 先を読み込んでいないので、未定義名は誤検出になりうる。
+            diagnostics.extend(
+                resolved
+                    .diagnostics
+                    .into_iter()
+                    .filter(|d| d.id != Some(DiagnosticId::ResolveUndefinedName)),
+            );
             name_resolution_to_editor(
                 source,
                 None,
-                !parse_result
-                    .diagnostics
-                    .iter()
-                    .any(|d| matches!(d.severity, Severity::Error)),
-                &parse_result.diagnostics,
-                &trace,
+                &diagnostics,
+                &resolved.symbols,
+                options.warn_important_shadow,
             )
         }
         None => empty_name_resolution(
@@ -453,9 +375,14 @@ pub fn analyze_loaded_name_resolution(
     loaded: &LoadResult,
     options: NameResolutionOptions,
 ) -> NameResolutionAnalysis {
-    let mut trace = NameResolutionTrace::new_with_options(options.warn_important_shadow);
-    trace_block(&mut trace, &loaded.module.root);
-    name_resolution_to_editor(source, Some(&loaded.source_map), true, &[], &trace)
+    let resolved = resolve_names(&loaded.module, Some(&loaded.source_map));
+    name_resolution_to_editor(
+        source,
+        Some(&loaded.source_map),
+        &resolved.diagnostics,
+        &resolved.symbols,
+        options.warn_important_shadow,
+    )
 }
 
 pub fn analyze_semantics(source: &str) -> SemanticsAnalysis {
@@ -475,6 +402,47 @@ pub fn analyze_loaded_semantics(source: &str, loaded: &LoadResult) -> SemanticsA
     analyze_semantics_from_loaded(source, Some(&loaded.source_map), tokens, token_infos, &loaded.module, lex_result.diagnostics)
 }
 
+/// モジュールの読み込みに失敗したときの名前解決結果を返します。
+pub fn analyze_name_resolution_load_failure(
+    source: &str,
+    error: &LoaderError,
+    options: NameResolutionOptions,
+) -> NameResolutionAnalysis {
+    empty_name_resolution(
+        source,
+        None,
+        false,
+        &[loader_failure_diagnostic(error)],
+        options.warn_important_shadow,
+    )
+}
+
+/// モジュールの読み込みに失敗したときの意味解析結果を返します。
+///
+/// token と字句診断は入力ソース単体から求め、読み込みエラーを診断に追加します。
+pub fn analyze_semantics_load_failure(source: &str, error: &LoaderError) -> SemanticsAnalysis {
+    let lex_result = lex(FileId(0), source);
+    let tokens = tokens_to_editor(source, None, &lex_result.tokens);
+    let mut diagnostics = lex_result.diagnostics;
+    diagnostics.push(loader_failure_diagnostic(error));
+    SemanticsAnalysis {
+        ok: false,
+        tokens,
+        diagnostics: diagnostics_to_editor(source, None, &diagnostics),
+        expressions: Vec::new(),
+        token_semantics: Vec::new(),
+        token_hints: Vec::new(),
+        functions: Vec::new(),
+        name_resolution: None,
+        token_resolution: Vec::new(),
+    }
+}
+
+fn loader_failure_diagnostic(error: &LoaderError) -> Diagnostic {
+    Diagnostic::error(format!("loader error: {}", error), Span::dummy())
+        .with_id(DiagnosticId::LoaderFailure)
+}
+
 fn analyze_semantics_from_parts(
     source: &str,
     source_map: Option<&SourceMap>,
@@ -502,10 +470,6 @@ fn analyze_semantics_from_parts(
         };
     };
 
-    let mut resolve_trace = NameResolutionTrace::new_with_options(true);
-    trace_block(&mut resolve_trace, &module.root);
-    let name_resolution = name_resolution_to_editor(source, source_map, true, &[], &resolve_trace);
-
     let (target, mut target_diags) = resolve_target_for_analysis(&module);
     has_error |= target_diags
         .iter()
@@ -518,13 +482,14 @@ fn analyze_semantics_from_parts(
         .iter()
         .any(|d| matches!(d.severity, Severity::Error));
     all_diags.extend(tc.diagnostics.clone());
+    let name_resolution = name_resolution_to_editor(source, source_map, &[], &tc.symbols, true);
 
     build_semantics_output(
         source,
         source_map,
         token_infos,
         &tokens,
-        &resolve_trace,
+        &tc.symbols,
         name_resolution,
         tc.module.as_ref(),
         &tc.types,
@@ -546,10 +511,6 @@ fn analyze_semantics_from_loaded(
         .iter()
         .any(|d| matches!(d.severity, Severity::Error));
 
-    let mut resolve_trace = NameResolutionTrace::new_with_options(true);
-    trace_block(&mut resolve_trace, &module.root);
-    let name_resolution = name_resolution_to_editor(source, source_map, true, &[], &resolve_trace);
-
     let (target, mut target_diags) = resolve_target_for_analysis(module);
     has_error |= target_diags
         .iter()
@@ -562,13 +523,14 @@ fn analyze_semantics_from_loaded(
         .iter()
         .any(|d| matches!(d.severity, Severity::Error));
     all_diags.extend(tc.diagnostics.clone());
+    let name_resolution = name_resolution_to_editor(source, source_map, &[], &tc.symbols, true);
 
     build_semantics_output(
         source,
         source_map,
         token_infos,
         &tokens,
-        &resolve_trace,
+        &tc.symbols,
         name_resolution,
         tc.module.as_ref(),
         &tc.types,
@@ -582,7 +544,7 @@ fn build_semantics_output(
     source_map: Option<&SourceMap>,
     token_infos: Vec<TokenInfo>,
     tokens: &[Token],
-    resolve_trace: &NameResolutionTrace,
+    symbols: &SymbolTable,
     name_resolution: NameResolutionAnalysis,
     hir_module: Option<&HirModule>,
     types: &TypeCtx,
@@ -635,13 +597,13 @@ fn build_semantics_output(
         })
         .collect::<Vec<_>>();
 
-    let token_resolution = build_token_resolution(source, source_map, tokens, resolve_trace);
+    let token_resolution = build_token_resolution(source, source_map, tokens, symbols);
     let token_semantics_trace = build_token_semantics(tokens, &exprs);
     let token_semantics = token_semantics_trace
         .iter()
         .map(|item| semantic_token_to_editor(source, source_map, item))
         .collect::<Vec<_>>();
-    let token_hints = build_token_hints(source, source_map, tokens, &token_semantics_trace, resolve_trace);
+    let token_hints = build_token_hints(source, source_map, tokens, &token_semantics_trace, symbols);
 
     SemanticsAnalysis {
         ok,
@@ -660,12 +622,15 @@ fn build_token_resolution(
     source: &str,
     source_map: Option<&SourceMap>,
     tokens: &[Token],
-    resolve_trace: &NameResolutionTrace,
-) -> Vec<NameReferenceInfo> {
+    symbols: &SymbolTable,
+) -> Vec<TokenResolutionInfo> {
     let mut out = Vec::new();
-    for token in tokens {
-        if let Some(reference) = best_ref_for_token(resolve_trace, token.span) {
-            out.push(ref_trace_to_editor(source, source_map, reference, &resolve_trace.defs));
+    for (token_index, token) in tokens.iter().enumerate() {
+        if let Some(reference) = symbols.reference_at(token.span) {
+            out.push(TokenResolutionInfo {
+                token_index,
+                reference: reference_to_editor(source, source_map, reference, symbols),
+            });
         }
     }
     out
@@ -719,32 +684,24 @@ fn build_token_hints(
     source_map: Option<&SourceMap>,
     tokens: &[Token],
     token_semantics: &[SemanticTokenTrace],
-    resolve_trace: &NameResolutionTrace,
+    symbols: &SymbolTable,
 ) -> Vec<TokenHintInfo> {
     let mut out = Vec::with_capacity(tokens.len());
     for (token_index, token) in tokens.iter().enumerate() {
         let semantics = token_semantics.get(token_index);
-        let reference = best_ref_for_token(resolve_trace, token.span);
+        let reference = symbols
+            .reference_at(token.span)
+            .map(|reference| reference_to_editor(source, source_map, reference, symbols));
 
         let (resolved_definition, candidate_definitions, candidate_def_ids, resolved_def_id, name, ref_range) =
             if let Some(reference) = reference {
-                let resolved_definition = reference
-                    .resolved_def_id
-                    .and_then(|id| resolve_trace.defs.get(id))
-                    .map(|def| def_trace_to_editor(source, source_map, def));
-                let candidate_definitions = reference
-                    .candidate_def_ids
-                    .iter()
-                    .filter_map(|id| resolve_trace.defs.get(*id))
-                    .map(|def| def_trace_to_editor(source, source_map, def))
-                    .collect::<Vec<_>>();
                 (
-                    resolved_definition,
-                    candidate_definitions,
-                    reference.candidate_def_ids.clone(),
+                    reference.resolved_definition,
+                    reference.candidate_definitions,
+                    reference.candidate_def_ids,
                     reference.resolved_def_id,
-                    Some(reference.name.clone()),
-                    Some(range_from_span(source, source_map, reference.span)),
+                    Some(reference.name),
+                    Some(reference.range),
                 )
             } else {
                 (None, Vec::new(), Vec::new(), None, None, None)
@@ -772,7 +729,8 @@ fn build_token_hints(
     out
 }
 
-fn tokens_to_editor(
+/// token 列をエディタ向けの token 情報に変換します。
+pub fn tokens_to_editor(
     source: &str,
     source_map: Option<&SourceMap>,
     tokens: &[Token],
@@ -788,7 +746,8 @@ fn tokens_to_editor(
         .collect()
 }
 
-fn diagnostics_to_editor(
+/// コンパイラ診断をエディタ向けの診断に変換します。
+pub fn diagnostics_to_editor(
     source: &str,
     source_map: Option<&SourceMap>,
     diagnostics: &[Diagnostic],
@@ -798,9 +757,18 @@ fn diagnostics_to_editor(
         .map(|diagnostic| EditorDiagnostic {
             severity: diagnostic.severity,
             id: diagnostic.id.map(DiagnosticId::as_u32),
+            id_message: diagnostic.id.map(DiagnosticId::message),
             code: diagnostic.code,
             message: diagnostic.message.clone(),
             range: range_from_span(source, source_map, diagnostic.primary.span),
+            secondary: diagnostic
+                .secondary
+                .iter()
+                .map(|label| EditorLabel {
+                    range: range_from_span(source, source_map, label.span),
+                    message: label.message.clone(),
+                })
+                .collect(),
         })
         .collect()
 }
@@ -831,34 +799,29 @@ fn empty_name_resolution(
 fn name_resolution_to_editor(
     source: &str,
     source_map: Option<&SourceMap>,
-    ok: bool,
     diagnostics: &[Diagnostic],
-    trace: &NameResolutionTrace,
+    symbols: &SymbolTable,
+    warn_important_shadow: bool,
 ) -> NameResolutionAnalysis {
-    let definitions = trace
-        .defs
+    let definitions = symbols
+        .definitions
         .iter()
-        .map(|definition| def_trace_to_editor(source, source_map, definition))
+        .map(|definition| definition_to_editor(source, source_map, definition))
         .collect::<Vec<_>>();
-    let references = trace
-        .refs
+    let references = symbols
+        .references
         .iter()
-        .map(|reference| ref_trace_to_editor(source, source_map, reference, &trace.defs))
+        .map(|reference| reference_to_editor(source, source_map, reference, symbols))
         .collect::<Vec<_>>();
-    let shadows = trace
-        .shadows
-        .iter()
-        .map(|shadow| shadow_trace_to_editor(source, source_map, shadow))
-        .collect::<Vec<_>>();
-    let shadow_diagnostics = trace
-        .shadows
+    let shadows = shadows_to_editor(source, source_map, symbols, warn_important_shadow);
+    let shadow_diagnostics = shadows
         .iter()
         .filter(|shadow| matches!(shadow.severity, "warning" | "info"))
-        .map(|shadow| shadow_trace_to_editor(source, source_map, shadow))
+        .cloned()
         .collect::<Vec<_>>();
 
     let mut by_name = BTreeMap::<String, NameIndexEntry>::new();
-    for definition in &trace.defs {
+    for definition in &symbols.definitions {
         by_name
             .entry(definition.name.clone())
             .or_insert_with(|| NameIndexEntry {
@@ -866,9 +829,9 @@ fn name_resolution_to_editor(
                 references: Vec::new(),
             })
             .definitions
-            .push(definition.id);
+            .push(definition.id.0 as usize);
     }
-    for (reference_index, reference) in trace.refs.iter().enumerate() {
+    for (reference_index, reference) in symbols.references.iter().enumerate() {
         by_name
             .entry(reference.name.clone())
             .or_insert_with(|| NameIndexEntry {
@@ -880,7 +843,9 @@ fn name_resolution_to_editor(
     }
 
     NameResolutionAnalysis {
-        ok,
+        ok: !diagnostics
+            .iter()
+            .any(|d| matches!(d.severity, Severity::Error)),
         diagnostics: diagnostics_to_editor(source, source_map, diagnostics),
         definitions,
         references,
@@ -890,20 +855,20 @@ fn name_resolution_to_editor(
         policy: NameResolutionPolicy {
             selection: "nearest_scope_first",
             hoist: "fn and non-mut let",
-            warn_important_shadow: trace.warn_important_shadow,
+            warn_important_shadow,
         },
     }
 }
 
-fn def_trace_to_editor(
+fn definition_to_editor(
     source: &str,
     source_map: Option<&SourceMap>,
-    definition: &NameDefTrace,
+    definition: &Definition,
 ) -> NameDefinitionInfo {
     NameDefinitionInfo {
-        id: definition.id,
+        id: definition.id.0 as usize,
         name: definition.name.clone(),
-        kind: definition.kind,
+        kind: definition.kind.as_str(),
         range: range_from_span(source, source_map, definition.span),
         scope_depth: definition.scope_depth,
         doc: definition.doc.clone(),
@@ -911,46 +876,112 @@ fn def_trace_to_editor(
     }
 }
 
-fn ref_trace_to_editor(
+fn reference_to_editor(
     source: &str,
     source_map: Option<&SourceMap>,
-    reference: &NameRefTrace,
-    definitions: &[NameDefTrace],
+    reference: &Reference,
+    symbols: &SymbolTable,
 ) -> NameReferenceInfo {
     NameReferenceInfo {
         name: reference.name.clone(),
         range: range_from_span(source, source_map, reference.span),
         scope_depth: reference.scope_depth,
-        resolved_def_id: reference.resolved_def_id,
-        candidate_def_ids: reference.candidate_def_ids.clone(),
+        resolved_def_id: reference.resolved.map(|id| id.0 as usize),
+        candidate_def_ids: reference
+            .candidates
+            .iter()
+            .map(|id| id.0 as usize)
+            .collect(),
         resolved_definition: reference
-            .resolved_def_id
-            .and_then(|id| definitions.get(id))
-            .map(|definition| def_trace_to_editor(source, source_map, definition)),
+            .resolved
+            .and_then(|id| symbols.definition(id))
+            .map(|definition| definition_to_editor(source, source_map, definition)),
         candidate_definitions: reference
-            .candidate_def_ids
+            .candidates
             .iter()
-            .filter_map(|id| definitions.get(*id))
-            .map(|definition| def_trace_to_editor(source, source_map, definition))
+            .filter_map(|id| symbols.definition(*id))
+            .map(|definition| definition_to_editor(source, source_map, definition))
             .collect(),
     }
 }
 
-fn shadow_trace_to_editor(
+/// core の shadow 記録に、重要な標準ライブラリ名を隠す定義への警告を加えて変換します。
+fn shadows_to_editor(
     source: &str,
     source_map: Option<&SourceMap>,
-    shadow: &NameShadowTrace,
-) -> NameShadowInfo {
-    NameShadowInfo {
-        name: shadow.name.clone(),
-        event_kind: shadow.event_kind,
-        range: range_from_span(source, source_map, shadow.span),
-        scope_depth: shadow.scope_depth,
-        selected_def_id: shadow.selected_def_id,
-        shadowed_def_ids: shadow.shadowed_def_ids.clone(),
-        severity: shadow.severity,
-        message: shadow.message.clone(),
+    symbols: &SymbolTable,
+    warn_important_shadow: bool,
+) -> Vec<NameShadowInfo> {
+    let is_important = |definition: &Definition| {
+        warn_important_shadow
+            && is_important_shadow_symbol(&definition.name)
+            && is_variable_def_kind(definition.kind.as_str())
+    };
+    let mut out = Vec::new();
+    let mut reported = Vec::new();
+    for shadow in &symbols.shadows {
+        let selected = shadow.selected.and_then(|id| symbols.definition(id));
+        let (event_kind, severity, message) = match shadow.kind {
+            ShadowKind::Definition => {
+                let definition = selected.expect("definition shadow has a definition");
+                reported.push(definition.id);
+                if is_important(definition) {
+                    (
+                        "definition_shadow",
+                        "warning",
+                        format!(
+                            "important symbol '{}' is shadowed by {} definition",
+                            shadow.name,
+                            definition.kind.as_str()
+                        ),
+                    )
+                } else {
+                    (
+                        "definition_shadow",
+                        "info",
+                        format!("'{}' shadows an outer definition", shadow.name),
+                    )
+                }
+            }
+            ShadowKind::Reference => (
+                "reference_shadow",
+                "info",
+                format!(
+                    "'{}' resolved to nearest definition with {} shadowed candidate(s)",
+                    shadow.name,
+                    shadow.shadowed.len()
+                ),
+            ),
+        };
+        out.push(NameShadowInfo {
+            name: shadow.name.clone(),
+            event_kind,
+            range: range_from_span(source, source_map, shadow.span),
+            scope_depth: shadow.scope_depth,
+            selected_def_id: shadow.selected.map(|id| id.0 as usize),
+            shadowed_def_ids: shadow.shadowed.iter().map(|id| id.0 as usize).collect(),
+            severity,
+            message,
+        });
+    }
+    for definition in &symbols.definitions {
+        if is_important(definition) && !reported.contains(&definition.id) {
+            out.push(NameShadowInfo {
+                name: definition.name.clone(),
+                event_kind: "important_name",
+                range: range_from_span(source, source_map, definition.span),
+                scope_depth: definition.scope_depth,
+                selected_def_id: Some(definition.id.0 as usize),
+                shadowed_def_ids: Vec::new(),
+                severity: "warning",
+                message: format!(
+                    "definition '{}' may shadow important stdlib symbol",
+                    definition.name
+                ),
+            });
+        }
     }
+    out
 }
 
 fn semantic_token_to_editor(
@@ -972,7 +1003,8 @@ fn semantic_token_to_editor(
     }
 }
 
-fn range_from_span(source: &str, source_map: Option<&SourceMap>, span: Span) -> TextRange {
+/// `Span` を行・列付きの `TextRange` に変換します。列は文字単位で数えます。
+pub fn range_from_span(source: &str, source_map: Option<&SourceMap>, span: Span) -> TextRange {
     let file_id = span.file_id.0;
     let path = source_map.and_then(|map| map.path(span.file_id)).cloned();
     let text = source_map
@@ -1001,13 +1033,11 @@ fn range_from_span(source: &str, source_map: Option<&SourceMap>, span: Span) ->
 fn line_col_in_text(source: &str, byte: u32) -> (usize, usize) {
     let mut line = 0usize;
     let mut column = 0usize;
-    let mut offset = 0u32;
-    for ch in source.bytes() {
-        if offset == byte {
+    for (offset, ch) in source.char_indices() {
+        if offset as u32 + ch.len_utf8() as u32 > byte {
             return (line, column);
         }
-        offset += 1;
-        if ch == b'\n' {
+        if ch == '\n' {
             line += 1;
             column = 0;
         } else {
@@ -1036,6 +1066,7 @@ fn token_kind_name(kind: &TokenKind) -> &'static str {
         TokenKind::At => "At",
         TokenKind::Dot => "Dot",
         TokenKind::Ampersand => "Ampersand",
+        TokenKind::Region(_) => "Region",
         TokenKind::Star => "Star",
         TokenKind::Minus => "Minus",
         TokenKind::Equals => "Equals",
@@ -1052,6 +1083,10 @@ fn token_kind_name(kind: &TokenKind) -> &'static str {
         TokenKind::KwSet => "KwSet",
         TokenKind::KwIf => "KwIf",
         TokenKind::KwWhile => "KwWhile",
+        TokenKind::KwBreak => "KwBreak",
+        TokenKind::KwContinue => "KwContinue",
+        TokenKind::KwReturn => "KwReturn",
+        TokenKind::KwTry => "KwTry",
         TokenKind::KwCond => "KwCond",
         TokenKind::KwThen => "KwThen",
         TokenKind::KwElse => "KwElse",
@@ -1063,6 +1098,10 @@ fn token_kind_name(kind: &TokenKind) -> &'static str {
         TokenKind::KwImpl => "KwImpl",
         TokenKind::KwFor => "KwFor",
         TokenKind::KwPub => "KwPub",
+        TokenKind::KwConst => "KwConst",
+        TokenKind::KwStatic => "KwStatic",
+        TokenKind::KwType => "KwType",
+        TokenKind::KwNewtype => "KwNewtype",
         TokenKind::KwBlock => "KwBlock",
         TokenKind::KwTuple => "KwTuple",
         TokenKind::KwMlstr => "KwMlstr",
@@ -1079,6 +1118,7 @@ fn token_kind_name(kind: &TokenKind) -> &'static str {
         TokenKind::DirInclude(_) => "DirInclude",
         TokenKind::DirExtern { .. } => "DirExtern",
         TokenKind::DirIntrinsic => "DirIntrinsic",
+        TokenKind::DirComptime => "DirComptime",
         TokenKind::DirPrelude(_) => "DirPrelude",
         TokenKind::DirNoPrelude => "DirNoPrelude",
         TokenKind::WasmText(_) => "WasmText",
@@ -1106,6 +1146,7 @@ fn token_value(kind: &TokenKind) -> Option<String> {
         | TokenKind::WasmText(value)
         | TokenKind::LlvmIrText(value)
         | TokenKind::MlstrLine(value)
+        | TokenKind::Region(value)
         | TokenKind::DocComment(value) => Some(value.clone()),
         TokenKind::BoolLiteral(value) => Some(value.to_string()),
         TokenKind::Arrow(effect) => Some(format!("{:?}", effect)),
@@ -1122,10 +1163,6 @@ fn token_value(kind: &TokenKind) -> Option<String> {
     }
 }
 
-fn is_layout_marker(name: &str) -> bool {
-    matches!(name, "cond" | "then" | "else" | "do" | "block")
-}
-
 fn is_important_shadow_symbol(name: &str) -> bool {
     matches!(
         name,
@@ -1151,163 +1188,6 @@ fn is_variable_def_kind(kind: &str) -> bool {
     matches!(kind, "let_hoisted" | "let_mut" | "param" | "match_bind")
 }
 
-fn hoist_block_defs(trace: &mut NameResolutionTrace, block: &Block) {
-    for stmt in &block.items {
-        match stmt {
-            Stmt::FnDef(definition) => {
-                trace.define(
-                    definition.name.name.clone(),
-                    "fn",
-                    definition.name.span,
-                    definition.doc.clone(),
-                );
-            }
-            Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
-                if let Some(PrefixItem::Symbol(Symbol::Let { name, mutable, .. })) = expr.items.first() {
-                    if !*mutable {
-                        trace.define(name.name.clone(), "let_hoisted", name.span, None);
-                    }
-                }
-            }
-            Stmt::StructDef(definition) => {
-                trace.define(
-                    definition.name.name.clone(),
-                    "struct",
-                    definition.name.span,
-                    definition.doc.clone(),
-                );
-            }
-            Stmt::EnumDef(definition) => {
-                trace.define(
-                    definition.name.name.clone(),
-                    "enum",
-                    definition.name.span,
-                    definition.doc.clone(),
-                );
-            }
-            Stmt::Trait(definition) => {
-                trace.define(
-                    definition.name.name.clone(),
-                    "trait",
-                    definition.name.span,
-                    definition.doc.clone(),
-                );
-            }
-            _ => {}
-        }
-    }
-}
-
-fn trace_match_arm(trace: &mut NameResolutionTrace, arm: &MatchArm) {
-    trace.push_scope();
-    if let Some(bind) = &arm.bind {
-        trace.define(bind.name.clone(), "match_bind", bind.span, None);
-    }
-    trace_block(trace, &arm.body);
-    trace.pop_scope();
-}
-
-fn trace_prefix_expr(trace: &mut NameResolutionTrace, expr: &PrefixExpr) {
-    for (index, item) in expr.items.iter().enumerate() {
-        match item {
-            PrefixItem::Symbol(Symbol::Let { name, mutable, .. }) => {
-                if *mutable {
-                    trace.define(name.name.clone(), "let_mut", name.span, None);
-                }
-                if index != 0 {
-                    trace.reference(name.name.clone(), name.span);
-                }
-            }
-            PrefixItem::Symbol(Symbol::Set { name }) => {
-                trace.reference(name.name.clone(), name.span);
-            }
-            PrefixItem::Symbol(Symbol::Ident(identifier, _, _)) => {
-                if !is_layout_marker(&identifier.name) {
-                    trace.reference(identifier.name.clone(), identifier.span);
-                }
-            }
-            PrefixItem::Block(block, _) => {
-                trace.push_scope();
-                trace_block(trace, block);
-                trace.pop_scope();
-            }
-            PrefixItem::Match(match_expr, _) => {
-                trace_prefix_expr(trace, &match_expr.scrutinee);
-                for arm in &match_expr.arms {
-                    trace_match_arm(trace, arm);
-                }
-            }
-            PrefixItem::Tuple(items, _) => {
-                for item in items {
-                    trace_prefix_expr(trace, item);
-                }
-            }
-            PrefixItem::Group(inner, _) => {
-                trace_prefix_expr(trace, inner);
-            }
-            PrefixItem::Intrinsic(intrinsic, _) => {
-                for arg in &intrinsic.args {
-                    trace_prefix_expr(trace, arg);
-                }
-            }
-            PrefixItem::Literal(_, _)
-            | PrefixItem::TypeAnnotation(_, _)
-            | PrefixItem::Pipe(_)
-            | PrefixItem::Symbol(Symbol::If(_))
-            | PrefixItem::Symbol(Symbol::While(_))
-            | PrefixItem::Symbol(Symbol::AddrOf(_))
-            | PrefixItem::Symbol(Symbol::Deref(_)) => {}
-        }
-    }
-}
-
-fn trace_stmt(trace: &mut NameResolutionTrace, stmt: &Stmt) {
-    match stmt {
-        Stmt::FnDef(definition) => match &definition.body {
-            FnBody::Parsed(body) => {
-                trace.push_scope();
-                for param in &definition.params {
-                    trace.define(param.name.clone(), "param", param.span, None);
-                }
-                trace_block(trace, body);
-                trace.pop_scope();
-            }
-            FnBody::Wasm(_) | FnBody::LlvmIr(_) => {}
-        },
-        Stmt::FnAlias(alias) => {
-            trace.reference(alias.target.name.clone(), alias.target.span);
-            trace.define(alias.name.name.clone(), "fn_alias", alias.name.span, alias.doc.clone());
-        }
-        Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
-            trace_prefix_expr(trace, expr);
-        }
-        _ => {}
-    }
-}
-
-fn trace_block(trace: &mut NameResolutionTrace, block: &Block) {
-    hoist_block_defs(trace, block);
-    for stmt in &block.items {
-        trace_stmt(trace, stmt);
-    }
-}
-
-fn best_ref_for_token<'a>(trace: &'a NameResolutionTrace, token_span: Span) -> Option<&'a NameRefTrace> {
-    let mut best: Option<&NameRefTrace> = None;
-    for reference in &trace.refs {
-        if span_contains(reference.span, token_span) {
-            if let Some(prev) = best {
-                if span_width(reference.span) < span_width(prev.span) {
-                    best = Some(reference);
-                }
-            } else {
-                best = Some(reference);
-            }
-        }
-    }
-    best
-}
-
 fn span_contains(outer: Span, inner: Span) -> bool {
     outer.file_id == inner.file_id && outer.start <= inner.start && inner.end <= outer.end
 }
@@ -1322,6 +1202,7 @@ fn hir_kind_name(kind: &HirExprKind) -> &'static str {
         HirExprKind::LiteralF32(_) => "LiteralF32",
         HirExprKind::LiteralBool(_) => "LiteralBool",
         HirExprKind::LiteralStr(_) => "LiteralStr",
+        HirExprKind::ConstData(_) => "ConstData",
         HirExprKind::Unit => "Unit",
         HirExprKind::Var(_) => "Var",
         HirExprKind::FnValue(_) => "FnValue",
@@ -1329,6 +1210,9 @@ fn hir_kind_name(kind: &HirExprKind) -> &'static str {
         HirExprKind::CallIndirect { .. } => "CallIndirect",
         HirExprKind::If { .. } => "If",
         HirExprKind::While { .. } => "While",
+        HirExprKind::Break { .. } => "Break",
+        HirExprKind::Continue { .. } => "Continue",
+        HirExprKind::Return(_) => "Return",
         HirExprKind::Match { .. } => "Match",
         HirExprKind::EnumConstruct { .. } => "EnumConstruct",
         HirExprKind::StructConstruct { .. } => "StructConstruct",
@@ -1336,6 +1220,8 @@ fn hir_kind_name(kind: &HirExprKind) -> &'static str {
         HirExprKind::Block(_) => "Block",
         HirExprKind::Let { .. } => "Let",
         HirExprKind::Set { .. } => "Set",
+        HirExprKind::GlobalGet(_) => "GlobalGet",
+        HirExprKind::GlobalSet { .. } => "GlobalSet",
         HirExprKind::Intrinsic { .. } => "Intrinsic",
         HirExprKind::AddrOf(_) => "AddrOf",
         HirExprKind::Deref(_) => "Deref",
@@ -1410,7 +1296,7 @@ fn collect_semantic_expr(
             collect_semantic_expr(then_branch, function_name, types, Some(id), out);
             collect_semantic_expr(else_branch, function_name, types, Some(id), out);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             arg_spans.push(cond.span);
             arg_spans.push(body.span);
             collect_semantic_expr(cond, function_name, types, Some(id), out);
@@ -1445,7 +1331,9 @@ fn collect_semantic_expr(
         HirExprKind::Block(block) => {
             collect_semantic_expr_from_block(block, function_name, types, Some(id), out);
         }
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             arg_spans.push(value.span);
             collect_semantic_expr(value, function_name, types, Some(id), out);
         }
@@ -1455,7 +1343,7 @@ fn collect_semantic_expr(
                 collect_semantic_expr(arg, function_name, types, Some(id), out);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
             arg_spans.push(inner.span);
             collect_semantic_expr(inner, function_name, types, Some(id), out);
         }
@@ -1463,9 +1351,13 @@ fn collect_semantic_expr(
         | HirExprKind::LiteralF32(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::ConstData(_)
         | HirExprKind::Unit
         | HirExprKind::Var(_)
-        | HirExprKind::Drop { .. } => {}
+        | HirExprKind::GlobalGet(_)
+        | HirExprKind::Drop { .. }
+        | HirExprKind::Break { .. }
+        | HirExprKind::Continue { .. } => {}
     }
 
     out[id].arg_spans = arg_spans;
@@ -1604,4 +1496,20 @@ fn main <()->i32> ():
         assert_eq!(resolved.doc.as_deref(), Some("from dep"));
         assert!(resolved.range.path.is_some());
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialized_ranges_use_flat_span_shape() {
+        let source = "#no_prelude\nfn main <()->str> ():\n    \"é\" ok\n";
+        let value = serde_json::to_value(analyze_lex(source)).expect("serialize lex analysis");
+        let token = value["tokens"]
+            .as_array()
+            .and_then(|tokens| tokens.iter().find(|token| token["kind"] == "Ident" && token["value"] == "ok"))
+            .expect("ident token");
+        let span = &token["span"];
+        assert_eq!(span["start_line"], 2);
+        assert_eq!(span["start_col"], 8);
+        assert_eq!(span["end_col"], 10);
+        assert!(span.get("file_path").is_none());
+    }
 }
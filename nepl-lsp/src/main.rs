@@ -446,11 +446,12 @@ fn semantic_token_type(token: &nepl_language::TokenInfo, analysis: &SemanticsAna
         kind if kind.starts_with("Kw") || kind.starts_with("Dir") => 9,
         "Ident" => {
             match hint.and_then(|hint| hint.resolved_definition.as_ref()).map(|definition| definition.kind) {
-                Some("fn") | Some("fn_alias") => 8,
+                Some("fn") | Some("fn_alias") | Some("extern") | Some("trait_method") => 8,
                 Some("param") => 5,
+                Some("field") => 7,
                 Some("struct") => 2,
                 Some("enum") => 3,
-                Some("trait") => 1,
+                Some("trait") | Some("type") | Some("newtype") => 1,
                 _ => 6,
             }
         }
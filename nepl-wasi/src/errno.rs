@@ -0,0 +1,15 @@
+//! ホスト関数が返す WASI preview1 の errno 値。
+
+pub type Errno = i32;
+
+pub const SUCCESS: Errno = 0;
+pub const BADF: Errno = 8;
+pub const EXIST: Errno = 20;
+pub const FAULT: Errno = 21;
+pub const INVAL: Errno = 28;
+pub const IO: Errno = 29;
+pub const ISDIR: Errno = 31;
+pub const NOENT: Errno = 44;
+pub const NOTDIR: Errno = 54;
+pub const SPIPE: Errno = 70;
+pub const NOTCAPABLE: Errno = 76;
@@ -0,0 +1,51 @@
+//! ゲストの線形メモリを読み書きする補助関数。
+//!
+//! 範囲外アクセスはすべて `FAULT` として呼び出し元へ返す。
+
+use crate::errno::{self, Errno};
+
+pub(crate) fn bytes(mem: &[u8], ptr: u32, len: u32) -> Result<&[u8], Errno> {
+    let start = ptr as usize;
+    let end = start.checked_add(len as usize).ok_or(errno::FAULT)?;
+    mem.get(start..end).ok_or(errno::FAULT)
+}
+
+pub(crate) fn bytes_mut(mem: &mut [u8], ptr: u32, len: u32) -> Result<&mut [u8], Errno> {
+    let start = ptr as usize;
+    let end = start.checked_add(len as usize).ok_or(errno::FAULT)?;
+    mem.get_mut(start..end).ok_or(errno::FAULT)
+}
+
+pub(crate) fn write(mem: &mut [u8], ptr: u32, data: &[u8]) -> Result<(), Errno> {
+    bytes_mut(mem, ptr, data.len() as u32)?.copy_from_slice(data);
+    Ok(())
+}
+
+pub(crate) fn read_u32(mem: &[u8], ptr: u32) -> Result<u32, Errno> {
+    let raw = bytes(mem, ptr, 4)?;
+    Ok(u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]))
+}
+
+pub(crate) fn write_u32(mem: &mut [u8], ptr: u32, value: u32) -> Result<(), Errno> {
+    write(mem, ptr, &value.to_le_bytes())
+}
+
+pub(crate) fn write_u64(mem: &mut [u8], ptr: u32, value: u64) -> Result<(), Errno> {
+    write(mem, ptr, &value.to_le_bytes())
+}
+
+/// `iovec` / `ciovec` の配列を `(buf, len)` の組として読み出す。
+pub(crate) fn iovecs(mem: &[u8], ptr: u32, count: u32) -> Result<Vec<(u32, u32)>, Errno> {
+    (0..count)
+        .map(|idx| {
+            let base = idx
+                .checked_mul(8)
+                .and_then(|offset| ptr.checked_add(offset))
+                .ok_or(errno::FAULT)?;
+            let buf = read_u32(mem, base)?;
+            let len = read_u32(mem, base.checked_add(4).ok_or(errno::FAULT)?)?;
+            bytes(mem, buf, len)?;
+            Ok((buf, len))
+        })
+        .collect()
+}
@@ -0,0 +1,778 @@
+//! wasmi 上で動く WASI preview1 のホスト実装。
+//!
+//! `nepl-cli --run`・Web プレイグラウンド (`nepl-web`)・`nepl-core` のテストハーネスが
+//! 同じ実装を共有し、ファイル読み込みや引数の扱いが実行環境ごとに食い違わないようにする。
+//!
+//! - ファイルシステムはメモリ上の [`Vfs`]。`fd 3` に `/` をプレオープンする。
+//! - stdin / stdout / stderr は任意の `Read` / `Write` を差し込める。
+//!   [`SharedBuffer`] を使えば出力を後から読み出せる。
+//! - 時計と乱数は決定的で、同じ設定なら毎回同じ値を返す。
+//!
+//! ```ignore
+//! let stdout = SharedBuffer::new();
+//! let ctx = WasiCtx::builder()
+//!     .arg("main.wasm")
+//!     .file("/input.txt", "hello")
+//!     .stdout(stdout.clone())
+//!     .build();
+//! let mut store = Store::new(&engine, ctx);
+//! let mut linker = Linker::new(&engine);
+//! nepl_wasi::add_to_linker(&mut linker)?;
+//! ```
+
+pub mod errno;
+mod guest;
+mod stdio;
+mod syscalls;
+mod vfs;
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+use wasmi::core::Trap;
+use wasmi::errors::LinkerError;
+use wasmi::{Caller, Extern, Linker};
+
+pub use errno::Errno;
+pub use stdio::SharedBuffer;
+pub use vfs::{normalize, Vfs};
+
+/// WASI のインポートモジュール名。
+pub const MODULE: &str = "wasi_snapshot_preview1";
+
+/// プレオープンしたルートディレクトリの fd。
+pub const PREOPEN_FD: u32 = 3;
+
+/// ストアのデータから [`WasiCtx`] を取り出すためのトレイト。
+/// ホスト側が WASI 以外の状態も持つ場合はこれを実装する。
+pub trait WasiView {
+    fn wasi(&mut self) -> &mut WasiCtx;
+}
+
+impl WasiView for WasiCtx {
+    fn wasi(&mut self) -> &mut WasiCtx {
+        self
+    }
+}
+
+/// 1 回の実行ぶんの WASI ホスト状態。
+pub struct WasiCtx {
+    args: Vec<Vec<u8>>,
+    env: Vec<Vec<u8>>,
+    vfs: Vfs,
+    stdin: Box<dyn Read + Send>,
+    stdout: Box<dyn Write + Send>,
+    stderr: Box<dyn Write + Send>,
+    fds: BTreeMap<u32, FdEntry>,
+    next_fd: u32,
+    clock: Clock,
+    random: Random,
+}
+
+enum FdEntry {
+    Stdin,
+    Stdout,
+    Stderr,
+    Dir(String),
+    File(OpenFile),
+}
+
+struct OpenFile {
+    key: String,
+    pos: u64,
+    append: bool,
+    writable: bool,
+}
+
+impl WasiCtx {
+    pub fn builder() -> WasiCtxBuilder {
+        WasiCtxBuilder::default()
+    }
+
+    pub fn vfs(&self) -> &Vfs {
+        &self.vfs
+    }
+
+    pub fn vfs_mut(&mut self) -> &mut Vfs {
+        &mut self.vfs
+    }
+
+    pub fn into_vfs(self) -> Vfs {
+        self.vfs
+    }
+
+    /// stdout / stderr に溜まった出力を書き出す。
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()?;
+        self.stderr.flush()
+    }
+}
+
+impl Default for WasiCtx {
+    fn default() -> Self {
+        WasiCtxBuilder::default().build()
+    }
+}
+
+/// [`WasiCtx`] の組み立て。指定しなかった stdin は空、stdout / stderr は破棄になる。
+pub struct WasiCtxBuilder {
+    args: Vec<Vec<u8>>,
+    env: Vec<Vec<u8>>,
+    vfs: Vfs,
+    stdin: Box<dyn Read + Send>,
+    stdout: Box<dyn Write + Send>,
+    stderr: Box<dyn Write + Send>,
+    clock: Clock,
+    random: Random,
+}
+
+impl Default for WasiCtxBuilder {
+    fn default() -> Self {
+        Self {
+            args: Vec::new(),
+            env: Vec::new(),
+            vfs: Vfs::new(),
+            stdin: Box::new(io::empty()),
+            stdout: Box::new(io::sink()),
+            stderr: Box::new(io::sink()),
+            clock: Clock::new(0, Clock::DEFAULT_STEP_NS),
+            random: Random::new(0),
+        }
+    }
+}
+
+impl WasiCtxBuilder {
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into().into_bytes());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.into().into_bytes()));
+        self
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.push(format!("{key}={value}").into_bytes());
+        self
+    }
+
+    pub fn vfs(mut self, vfs: Vfs) -> Self {
+        self.vfs = vfs;
+        self
+    }
+
+    pub fn file(mut self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.vfs.insert(path, data);
+        self
+    }
+
+    pub fn stdin(mut self, stdin: impl Read + Send + 'static) -> Self {
+        self.stdin = Box::new(stdin);
+        self
+    }
+
+    /// 固定のバイト列を stdin にする。読み切ると EOF になる。
+    pub fn stdin_bytes(self, data: impl Into<Vec<u8>>) -> Self {
+        self.stdin(io::Cursor::new(data.into()))
+    }
+
+    pub fn stdout(mut self, stdout: impl Write + Send + 'static) -> Self {
+        self.stdout = Box::new(stdout);
+        self
+    }
+
+    pub fn stderr(mut self, stderr: impl Write + Send + 'static) -> Self {
+        self.stderr = Box::new(stderr);
+        self
+    }
+
+    /// `clock_time_get` は `start_ns` から始まり、呼ばれるたびに `step_ns` 進む。
+    pub fn clock(mut self, start_ns: u64, step_ns: u64) -> Self {
+        self.clock = Clock::new(start_ns, step_ns);
+        self
+    }
+
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random = Random::new(seed);
+        self
+    }
+
+    pub fn build(self) -> WasiCtx {
+        let mut fds = BTreeMap::new();
+        fds.insert(0, FdEntry::Stdin);
+        fds.insert(1, FdEntry::Stdout);
+        fds.insert(2, FdEntry::Stderr);
+        fds.insert(PREOPEN_FD, FdEntry::Dir(String::new()));
+        WasiCtx {
+            args: self.args,
+            env: self.env,
+            vfs: self.vfs,
+            stdin: self.stdin,
+            stdout: self.stdout,
+            stderr: self.stderr,
+            fds,
+            next_fd: PREOPEN_FD + 1,
+            clock: self.clock,
+            random: self.random,
+        }
+    }
+}
+
+/// 呼ばれるたびに一定量だけ進む決定的な時計。
+struct Clock {
+    now_ns: u64,
+    step_ns: u64,
+}
+
+impl Clock {
+    const DEFAULT_STEP_NS: u64 = 1_000_000;
+
+    fn new(start_ns: u64, step_ns: u64) -> Self {
+        Self {
+            now_ns: start_ns,
+            step_ns,
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        let now = self.now_ns;
+        self.now_ns = self.now_ns.wrapping_add(self.step_ns);
+        now
+    }
+}
+
+/// シード固定の xorshift64* 乱数列。
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    const DEFAULT_SEED: u64 = 0x853c_49e6_748f_ea9b;
+
+    fn new(seed: u64) -> Self {
+        // xorshift は状態 0 から抜け出せないので既定のシードに置き換える。
+        let state = if seed == 0 { Self::DEFAULT_SEED } else { seed };
+        Self { state }
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            self.state ^= self.state >> 12;
+            self.state ^= self.state << 25;
+            self.state ^= self.state >> 27;
+            let value = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+            chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+/// ゲストの `memory` と [`WasiCtx`] を取り出して `f` を呼ぶ。
+/// `memory` をエクスポートしていないモジュールには `FAULT` を返す。
+fn with_memory<T: WasiView>(
+    caller: &mut Caller<'_, T>,
+    f: impl FnOnce(&mut [u8], &mut WasiCtx) -> Result<(), Errno>,
+) -> Errno {
+    let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) else {
+        return errno::FAULT;
+    };
+    let (mem, data) = memory.data_and_store_mut(caller);
+    match f(mem, data.wasi()) {
+        Ok(()) => errno::SUCCESS,
+        Err(code) => code,
+    }
+}
+
+/// `wasi_snapshot_preview1` の関数をすべて `linker` に登録する。
+pub fn add_to_linker<T: WasiView + 'static>(linker: &mut Linker<T>) -> Result<(), LinkerError> {
+    linker.func_wrap(
+        MODULE,
+        "args_sizes_get",
+        |mut caller: Caller<'_, T>, argc: i32, buf_size: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.args_sizes_get(mem, argc as u32, buf_size as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "args_get",
+        |mut caller: Caller<'_, T>, argv: i32, argv_buf: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.args_get(mem, argv as u32, argv_buf as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "environ_sizes_get",
+        |mut caller: Caller<'_, T>, count: i32, buf_size: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.environ_sizes_get(mem, count as u32, buf_size as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "environ_get",
+        |mut caller: Caller<'_, T>, environ: i32, environ_buf: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.environ_get(mem, environ as u32, environ_buf as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "clock_res_get",
+        |mut caller: Caller<'_, T>, id: i32, resolution: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.clock_res_get(mem, id as u32, resolution as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "clock_time_get",
+        |mut caller: Caller<'_, T>, id: i32, _precision: i64, time: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.clock_time_get(mem, id as u32, time as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "random_get",
+        |mut caller: Caller<'_, T>, buf: i32, len: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.random_get(mem, buf as u32, len as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_write",
+        |mut caller: Caller<'_, T>, fd: i32, iovs: i32, iovs_len: i32, nwritten: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.fd_write(
+                    mem,
+                    fd as u32,
+                    iovs as u32,
+                    iovs_len as u32,
+                    nwritten as u32,
+                )
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_read",
+        |mut caller: Caller<'_, T>, fd: i32, iovs: i32, iovs_len: i32, nread: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.fd_read(mem, fd as u32, iovs as u32, iovs_len as u32, nread as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_seek",
+        |mut caller: Caller<'_, T>, fd: i32, offset: i64, whence: i32, new_offset: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.fd_seek(mem, fd as u32, offset, whence, new_offset as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_tell",
+        |mut caller: Caller<'_, T>, fd: i32, offset: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.fd_seek(mem, fd as u32, 0, syscalls::WHENCE_CUR, offset as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_close",
+        |mut caller: Caller<'_, T>, fd: i32| -> i32 {
+            match caller.data_mut().wasi().fd_close(fd as u32) {
+                Ok(()) => errno::SUCCESS,
+                Err(code) => code,
+            }
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_fdstat_get",
+        |mut caller: Caller<'_, T>, fd: i32, stat: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.fd_fdstat_get(mem, fd as u32, stat as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_filestat_get",
+        |mut caller: Caller<'_, T>, fd: i32, stat: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.fd_filestat_get(mem, fd as u32, stat as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_prestat_get",
+        |mut caller: Caller<'_, T>, fd: i32, prestat: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.fd_prestat_get(mem, fd as u32, prestat as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_prestat_dir_name",
+        |mut caller: Caller<'_, T>, fd: i32, path: i32, path_len: i32| -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.fd_prestat_dir_name(mem, fd as u32, path as u32, path_len as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "path_open",
+        |mut caller: Caller<'_, T>,
+         dirfd: i32,
+         _dirflags: i32,
+         path: i32,
+         path_len: i32,
+         oflags: i32,
+         rights_base: i64,
+         _rights_inheriting: i64,
+         fdflags: i32,
+         fd_out: i32|
+         -> i32 {
+            with_memory(&mut caller, |mem, ctx| {
+                ctx.path_open(
+                    mem,
+                    dirfd as u32,
+                    path as u32,
+                    path_len as u32,
+                    syscalls::OpenFlags {
+                        oflags: oflags as u16,
+                        rights: rights_base as u64,
+                        fdflags: fdflags as u16,
+                    },
+                    fd_out as u32,
+                )
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "proc_exit",
+        |mut caller: Caller<'_, T>, code: i32| -> Result<(), Trap> {
+            let _ = caller.data_mut().wasi().flush();
+            Err(Trap::i32_exit(code))
+        },
+    )?;
+    linker.func_wrap(MODULE, "sched_yield", || -> i32 { errno::SUCCESS })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_encoder::{
+        CodeSection, ConstExpr, DataSection, EntityType, ExportKind, ExportSection, Function,
+        FunctionSection, ImportSection, Instruction, MemorySection, MemoryType, Module,
+        TypeSection, ValType,
+    };
+    use wasmi::{Engine, Store};
+
+    #[derive(Clone, Copy)]
+    enum Arg {
+        I32(i32),
+        I64(i64),
+    }
+
+    struct Outcome {
+        result: Result<i32, Trap>,
+        memory: Vec<u8>,
+        ctx: WasiCtx,
+    }
+
+    impl Outcome {
+        fn errno(&self) -> i32 {
+            *self.result.as_ref().expect("call should not trap")
+        }
+
+        fn u32_at(&self, ptr: usize) -> u32 {
+            u32::from_le_bytes(self.memory[ptr..ptr + 4].try_into().unwrap())
+        }
+
+        fn u64_at(&self, ptr: usize) -> u64 {
+            u64::from_le_bytes(self.memory[ptr..ptr + 8].try_into().unwrap())
+        }
+    }
+
+    /// `name` を 1 回だけ呼ぶモジュールを作って `ctx` の上で実行する。
+    fn call(ctx: WasiCtx, name: &str, args: &[Arg], data: &[(u32, &[u8])]) -> Outcome {
+        let returns = name != "proc_exit";
+        let params: Vec<ValType> = args
+            .iter()
+            .map(|arg| match arg {
+                Arg::I32(_) => ValType::I32,
+                Arg::I64(_) => ValType::I64,
+            })
+            .collect();
+        let results: &[ValType] = if returns { &[ValType::I32] } else { &[] };
+
+        let mut types = TypeSection::new();
+        types.ty().function(params, results.iter().copied());
+        types.ty().function([], [ValType::I32]);
+        let mut imports = ImportSection::new();
+        imports.import(MODULE, name, EntityType::Function(0));
+        let mut functions = FunctionSection::new();
+        functions.function(1);
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: 1,
+            maximum: None,
+            memory64: false,
+            shared: false,
+            page_size_log2: None,
+        });
+        let mut exports = ExportSection::new();
+        exports.export("memory", ExportKind::Memory, 0);
+        exports.export("run", ExportKind::Func, 1);
+        let mut func = Function::new([]);
+        for arg in args {
+            match *arg {
+                Arg::I32(value) => func.instruction(&Instruction::I32Const(value)),
+                Arg::I64(value) => func.instruction(&Instruction::I64Const(value)),
+            };
+        }
+        func.instruction(&Instruction::Call(0));
+        if !returns {
+            func.instruction(&Instruction::I32Const(0));
+        }
+        func.instruction(&Instruction::End);
+        let mut code = CodeSection::new();
+        code.function(&func);
+        let mut segments = DataSection::new();
+        for (offset, bytes) in data {
+            segments.active(
+                0,
+                &ConstExpr::i32_const(*offset as i32),
+                bytes.iter().copied(),
+            );
+        }
+
+        let mut module = Module::new();
+        module
+            .section(&types)
+            .section(&imports)
+            .section(&functions)
+            .section(&memories)
+            .section(&exports)
+            .section(&code)
+            .section(&segments);
+        let wasm = module.finish();
+
+        let engine = Engine::default();
+        let module = wasmi::Module::new(&engine, &wasm[..]).expect("module");
+        let mut linker = Linker::new(&engine);
+        add_to_linker(&mut linker).expect("link wasi");
+        let mut store = Store::new(&engine, ctx);
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .expect("instantiate");
+        let run = instance
+            .get_typed_func::<(), i32>(&store, "run")
+            .expect("run export");
+        let result = run.call(&mut store, ());
+        let memory = instance
+            .get_memory(&store, "memory")
+            .expect("memory export")
+            .data(&store)
+            .to_vec();
+        Outcome {
+            result,
+            memory,
+            ctx: store.into_data(),
+        }
+    }
+
+    fn open(ctx: WasiCtx, path: &str, oflags: i32, rights: i64) -> Outcome {
+        call(
+            ctx,
+            "path_open",
+            &[
+                Arg::I32(PREOPEN_FD as i32),
+                Arg::I32(0),
+                Arg::I32(64),
+                Arg::I32(path.len() as i32),
+                Arg::I32(oflags),
+                Arg::I64(rights),
+                Arg::I64(0),
+                Arg::I32(0),
+                Arg::I32(200),
+            ],
+            &[(64, path.as_bytes())],
+        )
+    }
+
+    /// iovec を 0 番地に 1 つ置き、バッファを 16 番地、結果の長さを 8 番地に受け取る。
+    fn io(ctx: WasiCtx, name: &str, fd: i32, payload: &[u8], len: u32) -> Outcome {
+        let mut iov = 16u32.to_le_bytes().to_vec();
+        iov.extend_from_slice(&len.to_le_bytes());
+        call(
+            ctx,
+            name,
+            &[Arg::I32(fd), Arg::I32(0), Arg::I32(1), Arg::I32(8)],
+            &[(0, &iov), (16, payload)],
+        )
+    }
+
+    #[test]
+    fn captures_stdout_and_stderr_separately() {
+        let stdout = SharedBuffer::new();
+        let stderr = SharedBuffer::new();
+        let ctx = WasiCtx::builder()
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .build();
+        let out = io(ctx, "fd_write", 1, b"hello\n", 6);
+        assert_eq!(out.errno(), errno::SUCCESS);
+        assert_eq!(out.u32_at(8), 6);
+        let err = io(out.ctx, "fd_write", 2, b"oops", 4);
+        assert_eq!(err.errno(), errno::SUCCESS);
+        assert_eq!(stdout.to_string_lossy(), "hello\n");
+        assert_eq!(stderr.to_string_lossy(), "oops");
+    }
+
+    #[test]
+    fn reads_stdin_until_eof() {
+        let ctx = WasiCtx::builder().stdin_bytes("abc").build();
+        let first = io(ctx, "fd_read", 0, &[], 2);
+        assert_eq!(first.errno(), errno::SUCCESS);
+        assert_eq!(first.u32_at(8), 2);
+        assert_eq!(&first.memory[16..18], b"ab");
+        let second = io(first.ctx, "fd_read", 0, &[], 2);
+        assert_eq!(second.u32_at(8), 1);
+        assert_eq!(second.memory[16], b'c');
+        let eof = io(second.ctx, "fd_read", 0, &[], 2);
+        assert_eq!(eof.u32_at(8), 0);
+    }
+
+    #[test]
+    fn opens_and_reads_vfs_files_through_the_preopen() {
+        let ctx = WasiCtx::builder().file("/data/in.txt", "payload").build();
+        let opened = open(ctx, "data/in.txt", 0, 0);
+        assert_eq!(opened.errno(), errno::SUCCESS);
+        let fd = opened.u32_at(200);
+        assert_eq!(fd, PREOPEN_FD + 1);
+        let read = io(opened.ctx, "fd_read", fd as i32, &[], 64);
+        assert_eq!(read.errno(), errno::SUCCESS);
+        assert_eq!(read.u32_at(8), 7);
+        assert_eq!(&read.memory[16..23], b"payload");
+        let closed = call(read.ctx, "fd_close", &[Arg::I32(fd as i32)], &[]);
+        assert_eq!(closed.errno(), errno::SUCCESS);
+        let again = call(closed.ctx, "fd_close", &[Arg::I32(fd as i32)], &[]);
+        assert_eq!(again.errno(), errno::BADF);
+    }
+
+    #[test]
+    fn rejects_missing_files_and_paths_outside_the_root() {
+        let missing = open(WasiCtx::default(), "nope.txt", 0, 0);
+        assert_eq!(missing.errno(), errno::NOENT);
+        let escaped = open(missing.ctx, "../secret", 0, 0);
+        assert_eq!(escaped.errno(), errno::NOTCAPABLE);
+    }
+
+    #[test]
+    fn created_files_are_visible_in_the_vfs_after_the_run() {
+        let opened = open(WasiCtx::default(), "out/log.txt", 1, 1 << 6);
+        assert_eq!(opened.errno(), errno::SUCCESS);
+        let fd = opened.u32_at(200) as i32;
+        let written = io(opened.ctx, "fd_write", fd, b"line", 4);
+        assert_eq!(written.errno(), errno::SUCCESS);
+        assert_eq!(written.ctx.vfs().get("/out/log.txt"), Some(&b"line"[..]));
+    }
+
+    #[test]
+    fn exposes_args_and_environment() {
+        let ctx = WasiCtx::builder()
+            .args(["prog", "x"])
+            .env("HOME", "/")
+            .build();
+        let sizes = call(ctx, "args_sizes_get", &[Arg::I32(0), Arg::I32(4)], &[]);
+        assert_eq!(sizes.errno(), errno::SUCCESS);
+        assert_eq!((sizes.u32_at(0), sizes.u32_at(4)), (2, 7));
+        let args = call(sizes.ctx, "args_get", &[Arg::I32(0), Arg::I32(16)], &[]);
+        assert_eq!((args.u32_at(0), args.u32_at(4)), (16, 21));
+        assert_eq!(&args.memory[16..23], b"prog\0x\0");
+        let env = call(args.ctx, "environ_get", &[Arg::I32(0), Arg::I32(16)], &[]);
+        assert_eq!(env.errno(), errno::SUCCESS);
+        assert_eq!(&env.memory[16..23], b"HOME=/\0");
+    }
+
+    #[test]
+    fn clock_and_random_are_deterministic() {
+        let ctx = WasiCtx::builder().clock(1_000, 10).random_seed(7).build();
+        let first = call(
+            ctx,
+            "clock_time_get",
+            &[Arg::I32(1), Arg::I64(0), Arg::I32(0)],
+            &[],
+        );
+        assert_eq!(first.u64_at(0), 1_000);
+        let second = call(
+            first.ctx,
+            "clock_time_get",
+            &[Arg::I32(1), Arg::I64(0), Arg::I32(0)],
+            &[],
+        );
+        assert_eq!(second.u64_at(0), 1_010);
+
+        let draw = |seed| {
+            let ctx = WasiCtx::builder().random_seed(seed).build();
+            call(ctx, "random_get", &[Arg::I32(0), Arg::I32(16)], &[]).memory[..16].to_vec()
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+    }
+
+    #[test]
+    fn reports_the_root_preopen() {
+        let prestat = call(
+            WasiCtx::default(),
+            "fd_prestat_get",
+            &[Arg::I32(3), Arg::I32(0)],
+            &[],
+        );
+        assert_eq!(prestat.errno(), errno::SUCCESS);
+        assert_eq!(prestat.u32_at(4), 1);
+        let name = call(
+            prestat.ctx,
+            "fd_prestat_dir_name",
+            &[Arg::I32(3), Arg::I32(16), Arg::I32(1)],
+            &[],
+        );
+        assert_eq!(name.memory[16], b'/');
+        let none = call(name.ctx, "fd_prestat_get", &[Arg::I32(4), Arg::I32(0)], &[]);
+        assert_eq!(none.errno(), errno::BADF);
+    }
+
+    #[test]
+    fn proc_exit_traps_with_the_exit_status() {
+        let exited = call(WasiCtx::default(), "proc_exit", &[Arg::I32(3)], &[]);
+        let trap = exited.result.expect_err("proc_exit should unwind");
+        assert_eq!(trap.i32_exit_status(), Some(3));
+    }
+}
@@ -0,0 +1,46 @@
+//! 標準出力・標準エラーを取り込むための共有バッファ。
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// 複製しても同じバッファを指す書き込み先。
+/// ストアへ渡した後も、手元に残した複製から出力を読み出せる。
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.lock().clone()
+    }
+
+    /// UTF-8 として読めない部分は置換文字にして返す。
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.lock()).into_owned()
+    }
+
+    /// 溜まった内容を取り出してバッファを空にする。
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,390 @@
+//! 各 WASI 関数の本体。
+//!
+//! どれもゲストの線形メモリをスライスとして受け取り、失敗時は errno を返す。
+//! wasmi との橋渡しは `add_to_linker` 側で行う。
+
+use std::io::{ErrorKind, Read, Write};
+
+use crate::errno::{self, Errno};
+use crate::{guest, FdEntry, OpenFile, WasiCtx};
+
+pub(crate) const WHENCE_SET: i32 = 0;
+pub(crate) const WHENCE_CUR: i32 = 1;
+pub(crate) const WHENCE_END: i32 = 2;
+
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+
+const OFLAGS_CREAT: u16 = 1 << 0;
+const OFLAGS_DIRECTORY: u16 = 1 << 1;
+const OFLAGS_EXCL: u16 = 1 << 2;
+const OFLAGS_TRUNC: u16 = 1 << 3;
+
+const FDFLAGS_APPEND: u16 = 1 << 0;
+
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+const RIGHTS_ALL: u64 = (1 << 30) - 1;
+
+/// `path_open` に渡されたフラグのうち、この実装が解釈するもの。
+pub(crate) struct OpenFlags {
+    pub oflags: u16,
+    pub rights: u64,
+    pub fdflags: u16,
+}
+
+/// NUL 終端の文字列リストを `(ポインタ配列, 文字列バッファ)` へ書き込む。
+fn write_string_list(mem: &mut [u8], items: &[Vec<u8>], ptrs: u32, buf: u32) -> Result<(), Errno> {
+    let mut ptr_at = ptrs;
+    let mut buf_at = buf;
+    for item in items {
+        let mut terminated = item.clone();
+        terminated.push(0);
+        guest::write_u32(mem, ptr_at, buf_at)?;
+        guest::write(mem, buf_at, &terminated)?;
+        ptr_at = ptr_at.checked_add(4).ok_or(errno::FAULT)?;
+        buf_at = buf_at
+            .checked_add(terminated.len() as u32)
+            .ok_or(errno::FAULT)?;
+    }
+    Ok(())
+}
+
+fn write_list_sizes(
+    mem: &mut [u8],
+    items: &[Vec<u8>],
+    count: u32,
+    buf_size: u32,
+) -> Result<(), Errno> {
+    let total: usize = items.iter().map(|item| item.len() + 1).sum();
+    guest::write_u32(mem, count, items.len() as u32)?;
+    guest::write_u32(mem, buf_size, total as u32)
+}
+
+impl WasiCtx {
+    pub(crate) fn args_sizes_get(
+        &mut self,
+        mem: &mut [u8],
+        argc: u32,
+        buf_size: u32,
+    ) -> Result<(), Errno> {
+        write_list_sizes(mem, &self.args, argc, buf_size)
+    }
+
+    pub(crate) fn args_get(
+        &mut self,
+        mem: &mut [u8],
+        argv: u32,
+        argv_buf: u32,
+    ) -> Result<(), Errno> {
+        write_string_list(mem, &self.args, argv, argv_buf)
+    }
+
+    pub(crate) fn environ_sizes_get(
+        &mut self,
+        mem: &mut [u8],
+        count: u32,
+        buf_size: u32,
+    ) -> Result<(), Errno> {
+        write_list_sizes(mem, &self.env, count, buf_size)
+    }
+
+    pub(crate) fn environ_get(
+        &mut self,
+        mem: &mut [u8],
+        environ: u32,
+        environ_buf: u32,
+    ) -> Result<(), Errno> {
+        write_string_list(mem, &self.env, environ, environ_buf)
+    }
+
+    pub(crate) fn clock_res_get(&mut self, mem: &mut [u8], id: u32, out: u32) -> Result<(), Errno> {
+        if id > 3 {
+            return Err(errno::INVAL);
+        }
+        guest::write_u64(mem, out, self.clock.step_ns.max(1))
+    }
+
+    pub(crate) fn clock_time_get(
+        &mut self,
+        mem: &mut [u8],
+        id: u32,
+        out: u32,
+    ) -> Result<(), Errno> {
+        if id > 3 {
+            return Err(errno::INVAL);
+        }
+        let now = self.clock.tick();
+        guest::write_u64(mem, out, now)
+    }
+
+    pub(crate) fn random_get(&mut self, mem: &mut [u8], buf: u32, len: u32) -> Result<(), Errno> {
+        let target = guest::bytes_mut(mem, buf, len)?;
+        self.random.fill(target);
+        Ok(())
+    }
+
+    pub(crate) fn fd_write(
+        &mut self,
+        mem: &mut [u8],
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nwritten: u32,
+    ) -> Result<(), Errno> {
+        let iovs = guest::iovecs(mem, iovs, iovs_len)?;
+        let mut total = 0u32;
+        for (buf, len) in iovs {
+            let data = guest::bytes(mem, buf, len)?;
+            match self.fds.get_mut(&fd) {
+                Some(FdEntry::Stdout) => self.stdout.write_all(data).map_err(|_| errno::IO)?,
+                Some(FdEntry::Stderr) => self.stderr.write_all(data).map_err(|_| errno::IO)?,
+                Some(FdEntry::File(file)) => {
+                    if !file.writable {
+                        return Err(errno::BADF);
+                    }
+                    let contents = self.vfs.data_mut(&file.key).ok_or(errno::BADF)?;
+                    if file.append {
+                        file.pos = contents.len() as u64;
+                    }
+                    let start = file.pos as usize;
+                    let end = start + data.len();
+                    if contents.len() < end {
+                        contents.resize(end, 0);
+                    }
+                    contents[start..end].copy_from_slice(data);
+                    file.pos = end as u64;
+                }
+                Some(FdEntry::Dir(_)) => return Err(errno::ISDIR),
+                Some(FdEntry::Stdin) | None => return Err(errno::BADF),
+            }
+            total = total.saturating_add(len);
+        }
+        guest::write_u32(mem, nwritten, total)
+    }
+
+    pub(crate) fn fd_read(
+        &mut self,
+        mem: &mut [u8],
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nread: u32,
+    ) -> Result<(), Errno> {
+        let iovs = guest::iovecs(mem, iovs, iovs_len)?;
+        let mut total = 0u32;
+        match self.fds.get_mut(&fd) {
+            Some(FdEntry::Stdin) => {
+                // 入力待ちの前にプロンプトなどの出力を見えるようにしておく。
+                let _ = self.stdout.flush();
+                for (buf, len) in iovs {
+                    let target = guest::bytes_mut(mem, buf, len)?;
+                    let read = loop {
+                        match self.stdin.read(target) {
+                            Ok(n) => break n,
+                            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                            Err(_) => return Err(errno::IO),
+                        }
+                    };
+                    total += read as u32;
+                    if read < len as usize {
+                        break;
+                    }
+                }
+            }
+            Some(FdEntry::File(file)) => {
+                let contents = self.vfs.data(&file.key).ok_or(errno::BADF)?;
+                for (buf, len) in iovs {
+                    let start = (file.pos as usize).min(contents.len());
+                    let take = (len as usize).min(contents.len() - start);
+                    guest::write(mem, buf, &contents[start..start + take])?;
+                    file.pos = (start + take) as u64;
+                    total += take as u32;
+                    if take < len as usize {
+                        break;
+                    }
+                }
+            }
+            Some(FdEntry::Dir(_)) => return Err(errno::ISDIR),
+            Some(FdEntry::Stdout) | Some(FdEntry::Stderr) | None => return Err(errno::BADF),
+        }
+        guest::write_u32(mem, nread, total)
+    }
+
+    pub(crate) fn fd_seek(
+        &mut self,
+        mem: &mut [u8],
+        fd: u32,
+        offset: i64,
+        whence: i32,
+        new_offset: u32,
+    ) -> Result<(), Errno> {
+        let file = match self.fds.get_mut(&fd) {
+            Some(FdEntry::File(file)) => file,
+            Some(_) => return Err(errno::SPIPE),
+            None => return Err(errno::BADF),
+        };
+        let len = self.vfs.data(&file.key).map_or(0, <[u8]>::len) as i64;
+        let base = match whence {
+            WHENCE_SET => 0,
+            WHENCE_CUR => file.pos as i64,
+            WHENCE_END => len,
+            _ => return Err(errno::INVAL),
+        };
+        let pos = base.checked_add(offset).filter(|pos| *pos >= 0);
+        file.pos = pos.ok_or(errno::INVAL)? as u64;
+        guest::write_u64(mem, new_offset, file.pos)
+    }
+
+    pub(crate) fn fd_close(&mut self, fd: u32) -> Result<(), Errno> {
+        // 標準入出力を閉じても実行は続けられるよう、fd 表からは消さない。
+        if fd <= 2 {
+            return Ok(());
+        }
+        self.fds.remove(&fd).map(|_| ()).ok_or(errno::BADF)
+    }
+
+    pub(crate) fn fd_fdstat_get(&mut self, mem: &mut [u8], fd: u32, out: u32) -> Result<(), Errno> {
+        let (filetype, flags) = match self.fds.get(&fd) {
+            Some(FdEntry::Stdin | FdEntry::Stdout | FdEntry::Stderr) => {
+                (FILETYPE_CHARACTER_DEVICE, 0)
+            }
+            Some(FdEntry::Dir(_)) => (FILETYPE_DIRECTORY, 0),
+            Some(FdEntry::File(file)) => (
+                FILETYPE_REGULAR_FILE,
+                if file.append { FDFLAGS_APPEND } else { 0 },
+            ),
+            None => return Err(errno::BADF),
+        };
+        let stat = guest::bytes_mut(mem, out, 24)?;
+        stat.fill(0);
+        stat[0] = filetype;
+        stat[2..4].copy_from_slice(&flags.to_le_bytes());
+        stat[8..16].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+        stat[16..24].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+        Ok(())
+    }
+
+    pub(crate) fn fd_filestat_get(
+        &mut self,
+        mem: &mut [u8],
+        fd: u32,
+        out: u32,
+    ) -> Result<(), Errno> {
+        let (filetype, size) = match self.fds.get(&fd) {
+            Some(FdEntry::Stdin | FdEntry::Stdout | FdEntry::Stderr) => {
+                (FILETYPE_CHARACTER_DEVICE, 0)
+            }
+            Some(FdEntry::Dir(_)) => (FILETYPE_DIRECTORY, 0),
+            Some(FdEntry::File(file)) => (
+                FILETYPE_REGULAR_FILE,
+                self.vfs.data(&file.key).map_or(0, <[u8]>::len) as u64,
+            ),
+            None => return Err(errno::BADF),
+        };
+        let stat = guest::bytes_mut(mem, out, 64)?;
+        stat.fill(0);
+        stat[16] = filetype;
+        stat[24..32].copy_from_slice(&1u64.to_le_bytes());
+        stat[32..40].copy_from_slice(&size.to_le_bytes());
+        Ok(())
+    }
+
+    pub(crate) fn fd_prestat_get(
+        &mut self,
+        mem: &mut [u8],
+        fd: u32,
+        out: u32,
+    ) -> Result<(), Errno> {
+        let Some(FdEntry::Dir(name)) = self.fds.get(&fd) else {
+            return Err(errno::BADF);
+        };
+        let len = prestat_name(name).len() as u32;
+        guest::write_u32(mem, out, 0)?;
+        guest::write_u32(mem, out.checked_add(4).ok_or(errno::FAULT)?, len)
+    }
+
+    pub(crate) fn fd_prestat_dir_name(
+        &mut self,
+        mem: &mut [u8],
+        fd: u32,
+        path: u32,
+        path_len: u32,
+    ) -> Result<(), Errno> {
+        let Some(FdEntry::Dir(name)) = self.fds.get(&fd) else {
+            return Err(errno::BADF);
+        };
+        let name = prestat_name(name);
+        if (path_len as usize) < name.len() {
+            return Err(errno::INVAL);
+        }
+        guest::write(mem, path, name.as_bytes())
+    }
+
+    pub(crate) fn path_open(
+        &mut self,
+        mem: &mut [u8],
+        dirfd: u32,
+        path: u32,
+        path_len: u32,
+        flags: OpenFlags,
+        fd_out: u32,
+    ) -> Result<(), Errno> {
+        let Some(FdEntry::Dir(dir)) = self.fds.get(&dirfd) else {
+            return Err(errno::BADF);
+        };
+        let raw =
+            std::str::from_utf8(guest::bytes(mem, path, path_len)?).map_err(|_| errno::INVAL)?;
+        let joined = if dir.is_empty() {
+            raw.to_string()
+        } else {
+            format!("{dir}/{raw}")
+        };
+        let key = crate::vfs::normalize(&joined);
+        if !self.vfs.allows(&key) {
+            return Err(errno::NOTCAPABLE);
+        }
+
+        let entry = if self.vfs.is_dir(&key) {
+            if flags.oflags & (OFLAGS_CREAT | OFLAGS_TRUNC) != 0 {
+                return Err(errno::ISDIR);
+            }
+            FdEntry::Dir(key)
+        } else {
+            if flags.oflags & OFLAGS_DIRECTORY != 0 {
+                return Err(errno::NOTDIR);
+            }
+            match self.vfs.lookup(&joined) {
+                Some(_) if flags.oflags & OFLAGS_EXCL != 0 => return Err(errno::EXIST),
+                Some(_) => {}
+                None if flags.oflags & OFLAGS_CREAT != 0 => self.vfs.create(&key),
+                None => return Err(errno::NOENT),
+            }
+            let writable = flags.rights & RIGHTS_FD_WRITE != 0
+                || flags.oflags & (OFLAGS_CREAT | OFLAGS_TRUNC) != 0
+                || flags.fdflags & FDFLAGS_APPEND != 0;
+            if flags.oflags & OFLAGS_TRUNC != 0 {
+                if let Some(contents) = self.vfs.data_mut(&key) {
+                    contents.clear();
+                }
+            }
+            FdEntry::File(OpenFile {
+                key,
+                pos: 0,
+                append: flags.fdflags & FDFLAGS_APPEND != 0,
+                writable,
+            })
+        };
+
+        let fd = self.next_fd;
+        guest::write_u32(mem, fd_out, fd)?;
+        self.next_fd += 1;
+        self.fds.insert(fd, entry);
+        Ok(())
+    }
+}
+
+/// プレオープンしたディレクトリをゲストへ見せるときの名前。
+fn prestat_name(key: &str) -> String {
+    format!("/{key}")
+}
@@ -0,0 +1,110 @@
+//! `path_open` が参照するメモリ上の仮想ファイルシステム。
+//!
+//! パスはプレオープンされたルート (`/`) からの相対パスとして正規化して保持する。
+//! `with_host_root` で作った場合は、未登録のパスをホストのファイルから読み込んで
+//! キャッシュする（書き込みはホストへ反映しない）。
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default)]
+pub struct Vfs {
+    files: BTreeMap<String, Vec<u8>>,
+    host_root: Option<PathBuf>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 未登録のパスを `root` 以下のホストファイルから読み込む VFS を作る。
+    /// 絶対パスは `root` に関係なくそのまま読む。
+    pub fn with_host_root(root: impl Into<PathBuf>) -> Self {
+        Self {
+            files: BTreeMap::new(),
+            host_root: Some(root.into()),
+        }
+    }
+
+    /// ファイルを登録する。既存の内容は置き換える。
+    pub fn insert(&mut self, path: &str, data: impl Into<Vec<u8>>) {
+        self.files.insert(normalize(path), data.into());
+    }
+
+    pub fn get(&self, path: &str) -> Option<&[u8]> {
+        self.files.get(&normalize(path)).map(Vec::as_slice)
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<Vec<u8>> {
+        self.files.remove(&normalize(path))
+    }
+
+    /// 登録済みのファイルを正規化済みパスの順に列挙する。
+    pub fn files(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.files
+            .iter()
+            .map(|(path, data)| (path.as_str(), data.as_slice()))
+    }
+
+    pub fn into_files(self) -> BTreeMap<String, Vec<u8>> {
+        self.files
+    }
+
+    /// `path` を開けるなら正規化済みのキーを返す。ホストから読んだ内容はキャッシュする。
+    pub(crate) fn lookup(&mut self, path: &str) -> Option<String> {
+        let key = normalize(path);
+        if self.files.contains_key(&key) {
+            return Some(key);
+        }
+        let root = self.host_root.as_ref()?;
+        let data = std::fs::read(root.join(path)).ok()?;
+        self.files.insert(key.clone(), data);
+        Some(key)
+    }
+
+    pub(crate) fn is_dir(&self, key: &str) -> bool {
+        if key.is_empty() {
+            return true;
+        }
+        let prefix = format!("{key}/");
+        self.files.keys().any(|path| path.starts_with(&prefix))
+            || self
+                .host_root
+                .as_ref()
+                .is_some_and(|root| root.join(key).is_dir())
+    }
+
+    pub(crate) fn data(&self, key: &str) -> Option<&[u8]> {
+        self.files.get(key).map(Vec::as_slice)
+    }
+
+    pub(crate) fn data_mut(&mut self, key: &str) -> Option<&mut Vec<u8>> {
+        self.files.get_mut(key)
+    }
+
+    pub(crate) fn create(&mut self, key: &str) {
+        self.files.entry(key.to_string()).or_default();
+    }
+
+    /// ルートより上を指す `..` を含むパスはホスト読み込み時のみ許可する。
+    pub(crate) fn allows(&self, key: &str) -> bool {
+        self.host_root.is_some() || !key.split('/').any(|seg| seg == "..")
+    }
+}
+
+/// 先頭の `/` と `.` を取り除き、`..` を畳み込んだ相対パスにする。
+/// ルートより上へ出る `..` はそのまま先頭に残す。
+pub fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for seg in path.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|last| *last != "..") => {
+                parts.pop();
+            }
+            _ => parts.push(seg),
+        }
+    }
+    parts.join("/")
+}
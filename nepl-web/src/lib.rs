@@ -2,18 +2,23 @@ use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use js_sys::{Reflect, Uint8Array};
-use nepl_core::ast::{Block, Directive, FnBody, MatchArm, PrefixExpr, PrefixItem, Stmt, Symbol};
-use nepl_core::compiler::compile_module_with_source_map;
+use nepl_core::ast::{Block, Directive, FnBody, PrefixExpr, PrefixItem, Stmt};
+use nepl_core::compiler::{compile_module_for_debugging, compile_module_with_source_map};
+use nepl_core::debugger::{DebugSession, DebugValue, StepCommand};
 use nepl_core::diagnostic::{Diagnostic, Severity};
-use nepl_core::diagnostic_ids::DiagnosticId;
 use nepl_core::error::CoreError;
-use nepl_core::hir::{HirBlock, HirExpr, HirExprKind, HirLine};
-use nepl_core::lexer::{lex, Token, TokenKind};
-use nepl_core::loader::{Loader, LoaderError, SourceMap};
+use nepl_core::lexer::lex;
+use nepl_core::loader::{LoadResult, Loader, LoaderError, SourceMap};
 use nepl_core::parser::parse_tokens;
 use nepl_core::span::{FileId, Span};
-use nepl_core::typecheck::typecheck;
-use nepl_core::{BuildProfile, CompileOptions, CompileTarget};
+use nepl_core::{BuildProfile, CompileOptions};
+use nepl_language::{
+    analyze_loaded_name_resolution, analyze_loaded_semantics, analyze_name_resolution_load_failure,
+    analyze_semantics_load_failure, diagnostics_to_editor, load_inline_module_with_provider,
+    range_from_span, tokens_to_editor, NameResolutionOptions,
+};
+use nepl_wasi::{SharedBuffer, Vfs, WasiCtx};
+use serde::{Deserialize, Serialize};
 use wasmprinter::print_bytes;
 use wasm_bindgen::prelude::*;
 
@@ -260,280 +265,58 @@ fn parse_emit_list(emit: JsValue) -> Result<Vec<String>, JsValue> {
     Err(JsValue::from_str("emit must be a string or an array of strings"))
 }
 
-fn line_col_of(source: &str, byte_pos: u32) -> (u32, u32) {
-    let mut line = 0u32;
-    let mut col = 0u32;
-    let mut seen = 0u32;
-    for ch in source.chars() {
-        if seen >= byte_pos {
-            break;
-        }
-        let len = ch.len_utf8() as u32;
-        if seen + len > byte_pos {
-            break;
-        }
-        seen += len;
-        if ch == '\n' {
-            line += 1;
-            col = 0;
-        } else {
-            col += 1;
-        }
-    }
-    (line, col)
+/// 解析結果を JS 値へ変換します。
+///
+/// `Option::None` は `null`、map は通常の object として渡されます。
+fn to_js<T: Serialize + ?Sized>(value: &T) -> JsValue {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap_or(JsValue::NULL)
 }
 
-fn span_to_js_with_map(source: &str, span: Span, source_map: Option<&SourceMap>) -> JsValue {
-    let span_source = source_map
-        .and_then(|sm| sm.get(span.file_id))
-        .unwrap_or(source);
-    let obj = js_sys::Object::new();
-    let (start_line, start_col) = line_col_of(span_source, span.start);
-    let (end_line, end_col) = line_col_of(span_source, span.end);
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("file_id"),
-        &JsValue::from_f64(span.file_id.0 as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("start"),
-        &JsValue::from_f64(span.start as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("end"),
-        &JsValue::from_f64(span.end as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("start_line"),
-        &JsValue::from_f64(start_line as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("start_col"),
-        &JsValue::from_f64(start_col as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("end_line"),
-        &JsValue::from_f64(end_line as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("end_col"),
-        &JsValue::from_f64(end_col as f64),
-    );
-    if let Some(path) = source_map.and_then(|sm| sm.path(span.file_id)) {
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("file_path"),
-            &JsValue::from_str(&path.to_string_lossy()),
-        );
-    }
-    obj.into()
+/// 解析結果に `stage` を付けて返すための包み。
+#[derive(Serialize)]
+struct Staged<'a, T: Serialize> {
+    stage: &'static str,
+    #[serde(flatten)]
+    analysis: &'a T,
 }
 
-fn span_to_js(source: &str, span: Span) -> JsValue {
-    span_to_js_with_map(source, span, None)
-}
-
-fn token_kind_name(kind: &TokenKind) -> &'static str {
-    match kind {
-        TokenKind::Indent => "Indent",
-        TokenKind::Dedent => "Dedent",
-        TokenKind::Newline => "Newline",
-        TokenKind::Eof => "Eof",
-        TokenKind::Colon => "Colon",
-        TokenKind::Semicolon => "Semicolon",
-        TokenKind::Pipe => "Pipe",
-        TokenKind::LParen => "LParen",
-        TokenKind::RParen => "RParen",
-        TokenKind::Comma => "Comma",
-        TokenKind::LAngle => "LAngle",
-        TokenKind::RAngle => "RAngle",
-        TokenKind::Arrow(_) => "Arrow",
-        TokenKind::PathSep => "PathSep",
-        TokenKind::At => "At",
-        TokenKind::Dot => "Dot",
-        TokenKind::Ampersand => "Ampersand",
-        TokenKind::Star => "Star",
-        TokenKind::Minus => "Minus",
-        TokenKind::Equals => "Equals",
-        TokenKind::Ident(_) => "Ident",
-        TokenKind::IntLiteral(_) => "IntLiteral",
-        TokenKind::FloatLiteral(_) => "FloatLiteral",
-        TokenKind::BoolLiteral(_) => "BoolLiteral",
-        TokenKind::StringLiteral(_) => "StringLiteral",
-        TokenKind::UnitLiteral => "UnitLiteral",
-        TokenKind::KwFn => "KwFn",
-        TokenKind::KwLet => "KwLet",
-        TokenKind::KwMut => "KwMut",
-        TokenKind::KwNoShadow => "KwNoShadow",
-        TokenKind::KwSet => "KwSet",
-        TokenKind::KwIf => "KwIf",
-        TokenKind::KwWhile => "KwWhile",
-        TokenKind::KwCond => "KwCond",
-        TokenKind::KwThen => "KwThen",
-        TokenKind::KwElse => "KwElse",
-        TokenKind::KwDo => "KwDo",
-        TokenKind::KwStruct => "KwStruct",
-        TokenKind::KwEnum => "KwEnum",
-        TokenKind::KwMatch => "KwMatch",
-        TokenKind::KwTrait => "KwTrait",
-        TokenKind::KwImpl => "KwImpl",
-        TokenKind::KwFor => "KwFor",
-        TokenKind::KwPub => "KwPub",
-        TokenKind::KwBlock => "KwBlock",
-        TokenKind::KwTuple => "KwTuple",
-        TokenKind::KwMlstr => "KwMlstr",
-        TokenKind::DirEntry(_) => "DirEntry",
-        TokenKind::DirTarget(_) => "DirTarget",
-        TokenKind::DirImport(_) => "DirImport",
-        TokenKind::DirUse(_) => "DirUse",
-        TokenKind::DirIfTarget(_) => "DirIfTarget",
-        TokenKind::DirIfProfile(_) => "DirIfProfile",
-        TokenKind::DirCapability(_) => "DirCapability",
-        TokenKind::DirWasm => "DirWasm",
-        TokenKind::DirLlvmIr => "DirLlvmIr",
-        TokenKind::DirIndentWidth(_) => "DirIndentWidth",
-        TokenKind::DirInclude(_) => "DirInclude",
-        TokenKind::DirExtern { .. } => "DirExtern",
-        TokenKind::DirIntrinsic => "DirIntrinsic",
-        TokenKind::DirPrelude(_) => "DirPrelude",
-        TokenKind::DirNoPrelude => "DirNoPrelude",
-        TokenKind::WasmText(_) => "WasmText",
-        TokenKind::LlvmIrText(_) => "LlvmIrText",
-        TokenKind::MlstrLine(_) => "MlstrLine",
-        TokenKind::DocComment(_) => "DocComment",
-    }
+fn staged_to_js<T: Serialize>(stage: &'static str, analysis: &T) -> JsValue {
+    to_js(&Staged { stage, analysis })
 }
 
-fn token_extra(kind: &TokenKind) -> Option<String> {
-    match kind {
-        TokenKind::Arrow(e) => Some(format!("{:?}", e)),
-        TokenKind::Ident(v)
-        | TokenKind::IntLiteral(v)
-        | TokenKind::FloatLiteral(v)
-        | TokenKind::StringLiteral(v)
-        | TokenKind::DirEntry(v)
-        | TokenKind::DirTarget(v)
-        | TokenKind::DirImport(v)
-        | TokenKind::DirUse(v)
-        | TokenKind::DirIfTarget(v)
-        | TokenKind::DirIfProfile(v)
-        | TokenKind::DirCapability(v)
-        | TokenKind::DirInclude(v)
-        | TokenKind::DirPrelude(v)
-        | TokenKind::WasmText(v)
-        | TokenKind::LlvmIrText(v)
-        | TokenKind::MlstrLine(v)
-        | TokenKind::DocComment(v) => Some(v.clone()),
-        TokenKind::BoolLiteral(v) => Some(v.to_string()),
-        TokenKind::DirIndentWidth(v) => Some(v.to_string()),
-        TokenKind::DirExtern {
-            module,
-            name,
-            func,
-            signature,
-        } => Some(format!(
-            "module={module}, name={name}, func={func}, signature={signature}"
-        )),
-        _ => None,
-    }
+fn span_to_js(source: &str, span: Span) -> JsValue {
+    to_js(&range_from_span(source, None, span))
 }
 
-fn token_to_js(source: &str, token: &Token) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("kind"),
-        &JsValue::from_str(token_kind_name(&token.kind)),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("debug"),
-        &JsValue::from_str(&format!("{:?}", token.kind)),
-    );
-    let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, token.span));
-    if let Some(extra) = token_extra(&token.kind) {
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("value"),
-            &JsValue::from_str(&extra),
-        );
+fn name_resolution_options(options: &JsValue) -> NameResolutionOptions {
+    let mut out = NameResolutionOptions::default();
+    if let Some(warn) = Reflect::get(options, &JsValue::from_str("warn_important_shadow"))
+        .ok()
+        .and_then(|v| v.as_bool())
+    {
+        out.warn_important_shadow = warn;
     }
-    obj.into()
+    out
 }
 
-fn diagnostics_to_js(source: &str, diagnostics: &[Diagnostic]) -> JsValue {
-    let arr = js_sys::Array::new();
-    for d in diagnostics {
-        let obj = js_sys::Object::new();
-        let severity = match d.severity {
-            Severity::Error => "error",
-            Severity::Warning => "warning",
-        };
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("severity"),
-            &JsValue::from_str(severity),
-        );
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("code"),
-            &d.code
-                .map(JsValue::from_str)
-                .unwrap_or(JsValue::NULL),
-        );
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("id"),
-            &d.id
-                .map(|v| JsValue::from_f64(v.as_u32() as f64))
-                .unwrap_or(JsValue::NULL),
-        );
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("id_message"),
-            &d.id
-                .map(|v| JsValue::from_str(v.message()))
-                .unwrap_or(JsValue::NULL),
-        );
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("message"),
-            &JsValue::from_str(&d.message),
-        );
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("primary"),
-            &span_to_js(source, d.primary.span),
-        );
+fn load_entry_for_analysis(
+    entry_path: &str,
+    source: &str,
+    vfs: JsValue,
+) -> Result<LoadResult, LoaderError> {
+    let stdlib_root = PathBuf::from("/stdlib");
+    let mut sources = stdlib_sources(&stdlib_root);
+    merge_vfs_sources(&mut sources, Some(vfs));
 
-        let secondary = js_sys::Array::new();
-        for s in &d.secondary {
-            let sub = js_sys::Object::new();
-            let _ = Reflect::set(
-                &sub,
-                &JsValue::from_str("span"),
-                &span_to_js(source, s.span),
-            );
-            let _ = Reflect::set(
-                &sub,
-                &JsValue::from_str("message"),
-                &s.message
-                    .as_ref()
-                    .map(|m| JsValue::from_str(m))
-                    .unwrap_or(JsValue::NULL),
-            );
-            secondary.push(&sub);
-        }
-        let _ = Reflect::set(&obj, &JsValue::from_str("secondary"), &secondary);
-        arr.push(&obj);
-    }
-    arr.into()
+    let mut provider = |path: &PathBuf| {
+        sources
+            .get(path)
+            .cloned()
+            .ok_or_else(|| LoaderError::Io(format!("missing source: {}", path.display())))
+    };
+    load_inline_module_with_provider(stdlib_root, entry_path, source, &mut provider)
 }
 
 fn expr_to_js(source: &str, expr: &PrefixExpr) -> JsValue {
@@ -680,6 +463,22 @@ fn stmt_to_js(source: &str, stmt: &Stmt) -> JsValue {
                 &JsValue::from_str(&format!("{:?}", block)),
             );
         }
+        Stmt::TypeDef(def) => {
+            let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("TypeDef"));
+            let _ = Reflect::set(
+                &obj,
+                &JsValue::from_str("name"),
+                &JsValue::from_str(&def.name.name),
+            );
+        }
+        Stmt::Global(def) => {
+            let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Global"));
+            let _ = Reflect::set(
+                &obj,
+                &JsValue::from_str("name"),
+                &JsValue::from_str(&def.name.name),
+            );
+        }
         Stmt::Trait(def) => {
             let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Trait"));
             let _ = Reflect::set(
@@ -774,6 +573,16 @@ fn prefix_item_to_js(source: &str, item: &PrefixItem) -> JsValue {
                 &JsValue::from_str(&format!("{:?}", m)),
             );
         }
+        PrefixItem::For(f, span) => {
+            let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("For"));
+            let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, *span));
+            let _ = Reflect::set(
+                &obj,
+                &JsValue::from_str("iterable"),
+                &expr_to_js(source, &f.iterable),
+            );
+            let _ = Reflect::set(&obj, &JsValue::from_str("block"), &block_to_js(source, &f.body));
+        }
         PrefixItem::Pipe(span) => {
             let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Pipe"));
             let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, *span));
@@ -809,2112 +618,177 @@ fn prefix_item_to_js(source: &str, item: &PrefixItem) -> JsValue {
     obj.into()
 }
 
-#[derive(Clone)]
-struct NameDefTrace {
-    id: usize,
-    name: String,
-    kind: &'static str,
-    span: Span,
-    scope_depth: usize,
-    doc: Option<String>,
-}
-
-#[derive(Clone)]
-struct NameRefTrace {
-    name: String,
-    span: Span,
-    scope_depth: usize,
-    resolved_def_id: Option<usize>,
-    candidate_def_ids: Vec<usize>,
-}
-
-#[derive(Clone)]
-struct NameShadowTrace {
-    name: String,
-    event_kind: &'static str,
-    span: Span,
-    scope_depth: usize,
-    selected_def_id: Option<usize>,
-    shadowed_def_ids: Vec<usize>,
-    severity: &'static str,
-    message: String,
-}
-
-#[derive(Clone)]
-struct SemanticExprTrace {
-    id: usize,
-    function_name: String,
-    kind: &'static str,
-    span: Span,
-    ty: String,
-    parent_id: Option<usize>,
-    arg_spans: Vec<Span>,
-}
-
-#[derive(Clone)]
-struct SemanticTokenTrace {
-    token_index: usize,
-    inferred_expr_id: Option<usize>,
-    inferred_type: Option<String>,
-    expr_span: Option<Span>,
-    arg_index: Option<usize>,
-    arg_span: Option<Span>,
-}
-
-#[derive(Default)]
-struct NameResolutionTrace {
-    defs: Vec<NameDefTrace>,
-    refs: Vec<NameRefTrace>,
-    shadows: Vec<NameShadowTrace>,
-    scopes: Vec<BTreeMap<String, Vec<usize>>>,
-    warn_important_shadow: bool,
-}
-
-impl NameResolutionTrace {
-    fn new() -> Self {
-        Self::new_with_options(true)
-    }
-
-    fn new_with_options(warn_important_shadow: bool) -> Self {
-        Self {
-            defs: Vec::new(),
-            refs: Vec::new(),
-            shadows: Vec::new(),
-            scopes: vec![BTreeMap::new()],
-            warn_important_shadow,
-        }
-    }
+/// 入力ソースを字句解析し、token 列と診断を JSON で返します。
+///
+/// VSCode 拡張や LSP 実装で、構文解析前の結果を可視化するための API です。
+#[wasm_bindgen]
+pub fn analyze_lex(source: &str) -> JsValue {
+    staged_to_js("lex", &nepl_language::analyze_lex(source))
+}
 
-    fn current_depth(&self) -> usize {
-        self.scopes.len().saturating_sub(1)
-    }
+/// 入力ソースを構文解析し、token・AST 木構造・診断を JSON で返します。
+///
+/// lexer/parser の結果確認や、エディタ拡張での構文可視化に利用します。
+#[wasm_bindgen]
+pub fn analyze_parse(source: &str) -> JsValue {
+    let file_id = FileId(0);
+    let lex_result = lex(file_id, source);
+    let tokens = tokens_to_editor(source, None, &lex_result.tokens);
+    let lex_diagnostics = diagnostics_to_editor(source, None, &lex_result.diagnostics);
+    let parse_result = parse_tokens(file_id, lex_result);
+    let diagnostics = diagnostics_to_editor(source, None, &parse_result.diagnostics);
 
-    fn push_scope(&mut self) {
-        self.scopes.push(BTreeMap::new());
-    }
+    let out = js_sys::Object::new();
+    let _ = Reflect::set(
+        &out,
+        &JsValue::from_str("stage"),
+        &JsValue::from_str("parse"),
+    );
+    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &to_js(&tokens));
+    let _ = Reflect::set(
+        &out,
+        &JsValue::from_str("lex_diagnostics"),
+        &to_js(&lex_diagnostics),
+    );
+    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &to_js(&diagnostics));
 
-    fn pop_scope(&mut self) {
-        if self.scopes.len() > 1 {
-            self.scopes.pop();
-        }
+    if let Some(module) = parse_result.module {
+        let module_obj = js_sys::Object::new();
+        let _ = Reflect::set(
+            &module_obj,
+            &JsValue::from_str("indent_width"),
+            &JsValue::from_f64(module.indent_width as f64),
+        );
+        let _ = Reflect::set(
+            &module_obj,
+            &JsValue::from_str("directives_count"),
+            &JsValue::from_f64(module.directives.len() as f64),
+        );
+        let _ = Reflect::set(
+            &module_obj,
+            &JsValue::from_str("root"),
+            &block_to_js(source, &module.root),
+        );
+        let _ = Reflect::set(
+            &module_obj,
+            &JsValue::from_str("debug"),
+            &JsValue::from_str(&format!("{:#?}", module)),
+        );
+        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(true));
+        let _ = Reflect::set(&out, &JsValue::from_str("module"), &module_obj);
+    } else {
+        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
+        let _ = Reflect::set(&out, &JsValue::from_str("module"), &JsValue::NULL);
     }
 
-    fn define(&mut self, name: String, kind: &'static str, span: Span, doc: Option<String>) -> usize {
-        let existing_candidates = self.lookup_candidates(&name);
-        let id = self.defs.len();
-        let depth = self.current_depth();
-        self.defs.push(NameDefTrace {
-            id,
-            name: name.clone(),
-            kind,
-            span,
-            scope_depth: depth,
-            doc,
-        });
-
-        if !existing_candidates.is_empty() {
-            let severity = if self.warn_important_shadow
-                && is_important_shadow_symbol(&name)
-                && is_variable_def_kind(kind)
-            {
-                "warning"
-            } else {
-                "info"
-            };
-            let message = if severity == "warning" {
-                format!(
-                    "important symbol '{}' is shadowed by {} definition",
-                    name, kind
-                )
-            } else {
-                format!("'{}' shadows an outer definition", name)
-            };
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "definition_shadow",
-                span,
-                scope_depth: depth,
-                selected_def_id: Some(id),
-                shadowed_def_ids: existing_candidates,
-                severity,
-                message,
-            });
-        } else if self.warn_important_shadow
-            && is_important_shadow_symbol(&name)
-            && is_variable_def_kind(kind)
-        {
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "important_name",
-                span,
-                scope_depth: depth,
-                selected_def_id: Some(id),
-                shadowed_def_ids: Vec::new(),
-                severity: "warning",
-                message: format!(
-                    "definition '{}' may shadow important stdlib symbol",
-                    name
-                ),
-            });
-        }
+    out.into()
+}
 
-        if let Some(scope) = self.scopes.last_mut() {
-            scope.entry(name).or_default().push(id);
-        }
-        id
-    }
+/// 同名識別子の解決結果を、LSP/エディタ向けに返します。
+///
+/// - `definitions`: 解析で見つかった定義点
+/// - `references`: 各参照点の候補と最終選択（最内側優先）
+/// - 巻き上げは現行仕様に合わせて `fn` と `let`(non-mut) を先行登録します
+#[wasm_bindgen]
+pub fn analyze_name_resolution(source: &str) -> JsValue {
+    analyze_name_resolution_with_options(source, JsValue::UNDEFINED)
+}
 
-    fn lookup_candidates(&self, name: &str) -> Vec<usize> {
-        let mut out = Vec::new();
-        for scope in self.scopes.iter().rev() {
-            if let Some(ids) = scope.get(name) {
-                out.extend(ids.iter().rev().copied());
-            }
-        }
-        out
-    }
+#[wasm_bindgen]
+pub fn analyze_name_resolution_with_options(source: &str, options: JsValue) -> JsValue {
+    let analysis = nepl_language::analyze_name_resolution(source, name_resolution_options(&options));
+    staged_to_js("name_resolution", &analysis)
+}
 
-    fn reference(&mut self, name: String, span: Span) {
-        let candidates = self.lookup_candidates(&name);
-        let resolved = candidates.first().copied();
-        if candidates.len() > 1 {
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "reference_shadow",
-                span,
-                scope_depth: self.current_depth(),
-                selected_def_id: resolved,
-                shadowed_def_ids: candidates[1..].to_vec(),
-                severity: "info",
-                message: format!(
-                    "'{}' resolved to nearest definition with {} shadowed candidate(s)",
-                    name,
-                    candidates.len().saturating_sub(1)
-                ),
-            });
-        }
-        self.refs.push(NameRefTrace {
-            name,
-            span,
-            scope_depth: self.current_depth(),
-            resolved_def_id: resolved,
-            candidate_def_ids: candidates,
-        });
-    }
+/// VFS を使って import/alias/use を含む複数ファイルの名前解決情報を返します。
+#[wasm_bindgen]
+pub fn analyze_name_resolution_with_vfs(
+    entry_path: &str,
+    source: &str,
+    vfs: JsValue,
+    options: JsValue,
+) -> JsValue {
+    let options = name_resolution_options(&options);
+    let analysis = match load_entry_for_analysis(entry_path, source, vfs) {
+        Ok(loaded) => analyze_loaded_name_resolution(source, &loaded, options),
+        Err(e) => analyze_name_resolution_load_failure(source, &e, options),
+    };
+    staged_to_js("name_resolution", &analysis)
 }
 
-fn is_layout_marker(name: &str) -> bool {
-    matches!(name, "cond" | "then" | "else" | "do" | "block")
-}
-
-fn is_important_shadow_symbol(name: &str) -> bool {
-    matches!(
-        name,
-        "print"
-            | "println"
-            | "print_i32"
-            | "println_i32"
-            | "add"
-            | "sub"
-            | "mul"
-            | "div"
-            | "eq"
-            | "lt"
-            | "le"
-            | "gt"
-            | "ge"
-            | "map"
-            | "len"
-    )
+/// 字句・構文・型検査の情報を統合し、LSP 向けの詳細解析結果を返します。
+///
+/// 返却する主な情報:
+/// - `expressions`: 各式の範囲・推論型・親子関係・引数範囲
+/// - `token_semantics`: token ごとの対応式と推論型、引数位置情報
+/// - `functions`: 関数定義の範囲とシグネチャ
+#[wasm_bindgen]
+pub fn analyze_semantics(source: &str) -> JsValue {
+    staged_to_js("semantics", &nepl_language::analyze_semantics(source))
 }
 
-fn is_variable_def_kind(kind: &str) -> bool {
-    matches!(kind, "let_hoisted" | "let_mut" | "param" | "match_bind")
+/// VFS を用いた複数ファイル解析版。
+/// import/alias/use で参照された定義について、token_resolution に file_path 付きの
+/// resolved_definition / candidate_definitions を返す。
+#[wasm_bindgen]
+pub fn analyze_semantics_with_vfs(entry_path: &str, source: &str, vfs: JsValue) -> JsValue {
+    let analysis = match load_entry_for_analysis(entry_path, source, vfs) {
+        Ok(loaded) => analyze_loaded_semantics(source, &loaded),
+        Err(e) => analyze_semantics_load_failure(source, &e),
+    };
+    staged_to_js("semantics", &analysis)
 }
 
-fn hoist_block_defs(trace: &mut NameResolutionTrace, block: &Block) {
-    for stmt in &block.items {
-        match stmt {
-            Stmt::FnDef(def) => {
-                trace.define(def.name.name.clone(), "fn", def.name.span, def.doc.clone());
-            }
-            Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
-                if let Some(PrefixItem::Symbol(Symbol::Let { name, mutable, .. })) = expr.items.first() {
-                    if !*mutable {
-                        trace.define(name.name.clone(), "let_hoisted", name.span, None);
-                    }
-                }
+fn compile_outputs_impl(
+    entry_path: &str,
+    source: &str,
+    vfs: Option<JsValue>,
+    emit: JsValue,
+    attach_source: bool,
+) -> Result<JsValue, JsValue> {
+    // 1) wasm を生成
+    let compiled = compile_wasm_with_entry(entry_path, source, vfs)
+        .map_err(|msg| JsValue::from_str(&msg))?;
+
+    // 2) 依頼された形式に応じて結果を詰める
+    let emit_list = parse_emit_list(emit)?;
+    let obj = js_sys::Object::new();
+
+    for e in emit_list {
+        match e.as_str() {
+            "wasm" => {
+                let bytes = Uint8Array::from(compiled.wasm.as_slice());
+                Reflect::set(&obj, &JsValue::from_str("wasm"), &bytes.into())?;
             }
-            Stmt::StructDef(def) => {
-                trace.define(def.name.name.clone(), "struct", def.name.span, def.doc.clone());
+            "wat" => {
+                let wat = make_wat(
+                    &compiled.wasm,
+                    attach_source,
+                    entry_path,
+                    source,
+                    &compiled.wat_comments,
+                )?;
+                Reflect::set(&obj, &JsValue::from_str("wat"), &JsValue::from_str(&wat))?;
             }
-            Stmt::EnumDef(def) => {
-                trace.define(def.name.name.clone(), "enum", def.name.span, def.doc.clone());
+            "wat-min" => {
+                let wat_min = make_wat_min(&compiled.wasm, attach_source, entry_path, source)?;
+                Reflect::set(&obj, &JsValue::from_str("wat-min"), &JsValue::from_str(&wat_min))?;
             }
-            Stmt::Trait(def) => {
-                trace.define(def.name.name.clone(), "trait", def.name.span, def.doc.clone());
+            other => {
+                let msg = format!("unknown emit kind: {other} (expected wasm, wat, wat-min)");
+                return Err(JsValue::from_str(&msg));
             }
-            _ => {}
         }
     }
+
+    Ok(obj.into())
 }
 
-fn trace_match_arm(trace: &mut NameResolutionTrace, arm: &MatchArm) {
-    trace.push_scope();
-    if let Some(bind) = &arm.bind {
-        trace.define(bind.name.clone(), "match_bind", bind.span, None);
-    }
-    trace_block(trace, &arm.body);
-    trace.pop_scope();
-}
-
-fn trace_prefix_expr(trace: &mut NameResolutionTrace, expr: &PrefixExpr) {
-    for (idx, item) in expr.items.iter().enumerate() {
-        match item {
-            PrefixItem::Symbol(Symbol::Let { name, mutable, .. }) => {
-                if *mutable {
-                    trace.define(name.name.clone(), "let_mut", name.span, None);
-                }
-                if idx != 0 {
-                    trace.reference(name.name.clone(), name.span);
-                }
-            }
-            PrefixItem::Symbol(Symbol::Set { name }) => {
-                trace.reference(name.name.clone(), name.span);
-            }
-            PrefixItem::Symbol(Symbol::Ident(id, _, _)) => {
-                if !is_layout_marker(&id.name) {
-                    trace.reference(id.name.clone(), id.span);
-                }
-            }
-            PrefixItem::Block(block, _) => {
-                trace.push_scope();
-                trace_block(trace, block);
-                trace.pop_scope();
-            }
-            PrefixItem::Match(m, _) => {
-                trace_prefix_expr(trace, &m.scrutinee);
-                for arm in &m.arms {
-                    trace_match_arm(trace, arm);
-                }
-            }
-            PrefixItem::Tuple(items, _) => {
-                for item_expr in items {
-                    trace_prefix_expr(trace, item_expr);
-                }
-            }
-            PrefixItem::Group(inner, _) => {
-                trace_prefix_expr(trace, inner);
-            }
-            PrefixItem::Intrinsic(intr, _) => {
-                for arg in &intr.args {
-                    trace_prefix_expr(trace, arg);
-                }
-            }
-            PrefixItem::Literal(_, _) | PrefixItem::TypeAnnotation(_, _) | PrefixItem::Pipe(_) => {}
-            PrefixItem::Symbol(Symbol::If(_))
-            | PrefixItem::Symbol(Symbol::While(_))
-            | PrefixItem::Symbol(Symbol::AddrOf(_))
-            | PrefixItem::Symbol(Symbol::Deref(_)) => {}
-        }
-    }
-}
-
-fn trace_stmt(trace: &mut NameResolutionTrace, stmt: &Stmt) {
-    match stmt {
-        Stmt::FnDef(def) => match &def.body {
-            FnBody::Parsed(body) => {
-                trace.push_scope();
-                for param in &def.params {
-                    trace.define(param.name.clone(), "param", param.span, None);
-                }
-                trace_block(trace, body);
-                trace.pop_scope();
-            }
-            FnBody::Wasm(_) => {}
-            FnBody::LlvmIr(_) => {}
-        },
-        Stmt::FnAlias(alias) => {
-            trace.reference(alias.target.name.clone(), alias.target.span);
-            trace.define(alias.name.name.clone(), "fn_alias", alias.name.span, alias.doc.clone());
-        }
-        Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
-            trace_prefix_expr(trace, expr);
-        }
-        _ => {}
-    }
-}
-
-fn trace_block(trace: &mut NameResolutionTrace, block: &Block) {
-    hoist_block_defs(trace, block);
-    for stmt in &block.items {
-        trace_stmt(trace, stmt);
-    }
-}
-
-fn def_trace_to_js(source: &str, source_map: Option<&SourceMap>, def: &NameDefTrace) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(&obj, &JsValue::from_str("id"), &JsValue::from_f64(def.id as f64));
-    let _ = Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&def.name));
-    let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str(def.kind));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("scope_depth"),
-        &JsValue::from_f64(def.scope_depth as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("span"),
-        &span_to_js_with_map(source, def.span, source_map),
-    );
-    if let Some(doc) = &def.doc {
-        let _ = Reflect::set(&obj, &JsValue::from_str("doc"), &JsValue::from_str(doc));
-    }
-    obj.into()
-}
-
-fn ref_trace_to_js(
-    source: &str,
-    source_map: Option<&SourceMap>,
-    rf: &NameRefTrace,
-    defs: &[NameDefTrace],
-) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&rf.name));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("scope_depth"),
-        &JsValue::from_f64(rf.scope_depth as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("span"),
-        &span_to_js_with_map(source, rf.span, source_map),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("resolved_def_id"),
-        &rf.resolved_def_id
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let cand = js_sys::Array::new();
-    for id in &rf.candidate_def_ids {
-        cand.push(&JsValue::from_f64(*id as f64));
-    }
-    let _ = Reflect::set(&obj, &JsValue::from_str("candidate_def_ids"), &cand);
-
-    if let Some(id) = rf.resolved_def_id {
-        if let Some(def) = defs.get(id) {
-            let resolved = js_sys::Object::new();
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("id"),
-                &JsValue::from_f64(def.id as f64),
-            );
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("name"),
-                &JsValue::from_str(&def.name),
-            );
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("kind"),
-                &JsValue::from_str(def.kind),
-            );
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("scope_depth"),
-                &JsValue::from_f64(def.scope_depth as f64),
-            );
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("span"),
-                &span_to_js_with_map(source, def.span, source_map),
-            );
-            let _ = Reflect::set(&obj, &JsValue::from_str("resolved_def"), &resolved);
-        } else {
-            let _ = Reflect::set(&obj, &JsValue::from_str("resolved_def"), &JsValue::NULL);
-        }
-    } else {
-        let _ = Reflect::set(&obj, &JsValue::from_str("resolved_def"), &JsValue::NULL);
-    }
-
-    let cand_defs = js_sys::Array::new();
-    for id in &rf.candidate_def_ids {
-        if let Some(def) = defs.get(*id) {
-            let item = js_sys::Object::new();
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("id"),
-                &JsValue::from_f64(def.id as f64),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("name"),
-                &JsValue::from_str(&def.name),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("kind"),
-                &JsValue::from_str(def.kind),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("scope_depth"),
-                &JsValue::from_f64(def.scope_depth as f64),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("span"),
-                &span_to_js_with_map(source, def.span, source_map),
-            );
-            cand_defs.push(&item);
-        }
-    }
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("candidate_definitions"),
-        &cand_defs,
-    );
-    obj.into()
-}
-
-fn shadow_trace_to_js(source: &str, source_map: Option<&SourceMap>, sh: &NameShadowTrace) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&sh.name));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("event_kind"),
-        &JsValue::from_str(sh.event_kind),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("scope_depth"),
-        &JsValue::from_f64(sh.scope_depth as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("span"),
-        &span_to_js_with_map(source, sh.span, source_map),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("selected_def_id"),
-        &sh.selected_def_id
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let hidden = js_sys::Array::new();
-    for id in &sh.shadowed_def_ids {
-        hidden.push(&JsValue::from_f64(*id as f64));
-    }
-    let _ = Reflect::set(&obj, &JsValue::from_str("shadowed_def_ids"), &hidden);
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("severity"),
-        &JsValue::from_str(sh.severity),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("message"),
-        &JsValue::from_str(&sh.message),
-    );
-    obj.into()
-}
-
-fn name_resolution_payload_to_js(
-    source: &str,
-    source_map: Option<&SourceMap>,
-    trace: &NameResolutionTrace,
-) -> JsValue {
-    let payload = js_sys::Object::new();
-
-    let defs = js_sys::Array::new();
-    for def in &trace.defs {
-        defs.push(&def_trace_to_js(source, source_map, def));
-    }
-    let refs = js_sys::Array::new();
-    for rf in &trace.refs {
-        refs.push(&ref_trace_to_js(source, source_map, rf, &trace.defs));
-    }
-    let shadows = js_sys::Array::new();
-    for sh in &trace.shadows {
-        shadows.push(&shadow_trace_to_js(source, source_map, sh));
-    }
-    let shadow_diagnostics = js_sys::Array::new();
-    for sh in &trace.shadows {
-        if matches!(sh.severity, "warning" | "info") {
-            shadow_diagnostics.push(&shadow_trace_to_js(source, source_map, sh));
-        }
-    }
-
-    let by_name = js_sys::Object::new();
-    let mut names = BTreeMap::<String, (Vec<usize>, Vec<usize>)>::new();
-    for d in &trace.defs {
-        names.entry(d.name.clone()).or_default().0.push(d.id);
-    }
-    for (idx, r) in trace.refs.iter().enumerate() {
-        names.entry(r.name.clone()).or_default().1.push(idx);
-    }
-    for (name, (def_ids, ref_ids)) in names {
-        let name_obj = js_sys::Object::new();
-        let d_arr = js_sys::Array::new();
-        for id in def_ids {
-            d_arr.push(&JsValue::from_f64(id as f64));
-        }
-        let r_arr = js_sys::Array::new();
-        for id in ref_ids {
-            r_arr.push(&JsValue::from_f64(id as f64));
-        }
-        let _ = Reflect::set(&name_obj, &JsValue::from_str("definitions"), &d_arr);
-        let _ = Reflect::set(&name_obj, &JsValue::from_str("references"), &r_arr);
-        let _ = Reflect::set(&by_name, &JsValue::from_str(&name), &name_obj);
-    }
-
-    let policy = js_sys::Object::new();
-    let _ = Reflect::set(
-        &policy,
-        &JsValue::from_str("selection"),
-        &JsValue::from_str("nearest_scope_first"),
-    );
-    let _ = Reflect::set(
-        &policy,
-        &JsValue::from_str("hoist"),
-        &JsValue::from_str("fn and non-mut let"),
-    );
-    let _ = Reflect::set(
-        &policy,
-        &JsValue::from_str("warn_important_shadow"),
-        &JsValue::from_bool(trace.warn_important_shadow),
-    );
-
-    let _ = Reflect::set(&payload, &JsValue::from_str("definitions"), &defs);
-    let _ = Reflect::set(&payload, &JsValue::from_str("references"), &refs);
-    let _ = Reflect::set(&payload, &JsValue::from_str("shadows"), &shadows);
-    let _ = Reflect::set(
-        &payload,
-        &JsValue::from_str("shadow_diagnostics"),
-        &shadow_diagnostics,
-    );
-    let _ = Reflect::set(&payload, &JsValue::from_str("by_name"), &by_name);
-    let _ = Reflect::set(&payload, &JsValue::from_str("policy"), &policy);
-    payload.into()
-}
-
-fn semantic_expr_to_js(source: &str, se: &SemanticExprTrace) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(&obj, &JsValue::from_str("id"), &JsValue::from_f64(se.id as f64));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("function_name"),
-        &JsValue::from_str(&se.function_name),
-    );
-    let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str(se.kind));
-    let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, se.span));
-    let _ = Reflect::set(&obj, &JsValue::from_str("inferred_type"), &JsValue::from_str(&se.ty));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("parent_id"),
-        &se.parent_id
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let arg_arr = js_sys::Array::new();
-    for sp in &se.arg_spans {
-        arg_arr.push(&span_to_js(source, *sp));
-    }
-    let _ = Reflect::set(&obj, &JsValue::from_str("argument_ranges"), &arg_arr);
-    obj.into()
-}
-
-fn semantic_token_to_js(source: &str, st: &SemanticTokenTrace) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("token_index"),
-        &JsValue::from_f64(st.token_index as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("inferred_expr_id"),
-        &st.inferred_expr_id
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("inferred_type"),
-        &st.inferred_type
-            .as_ref()
-            .map(|s| JsValue::from_str(s))
-            .unwrap_or(JsValue::NULL),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("expression_range"),
-        &st.expr_span
-            .map(|s| span_to_js(source, s))
-            .unwrap_or(JsValue::NULL),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("arg_index"),
-        &st.arg_index
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("arg_range"),
-        &st.arg_span
-            .map(|s| span_to_js(source, s))
-            .unwrap_or(JsValue::NULL),
-    );
-    obj.into()
-}
-
-fn build_token_hints_to_js(
-    source: &str,
-    source_map: Option<&SourceMap>,
-    tokens: &[Token],
-    token_semantics: &[SemanticTokenTrace],
-    resolve_trace: &NameResolutionTrace,
-) -> js_sys::Array {
-    let hints = js_sys::Array::new();
-    for (tok_idx, token) in tokens.iter().enumerate() {
-        let item = js_sys::Object::new();
-        let _ = Reflect::set(
-            &item,
-            &JsValue::from_str("token_index"),
-            &JsValue::from_f64(tok_idx as f64),
-        );
-
-        if let Some(ts) = token_semantics.get(tok_idx) {
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("inferred_expr_id"),
-                &ts.inferred_expr_id
-                    .map(|v| JsValue::from_f64(v as f64))
-                    .unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("inferred_type"),
-                &ts.inferred_type
-                    .as_ref()
-                    .map(|s| JsValue::from_str(s))
-                    .unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("expression_range"),
-                &ts.expr_span
-                    .map(|s| span_to_js_with_map(source, s, source_map))
-                    .unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("arg_index"),
-                &ts.arg_index
-                    .map(|v| JsValue::from_f64(v as f64))
-                    .unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("arg_range"),
-                &ts.arg_span
-                    .map(|s| span_to_js_with_map(source, s, source_map))
-                    .unwrap_or(JsValue::NULL),
-            );
-        } else {
-            let _ = Reflect::set(&item, &JsValue::from_str("inferred_expr_id"), &JsValue::NULL);
-            let _ = Reflect::set(&item, &JsValue::from_str("inferred_type"), &JsValue::NULL);
-            let _ = Reflect::set(&item, &JsValue::from_str("expression_range"), &JsValue::NULL);
-            let _ = Reflect::set(&item, &JsValue::from_str("arg_index"), &JsValue::NULL);
-            let _ = Reflect::set(&item, &JsValue::from_str("arg_range"), &JsValue::NULL);
-        }
-
-        let mut best_ref: Option<&NameRefTrace> = None;
-        for rf in &resolve_trace.refs {
-            if span_contains(rf.span, token.span) {
-                if let Some(prev) = best_ref {
-                    if span_width(rf.span) < span_width(prev.span) {
-                        best_ref = Some(rf);
-                    }
-                } else {
-                    best_ref = Some(rf);
-                }
-            }
-        }
-
-        if let Some(rf) = best_ref {
-            let _ = Reflect::set(&item, &JsValue::from_str("name"), &JsValue::from_str(&rf.name));
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("ref_span"),
-                &span_to_js_with_map(source, rf.span, source_map),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("resolved_def_id"),
-                &rf.resolved_def_id
-                    .map(|v| JsValue::from_f64(v as f64))
-                    .unwrap_or(JsValue::NULL),
-            );
-            let cand = js_sys::Array::new();
-            for id in &rf.candidate_def_ids {
-                cand.push(&JsValue::from_f64(*id as f64));
-            }
-            let _ = Reflect::set(&item, &JsValue::from_str("candidate_def_ids"), &cand);
-            if let Some(id) = rf.resolved_def_id {
-                if let Some(def) = resolve_trace.defs.get(id) {
-                    let resolved = js_sys::Object::new();
-                    let _ = Reflect::set(
-                        &resolved,
-                        &JsValue::from_str("id"),
-                        &JsValue::from_f64(def.id as f64),
-                    );
-                    let _ = Reflect::set(
-                        &resolved,
-                        &JsValue::from_str("name"),
-                        &JsValue::from_str(&def.name),
-                    );
-                    let _ = Reflect::set(
-                        &resolved,
-                        &JsValue::from_str("kind"),
-                        &JsValue::from_str(def.kind),
-                    );
-                    let _ = Reflect::set(
-                        &resolved,
-                        &JsValue::from_str("scope_depth"),
-                        &JsValue::from_f64(def.scope_depth as f64),
-                    );
-                    let _ = Reflect::set(
-                        &resolved,
-                        &JsValue::from_str("span"),
-                        &span_to_js_with_map(source, def.span, source_map),
-                    );
-                    if let Some(doc) = &def.doc {
-                        let _ = Reflect::set(
-                            &resolved,
-                            &JsValue::from_str("doc"),
-                            &JsValue::from_str(doc),
-                        );
-                    }
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("resolved_definition"),
-                        &resolved,
-                    );
-                } else {
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("resolved_definition"),
-                        &JsValue::NULL,
-                    );
-                }
-            } else {
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("resolved_definition"),
-                    &JsValue::NULL,
-                );
-            }
-
-            let cand_defs = js_sys::Array::new();
-            for id in &rf.candidate_def_ids {
-                if let Some(def) = resolve_trace.defs.get(*id) {
-                    let cand_def = js_sys::Object::new();
-                    let _ = Reflect::set(
-                        &cand_def,
-                        &JsValue::from_str("id"),
-                        &JsValue::from_f64(def.id as f64),
-                    );
-                    let _ = Reflect::set(
-                        &cand_def,
-                        &JsValue::from_str("name"),
-                        &JsValue::from_str(&def.name),
-                    );
-                    let _ = Reflect::set(
-                        &cand_def,
-                        &JsValue::from_str("kind"),
-                        &JsValue::from_str(def.kind),
-                    );
-                    let _ = Reflect::set(
-                        &cand_def,
-                        &JsValue::from_str("scope_depth"),
-                        &JsValue::from_f64(def.scope_depth as f64),
-                    );
-                    let _ = Reflect::set(
-                        &cand_def,
-                        &JsValue::from_str("span"),
-                        &span_to_js_with_map(source, def.span, source_map),
-                    );
-                    if let Some(doc) = &def.doc {
-                        let _ = Reflect::set(
-                            &cand_def,
-                            &JsValue::from_str("doc"),
-                            &JsValue::from_str(doc),
-                        );
-                    }
-                    cand_defs.push(&cand_def);
-                }
-            }
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("candidate_definitions"),
-                &cand_defs,
-            );
-        } else {
-            let _ = Reflect::set(&item, &JsValue::from_str("name"), &JsValue::NULL);
-            let _ = Reflect::set(&item, &JsValue::from_str("ref_span"), &JsValue::NULL);
-            let _ = Reflect::set(&item, &JsValue::from_str("resolved_def_id"), &JsValue::NULL);
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("candidate_def_ids"),
-                &js_sys::Array::new(),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("resolved_definition"),
-                &JsValue::NULL,
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("candidate_definitions"),
-                &js_sys::Array::new(),
-            );
-        }
-
-        hints.push(&item);
-    }
-    hints
-}
-
-fn span_contains(outer: Span, inner: Span) -> bool {
-    outer.file_id == inner.file_id && outer.start <= inner.start && inner.end <= outer.end
-}
-
-fn span_width(span: Span) -> usize {
-    span.end.saturating_sub(span.start) as usize
-}
-
-fn hir_kind_name(kind: &HirExprKind) -> &'static str {
-    match kind {
-        HirExprKind::LiteralI32(_) => "LiteralI32",
-        HirExprKind::LiteralF32(_) => "LiteralF32",
-        HirExprKind::LiteralBool(_) => "LiteralBool",
-        HirExprKind::LiteralStr(_) => "LiteralStr",
-        HirExprKind::Unit => "Unit",
-        HirExprKind::Var(_) => "Var",
-        HirExprKind::FnValue(_) => "FnValue",
-        HirExprKind::Call { .. } => "Call",
-        HirExprKind::CallIndirect { .. } => "CallIndirect",
-        HirExprKind::If { .. } => "If",
-        HirExprKind::While { .. } => "While",
-        HirExprKind::Match { .. } => "Match",
-        HirExprKind::EnumConstruct { .. } => "EnumConstruct",
-        HirExprKind::StructConstruct { .. } => "StructConstruct",
-        HirExprKind::TupleConstruct { .. } => "TupleConstruct",
-        HirExprKind::Block(_) => "Block",
-        HirExprKind::Let { .. } => "Let",
-        HirExprKind::Set { .. } => "Set",
-        HirExprKind::Intrinsic { .. } => "Intrinsic",
-        HirExprKind::AddrOf(_) => "AddrOf",
-        HirExprKind::Deref(_) => "Deref",
-        HirExprKind::Drop { .. } => "Drop",
-    }
-}
-
-fn collect_semantic_expr_from_line(
-    line: &HirLine,
-    function_name: &str,
-    types: &nepl_core::types::TypeCtx,
-    out: &mut Vec<SemanticExprTrace>,
-) {
-    collect_semantic_expr(&line.expr, function_name, types, None, out);
-}
-
-fn collect_semantic_expr_from_block(
-    block: &HirBlock,
-    function_name: &str,
-    types: &nepl_core::types::TypeCtx,
-    parent_id: Option<usize>,
-    out: &mut Vec<SemanticExprTrace>,
-) {
-    for line in &block.lines {
-        collect_semantic_expr(&line.expr, function_name, types, parent_id, out);
-    }
-}
-
-fn collect_semantic_expr(
-    expr: &HirExpr,
-    function_name: &str,
-    types: &nepl_core::types::TypeCtx,
-    parent_id: Option<usize>,
-    out: &mut Vec<SemanticExprTrace>,
-) -> usize {
-    let id = out.len();
-    out.push(SemanticExprTrace {
-        id,
-        function_name: function_name.to_string(),
-        kind: hir_kind_name(&expr.kind),
-        span: expr.span,
-        ty: types.type_to_string(expr.ty),
-        parent_id,
-        arg_spans: Vec::new(),
-    });
-
-    let mut arg_spans = Vec::new();
-    match &expr.kind {
-        HirExprKind::Call { args, .. } => {
-            for a in args {
-                arg_spans.push(a.span);
-                collect_semantic_expr(a, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::FnValue(_) => {}
-        HirExprKind::CallIndirect { callee, args, .. } => {
-            collect_semantic_expr(callee, function_name, types, Some(id), out);
-            for a in args {
-                arg_spans.push(a.span);
-                collect_semantic_expr(a, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::If {
-            cond,
-            then_branch,
-            else_branch,
-        } => {
-            arg_spans.push(cond.span);
-            arg_spans.push(then_branch.span);
-            arg_spans.push(else_branch.span);
-            collect_semantic_expr(cond, function_name, types, Some(id), out);
-            collect_semantic_expr(then_branch, function_name, types, Some(id), out);
-            collect_semantic_expr(else_branch, function_name, types, Some(id), out);
-        }
-        HirExprKind::While { cond, body } => {
-            arg_spans.push(cond.span);
-            arg_spans.push(body.span);
-            collect_semantic_expr(cond, function_name, types, Some(id), out);
-            collect_semantic_expr(body, function_name, types, Some(id), out);
-        }
-        HirExprKind::Match { scrutinee, arms } => {
-            arg_spans.push(scrutinee.span);
-            collect_semantic_expr(scrutinee, function_name, types, Some(id), out);
-            for arm in arms {
-                arg_spans.push(arm.body.span);
-                collect_semantic_expr(&arm.body, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::EnumConstruct { payload, .. } => {
-            if let Some(p) = payload {
-                arg_spans.push(p.span);
-                collect_semantic_expr(p, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::StructConstruct { fields, .. } => {
-            for f in fields {
-                arg_spans.push(f.span);
-                collect_semantic_expr(f, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::TupleConstruct { items } => {
-            for e in items {
-                arg_spans.push(e.span);
-                collect_semantic_expr(e, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::Block(b) => {
-            collect_semantic_expr_from_block(b, function_name, types, Some(id), out);
-        }
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
-            arg_spans.push(value.span);
-            collect_semantic_expr(value, function_name, types, Some(id), out);
-        }
-        HirExprKind::Intrinsic { args, .. } => {
-            for a in args {
-                arg_spans.push(a.span);
-                collect_semantic_expr(a, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
-            arg_spans.push(inner.span);
-            collect_semantic_expr(inner, function_name, types, Some(id), out);
-        }
-        HirExprKind::LiteralI32(_)
-        | HirExprKind::LiteralF32(_)
-        | HirExprKind::LiteralBool(_)
-        | HirExprKind::LiteralStr(_)
-        | HirExprKind::Unit
-        | HirExprKind::Var(_)
-        | HirExprKind::Drop { .. } => {}
-    }
-
-    out[id].arg_spans = arg_spans;
-    id
-}
-
-fn resolve_target_for_analysis(module: &nepl_core::ast::Module) -> (CompileTarget, Vec<Diagnostic>) {
-    let mut found: Option<(CompileTarget, Span)> = None;
-    let mut diags = Vec::new();
-
-    for d in &module.directives {
-        if let Directive::Target { target, span } = d {
-            let parsed = match target.as_str() {
-                "wasm" | "core" => Some(CompileTarget::Wasm),
-                "wasi" | "std" => Some(CompileTarget::Wasi),
-                "llvm" => Some(CompileTarget::Llvm),
-                _ => None,
-            };
-            if let Some(t) = parsed {
-                if let Some((_, prev_span)) = found {
-                    diags.push(
-                        Diagnostic::error("multiple #target directives are not allowed", *span)
-                            .with_id(DiagnosticId::MultipleTargetDirective)
-                            .with_secondary_label(prev_span, Some("previous #target here".into())),
-                    );
-                } else {
-                    found = Some((t, *span));
-                }
-            } else {
-                diags.push(
-                    Diagnostic::error("unknown target in #target", *span)
-                        .with_id(DiagnosticId::UnknownTargetDirective),
-                );
-            }
-        }
-    }
-
-    if found.is_none() {
-        for it in &module.root.items {
-            if let Stmt::Directive(Directive::Target { target, span }) = it {
-                let parsed = match target.as_str() {
-                    "wasm" | "core" => Some(CompileTarget::Wasm),
-                    "wasi" | "std" => Some(CompileTarget::Wasi),
-                    "llvm" => Some(CompileTarget::Llvm),
-                    _ => None,
-                };
-                if let Some(t) = parsed {
-                    if let Some((_, prev_span)) = found {
-                        diags.push(
-                            Diagnostic::error("multiple #target directives are not allowed", *span)
-                                .with_id(DiagnosticId::MultipleTargetDirective)
-                                .with_secondary_label(
-                                    prev_span,
-                                    Some("previous #target here".into()),
-                                ),
-                        );
-                    } else {
-                        found = Some((t, *span));
-                    }
-                } else {
-                    diags.push(
-                        Diagnostic::error("unknown target in #target", *span)
-                            .with_id(DiagnosticId::UnknownTargetDirective),
-                    );
-                }
-            }
-        }
-    }
-
-    (found.map(|(t, _)| t).unwrap_or(CompileTarget::Wasm), diags)
-}
-
-/// 入力ソースを字句解析し、token 列と診断を JSON で返します。
-///
-/// VSCode 拡張や LSP 実装で、構文解析前の結果を可視化するための API です。
-#[wasm_bindgen]
-pub fn analyze_lex(source: &str) -> JsValue {
-    let file_id = FileId(0);
-    let lex_result = lex(file_id, source);
-    let out = js_sys::Object::new();
-    let token_arr = js_sys::Array::new();
-    for token in &lex_result.tokens {
-        token_arr.push(&token_to_js(source, token));
-    }
-    let diagnostics = diagnostics_to_js(source, &lex_result.diagnostics);
-    let has_error = lex_result
-        .diagnostics
-        .iter()
-        .any(|d| d.severity == Severity::Error);
-    let _ = Reflect::set(&out, &JsValue::from_str("stage"), &JsValue::from_str("lex"));
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("ok"),
-        &JsValue::from_bool(!has_error),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &token_arr);
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("indent_width"),
-        &JsValue::from_f64(lex_result.indent_width as f64),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-    out.into()
-}
-
-/// 入力ソースを構文解析し、token・AST 木構造・診断を JSON で返します。
-///
-/// lexer/parser の結果確認や、エディタ拡張での構文可視化に利用します。
-#[wasm_bindgen]
-pub fn analyze_parse(source: &str) -> JsValue {
-    let file_id = FileId(0);
-    let lex_result = lex(file_id, source);
-    let token_arr = js_sys::Array::new();
-    for token in &lex_result.tokens {
-        token_arr.push(&token_to_js(source, token));
-    }
-    let lex_diagnostics = diagnostics_to_js(source, &lex_result.diagnostics);
-    let parse_result = parse_tokens(file_id, lex_result);
-    let diagnostics = diagnostics_to_js(source, &parse_result.diagnostics);
-
-    let out = js_sys::Object::new();
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("stage"),
-        &JsValue::from_str("parse"),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &token_arr);
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("lex_diagnostics"),
-        &lex_diagnostics,
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-
-    if let Some(module) = parse_result.module {
-        let module_obj = js_sys::Object::new();
-        let _ = Reflect::set(
-            &module_obj,
-            &JsValue::from_str("indent_width"),
-            &JsValue::from_f64(module.indent_width as f64),
-        );
-        let _ = Reflect::set(
-            &module_obj,
-            &JsValue::from_str("directives_count"),
-            &JsValue::from_f64(module.directives.len() as f64),
-        );
-        let _ = Reflect::set(
-            &module_obj,
-            &JsValue::from_str("root"),
-            &block_to_js(source, &module.root),
-        );
-        let _ = Reflect::set(
-            &module_obj,
-            &JsValue::from_str("debug"),
-            &JsValue::from_str(&format!("{:#?}", module)),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(true));
-        let _ = Reflect::set(&out, &JsValue::from_str("module"), &module_obj);
-    } else {
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-        let _ = Reflect::set(&out, &JsValue::from_str("module"), &JsValue::NULL);
-    }
-
-    out.into()
-}
-
-/// 同名識別子の解決結果を、LSP/エディタ向けに返します。
-///
-/// - `definitions`: 解析で見つかった定義点
-/// - `references`: 各参照点の候補と最終選択（最内側優先）
-/// - 巻き上げは現行仕様に合わせて `fn` と `let`(non-mut) を先行登録します
-#[wasm_bindgen]
-pub fn analyze_name_resolution(source: &str) -> JsValue {
-    analyze_name_resolution_with_options(source, JsValue::UNDEFINED)
-}
-
-#[wasm_bindgen]
-pub fn analyze_name_resolution_with_options(source: &str, options: JsValue) -> JsValue {
-    let file_id = FileId(0);
-    let lex_result = lex(file_id, source);
-    let parse_result = parse_tokens(file_id, lex_result);
-    let diagnostics = diagnostics_to_js(source, &parse_result.diagnostics);
-    let warn_important_shadow = Reflect::get(&options, &JsValue::from_str("warn_important_shadow"))
-        .ok()
-        .and_then(|v| v.as_bool())
-        .unwrap_or(true);
-
-    let out = js_sys::Object::new();
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("stage"),
-        &JsValue::from_str("name_resolution"),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-
-    if let Some(module) = parse_result.module {
-        let mut trace = NameResolutionTrace::new_with_options(warn_important_shadow);
-        trace_block(&mut trace, &module.root);
-        let payload = name_resolution_payload_to_js(source, None, &trace);
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(true));
-        let _ = Reflect::set(&out, &JsValue::from_str("definitions"), &Reflect::get(&payload, &JsValue::from_str("definitions")).unwrap_or(JsValue::NULL));
-        let _ = Reflect::set(&out, &JsValue::from_str("references"), &Reflect::get(&payload, &JsValue::from_str("references")).unwrap_or(JsValue::NULL));
-        let _ = Reflect::set(&out, &JsValue::from_str("shadows"), &Reflect::get(&payload, &JsValue::from_str("shadows")).unwrap_or(JsValue::NULL));
-        let _ = Reflect::set(
-            &out,
-            &JsValue::from_str("shadow_diagnostics"),
-            &Reflect::get(&payload, &JsValue::from_str("shadow_diagnostics")).unwrap_or(JsValue::NULL),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("by_name"), &Reflect::get(&payload, &JsValue::from_str("by_name")).unwrap_or(JsValue::NULL));
-        let _ = Reflect::set(&out, &JsValue::from_str("policy"), &Reflect::get(&payload, &JsValue::from_str("policy")).unwrap_or(JsValue::NULL));
-    } else {
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-        let _ = Reflect::set(&out, &JsValue::from_str("definitions"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("references"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("shadows"), &js_sys::Array::new());
-        let _ = Reflect::set(
-            &out,
-            &JsValue::from_str("shadow_diagnostics"),
-            &js_sys::Array::new(),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("by_name"), &js_sys::Object::new());
-        let policy = js_sys::Object::new();
-        let _ = Reflect::set(
-            &policy,
-            &JsValue::from_str("warn_important_shadow"),
-            &JsValue::from_bool(warn_important_shadow),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("policy"), &policy);
-    }
-
-    out.into()
-}
-
-/// VFS を使って import/alias/use を含む複数ファイルの名前解決情報を返します。
-#[wasm_bindgen]
-pub fn analyze_name_resolution_with_vfs(
-    entry_path: &str,
-    source: &str,
-    vfs: JsValue,
-    options: JsValue,
-) -> JsValue {
-    let warn_important_shadow = Reflect::get(&options, &JsValue::from_str("warn_important_shadow"))
-        .ok()
-        .and_then(|v| v.as_bool())
-        .unwrap_or(true);
-
-    let stdlib_root = PathBuf::from("/stdlib");
-    let mut sources = stdlib_sources(&stdlib_root);
-    merge_vfs_sources(&mut sources, Some(vfs));
-
-    let mut loader = Loader::new(stdlib_root);
-    let mut provider = |path: &PathBuf| {
-        sources.get(path).cloned().ok_or_else(|| {
-            nepl_core::loader::LoaderError::Io(format!("missing source: {}", path.display()))
-        })
-    };
-    let loaded = loader.load_inline_with_provider(
-        PathBuf::from(entry_path),
-        source.to_string(),
-        &mut provider,
-    );
-
-    let out = js_sys::Object::new();
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("stage"),
-        &JsValue::from_str("name_resolution"),
-    );
-
-    match loaded {
-        Ok(loaded) => {
-            let mut trace = NameResolutionTrace::new_with_options(warn_important_shadow);
-            trace_block(&mut trace, &loaded.module.root);
-            let payload = name_resolution_payload_to_js(source, Some(&loaded.source_map), &trace);
-            let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(true));
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("diagnostics"),
-                &js_sys::Array::new(),
-            );
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("definitions"),
-                &Reflect::get(&payload, &JsValue::from_str("definitions")).unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("references"),
-                &Reflect::get(&payload, &JsValue::from_str("references")).unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("shadows"),
-                &Reflect::get(&payload, &JsValue::from_str("shadows")).unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("shadow_diagnostics"),
-                &Reflect::get(&payload, &JsValue::from_str("shadow_diagnostics"))
-                    .unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("by_name"),
-                &Reflect::get(&payload, &JsValue::from_str("by_name")).unwrap_or(JsValue::NULL),
-            );
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("policy"),
-                &Reflect::get(&payload, &JsValue::from_str("policy")).unwrap_or(JsValue::NULL),
-            );
-        }
-        Err(e) => {
-            let mut ds = Vec::new();
-            ds.push(
-                Diagnostic::error(format!("loader error: {}", e), Span::dummy())
-                    .with_id(DiagnosticId::LoaderFailure),
-            );
-            let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("diagnostics"),
-                &diagnostics_to_js(source, &ds),
-            );
-            let _ = Reflect::set(&out, &JsValue::from_str("definitions"), &js_sys::Array::new());
-            let _ = Reflect::set(&out, &JsValue::from_str("references"), &js_sys::Array::new());
-            let _ = Reflect::set(&out, &JsValue::from_str("shadows"), &js_sys::Array::new());
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("shadow_diagnostics"),
-                &js_sys::Array::new(),
-            );
-            let _ = Reflect::set(&out, &JsValue::from_str("by_name"), &js_sys::Object::new());
-            let policy = js_sys::Object::new();
-            let _ = Reflect::set(
-                &policy,
-                &JsValue::from_str("warn_important_shadow"),
-                &JsValue::from_bool(warn_important_shadow),
-            );
-            let _ = Reflect::set(&out, &JsValue::from_str("policy"), &policy);
-        }
-    }
-
-    out.into()
-}
-
-/// 字句・構文・型検査の情報を統合し、LSP 向けの詳細解析結果を返します。
-///
-/// 返却する主な情報:
-/// - `expressions`: 各式の範囲・推論型・親子関係・引数範囲
-/// - `token_semantics`: token ごとの対応式と推論型、引数位置情報
-/// - `functions`: 関数定義の範囲とシグネチャ
-#[wasm_bindgen]
-pub fn analyze_semantics(source: &str) -> JsValue {
-    let file_id = FileId(0);
-    let lex_result = lex(file_id, source);
-    let tokens = lex_result.tokens.clone();
-    let token_arr = js_sys::Array::new();
-    for token in &tokens {
-        token_arr.push(&token_to_js(source, token));
-    }
-
-    let parse_result = parse_tokens(file_id, lex_result);
-    let out = js_sys::Object::new();
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("stage"),
-        &JsValue::from_str("semantics"),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &token_arr);
-
-    let mut all_diags = parse_result.diagnostics.clone();
-    let mut has_error = all_diags
-        .iter()
-        .any(|d| matches!(d.severity, Severity::Error));
-
-    if let Some(module) = &parse_result.module {
-        let mut resolve_trace = NameResolutionTrace::new();
-        trace_block(&mut resolve_trace, &module.root);
-        let resolve_payload = name_resolution_payload_to_js(source, None, &resolve_trace);
-
-        let (target, mut target_diags) = resolve_target_for_analysis(module);
-        has_error |= target_diags
-            .iter()
-            .any(|d| matches!(d.severity, Severity::Error));
-        all_diags.append(&mut target_diags);
-
-        let tc = typecheck(module, target, BuildProfile::Debug, None);
-        has_error |= tc
-            .diagnostics
-            .iter()
-            .any(|d| matches!(d.severity, Severity::Error));
-        all_diags.extend(tc.diagnostics.clone());
-
-        let diagnostics = diagnostics_to_js(source, &all_diags);
-        let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-
-        if let Some(hir_module) = tc.module {
-            let mut exprs = Vec::<SemanticExprTrace>::new();
-            let function_arr = js_sys::Array::new();
-            for f in &hir_module.functions {
-                let f_obj = js_sys::Object::new();
-                let _ = Reflect::set(&f_obj, &JsValue::from_str("name"), &JsValue::from_str(&f.name));
-                let _ = Reflect::set(&f_obj, &JsValue::from_str("span"), &span_to_js(source, f.span));
-                let _ = Reflect::set(
-                    &f_obj,
-                    &JsValue::from_str("signature"),
-                    &JsValue::from_str(&tc.types.type_to_string(f.func_ty)),
-                );
-                function_arr.push(&f_obj);
-                if let nepl_core::hir::HirBody::Block(b) = &f.body {
-                    for line in &b.lines {
-                        collect_semantic_expr_from_line(line, &f.name, &tc.types, &mut exprs);
-                    }
-                }
-            }
-
-            let expr_arr = js_sys::Array::new();
-            for ex in &exprs {
-                expr_arr.push(&semantic_expr_to_js(source, ex));
-            }
-
-            let token_res_arr = js_sys::Array::new();
-            for (tok_idx, token) in tokens.iter().enumerate() {
-                let mut best_ref: Option<&NameRefTrace> = None;
-                for rf in &resolve_trace.refs {
-                    if span_contains(rf.span, token.span) {
-                        if let Some(prev) = best_ref {
-                            if span_width(rf.span) < span_width(prev.span) {
-                                best_ref = Some(rf);
-                            }
-                        } else {
-                            best_ref = Some(rf);
-                        }
-                    }
-                }
-                let item = js_sys::Object::new();
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("token_index"),
-                    &JsValue::from_f64(tok_idx as f64),
-                );
-                if let Some(rf) = best_ref {
-                    let _ = Reflect::set(&item, &JsValue::from_str("name"), &JsValue::from_str(&rf.name));
-                    let _ = Reflect::set(&item, &JsValue::from_str("ref_span"), &span_to_js(source, rf.span));
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("resolved_def_id"),
-                        &rf.resolved_def_id
-                            .map(|v| JsValue::from_f64(v as f64))
-                            .unwrap_or(JsValue::NULL),
-                    );
-                    let cand = js_sys::Array::new();
-                    for id in &rf.candidate_def_ids {
-                        cand.push(&JsValue::from_f64(*id as f64));
-                    }
-                    let _ = Reflect::set(&item, &JsValue::from_str("candidate_def_ids"), &cand);
-                    if let Some(id) = rf.resolved_def_id {
-                        if let Some(def) = resolve_trace.defs.get(id) {
-                            let resolved = js_sys::Object::new();
-                            let _ = Reflect::set(
-                                &resolved,
-                                &JsValue::from_str("id"),
-                                &JsValue::from_f64(def.id as f64),
-                            );
-                            let _ = Reflect::set(
-                                &resolved,
-                                &JsValue::from_str("name"),
-                                &JsValue::from_str(&def.name),
-                            );
-                            let _ = Reflect::set(
-                                &resolved,
-                                &JsValue::from_str("kind"),
-                                &JsValue::from_str(def.kind),
-                            );
-                            let _ = Reflect::set(
-                                &resolved,
-                                &JsValue::from_str("scope_depth"),
-                                &JsValue::from_f64(def.scope_depth as f64),
-                            );
-                        let _ = Reflect::set(
-                            &resolved,
-                            &JsValue::from_str("span"),
-                            &span_to_js(source, def.span),
-                        );
-                        if let Some(doc) = &def.doc {
-                            let _ = Reflect::set(
-                                &resolved,
-                                &JsValue::from_str("doc"),
-                                &JsValue::from_str(doc),
-                            );
-                        }
-                        let _ = Reflect::set(
-                            &item,
-                            &JsValue::from_str("resolved_definition"),
-                            &resolved,
-                        );
-                        } else {
-                            let _ = Reflect::set(
-                                &item,
-                                &JsValue::from_str("resolved_definition"),
-                                &JsValue::NULL,
-                            );
-                        }
-                    } else {
-                        let _ = Reflect::set(
-                            &item,
-                            &JsValue::from_str("resolved_definition"),
-                            &JsValue::NULL,
-                        );
-                    }
-                    let cand_defs = js_sys::Array::new();
-                    for id in &rf.candidate_def_ids {
-                        if let Some(def) = resolve_trace.defs.get(*id) {
-                            let cand_def = js_sys::Object::new();
-                            let _ = Reflect::set(
-                                &cand_def,
-                                &JsValue::from_str("id"),
-                                &JsValue::from_f64(def.id as f64),
-                            );
-                            let _ = Reflect::set(
-                                &cand_def,
-                                &JsValue::from_str("name"),
-                                &JsValue::from_str(&def.name),
-                            );
-                            let _ = Reflect::set(
-                                &cand_def,
-                                &JsValue::from_str("kind"),
-                                &JsValue::from_str(def.kind),
-                            );
-                            let _ = Reflect::set(
-                                &cand_def,
-                                &JsValue::from_str("scope_depth"),
-                                &JsValue::from_f64(def.scope_depth as f64),
-                            );
-                        let _ = Reflect::set(
-                            &cand_def,
-                            &JsValue::from_str("span"),
-                            &span_to_js(source, def.span),
-                        );
-                        if let Some(doc) = &def.doc {
-                            let _ = Reflect::set(
-                                &cand_def,
-                                &JsValue::from_str("doc"),
-                                &JsValue::from_str(doc),
-                            );
-                        }
-                        cand_defs.push(&cand_def);
-                    }
-                }
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("candidate_definitions"),
-                        &cand_defs,
-                    );
-                } else {
-                    let _ = Reflect::set(&item, &JsValue::from_str("name"), &JsValue::NULL);
-                    let _ = Reflect::set(&item, &JsValue::from_str("ref_span"), &JsValue::NULL);
-                    let _ = Reflect::set(&item, &JsValue::from_str("resolved_def_id"), &JsValue::NULL);
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("candidate_def_ids"),
-                        &js_sys::Array::new(),
-                    );
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("resolved_definition"),
-                        &JsValue::NULL,
-                    );
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("candidate_definitions"),
-                        &js_sys::Array::new(),
-                    );
-                }
-                token_res_arr.push(&item);
-            }
-
-            let mut token_semantics = Vec::<SemanticTokenTrace>::new();
-            for (tok_idx, token) in tokens.iter().enumerate() {
-                let mut best_expr: Option<&SemanticExprTrace> = None;
-                for ex in &exprs {
-                    if span_contains(ex.span, token.span) {
-                        if let Some(prev) = best_expr {
-                            if span_width(ex.span) < span_width(prev.span) {
-                                best_expr = Some(ex);
-                            }
-                        } else {
-                            best_expr = Some(ex);
-                        }
-                    }
-                }
-                let mut arg_hit: Option<(usize, Span)> = None;
-                for ex in &exprs {
-                    for (a_idx, a_sp) in ex.arg_spans.iter().enumerate() {
-                        if span_contains(*a_sp, token.span) {
-                            if let Some((_, prev_sp)) = arg_hit {
-                                if span_width(*a_sp) < span_width(prev_sp) {
-                                    arg_hit = Some((a_idx, *a_sp));
-                                }
-                            } else {
-                                arg_hit = Some((a_idx, *a_sp));
-                            }
-                        }
-                    }
-                }
-                token_semantics.push(SemanticTokenTrace {
-                    token_index: tok_idx,
-                    inferred_expr_id: best_expr.map(|x| x.id),
-                    inferred_type: best_expr.map(|x| x.ty.clone()),
-                    expr_span: best_expr.map(|x| x.span),
-                    arg_index: arg_hit.map(|(idx, _)| idx),
-                    arg_span: arg_hit.map(|(_, sp)| sp),
-                });
-            }
-            let token_sem_arr = js_sys::Array::new();
-            for ts in &token_semantics {
-                token_sem_arr.push(&semantic_token_to_js(source, ts));
-            }
-            let token_hint_arr =
-                build_token_hints_to_js(source, None, &tokens, &token_semantics, &resolve_trace);
-
-            let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(!has_error));
-            let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &expr_arr);
-            let _ = Reflect::set(&out, &JsValue::from_str("token_semantics"), &token_sem_arr);
-            let _ = Reflect::set(&out, &JsValue::from_str("token_hints"), &token_hint_arr);
-            let _ = Reflect::set(&out, &JsValue::from_str("functions"), &function_arr);
-            let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &resolve_payload);
-            let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &token_res_arr);
-        } else {
-            let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-            let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &js_sys::Array::new());
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("token_semantics"),
-                &js_sys::Array::new(),
-            );
-            let _ = Reflect::set(&out, &JsValue::from_str("token_hints"), &js_sys::Array::new());
-            let _ = Reflect::set(&out, &JsValue::from_str("functions"), &js_sys::Array::new());
-            let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &resolve_payload);
-            let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &js_sys::Array::new());
-        }
-    } else {
-        let diagnostics = diagnostics_to_js(source, &all_diags);
-        let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-        let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &js_sys::Array::new());
-        let _ = Reflect::set(
-            &out,
-            &JsValue::from_str("token_semantics"),
-            &js_sys::Array::new(),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("token_hints"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("functions"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &JsValue::NULL);
-        let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &js_sys::Array::new());
-    }
-
-    out.into()
-}
-
-/// VFS を用いた複数ファイル解析版。
-/// import/alias/use で参照された定義について、token_resolution に file_path 付きの
-/// resolved_definition / candidate_definitions を返す。
-#[wasm_bindgen]
-pub fn analyze_semantics_with_vfs(entry_path: &str, source: &str, vfs: JsValue) -> JsValue {
-    let stdlib_root = PathBuf::from("/stdlib");
-    let mut sources = stdlib_sources(&stdlib_root);
-    merge_vfs_sources(&mut sources, Some(vfs));
-
-    let mut loader = Loader::new(stdlib_root);
-    let mut provider = |path: &PathBuf| {
-        sources
-            .get(path)
-            .cloned()
-            .ok_or_else(|| nepl_core::loader::LoaderError::Io(format!("missing source: {}", path.display())))
-    };
-
-    let loaded = loader.load_inline_with_provider(
-        PathBuf::from(entry_path),
-        source.to_string(),
-        &mut provider,
-    );
-
-    let out = js_sys::Object::new();
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("stage"),
-        &JsValue::from_str("semantics"),
-    );
-
-    let file_id = FileId(0);
-    let lex_result = lex(file_id, source);
-    let tokens = lex_result.tokens.clone();
-    let token_arr = js_sys::Array::new();
-    for token in &tokens {
-        token_arr.push(&token_to_js(source, token));
-    }
-    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &token_arr);
-
-    let mut all_diags = lex_result.diagnostics.clone();
-    let mut has_error = all_diags
-        .iter()
-        .any(|d| matches!(d.severity, Severity::Error));
-
-    let loaded = match loaded {
-        Ok(v) => v,
-        Err(e) => {
-            let mut ds = all_diags;
-            ds.push(
-                Diagnostic::error(format!("loader error: {}", e), Span::dummy())
-                    .with_id(DiagnosticId::LoaderFailure),
-            );
-            let diagnostics = diagnostics_to_js(source, &ds);
-            let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-            let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-            let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &js_sys::Array::new());
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("token_semantics"),
-                &js_sys::Array::new(),
-            );
-            let _ = Reflect::set(&out, &JsValue::from_str("token_hints"), &js_sys::Array::new());
-            let _ = Reflect::set(&out, &JsValue::from_str("functions"), &js_sys::Array::new());
-            let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &JsValue::NULL);
-            let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &js_sys::Array::new());
-            return out.into();
-        }
-    };
-
-    let module = loaded.module;
-    let source_map = loaded.source_map;
-
-    let mut resolve_trace = NameResolutionTrace::new();
-    trace_block(&mut resolve_trace, &module.root);
-    let resolve_payload = name_resolution_payload_to_js(source, Some(&source_map), &resolve_trace);
-
-    let (target, mut target_diags) = resolve_target_for_analysis(&module);
-    has_error |= target_diags
-        .iter()
-        .any(|d| matches!(d.severity, Severity::Error));
-    all_diags.append(&mut target_diags);
-
-    let tc = typecheck(&module, target, BuildProfile::Debug, Some(&source_map));
-    has_error |= tc
-        .diagnostics
-        .iter()
-        .any(|d| matches!(d.severity, Severity::Error));
-    all_diags.extend(tc.diagnostics.clone());
-
-    let diagnostics = diagnostics_to_js(source, &all_diags);
-    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-
-    if let Some(hir_module) = tc.module {
-        let mut exprs = Vec::<SemanticExprTrace>::new();
-        let function_arr = js_sys::Array::new();
-        for f in &hir_module.functions {
-            let f_obj = js_sys::Object::new();
-            let _ = Reflect::set(&f_obj, &JsValue::from_str("name"), &JsValue::from_str(&f.name));
-            let _ = Reflect::set(
-                &f_obj,
-                &JsValue::from_str("span"),
-                &span_to_js_with_map(source, f.span, Some(&source_map)),
-            );
-            let _ = Reflect::set(
-                &f_obj,
-                &JsValue::from_str("signature"),
-                &JsValue::from_str(&tc.types.type_to_string(f.func_ty)),
-            );
-            function_arr.push(&f_obj);
-            if let nepl_core::hir::HirBody::Block(b) = &f.body {
-                for line in &b.lines {
-                    collect_semantic_expr_from_line(line, &f.name, &tc.types, &mut exprs);
-                }
-            }
-        }
-
-        let expr_arr = js_sys::Array::new();
-        for ex in &exprs {
-            expr_arr.push(&semantic_expr_to_js(source, ex));
-        }
-
-        let token_res_arr = js_sys::Array::new();
-        for (tok_idx, token) in tokens.iter().enumerate() {
-            let mut best_ref: Option<&NameRefTrace> = None;
-            for rf in &resolve_trace.refs {
-                if span_contains(rf.span, token.span) {
-                    if let Some(prev) = best_ref {
-                        if span_width(rf.span) < span_width(prev.span) {
-                            best_ref = Some(rf);
-                        }
-                    } else {
-                        best_ref = Some(rf);
-                    }
-                }
-            }
-            let item = js_sys::Object::new();
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("token_index"),
-                &JsValue::from_f64(tok_idx as f64),
-            );
-            if let Some(rf) = best_ref {
-                let _ = Reflect::set(&item, &JsValue::from_str("name"), &JsValue::from_str(&rf.name));
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("ref_span"),
-                    &span_to_js_with_map(source, rf.span, Some(&source_map)),
-                );
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("resolved_def_id"),
-                    &rf.resolved_def_id
-                        .map(|v| JsValue::from_f64(v as f64))
-                        .unwrap_or(JsValue::NULL),
-                );
-                let cand = js_sys::Array::new();
-                for id in &rf.candidate_def_ids {
-                    cand.push(&JsValue::from_f64(*id as f64));
-                }
-                let _ = Reflect::set(&item, &JsValue::from_str("candidate_def_ids"), &cand);
-                if let Some(id) = rf.resolved_def_id {
-                    if let Some(def) = resolve_trace.defs.get(id) {
-                        let resolved = js_sys::Object::new();
-                        let _ = Reflect::set(
-                            &resolved,
-                            &JsValue::from_str("id"),
-                            &JsValue::from_f64(def.id as f64),
-                        );
-                        let _ = Reflect::set(
-                            &resolved,
-                            &JsValue::from_str("name"),
-                            &JsValue::from_str(&def.name),
-                        );
-                        let _ = Reflect::set(
-                            &resolved,
-                            &JsValue::from_str("kind"),
-                            &JsValue::from_str(def.kind),
-                        );
-                        let _ = Reflect::set(
-                            &resolved,
-                            &JsValue::from_str("scope_depth"),
-                            &JsValue::from_f64(def.scope_depth as f64),
-                        );
-                        let _ = Reflect::set(
-                            &resolved,
-                            &JsValue::from_str("span"),
-                            &span_to_js_with_map(source, def.span, Some(&source_map)),
-                        );
-                        if let Some(doc) = &def.doc {
-                            let _ = Reflect::set(
-                                &resolved,
-                                &JsValue::from_str("doc"),
-                                &JsValue::from_str(doc),
-                            );
-                        }
-                        let _ = Reflect::set(
-                            &item,
-                            &JsValue::from_str("resolved_definition"),
-                            &resolved,
-                        );
-                    } else {
-                        let _ = Reflect::set(
-                            &item,
-                            &JsValue::from_str("resolved_definition"),
-                            &JsValue::NULL,
-                        );
-                    }
-                } else {
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("resolved_definition"),
-                        &JsValue::NULL,
-                    );
-                }
-                let cand_defs = js_sys::Array::new();
-                for id in &rf.candidate_def_ids {
-                    if let Some(def) = resolve_trace.defs.get(*id) {
-                        let cand_def = js_sys::Object::new();
-                        let _ = Reflect::set(
-                            &cand_def,
-                            &JsValue::from_str("id"),
-                            &JsValue::from_f64(def.id as f64),
-                        );
-                        let _ = Reflect::set(
-                            &cand_def,
-                            &JsValue::from_str("name"),
-                            &JsValue::from_str(&def.name),
-                        );
-                        let _ = Reflect::set(
-                            &cand_def,
-                            &JsValue::from_str("kind"),
-                            &JsValue::from_str(def.kind),
-                        );
-                        let _ = Reflect::set(
-                            &cand_def,
-                            &JsValue::from_str("scope_depth"),
-                            &JsValue::from_f64(def.scope_depth as f64),
-                        );
-                        let _ = Reflect::set(
-                            &cand_def,
-                            &JsValue::from_str("span"),
-                            &span_to_js_with_map(source, def.span, Some(&source_map)),
-                        );
-                        if let Some(doc) = &def.doc {
-                            let _ = Reflect::set(
-                                &cand_def,
-                                &JsValue::from_str("doc"),
-                                &JsValue::from_str(doc),
-                            );
-                        }
-                        cand_defs.push(&cand_def);
-                    }
-                }
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("candidate_definitions"),
-                    &cand_defs,
-                );
-            } else {
-                let _ = Reflect::set(&item, &JsValue::from_str("name"), &JsValue::NULL);
-                let _ = Reflect::set(&item, &JsValue::from_str("ref_span"), &JsValue::NULL);
-                let _ = Reflect::set(&item, &JsValue::from_str("resolved_def_id"), &JsValue::NULL);
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("candidate_def_ids"),
-                    &js_sys::Array::new(),
-                );
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("resolved_definition"),
-                    &JsValue::NULL,
-                );
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("candidate_definitions"),
-                    &js_sys::Array::new(),
-                );
-            }
-            token_res_arr.push(&item);
-        }
-
-        let mut token_semantics = Vec::<SemanticTokenTrace>::new();
-        for (tok_idx, token) in tokens.iter().enumerate() {
-            let mut best_expr: Option<&SemanticExprTrace> = None;
-            for ex in &exprs {
-                if span_contains(ex.span, token.span) {
-                    if let Some(prev) = best_expr {
-                        if span_width(ex.span) < span_width(prev.span) {
-                            best_expr = Some(ex);
-                        }
-                    } else {
-                        best_expr = Some(ex);
-                    }
-                }
-            }
-            let mut arg_hit: Option<(usize, Span)> = None;
-            for ex in &exprs {
-                for (a_idx, a_sp) in ex.arg_spans.iter().enumerate() {
-                    if span_contains(*a_sp, token.span) {
-                        if let Some((_, prev_sp)) = arg_hit {
-                            if span_width(*a_sp) < span_width(prev_sp) {
-                                arg_hit = Some((a_idx, *a_sp));
-                            }
-                        } else {
-                            arg_hit = Some((a_idx, *a_sp));
-                        }
-                    }
-                }
-            }
-            token_semantics.push(SemanticTokenTrace {
-                token_index: tok_idx,
-                inferred_expr_id: best_expr.map(|x| x.id),
-                inferred_type: best_expr.map(|x| x.ty.clone()),
-                expr_span: best_expr.map(|x| x.span),
-                arg_index: arg_hit.map(|(idx, _)| idx),
-                arg_span: arg_hit.map(|(_, sp)| sp),
-            });
-        }
-        let token_sem_arr = js_sys::Array::new();
-        for ts in &token_semantics {
-            token_sem_arr.push(&semantic_token_to_js(source, ts));
-        }
-        let token_hint_arr = build_token_hints_to_js(
-            source,
-            Some(&source_map),
-            &tokens,
-            &token_semantics,
-            &resolve_trace,
-        );
-
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(!has_error));
-        let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &expr_arr);
-        let _ = Reflect::set(&out, &JsValue::from_str("token_semantics"), &token_sem_arr);
-        let _ = Reflect::set(&out, &JsValue::from_str("token_hints"), &token_hint_arr);
-        let _ = Reflect::set(&out, &JsValue::from_str("functions"), &function_arr);
-        let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &resolve_payload);
-        let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &token_res_arr);
-    } else {
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-        let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &js_sys::Array::new());
-        let _ = Reflect::set(
-            &out,
-            &JsValue::from_str("token_semantics"),
-            &js_sys::Array::new(),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("token_hints"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("functions"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &resolve_payload);
-        let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &js_sys::Array::new());
-    }
-
-    out.into()
-}
-
-fn compile_outputs_impl(
-    entry_path: &str,
-    source: &str,
-    vfs: Option<JsValue>,
-    emit: JsValue,
-    attach_source: bool,
-) -> Result<JsValue, JsValue> {
-    // 1) wasm を生成
-    let compiled = compile_wasm_with_entry(entry_path, source, vfs)
-        .map_err(|msg| JsValue::from_str(&msg))?;
-
-    // 2) 依頼された形式に応じて結果を詰める
-    let emit_list = parse_emit_list(emit)?;
-    let obj = js_sys::Object::new();
-
-    for e in emit_list {
-        match e.as_str() {
-            "wasm" => {
-                let bytes = Uint8Array::from(compiled.wasm.as_slice());
-                Reflect::set(&obj, &JsValue::from_str("wasm"), &bytes.into())?;
-            }
-            "wat" => {
-                let wat = make_wat(
-                    &compiled.wasm,
-                    attach_source,
-                    entry_path,
-                    source,
-                    &compiled.wat_comments,
-                )?;
-                Reflect::set(&obj, &JsValue::from_str("wat"), &JsValue::from_str(&wat))?;
-            }
-            "wat-min" => {
-                let wat_min = make_wat_min(&compiled.wasm, attach_source, entry_path, source)?;
-                Reflect::set(&obj, &JsValue::from_str("wat-min"), &JsValue::from_str(&wat_min))?;
-            }
-            other => {
-                let msg = format!("unknown emit kind: {other} (expected wasm, wat, wat-min)");
-                return Err(JsValue::from_str(&msg));
-            }
-        }
-    }
-
-    Ok(obj.into())
-}
-
-#[wasm_bindgen]
-pub fn compile_source(source: &str) -> Result<Vec<u8>, JsValue> {
-    compile_wasm_with_entry("/virtual/entry.nepl", source, None)
-        .map(|a| a.wasm)
-        .map_err(|msg| JsValue::from_str(&msg))
+#[wasm_bindgen]
+pub fn compile_source(source: &str) -> Result<Vec<u8>, JsValue> {
+    compile_wasm_with_entry("/virtual/entry.nepl", source, None)
+        .map(|a| a.wasm)
+        .map_err(|msg| JsValue::from_str(&msg))
 }
 
 #[wasm_bindgen]
@@ -3083,6 +957,29 @@ fn compile_wasm_with_entry_and_profile_and_stdlib(
     stdlib_vfs: Option<JsValue>,
     profile: Option<BuildProfile>,
 ) -> Result<CompiledWasm, String> {
+    let loaded = load_entry_with_vfs(entry_path, source, vfs, stdlib_vfs)?;
+    let artifact = compile_module_with_source_map(
+        loaded.module,
+        Some(&loaded.source_map),
+        CompileOptions {
+            target: None,
+            verbose: false,
+            profile,
+        },
+    )
+    .map_err(|e| render_core_error(e, &loaded.source_map))?;
+    Ok(CompiledWasm {
+        wasm: artifact.wasm,
+        wat_comments: artifact.wat_comments,
+    })
+}
+
+fn load_entry_with_vfs(
+    entry_path: &str,
+    source: &str,
+    vfs: Option<JsValue>,
+    stdlib_vfs: Option<JsValue>,
+) -> Result<LoadResult, String> {
     let stdlib_root = PathBuf::from("/stdlib");
     let mut sources = stdlib_sources(&stdlib_root);
     // stdlib 差し替えが指定された場合は、先に上書きで適用する
@@ -3120,22 +1017,316 @@ fn compile_wasm_with_entry_and_profile_and_stdlib(
         })?;
     #[cfg(target_arch = "wasm32")]
     web_sys::console::log_1(&"[nepl-web] loading success. Proceeding to compilation phases.".into());
-    let artifact = compile_module_with_source_map(
+    Ok(loaded)
+}
+
+/// デバッグ用フック（`nepl_debug` import）付きで wasm を生成し、実行を制御するセッションを返します。
+///
+/// JS 側は `nepl_debug` の各 import からセッションの `on_*` を呼び、`pause` で実行を中断します。
+/// ブレークポイントやステップの判定、ローカル変数の表示は `nepl-cli debug` と同じ実装です。
+#[wasm_bindgen]
+pub fn compile_for_debug(entry_path: &str, source: &str, vfs: JsValue) -> Result<DebugProgram, JsValue> {
+    let loaded = load_entry_with_vfs(entry_path, source, Some(vfs), None).map_err(|msg| JsValue::from_str(&msg))?;
+    let source_map = loaded.source_map;
+    let (artifact, info) = compile_module_for_debugging(
         loaded.module,
-        Some(&loaded.source_map),
+        Some(&source_map),
         CompileOptions {
             target: None,
             verbose: false,
-            profile,
+            profile: None,
         },
     )
-    .map_err(|e| render_core_error(e, &loaded.source_map))?;
-    Ok(CompiledWasm {
+    .map_err(|e| JsValue::from_str(&render_core_error(e, &source_map)))?;
+    let entry = source_map
+        .iter_paths()
+        .find(|(_, path)| path.as_path() == std::path::Path::new(entry_path))
+        .map(|(id, _)| id)
+        .unwrap_or(FileId(0));
+    let mut session = DebugSession::new(info);
+    for (id, path) in source_map.iter_paths() {
+        if path.starts_with("/stdlib") {
+            session.skip_file(id);
+        }
+    }
+    Ok(DebugProgram {
         wasm: artifact.wasm,
-        wat_comments: artifact.wat_comments,
+        session,
+        source_map,
+        entry,
     })
 }
 
+/// `compile_for_debug` の結果。wasm 本体と、その実行に対応するデバッグセッションを持ちます。
+#[wasm_bindgen]
+pub struct DebugProgram {
+    wasm: Vec<u8>,
+    session: DebugSession,
+    source_map: SourceMap,
+    entry: FileId,
+}
+
+#[wasm_bindgen]
+impl DebugProgram {
+    #[wasm_bindgen(getter)]
+    pub fn wasm(&self) -> Vec<u8> {
+        self.wasm.clone()
+    }
+
+    /// エントリファイルの `line`（1 始まり）にブレークポイントを置き、置けた文の数を返します。
+    pub fn set_breakpoint(&mut self, line: u32) -> u32 {
+        let sites = self.sites_on_line(line);
+        for site in &sites {
+            self.session.set_breakpoint(*site);
+        }
+        sites.len() as u32
+    }
+
+    pub fn clear_breakpoint(&mut self, line: u32) {
+        for site in self.sites_on_line(line) {
+            self.session.clear_breakpoint(site);
+        }
+    }
+
+    /// `continue` / `step` / `next` / `finish` のいずれかで実行を再開します。
+    pub fn resume(&mut self, command: &str) -> Result<(), JsValue> {
+        let command = match command {
+            "continue" => StepCommand::Continue,
+            "step" => StepCommand::StepInto,
+            "next" => StepCommand::StepOver,
+            "finish" => StepCommand::StepOut,
+            other => return Err(JsValue::from_str(&format!("unknown step command: {other}"))),
+        };
+        self.session.resume(command);
+        Ok(())
+    }
+
+    pub fn interrupt(&mut self) {
+        self.session.interrupt();
+    }
+
+    pub fn on_enter(&mut self, function: u32) {
+        self.session.on_enter(function as usize);
+    }
+
+    pub fn on_leave(&mut self) {
+        self.session.on_leave();
+    }
+
+    /// `nepl_debug::step` の戻り値。`true` の場合はローカル変数の報告後に `pause` が呼ばれます。
+    pub fn on_step(&mut self, site: u32) -> bool {
+        self.session.on_step(site as usize)
+    }
+
+    pub fn on_local_i32(&mut self, slot: u32, value: i32) {
+        self.session.on_local(slot as usize, DebugValue::I32(value));
+    }
+
+    pub fn on_local_i64(&mut self, slot: u32, value: i64) {
+        self.session.on_local(slot as usize, DebugValue::I64(value));
+    }
+
+    pub fn on_local_f32(&mut self, slot: u32, value: f32) {
+        self.session.on_local(slot as usize, DebugValue::F32(value));
+    }
+
+    pub fn on_local_f64(&mut self, slot: u32, value: f64) {
+        self.session.on_local(slot as usize, DebugValue::F64(value));
+    }
+
+    /// 停止位置を `{ path, line, column, function }` で返します。停止していなければ `null`。
+    pub fn paused_location(&self) -> JsValue {
+        let Some(site) = self.session.paused_at() else {
+            return JsValue::NULL;
+        };
+        let function = self
+            .session
+            .function(site.function)
+            .map(|f| f.name.clone())
+            .unwrap_or_default();
+        self.location_to_js(site.span, &function).into()
+    }
+
+    /// 呼び出しスタックを内側から順に `{ path, line, column, function }` の配列で返します。
+    pub fn call_stack(&self) -> JsValue {
+        let arr = js_sys::Array::new();
+        for frame in self.session.call_stack() {
+            let Some(function) = self.session.function(frame.function) else {
+                continue;
+            };
+            let span = frame
+                .site
+                .and_then(|site| self.session.info().sites.get(site))
+                .map(|site| site.span)
+                .unwrap_or(function.span);
+            arr.push(&self.location_to_js(span, &function.name));
+        }
+        arr.into()
+    }
+
+    /// 停止中の文のローカル変数を `{ name, value }` の配列で返します。`memory` は線形メモリの内容です。
+    pub fn locals(&self, memory: &[u8]) -> JsValue {
+        let arr = js_sys::Array::new();
+        for (name, value) in self.session.locals(memory) {
+            let obj = js_sys::Object::new();
+            let _ = Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&name));
+            let _ = Reflect::set(&obj, &JsValue::from_str("value"), &JsValue::from_str(&value));
+            arr.push(&obj);
+        }
+        arr.into()
+    }
+}
+
+impl DebugProgram {
+    fn sites_on_line(&self, line: u32) -> Vec<usize> {
+        match line.checked_sub(1) {
+            Some(line) => self.session.sites_on_line(&self.source_map, self.entry, line as usize),
+            None => Vec::new(),
+        }
+    }
+
+    fn location_to_js(&self, span: Span, function: &str) -> js_sys::Object {
+        let (line, col) = self
+            .source_map
+            .line_col(span.file_id, span.start)
+            .unwrap_or((0, 0));
+        let path = self
+            .source_map
+            .path(span.file_id)
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        let obj = js_sys::Object::new();
+        let _ = Reflect::set(&obj, &JsValue::from_str("path"), &JsValue::from_str(&path));
+        let _ = Reflect::set(&obj, &JsValue::from_str("line"), &JsValue::from_f64((line + 1) as f64));
+        let _ = Reflect::set(&obj, &JsValue::from_str("column"), &JsValue::from_f64((col + 1) as f64));
+        let _ = Reflect::set(&obj, &JsValue::from_str("function"), &JsValue::from_str(function));
+        obj
+    }
+}
+
+/// `run_wasi` のオプション。省略した項目は空または既定値になります。
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RunWasiOptions {
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    stdin: String,
+    random_seed: Option<u64>,
+    fuel: Option<u64>,
+}
+
+/// WASI 向けの wasm を `nepl-wasi` 上で最後まで実行します。
+///
+/// `nepl-cli --run` と同じ WASI 実装なので、ファイル・引数・標準入出力の扱いが CLI と一致します。
+/// `vfs` はシェルの `VFS.serialize()` と同じ `{ path: string | Uint8Array }` 形式です。
+/// 戻り値は `{ exit_code, stdout, stderr, files, error }` で、`files` は実行後の VFS 全体です。
+/// トラップした場合は `exit_code` が `null` になり、`error` に理由が入ります。
+#[wasm_bindgen]
+pub fn run_wasi(wasm: &[u8], vfs: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: RunWasiOptions = if options.is_undefined() || options.is_null() {
+        RunWasiOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    let stdout = SharedBuffer::new();
+    let stderr = SharedBuffer::new();
+    let mut builder = WasiCtx::builder()
+        .args(options.args)
+        .vfs(vfs_from_js(&vfs))
+        .stdin_bytes(options.stdin)
+        .stdout(stdout.clone())
+        .stderr(stderr.clone());
+    for (key, value) in &options.env {
+        builder = builder.env(key, value);
+    }
+    if let Some(seed) = options.random_seed {
+        builder = builder.random_seed(seed);
+    }
+
+    let (result, mut ctx) = execute_wasi(wasm, builder.build(), options.fuel);
+    let _ = ctx.flush();
+    let files = js_sys::Object::new();
+    for (path, data) in ctx.vfs().files() {
+        let _ = Reflect::set(
+            &files,
+            &JsValue::from_str(&format!("/{path}")),
+            &Uint8Array::from(data).into(),
+        );
+    }
+    let obj = js_sys::Object::new();
+    let (exit_code, error) = match result {
+        Ok(code) => (JsValue::from_f64(code as f64), JsValue::UNDEFINED),
+        Err(msg) => (JsValue::NULL, JsValue::from_str(&msg)),
+    };
+    let _ = Reflect::set(&obj, &JsValue::from_str("exit_code"), &exit_code);
+    let _ = Reflect::set(&obj, &JsValue::from_str("stdout"), &JsValue::from_str(&stdout.to_string_lossy()));
+    let _ = Reflect::set(&obj, &JsValue::from_str("stderr"), &JsValue::from_str(&stderr.to_string_lossy()));
+    let _ = Reflect::set(&obj, &JsValue::from_str("files"), &files);
+    let _ = Reflect::set(&obj, &JsValue::from_str("error"), &error);
+    Ok(obj.into())
+}
+
+fn execute_wasi(wasm: &[u8], ctx: WasiCtx, fuel: Option<u64>) -> (Result<i32, String>, WasiCtx) {
+    let mut config = wasmi::Config::default();
+    config.consume_fuel(fuel.is_some());
+    let engine = wasmi::Engine::new(&config);
+    let mut store = wasmi::Store::new(&engine, ctx);
+    if let Some(fuel) = fuel {
+        let _ = store.add_fuel(fuel);
+    }
+    let result = run_wasi_entry(&engine, &mut store, wasm);
+    (result, store.into_data())
+}
+
+/// `_start` があればそれを、無ければ `main` を呼びます。`proc_exit` の終了コードもここで受け取ります。
+fn run_wasi_entry(
+    engine: &wasmi::Engine,
+    store: &mut wasmi::Store<WasiCtx>,
+    wasm: &[u8],
+) -> Result<i32, String> {
+    let module = wasmi::Module::new(engine, wasm).map_err(|e| e.to_string())?;
+    let mut linker = wasmi::Linker::new(engine);
+    nepl_wasi::add_to_linker(&mut linker).map_err(|e| e.to_string())?;
+    let instance = linker
+        .instantiate(&mut *store, &module)
+        .and_then(|pre| pre.start(&mut *store))
+        .map_err(|e| e.to_string())?;
+    let called = if let Ok(start) = instance.get_typed_func::<(), ()>(&*store, "_start") {
+        start.call(&mut *store, ()).map(|()| 0)
+    } else if let Ok(main) = instance.get_typed_func::<(), i32>(&*store, "main") {
+        main.call(&mut *store, ())
+    } else if let Ok(main) = instance.get_typed_func::<(), ()>(&*store, "main") {
+        main.call(&mut *store, ()).map(|()| 0)
+    } else {
+        return Err("exported main function missing or has wrong type".to_string());
+    };
+    called
+        .or_else(|trap| trap.i32_exit_status().ok_or(trap))
+        .map_err(|trap| trap.to_string())
+}
+
+/// シェルの VFS (`{ path: string | Uint8Array }`) を `nepl-wasi` の VFS に変換します。
+fn vfs_from_js(vfs: &JsValue) -> Vfs {
+    let mut out = Vfs::new();
+    if !vfs.is_object() {
+        return out;
+    }
+    let entries = js_sys::Object::entries(&vfs.clone().into());
+    for entry in entries.iter() {
+        let pair = js_sys::Array::from(&entry);
+        let Some(path) = pair.get(0).as_string() else {
+            continue;
+        };
+        let content = pair.get(1);
+        if let Some(text) = content.as_string() {
+            out.insert(&path, text);
+        } else if content.is_instance_of::<Uint8Array>() {
+            out.insert(&path, Uint8Array::new(&content).to_vec());
+        }
+    }
+    out
+}
+
 #[wasm_bindgen]
 pub fn compile_source_with_vfs_and_stdlib(
     entry_path: &str,
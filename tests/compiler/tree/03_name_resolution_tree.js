@@ -40,13 +40,13 @@ fn main <()->i32> ():
             'candidate_def_ids should be available for debug/LSP'
         );
         assert.ok(
-            targetRef?.resolved_def && typeof targetRef.resolved_def === 'object',
-            'resolved_def object should be provided for LSP jump metadata'
+            targetRef?.resolved_definition && typeof targetRef.resolved_definition === 'object',
+            'resolved_definition object should be provided for LSP jump metadata'
         );
         assert.equal(
-            targetRef?.resolved_def?.id,
+            targetRef?.resolved_definition?.id,
             targetRef?.resolved_def_id,
-            'resolved_def.id must match resolved_def_id'
+            'resolved_definition.id must match resolved_def_id'
         );
         assert.ok(
             Array.isArray(targetRef?.candidate_definitions),
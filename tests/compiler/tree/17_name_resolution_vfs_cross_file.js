@@ -28,14 +28,14 @@ fn main <()->i32> ():
         const addRef = refs.find(
             (r) =>
                 r?.name === 'add' &&
-                r?.resolved_def &&
-                r.resolved_def?.span &&
-                typeof r.resolved_def.span?.file_path === 'string'
+                r?.resolved_definition &&
+                r.resolved_definition?.span &&
+                typeof r.resolved_definition.span?.file_path === 'string'
         );
-        assert.ok(addRef, 'add reference should include resolved_def span file_path');
+        assert.ok(addRef, 'add reference should include resolved_definition span file_path');
         assert.ok(
-            addRef.resolved_def.span.file_path.includes('/stdlib/core/math.nepl'),
-            'resolved_def should point to stdlib/core/math.nepl'
+            addRef.resolved_definition.span.file_path.includes('/stdlib/core/math.nepl'),
+            'resolved_definition should point to stdlib/core/math.nepl'
         );
         return { checked: 5, reference_count: refs.length };
     },
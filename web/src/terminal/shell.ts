@@ -361,6 +361,12 @@ export class Shell {
 
         this.terminal.print(`Executing ${filename} ...`);
 
+        // 対話的な stdin が要らない場合は、CLI と同じ Rust 製 WASI (nepl-wasi) でその場で実行する。
+        const wasmBindings = (window as any).wasmBindings;
+        if (wasmBindings?.run_wasi && (typeof stdin === 'string' || typeof SharedArrayBuffer === 'undefined')) {
+            return this.runWasiInline(wasmBindings, filename, bin, typeof stdin === 'string' ? stdin : '');
+        }
+
         if (!this.sab) {
             try {
                 if (typeof SharedArrayBuffer !== 'undefined') {
@@ -424,6 +430,33 @@ export class Shell {
         });
     }
 
+    private runWasiInline(wasmBindings: any, filename: string, bin: Uint8Array, stdin: string): string | null {
+        const result = wasmBindings.run_wasi(bin, this.vfs.serialize(), {
+            args: [filename],
+            env: Object.fromEntries(this.env),
+            stdin,
+        });
+        if (result.stdout) this.terminal.write(result.stdout);
+        if (result.stderr) this.terminal.write(result.stderr);
+
+        // 実行中に作成・更新されたファイルだけを VFS に書き戻す
+        const encoder = new TextEncoder();
+        for (const [path, data] of Object.entries(result.files as Record<string, Uint8Array>)) {
+            const before = this.vfs.exists(path) ? this.vfs.readFile(path) : null;
+            const beforeBytes = typeof before === 'string' ? encoder.encode(before) : before;
+            const unchanged = beforeBytes !== null
+                && beforeBytes.length === data.length
+                && beforeBytes.every((b, i) => b === data[i]);
+            if (unchanged) continue;
+            this.vfs.writeFile(path, typeof before === 'string' ? new TextDecoder().decode(data) : data);
+        }
+
+        if (result.error) {
+            throw new Error(`Execution trapped: ${result.error}`);
+        }
+        return result.exit_code === 0 ? null : `Program exited with code ${result.exit_code}`;
+    }
+
     handleStdin(text: string | null) {
         if (this.stdinBuffer && this.stdinData) {
             if (text === null) {